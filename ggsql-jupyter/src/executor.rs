@@ -7,6 +7,7 @@ use anyhow::Result;
 use ggsql::{
     execute::prepare_data,
     parser,
+    plot::ThemeRegistry,
     reader::{DuckDBReader, Reader},
     writer::{VegaLiteWriter, Writer},
 };
@@ -39,6 +40,12 @@ impl QueryExecutor {
         Ok(Self { reader, writer })
     }
 
+    /// Make the themes of `themes` available to THEME
+    pub fn with_themes(mut self, themes: ThemeRegistry) -> Self {
+        self.writer = VegaLiteWriter::new().with_themes(themes);
+        self
+    }
+
     /// Execute a ggsql query
     ///
     /// This handles both pure SQL queries and queries with VISUALISE clauses.
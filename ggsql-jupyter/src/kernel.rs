@@ -7,6 +7,7 @@ use crate::display::format_display_data;
 use crate::executor::QueryExecutor;
 use crate::message::{ConnectionInfo, JupyterMessage, MessageHeader};
 use anyhow::Result;
+use ggsql::plot::ThemeRegistry;
 use hmac::{Hmac, Mac};
 use serde_json::{json, Value};
 use sha2::Sha256;
@@ -36,7 +37,7 @@ pub struct KernelServer {
 
 impl KernelServer {
     /// Create a new kernel server from connection info
-    pub async fn new(connection: ConnectionInfo) -> Result<Self> {
+    pub async fn new(connection: ConnectionInfo, themes: ThemeRegistry) -> Result<Self> {
         tracing::info!("Initializing kernel server");
 
         // Initialize sockets
@@ -69,7 +70,7 @@ impl KernelServer {
         heartbeat.bind(&hb_addr).await?;
 
         // Create executor
-        let executor = QueryExecutor::new()?;
+        let executor = QueryExecutor::new()?.with_themes(themes);
 
         // Generate session ID
         let session = uuid::Uuid::new_v4().to_string();
@@ -9,9 +9,11 @@ mod message;
 
 use anyhow::{Context, Result};
 use clap::Parser;
+use ggsql::plot::ThemeRegistry;
 use message::ConnectionInfo;
 use std::env;
 use std::fs;
+use std::path::Path;
 use std::process::Command;
 
 #[derive(Parser)]
@@ -33,6 +35,11 @@ struct Args {
     /// Install the kernel spec system-wide (used with --install, may require sudo)
     #[arg(long, requires = "install", conflicts_with = "user")]
     sys_prefix: bool,
+
+    /// JSON theme file to make available to THEME (can be repeated).
+    /// With --install, the installed kernel loads the file on startup
+    #[arg(long = "theme-file")]
+    theme_files: Vec<String>,
 }
 
 #[tokio::main]
@@ -53,7 +60,7 @@ async fn main() -> Result<()> {
 
     // Handle --install
     if args.install {
-        return install_kernel(args.user, args.sys_prefix);
+        return install_kernel(args.user, args.sys_prefix, &args.theme_files);
     }
 
     // Normal kernel operation
@@ -66,10 +73,19 @@ async fn main() -> Result<()> {
     // Load connection info
     let connection = ConnectionInfo::from_file(&connection_file)?;
 
+    // Load themes from GGSQL_THEME_PATH and --theme-file
+    let mut themes = ThemeRegistry::from_env()?;
+    for file in &args.theme_files {
+        let name = themes
+            .load_file(Path::new(file))
+            .with_context(|| format!("Failed to load theme file {}", file))?;
+        tracing::info!("Loaded theme '{}' from {}", name, file);
+    }
+
     tracing::info!("Creating kernel server");
 
     // Create and run kernel
-    let mut kernel = kernel::KernelServer::new(connection).await?;
+    let mut kernel = kernel::KernelServer::new(connection, themes).await?;
 
     tracing::info!("Kernel ready, starting event loop");
 
@@ -81,7 +97,7 @@ async fn main() -> Result<()> {
 }
 
 /// Install the kernel spec using `jupyter kernelspec install`
-fn install_kernel(user: bool, sys_prefix: bool) -> Result<()> {
+fn install_kernel(user: bool, sys_prefix: bool, theme_files: &[String]) -> Result<()> {
     println!("Installing ggsql Jupyter kernel...");
 
     // Create a temporary directory for the kernel spec
@@ -112,13 +128,20 @@ fn install_kernel(user: bool, sys_prefix: bool) -> Result<()> {
         fs::set_permissions(&dest_exe, perms)?;
     }
 
-    // Create kernel.json
+    // Create kernel.json, passing theme files on to the kernel by absolute path
+    let mut argv = vec![
+        "{resource_dir}/ggsql-jupyter".to_string(),
+        "-f".to_string(),
+        "{connection_file}".to_string(),
+    ];
+    for file in theme_files {
+        let path = fs::canonicalize(file)
+            .with_context(|| format!("Failed to find theme file {}", file))?;
+        argv.push("--theme-file".to_string());
+        argv.push(path.display().to_string());
+    }
     let kernel_json = serde_json::json!({
-        "argv": [
-            "{resource_dir}/ggsql-jupyter",
-            "-f",
-            "{connection_file}"
-        ],
+        "argv": argv,
         "display_name": "ggsql",
         "language": "ggsql",
         "interrupt_mode": "signal",
@@ -6,6 +6,7 @@ Provides commands for executing ggsql queries with various data sources and outp
 
 use clap::{Parser, Subcommand};
 use ggsql::parser::extract_sql;
+use ggsql::plot::ThemeRegistry;
 use ggsql::{parser, VERSION};
 use std::path::PathBuf;
 
@@ -45,6 +46,10 @@ pub enum Commands {
         #[arg(long)]
         output: Option<PathBuf>,
 
+        /// JSON theme file to make available to THEME (can be repeated)
+        #[arg(long = "theme-file")]
+        theme_files: Vec<PathBuf>,
+
         /// Show verbose output (execution details, statistics)
         #[arg(short, long)]
         verbose: bool,
@@ -67,6 +72,10 @@ pub enum Commands {
         #[arg(long)]
         output: Option<PathBuf>,
 
+        /// JSON theme file to make available to THEME (can be repeated)
+        #[arg(long = "theme-file")]
+        theme_files: Vec<PathBuf>,
+
         /// Show verbose output (execution details, statistics)
         #[arg(short, long)]
         verbose: bool,
@@ -102,12 +111,13 @@ fn main() -> anyhow::Result<()> {
             reader,
             writer,
             output,
+            theme_files,
             verbose,
         } => {
             if verbose {
                 eprintln!("Executing query: {}", query);
             }
-            cmd_exec(query, reader, writer, output, verbose);
+            cmd_exec(query, reader, writer, output, theme_files, verbose);
         }
 
         Commands::Run {
@@ -115,12 +125,13 @@ fn main() -> anyhow::Result<()> {
             reader,
             writer,
             output,
+            theme_files,
             verbose,
         } => {
             if verbose {
                 eprintln!("Running query from file: {}", file.display());
             }
-            cmd_run(file, reader, writer, output, verbose);
+            cmd_run(file, reader, writer, output, theme_files, verbose);
         }
 
         Commands::Parse { query, format } => {
@@ -135,9 +146,16 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn cmd_run(file: PathBuf, reader: String, writer: String, output: Option<PathBuf>, verbose: bool) {
+fn cmd_run(
+    file: PathBuf,
+    reader: String,
+    writer: String,
+    output: Option<PathBuf>,
+    theme_files: Vec<PathBuf>,
+    verbose: bool,
+) {
     match std::fs::read_to_string(&file) {
-        Ok(query) => cmd_exec(query, reader, writer, output, verbose),
+        Ok(query) => cmd_exec(query, reader, writer, output, theme_files, verbose),
         Err(e) => {
             eprintln!("Failed to read file {}: {}", file.display(), e);
             std::process::exit(1);
@@ -145,7 +163,14 @@ fn cmd_run(file: PathBuf, reader: String, writer: String, output: Option<PathBuf
     }
 }
 
-fn cmd_exec(query: String, reader: String, writer: String, output: Option<PathBuf>, verbose: bool) {
+fn cmd_exec(
+    query: String,
+    reader: String,
+    writer: String,
+    output: Option<PathBuf>,
+    theme_files: Vec<PathBuf>,
+    verbose: bool,
+) {
     if verbose {
         eprintln!("Reader: {}", reader);
         eprintln!("Writer: {}", writer);
@@ -220,8 +245,19 @@ fn cmd_exec(query: String, reader: String, writer: String, output: Option<PathBu
         std::process::exit(1)
     }
 
+    // Themes from GGSQL_THEME_PATH and --theme-file
+    let themes = load_themes(&theme_files);
+    if let Err(e) = themes {
+        eprintln!("Failed to load themes: {}", e);
+        std::process::exit(1);
+    }
+    let themes = themes.unwrap();
+    if verbose && !themes.names().is_empty() {
+        eprintln!("Themes loaded: {}", themes.names().join(", "));
+    }
+
     // Write visualization
-    let vl_writer = VegaLiteWriter::new();
+    let vl_writer = VegaLiteWriter::new().with_themes(themes);
     let json_output = vl_writer.write(first_spec, &prepared.data);
     if let Err(ref e) = json_output {
         eprintln!("Failed to generate Vega-Lite output: {}", e);
@@ -250,6 +286,15 @@ fn cmd_exec(query: String, reader: String, writer: String, output: Option<PathBu
     }
 }
 
+/// Themes found on `GGSQL_THEME_PATH`, followed by the given theme files
+fn load_themes(theme_files: &[PathBuf]) -> ggsql::Result<ThemeRegistry> {
+    let mut themes = ThemeRegistry::from_env()?;
+    for file in theme_files {
+        themes.load_file(file)?;
+    }
+    Ok(themes)
+}
+
 fn cmd_parse(query: String, format: String) {
     println!("Parsing query: {}", query);
     println!("Format: {}", format);
@@ -4,7 +4,10 @@
 //! handling both global SQL and layer-specific data sources.
 
 use crate::naming;
-use crate::plot::{AestheticValue, ColumnInfo, Layer, LiteralValue, Schema, StatResult};
+use crate::plot::{
+    train_scales, AestheticValue, ColumnInfo, GeomAesthetics, Layer, LiteralValue, OutOfBounds,
+    Position, Scale, ScaleType, Schema, StatResult,
+};
 use crate::{parser, DataFrame, DataSource, Facet, GgsqlError, Plot, Result};
 use std::collections::{HashMap, HashSet};
 use tree_sitter::{Node, Parser};
@@ -550,6 +553,7 @@ fn transform_global_sql(sql: &str, materialized_ctes: &HashSet<String>) -> Optio
 ///
 /// Note: This function takes `&mut Layer` because stat transforms may add new aesthetic mappings
 /// (e.g., mapping y to `__ggsql_stat__count` for histogram or bar count).
+#[allow(clippy::too_many_arguments)]
 fn build_layer_query<F>(
     layer: &mut Layer,
     schema: &Schema,
@@ -557,6 +561,7 @@ fn build_layer_query<F>(
     has_global: bool,
     layer_idx: usize,
     facet: Option<&Facet>,
+    scales: &[Scale],
     constants: &[(String, LiteralValue)],
     execute_query: &F,
 ) -> Result<Option<String>>
@@ -566,10 +571,22 @@ where
     // Apply default parameter values (e.g., bins=30 for histogram)
     // Must be done before any immutable borrows of layer
     layer.apply_default_params();
+    layer.position = Position::from_parameters(&layer.parameters, layer.geom.default_position())?;
+    let position = layer.position;
+    let adjusts_position = position.adjusts(&layer.partition_by);
 
     let filter = layer.filter.as_ref().map(|f| f.as_str());
     let order_by = layer.order_by.as_ref().map(|f| f.as_str());
 
+    // Scales that censor or squish data outside their limits, and whether they
+    // affect any column of this layer
+    let is_continuous = |column: &str| schema.iter().any(|c| c.name == column && !c.is_discrete);
+    let oob = scale_oob(scales)?;
+    let applies_oob = layer.mappings.aesthetics.iter().any(|(aesthetic, value)| {
+        let family = GeomAesthetics::primary_aesthetic(aesthetic);
+        value.column_name().is_some_and(&is_continuous) && oob.iter().any(|(f, _, _)| *f == family)
+    });
+
     let table_name = match &layer.source {
         Some(DataSource::Identifier(name)) => {
             // Check if it's a materialized CTE
@@ -593,7 +610,10 @@ where
                     )));
                 }
                 naming::global_table()
-            } else if layer.geom.needs_stat_transform(&layer.mappings) {
+            } else if layer
+                .geom
+                .needs_stat_transform(&layer.mappings, &layer.parameters)
+            {
                 if !has_global {
                     return Err(GgsqlError::ValidationError(format!(
                         "Layer {} requires data for statistical transformation but no data source.",
@@ -601,8 +621,20 @@ where
                     )));
                 }
                 naming::global_table()
+            } else if adjusts_position {
+                if !has_global {
+                    return Err(GgsqlError::ValidationError(format!(
+                        "Layer {} requires data for its '{}' position adjustment but no data source.",
+                        layer_idx + 1,
+                        position.name()
+                    )));
+                }
+                naming::global_table()
+            } else if applies_oob && has_global {
+                naming::global_table()
             } else {
-                // No source, no filter, no constants, no stat transform - use __global__ data directly
+                // No source, no filter, no constants, no stat transform, no position adjustment
+                // - use __global__ data directly
                 return Ok(None);
             }
         }
@@ -621,12 +653,17 @@ where
 
     // Combine partition_by (which includes discrete mapped columns) and facet variables for grouping
     // Note: partition_by is pre-populated with discrete columns by add_discrete_columns_to_partition_by()
+    // Layers without a facet variable are repeated across its panels by the writer
+    let facet_vars: Vec<String> = facet
+        .map(|f| f.get_variables())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|var| schema.iter().any(|col| &col.name == var))
+        .collect();
     let mut group_by = layer.partition_by.clone();
-    if let Some(f) = facet {
-        for var in f.get_variables() {
-            if !group_by.contains(&var) {
-                group_by.push(var);
-            }
+    for var in &facet_vars {
+        if !group_by.contains(var) {
+            group_by.push(var.clone());
         }
     }
 
@@ -635,10 +672,39 @@ where
         query = format!("{} WHERE {}", query, f);
     }
 
+    // Censor or squish data outside the scale limits before any stat sees it
+    if applies_oob {
+        query = apply_scale_oob(&query, &layer.mappings, &oob, is_continuous);
+    }
+
+    // Stats work in the space of continuous scale transforms (e.g. bins of equal
+    // width on a log scale), so the position columns they read are transformed first
+    let transforms = scale_transforms(scales);
+    let (stat_query, transformed_families) =
+        rewrite_scaled_columns(&query, &layer.mappings, &transforms, false, is_continuous);
+    let transforms: Vec<(&str, &ScaleType)> = transforms
+        .into_iter()
+        .filter(|(family, _)| transformed_families.contains(*family))
+        .collect();
+    if !transforms.is_empty()
+        && layer
+            .geom
+            .needs_stat_transform(&layer.mappings, &layer.parameters)
+    {
+        validate_stat_input_domains(
+            &query,
+            &layer.mappings,
+            &transforms,
+            layer_idx,
+            is_continuous,
+            execute_query,
+        )?;
+    }
+
     // Apply statistical transformation (after filter, uses combined group_by)
     // Returns StatResult::Identity for no transformation, StatResult::Transformed for transformed query
     let stat_result = layer.geom.apply_stat_transform(
-        &query,
+        &stat_query,
         schema,
         &layer.mappings,
         &group_by,
@@ -674,6 +740,7 @@ where
             for aes in &consumed_aesthetics {
                 layer.mappings.aesthetics.remove(aes);
             }
+            layer.consumed_aesthetics = consumed_aesthetics;
 
             // THEN: Apply stat_columns to layer aesthetics using the remappings
             for stat in &stat_columns {
@@ -691,8 +758,23 @@ where
                 }
             }
 
-            // Use the transformed query
-            let mut final_query = transformed_query;
+            // Map the stat's output back from the transformed space
+            let (transformed_query, _) = rewrite_scaled_columns(
+                &transformed_query,
+                &layer.mappings,
+                &transforms,
+                true,
+                |column| naming::is_stat_column(column) || is_continuous(column),
+            );
+
+            // Use the transformed query, with the position adjustment on top
+            let mut final_query = position.apply(
+                &transformed_query,
+                &mut layer.mappings,
+                &layer.partition_by,
+                &facet_vars,
+                execute_query,
+            )?;
             if let Some(o) = order_by {
                 final_query = format!("{} ORDER BY {}", final_query, o);
             }
@@ -700,17 +782,25 @@ where
         }
         StatResult::Identity => {
             // Identity - no stat transformation
-            // If the layer has no explicit source, no filter, no order_by, and no constants,
-            // we can use __global__ directly (return None)
+            // If the layer has no explicit source, no filter, no order_by, no constants,
+            // and no position adjustment, we can use __global__ directly (return None)
             if layer.source.is_none()
                 && filter.is_none()
                 && order_by.is_none()
                 && constants.is_empty()
+                && !adjusts_position
+                && !applies_oob
             {
                 Ok(None)
             } else {
-                // Layer has filter, order_by, or constants - still need the query
-                let mut final_query = query;
+                // Layer has filter, order_by, constants, or a position adjustment - still need the query
+                let mut final_query = position.apply(
+                    &query,
+                    &mut layer.mappings,
+                    &layer.partition_by,
+                    &facet_vars,
+                    execute_query,
+                )?;
                 if let Some(o) = order_by {
                     final_query = format!("{} ORDER BY {}", final_query, o);
                 }
@@ -720,6 +810,276 @@ where
     }
 }
 
+/// Continuous transforms of the position scales, keyed by aesthetic family
+fn scale_transforms(scales: &[Scale]) -> Vec<(&str, &ScaleType)> {
+    scales
+        .iter()
+        .filter(|scale| scale.aesthetic == "x" || scale.aesthetic == "y")
+        .filter_map(|scale| match &scale.scale_type {
+            Some(scale_type) if scale_type.transform_name().is_some() => {
+                Some((scale.aesthetic.as_str(), scale_type))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Aesthetic family, limits and out-of-bounds handling of a scale
+type ScaleOob<'a> = (&'a str, (f64, f64), OutOfBounds);
+
+/// Limits of the scales that censor or squish data outside of them, keyed by aesthetic family
+fn scale_oob(scales: &[Scale]) -> Result<Vec<ScaleOob<'_>>> {
+    let mut oob = Vec::new();
+    for scale in scales {
+        let treatment = scale.oob()?;
+        if treatment == OutOfBounds::Keep {
+            continue;
+        }
+        let limits = scale.limits().ok_or_else(|| {
+            GgsqlError::ValidationError(format!(
+                "SCALE {} oob needs numeric limits, e.g. limits => [0, 100]",
+                scale.aesthetic
+            ))
+        })?;
+        oob.push((scale.aesthetic.as_str(), limits, treatment));
+    }
+    Ok(oob)
+}
+
+/// Censor or squish the continuous columns mapped to aesthetics with scale limits
+///
+/// Squished values are moved onto the nearest limit. Censored position values
+/// drop their rows, while other censored values become NULL so the rows are
+/// still drawn, e.g. in the `na_value` colour.
+fn apply_scale_oob(
+    query: &str,
+    mappings: &crate::Mappings,
+    oob: &[(&str, (f64, f64), OutOfBounds)],
+    include: impl Fn(&str) -> bool,
+) -> String {
+    let mut seen = HashSet::new();
+    let mut replacements = Vec::new();
+    let mut conditions = Vec::new();
+    let mut aesthetics: Vec<&String> = mappings.aesthetics.keys().collect();
+    aesthetics.sort();
+    for aesthetic in aesthetics {
+        let Some(column) = mappings.aesthetics[aesthetic].column_name() else {
+            continue;
+        };
+        let family = GeomAesthetics::primary_aesthetic(aesthetic);
+        let Some((_, (lo, hi), treatment)) = oob.iter().find(|(f, _, _)| *f == family) else {
+            continue;
+        };
+        if !include(column) || !seen.insert(column.to_string()) {
+            continue;
+        }
+        match treatment {
+            OutOfBounds::Squish => replacements.push(format!(
+                "LEAST(GREATEST({col}, {lo}), {hi}) AS {col}",
+                col = column
+            )),
+            OutOfBounds::Censor if matches!(family, "x" | "y") => {
+                conditions.push(format!("{} BETWEEN {} AND {}", column, lo, hi))
+            }
+            OutOfBounds::Censor => replacements.push(format!(
+                "CASE WHEN {col} BETWEEN {lo} AND {hi} THEN {col} END AS {col}",
+                col = column
+            )),
+            OutOfBounds::Keep => {}
+        }
+    }
+
+    let select = if replacements.is_empty() {
+        "SELECT *".to_string()
+    } else {
+        format!("SELECT * REPLACE ({})", replacements.join(", "))
+    };
+    let mut query = format!("{} FROM ({}) AS __oob_src__", select, query);
+    if !conditions.is_empty() {
+        query = format!("{} WHERE {}", query, conditions.join(" AND "));
+    }
+    query
+}
+
+/// Apply scale transforms to the columns mapped to transformed aesthetic families
+///
+/// Columns for which `include` returns false are left alone. With `inverse`, the
+/// columns are mapped back from the transformed space instead. Returns the query
+/// and the families that had columns rewritten.
+fn rewrite_scaled_columns<'a>(
+    query: &str,
+    mappings: &crate::Mappings,
+    transforms: &[(&'a str, &ScaleType)],
+    inverse: bool,
+    include: impl Fn(&str) -> bool,
+) -> (String, HashSet<&'a str>) {
+    let mut families = HashSet::new();
+    let mut seen = HashSet::new();
+    let mut replacements = Vec::new();
+    let mut aesthetics: Vec<&String> = mappings.aesthetics.keys().collect();
+    aesthetics.sort();
+    for aesthetic in aesthetics {
+        let Some(column) = mappings.aesthetics[aesthetic].column_name() else {
+            continue;
+        };
+        let family = GeomAesthetics::primary_aesthetic(aesthetic);
+        let Some((family, scale_type)) = transforms.iter().find(|(f, _)| *f == family) else {
+            continue;
+        };
+        if !include(column) || !seen.insert(column.to_string()) {
+            continue;
+        }
+        families.insert(*family);
+        let expr = if inverse {
+            scale_type.inverse_sql(column)
+        } else {
+            scale_type.transform_sql(column)
+        };
+        replacements.push(format!("{} AS {}", expr.unwrap_or_default(), column));
+    }
+    if replacements.is_empty() {
+        (query.to_string(), families)
+    } else {
+        (
+            format!(
+                "SELECT * REPLACE ({}) FROM ({}) AS __scale_src__",
+                replacements.join(", "),
+                query
+            ),
+            families,
+        )
+    }
+}
+
+/// Check that the data of transformed scales lies in the domain of the transform
+///
+/// Log scales need positive values and sqrt scales non-negative ones. Columns
+/// added by position adjustments are not checked.
+fn validate_scale_domains(spec: &Plot, data_map: &HashMap<String, DataFrame>) -> Result<()> {
+    for scale in &spec.scales {
+        let Some(scale_type) = &scale.scale_type else {
+            continue;
+        };
+        if scale_type.transform_name().is_none() {
+            continue;
+        }
+        for (idx, layer) in spec.layers.iter().enumerate() {
+            let Some(df) = data_map
+                .get(&naming::layer_key(idx))
+                .or_else(|| data_map.get(naming::GLOBAL_DATA_KEY))
+            else {
+                continue;
+            };
+            for (aesthetic, value) in &layer.mappings.aesthetics {
+                if GeomAesthetics::primary_aesthetic(aesthetic) != scale.aesthetic {
+                    continue;
+                }
+                let Some(column) = value.column_name() else {
+                    continue;
+                };
+                if naming::is_position_column(column) {
+                    continue;
+                }
+                let Ok(values) = df.column(column) else {
+                    continue;
+                };
+                if !values.dtype().is_numeric() {
+                    continue;
+                }
+                let Ok(values) = values.cast(&polars::prelude::DataType::Float64) else {
+                    continue;
+                };
+                let invalid = values
+                    .f64()
+                    .ok()
+                    .and_then(|v| v.into_iter().flatten().find(|v| !scale_type.accepts(*v)));
+                if let Some(invalid) = invalid {
+                    return Err(scale_domain_error(
+                        scale_type,
+                        &scale.aesthetic,
+                        column,
+                        idx,
+                        invalid,
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that the columns a stat reads through a transformed scale lie in its domain
+///
+/// The stat sees the transformed columns, so values outside the domain would
+/// otherwise surface as a database error rather than a validation error.
+fn validate_stat_input_domains<F>(
+    query: &str,
+    mappings: &crate::Mappings,
+    transforms: &[(&str, &ScaleType)],
+    layer_idx: usize,
+    include: impl Fn(&str) -> bool,
+    execute_query: &F,
+) -> Result<()>
+where
+    F: Fn(&str) -> Result<DataFrame>,
+{
+    let mut aesthetics: Vec<&String> = mappings.aesthetics.keys().collect();
+    aesthetics.sort();
+    for aesthetic in aesthetics {
+        let Some(column) = mappings.aesthetics[aesthetic].column_name() else {
+            continue;
+        };
+        let family = GeomAesthetics::primary_aesthetic(aesthetic);
+        let Some((family, scale_type)) = transforms.iter().find(|(f, _)| *f == family) else {
+            continue;
+        };
+        let Some(condition) = scale_type.domain_sql(&format!("TRY_CAST({} AS DOUBLE)", column))
+        else {
+            continue;
+        };
+        if !include(column) {
+            continue;
+        }
+        let check = format!(
+            "SELECT TRY_CAST({col} AS DOUBLE) AS __value__ FROM ({query}) AS __domain_src__ \
+             WHERE NOT ({condition}) LIMIT 1",
+            col = column,
+            query = query,
+            condition = condition
+        );
+        let df = execute_query(&check)?;
+        let invalid = df
+            .column("__value__")
+            .ok()
+            .and_then(|values| values.f64().ok().and_then(|v| v.get(0)));
+        if let Some(invalid) = invalid {
+            return Err(scale_domain_error(
+                scale_type, family, column, layer_idx, invalid,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Error for a value outside the domain of a transformed scale
+fn scale_domain_error(
+    scale_type: &ScaleType,
+    aesthetic: &str,
+    column: &str,
+    layer_idx: usize,
+    value: f64,
+) -> GgsqlError {
+    GgsqlError::ValidationError(format!(
+        "The {} scale of '{}' requires {} values, but column '{}' of layer {} contains {}",
+        scale_type.transform_name().unwrap_or_default(),
+        aesthetic,
+        scale_type.domain_description(),
+        column,
+        layer_idx + 1,
+        value
+    ))
+}
+
 /// Merge global mappings into layer aesthetics and expand wildcards
 ///
 /// This function performs smart wildcard expansion with schema awareness:
@@ -802,11 +1162,9 @@ fn has_executable_sql(sql: &str) -> bool {
                         match stmt_child.kind() {
                             "select_statement" | "create_statement" | "insert_statement"
                             | "update_statement" | "delete_statement" => return true,
-                            "with_statement" => {
-                                // Check if WITH has trailing SELECT
-                                if with_has_trailing_select(&stmt_child) {
-                                    return true;
-                                }
+                            // Check if WITH has trailing SELECT
+                            "with_statement" if with_has_trailing_select(&stmt_child) => {
+                                return true;
                             }
                             _ => {}
                         }
@@ -1053,6 +1411,7 @@ where
     // - Layer with filter/order_by but no source → query __ggsql_global__ with filter/order_by and constants
     // - Layer with no source, no filter, no order_by → returns None (use global directly, constants already injected)
     let facet = specs[0].facet.clone();
+    let scales = specs[0].scales.clone();
 
     for (idx, layer) in specs[0].layers.iter_mut().enumerate() {
         // For layers using global data without filter, constants are already in global data
@@ -1071,6 +1430,7 @@ where
             has_global,
             idx,
             facet.as_ref(),
+            &scales,
             &constants,
             &execute_query,
         )? {
@@ -1094,6 +1454,9 @@ where
         ));
     }
 
+    // Transformed scales can't show values outside the domain of their transform
+    validate_scale_domains(&specs[0], &data_map)?;
+
     // For layers without specific sources, ensure global data exists
     let has_layer_without_source = specs[0]
         .layers
@@ -1116,6 +1479,9 @@ where
         split_color_aesthetic(&mut spec.layers);
     }
 
+    // Resolve every scale from the data of all layers, so writers agree on them
+    specs[0].trained_scales = train_scales(&specs[0], &data_map)?;
+
     Ok(PreparedData {
         data: data_map,
         specs,
@@ -1134,7 +1500,7 @@ pub fn prepare_data(query: &str, reader: &DuckDBReader) -> Result<PreparedData>
 mod tests {
     use super::*;
     use crate::naming;
-    use crate::plot::SqlExpression;
+    use crate::plot::{ScaleDomain, ScaleKind, SqlExpression};
     use crate::Geom;
 
     #[cfg(feature = "duckdb")]
@@ -1369,6 +1735,7 @@ mod tests {
             0,
             None,
             &[],
+            &[],
             &mock_execute,
         );
 
@@ -1397,6 +1764,7 @@ mod tests {
             0,
             None,
             &[],
+            &[],
             &mock_execute,
         );
 
@@ -1423,6 +1791,7 @@ mod tests {
             0,
             None,
             &[],
+            &[],
             &mock_execute,
         );
 
@@ -1450,6 +1819,7 @@ mod tests {
             0,
             None,
             &[],
+            &[],
             &mock_execute,
         );
 
@@ -1475,6 +1845,7 @@ mod tests {
             0,
             None,
             &[],
+            &[],
             &mock_execute,
         );
 
@@ -1502,6 +1873,7 @@ mod tests {
             0,
             None,
             &[],
+            &[],
             &mock_execute,
         );
 
@@ -1527,6 +1899,7 @@ mod tests {
             0,
             None,
             &[],
+            &[],
             &mock_execute,
         );
 
@@ -1552,6 +1925,7 @@ mod tests {
             0,
             None,
             &[],
+            &[],
             &mock_execute,
         );
 
@@ -1575,6 +1949,7 @@ mod tests {
             2,
             None,
             &[],
+            &[],
             &mock_execute,
         );
 
@@ -1602,6 +1977,7 @@ mod tests {
             0,
             None,
             &[],
+            &[],
             &mock_execute,
         );
 
@@ -1629,6 +2005,7 @@ mod tests {
             0,
             None,
             &[],
+            &[],
             &mock_execute,
         );
 
@@ -1657,6 +2034,7 @@ mod tests {
             0,
             None,
             &[],
+            &[],
             &mock_execute,
         );
 
@@ -1692,6 +2070,7 @@ mod tests {
             false,
             0,
             None,
+            &[],
             &constants,
             &mock_execute,
         );
@@ -1723,6 +2102,7 @@ mod tests {
             true,
             0,
             None,
+            &[],
             &constants,
             &mock_execute,
         );
@@ -2074,6 +2454,1311 @@ mod tests {
         assert!(col_names.contains(&naming::stat_column("count").as_str()));
     }
 
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_smooth_stat_transform() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        // Two perfectly linear groups with different slopes
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE smooth_test AS SELECT i AS x, CASE WHEN i % 2 = 0 THEN 2 * i + 1 ELSE 10 - i END AS y, CASE WHEN i % 2 = 0 THEN 'even' ELSE 'odd' END AS parity FROM range(20) t(i)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM smooth_test
+            VISUALISE
+            DRAW smooth MAPPING x AS x, y AS y, parity AS color SETTING method => 'lm', n => 10
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+
+        // One grid of n points per group
+        assert_eq!(layer_df.height(), 20);
+
+        let col_names: Vec<&str> = layer_df
+            .get_column_names()
+            .iter()
+            .map(|s| s.as_str())
+            .collect();
+        assert!(col_names.contains(&"parity"));
+        for stat in ["x", "y", "ymin", "ymax", "se"] {
+            assert!(col_names.contains(&naming::stat_column(stat).as_str()));
+        }
+
+        // Fitted values lie on the original lines
+        let parity: Vec<&str> = layer_df
+            .column("parity")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        let xs: Vec<f64> = layer_df
+            .column(&naming::stat_column("x"))
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        let ys: Vec<f64> = layer_df
+            .column(&naming::stat_column("y"))
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        for ((p, x), y) in parity.iter().zip(&xs).zip(&ys) {
+            let expected = if *p == "even" {
+                2.0 * x + 1.0
+            } else {
+                10.0 - x
+            };
+            assert!((y - expected).abs() < 1e-6);
+        }
+
+        // Stat columns are remapped onto the layer
+        let layer = &result.specs[0].layers[0];
+        assert_eq!(
+            layer.mappings.get("ymin").and_then(|v| v.column_name()),
+            Some(naming::stat_column("ymin").as_str())
+        );
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_smooth_without_se() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE smooth_se_test AS SELECT i AS x, SIN(i / 5.0) AS y FROM range(50) t(i)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM smooth_se_test
+            VISUALISE
+            DRAW smooth MAPPING x AS x, y AS y SETTING se => false
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+
+        // Default grid size, no confidence band columns
+        assert_eq!(layer_df.height(), 80);
+        let col_names: Vec<&str> = layer_df
+            .get_column_names()
+            .iter()
+            .map(|s| s.as_str())
+            .collect();
+        assert!(col_names.contains(&naming::stat_column("y").as_str()));
+        assert!(!col_names.contains(&naming::stat_column("ymin").as_str()));
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_smooth_rejects_unknown_method() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        let query = r#"
+            SELECT 1 AS x, 2 AS y
+            VISUALISE
+            DRAW smooth MAPPING x AS x, y AS y SETTING method => 'spline'
+        "#;
+
+        let err = prepare_data(query, &reader).err().unwrap();
+        assert!(err.to_string().contains("Unknown smooth method"));
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_boxplot_stat_transform() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        // Group A has values 1..=9 plus an outlier at 100
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE box_test AS SELECT * FROM (VALUES ('A', 1), ('A', 2), ('A', 3), ('A', 4), ('A', 5), ('A', 6), ('A', 7), ('A', 8), ('A', 9), ('A', 100), ('B', 10), ('B', 20)) AS t(grp, value)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM box_test
+            VISUALISE
+            DRAW boxplot MAPPING grp AS x, value AS y
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+
+        // One row per box plus one row per outlier
+        assert_eq!(layer_df.height(), 3);
+
+        let stat = |name: &str| -> Vec<Option<f64>> {
+            layer_df
+                .column(&naming::stat_column(name))
+                .unwrap()
+                .cast(&polars::prelude::DataType::Float64)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .collect()
+        };
+        let groups: Vec<Option<&str>> = layer_df
+            .column("grp")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .collect();
+        let outliers = stat("outlier");
+        let medians = stat("median");
+        let uppers = stat("upper");
+        let counts = stat("n");
+
+        let box_a = (0..3)
+            .find(|&i| groups[i] == Some("A") && outliers[i].is_none())
+            .unwrap();
+        assert_eq!(medians[box_a], Some(5.5));
+        assert_eq!(counts[box_a], Some(10.0));
+        // Whisker stops at the largest value within the fence
+        assert_eq!(uppers[box_a], Some(9.0));
+
+        let outlier_rows: Vec<f64> = outliers.iter().flatten().copied().collect();
+        assert_eq!(outlier_rows, vec![100.0]);
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_density_stat_transform() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE density_test AS SELECT * FROM (VALUES (1.0, 'a'), (2.0, 'a'), (2.5, 'a'), (4.0, 'a'), (10.0, 'b'), (11.0, 'b'), (13.0, 'b')) AS t(value, grp)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM density_test
+            VISUALISE
+            DRAW density MAPPING value AS x, grp AS fill SETTING n => 64
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+
+        // One grid of n points per group
+        assert_eq!(layer_df.height(), 128);
+
+        let column = |name: &str| -> Vec<f64> {
+            layer_df
+                .column(&naming::stat_column(name))
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect()
+        };
+        let xs = column("x");
+        let density = column("density");
+        let scaled = column("scaled");
+
+        // Untrimmed groups share the range of the whole layer
+        let min_x = xs.iter().copied().fold(f64::INFINITY, f64::min);
+        let max_x = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        assert!((min_x - 1.0).abs() < 1e-9);
+        assert!((max_x - 13.0).abs() < 1e-9);
+
+        assert!(density.iter().all(|d| *d >= 0.0));
+        assert!(scaled.iter().all(|s| *s <= 1.0 + 1e-9));
+
+        // Density is mapped to y
+        let layer = &result.specs[0].layers[0];
+        assert_eq!(
+            layer.mappings.get("y").and_then(|v| v.column_name()),
+            Some(naming::stat_column("density").as_str())
+        );
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_density_fixed_bandwidth() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        // With a gaussian kernel and a bandwidth of 1, the density is the mean of
+        // standard normal densities centred on each value
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE density_r_test AS SELECT * FROM (VALUES (1), (2), (3), (4), (10)) AS t(value)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM density_r_test
+            VISUALISE
+            DRAW density MAPPING value AS x SETTING bw => 1, n => 2
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        let mut rows: Vec<(f64, f64)> = layer_df
+            .column(&naming::stat_column("x"))
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_iter()
+            .zip(
+                layer_df
+                    .column(&naming::stat_column("density"))
+                    .unwrap()
+                    .f64()
+                    .unwrap(),
+            )
+            .map(|(x, d)| (x.unwrap(), d.unwrap()))
+            .collect();
+        rows.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let expected = |x: f64| -> f64 {
+            [1.0, 2.0, 3.0, 4.0, 10.0]
+                .iter()
+                .map(|v: &f64| (-0.5 * (x - v).powi(2)).exp() / (2.0 * std::f64::consts::PI).sqrt())
+                .sum::<f64>()
+                / 5.0
+        };
+        assert_eq!(rows.len(), 2);
+        for (x, d) in rows {
+            assert!((d - expected(x)).abs() < 1e-12);
+        }
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_violin_stat_transform() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE violin_test AS SELECT CASE WHEN i < 50 THEN 'A' ELSE 'B' END AS grp, (i % 50) * 0.1 AS value FROM range(100) t(i)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM violin_test
+            VISUALISE
+            DRAW violin MAPPING grp AS x, value AS y SETTING n => 32
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        assert_eq!(layer_df.height(), 64);
+
+        let column = |name: &str| -> Vec<f64> {
+            layer_df
+                .column(name)
+                .unwrap()
+                .cast(&polars::prelude::DataType::Float64)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect()
+        };
+        let xpos = column(&naming::stat_column("xpos"));
+        let xmin = column(&naming::stat_column("xmin"));
+        let xmax = column(&naming::stat_column("xmax"));
+
+        // Each violin is mirrored around its position and stays within its slot
+        for ((pos, lo), hi) in xpos.iter().zip(&xmin).zip(&xmax) {
+            assert!(((lo + hi) / 2.0 - pos).abs() < 1e-9);
+            assert!(pos - lo <= 0.45 + 1e-9);
+        }
+        assert!(xpos.contains(&1.0) && xpos.contains(&2.0));
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_violin_with_points_on_discrete_x() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE violin_points AS SELECT CASE WHEN i < 50 THEN 'A' ELSE 'B' END AS grp, (i % 50) * 0.1 AS value FROM range(100) t(i)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM violin_points
+            VISUALISE grp AS x, value AS y
+            DRAW violin
+            DRAW point
+        "#;
+
+        // The violin outline's numeric positions don't train the discrete x scale
+        let result = prepare_data(query, &reader).unwrap();
+        let x = result.specs[0].find_trained_scale("x").unwrap();
+        assert_eq!(x.kind, ScaleKind::Discrete);
+        assert_eq!(
+            x.domain,
+            Some(ScaleDomain::Values(vec!["A".to_string(), "B".to_string()]))
+        );
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_point_summary_stat() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE summary_test AS SELECT * FROM (VALUES ('A', 1.0), ('A', 2.0), ('A', 3.0), ('B', 10.0), ('B', 20.0)) AS t(grp, value)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM summary_test
+            VISUALISE
+            DRAW point MAPPING grp AS x, value AS y SETTING stat => 'summary', fun_min => 'sd', fun_max => 'sd'
+            ORDER BY grp
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer = &result.specs[0].layers[0];
+        let y_col = naming::stat_column("y");
+        assert_eq!(
+            layer.mappings.get("y").unwrap().column_name(),
+            Some(y_col.as_str())
+        );
+
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        assert_eq!(layer_df.height(), 2);
+
+        let stat = |name: &str| -> Vec<f64> {
+            layer_df
+                .column(&naming::stat_column(name))
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect()
+        };
+        let close = |actual: Vec<f64>, expected: [f64; 2]| {
+            assert_eq!(actual.len(), 2);
+            for (a, e) in actual.iter().zip(expected) {
+                assert!((a - e).abs() < 1e-9, "expected {}, got {}", e, a);
+            }
+        };
+        close(stat("y"), [2.0, 15.0]);
+        close(stat("ymin"), [1.0, 15.0 - 50.0_f64.sqrt()]);
+        close(stat("ymax"), [3.0, 15.0 + 50.0_f64.sqrt()]);
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_errorbar_summary_confidence_interval() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE ci_test AS SELECT * FROM (VALUES (1, 1.0), (1, 2.0), (1, 3.0)) AS t(x, value)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM ci_test
+            VISUALISE
+            DRAW errorbar MAPPING x AS x, value AS y SETTING stat => 'summary', fun_min => 'ci', fun_max => 'ci'
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer = &result.specs[0].layers[0];
+        assert!(layer.mappings.get("ymin").is_some());
+        assert!(layer.mappings.get("ymax").is_some());
+
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        let ymax = layer_df
+            .column(&naming::stat_column("ymax"))
+            .unwrap()
+            .f64()
+            .unwrap()
+            .get(0)
+            .unwrap();
+        // mean 2, sd 1, n 3: t(0.975, 2) = 4.302653
+        assert!((ymax - (2.0 + 4.302653 / 3.0_f64.sqrt())).abs() < 1e-5);
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_summary_rejects_unknown_stat() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        let query = r#"
+            SELECT 1 AS x, 2 AS y
+            VISUALISE
+            DRAW point MAPPING x AS x, y AS y SETTING stat => 'bogus'
+        "#;
+
+        let err = prepare_data(query, &reader).err().unwrap();
+        assert!(err.to_string().contains("Unknown stat 'bogus'"));
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_bin2d_stat_transform() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE bin2d_test AS SELECT * FROM (VALUES (0, 0), (0, 0), (1, 1), (10, 10)) AS t(a, b)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM bin2d_test
+            VISUALISE
+            DRAW bin2d MAPPING a AS x, b AS y SETTING bins => 2
+            ORDER BY __ggsql_stat_xbin, __ggsql_stat_ybin
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer = &result.specs[0].layers[0];
+        let count_col = naming::stat_column("count");
+        assert_eq!(
+            layer.mappings.get("fill").unwrap().column_name(),
+            Some(count_col.as_str())
+        );
+
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        let column = |name: &str| -> Vec<f64> {
+            layer_df
+                .column(&naming::stat_column(name))
+                .unwrap()
+                .cast(&polars::prelude::DataType::Float64)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect()
+        };
+        // Bins of width 10 centred on 0 and 10
+        assert_eq!(column("xbin"), vec![-5.0, 5.0]);
+        assert_eq!(column("xbin_end"), vec![5.0, 15.0]);
+        assert_eq!(column("count"), vec![3.0, 1.0]);
+        assert_eq!(column("density"), vec![0.75, 0.25]);
+
+        // Keeping empty cells fills in the rest of the grid
+        let query = r#"
+            SELECT * FROM bin2d_test
+            VISUALISE
+            DRAW bin2d MAPPING a AS x, b AS y SETTING bins => 2, drop => false
+        "#;
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        assert_eq!(layer_df.height(), 4);
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_hex_stat_transform() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE hex_test AS SELECT * FROM (VALUES (0, 0), (0, 0), (1, 1), (10, 10)) AS t(a, b)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM hex_test
+            VISUALISE
+            DRAW hex MAPPING a AS x, b AS y SETTING bins => 2
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+
+        // Two hexagons with six corners each
+        assert_eq!(layer_df.height(), 12);
+        let counts: Vec<i64> = layer_df
+            .column(&naming::stat_column("count"))
+            .unwrap()
+            .cast(&polars::prelude::DataType::Int64)
+            .unwrap()
+            .i64()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        assert_eq!(counts.iter().filter(|&&c| c == 3).count(), 6);
+        assert_eq!(counts.iter().filter(|&&c| c == 1).count(), 6);
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_contour_stat_transform() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        // A cone peaking at (2, 2)
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE contour_test AS SELECT a, b, 1 - GREATEST(ABS(a - 2), ABS(b - 2)) / 2 AS c \
+                 FROM range(5) AS t1(a) CROSS JOIN range(5) AS t2(b)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM contour_test
+            VISUALISE
+            DRAW contour MAPPING a AS x, b AS y, c AS z SETTING breaks => [0.75]
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer = &result.specs[0].layers[0];
+        assert_eq!(
+            layer.mappings.get("fill").and_then(|v| v.column_name()),
+            Some(naming::stat_column("level").as_str())
+        );
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+
+        // One closed ring around the peak: four crossings plus the repeated first point
+        assert_eq!(layer_df.height(), 9);
+        let pieces = layer_df
+            .column(&naming::stat_column("piece"))
+            .unwrap()
+            .n_unique()
+            .unwrap();
+        assert_eq!(pieces, 1);
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_density2d_stat_transform() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE density2d_test AS SELECT a, b, CASE WHEN a < 0 THEN 'L' ELSE 'R' END AS side \
+                 FROM (VALUES (-3, 0), (-2.5, 0.5), (-3.5, -0.5), (-3, 1), (3, 0), (2.5, 0.5), (3.5, -0.5), (3, -1)) AS t(a, b)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM density2d_test
+            VISUALISE
+            DRAW density2d MAPPING a AS x, b AS y, side AS color SETTING bins => 4, n => 50
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        assert!(layer_df.height() > 0);
+
+        // Each cluster gets its own contours, which stay on its side
+        let sides: Vec<&str> = layer_df
+            .column("side")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        let xs: Vec<f64> = layer_df
+            .column(&naming::stat_column("x"))
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        assert_eq!(sides.len(), xs.len());
+        for (side, x) in sides.iter().zip(&xs) {
+            assert_eq!(*side == "L", *x < 0.0);
+        }
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_ecdf_stat_transform() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE ecdf_test AS SELECT * FROM (VALUES (1), (2), (2), (4)) AS t(value)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM ecdf_test
+            VISUALISE
+            DRAW ecdf MAPPING value AS x
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer = &result.specs[0].layers[0];
+        assert_eq!(
+            layer.mappings.get("y").and_then(|v| v.column_name()),
+            Some(naming::stat_column("ecdf").as_str())
+        );
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        let column = |name: &str| -> Vec<f64> {
+            layer_df
+                .column(&naming::stat_column(name))
+                .unwrap()
+                .cast(&polars::prelude::DataType::Float64)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect()
+        };
+        let mut steps: Vec<(f64, f64)> = column("x").into_iter().zip(column("ecdf")).collect();
+        steps.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        // Padding row at the smallest value, then one step per distinct value
+        assert_eq!(
+            steps,
+            vec![(1.0, 0.0), (1.0, 0.25), (2.0, 0.75), (4.0, 1.0)]
+        );
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_qq_stat_transform() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE qq_test AS SELECT * FROM (VALUES (3.0), (1.0), (2.0)) AS t(value)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM qq_test
+            VISUALISE
+            DRAW qq MAPPING value AS sample
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer = &result.specs[0].layers[0];
+        assert_eq!(
+            layer.mappings.get("x").and_then(|v| v.column_name()),
+            Some(naming::stat_column("theoretical").as_str())
+        );
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        let column = |name: &str| -> Vec<f64> {
+            layer_df
+                .column(&naming::stat_column(name))
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect()
+        };
+        let mut points: Vec<(f64, f64)> = column("sample")
+            .into_iter()
+            .zip(column("theoretical"))
+            .collect();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let theoretical: Vec<f64> = points.iter().map(|p| p.1).collect();
+
+        // Standard normal quantiles of ppoints(3) = (0.192, 0.5, 0.808)
+        assert_eq!(theoretical.len(), 3);
+        assert!(theoretical[1].abs() < 1e-9);
+        assert!((theoretical[0] + theoretical[2]).abs() < 1e-9);
+        assert!((theoretical[2] - 0.869_423).abs() < 1e-5);
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_qqline_stat_transform() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE qqline_test AS SELECT range + 1 AS value FROM range(9)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM qqline_test
+            VISUALISE
+            DRAW qqline MAPPING value AS sample SETTING distribution => 'uniform'
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        assert_eq!(layer_df.height(), 2);
+
+        // The quartiles 3 and 7 of the sample sit at 0.25 and 0.75
+        let value = |name: &str| -> f64 {
+            layer_df
+                .column(&naming::stat_column(name))
+                .unwrap()
+                .f64()
+                .unwrap()
+                .get(0)
+                .unwrap()
+        };
+        assert!((value("slope") - 8.0).abs() < 1e-9);
+        assert!((value("intercept") - 1.0).abs() < 1e-9);
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_position_stack_bars() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE stack_test AS SELECT * FROM (VALUES
+                    ('A', 'p', 1), ('A', 'q', 3), ('B', 'p', 2), ('B', 'q', 2)
+                ) AS t(category, series, value)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM stack_test
+            VISUALISE
+            DRAW bar MAPPING category AS x, value AS y, series AS fill
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer = &result.specs[0].layers[0];
+        assert_eq!(layer.position, Position::Stack { reverse: false });
+        assert_eq!(
+            layer.mappings.get("y2").and_then(|v| v.column_name()),
+            Some(naming::position_column("ymin").as_str())
+        );
+
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        let sorted = layer_df
+            .sort(["category", "series"], Default::default())
+            .unwrap();
+        let column = |name: &str| -> Vec<f64> {
+            sorted
+                .column(name)
+                .unwrap()
+                .cast(&polars::prelude::DataType::Float64)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect()
+        };
+
+        // The first series ends up on top of each stack
+        assert_eq!(column("value"), vec![4.0, 3.0, 4.0, 2.0]);
+        assert_eq!(
+            column(&naming::position_column("ymin")),
+            vec![3.0, 0.0, 2.0, 0.0]
+        );
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_position_fill_and_dodge() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE fill_test AS SELECT * FROM (VALUES
+                    ('A', 'p', 1), ('A', 'q', 3), ('B', 'p', 2), ('B', 'q', 2)
+                ) AS t(category, series, value)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM fill_test
+            VISUALISE
+            DRAW bar MAPPING category AS x, value AS y, series AS fill SETTING position => 'fill', reverse => true
+            DRAW point MAPPING category AS x, value AS y, series AS color SETTING position => 'dodge'
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let column = |df: &DataFrame, name: &str| -> Vec<f64> {
+            df.sort(["category", "series"], Default::default())
+                .unwrap()
+                .column(name)
+                .unwrap()
+                .cast(&polars::prelude::DataType::Float64)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect()
+        };
+
+        // Reversed fill: the first series sits at the bottom of each normalised stack
+        let fill_df = result.data.get(&naming::layer_key(0)).unwrap();
+        assert_eq!(column(fill_df, "value"), vec![0.25, 1.0, 0.5, 1.0]);
+        assert_eq!(
+            column(fill_df, &naming::position_column("ymin")),
+            vec![0.0, 0.25, 0.0, 0.5]
+        );
+
+        // Dodging on a discrete axis numbers the series within each category
+        let dodge_df = result.data.get(&naming::layer_key(1)).unwrap();
+        assert_eq!(
+            column(dodge_df, &naming::position_column("dodge")),
+            vec![0.0, 1.0, 0.0, 1.0]
+        );
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_position_jitter_and_nudge() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE jitter_test AS SELECT * FROM (VALUES
+                    (1.0, 10.0), (2.0, 20.0), (3.0, 30.0), (4.0, 40.0)
+                ) AS t(a, b)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM jitter_test
+            VISUALISE
+            DRAW point MAPPING a AS x, b AS y SETTING position => 'jitter', jitter_width => 0.2, jitter_height => 0, seed => 7
+            DRAW text MAPPING a AS x, b AS y, 'hi' AS label SETTING position => 'nudge', nudge_y => 1.5
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let column = |df: &DataFrame, name: &str| -> Vec<f64> {
+            df.sort(["a"], Default::default())
+                .unwrap()
+                .column(name)
+                .unwrap()
+                .cast(&polars::prelude::DataType::Float64)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect()
+        };
+
+        // x moves by at most the jitter width; height 0 leaves y alone
+        let jitter_df = result.data.get(&naming::layer_key(0)).unwrap();
+        let jittered = column(jitter_df, "a");
+        assert!(jittered
+            .iter()
+            .zip([1.0, 2.0, 3.0, 4.0])
+            .all(|(x, original)| (x - original).abs() <= 0.2));
+        assert!(jittered.iter().any(|x| x.fract() != 0.0));
+        assert_eq!(column(jitter_df, "b"), vec![10.0, 20.0, 30.0, 40.0]);
+
+        // The same seed jitters the same way every time
+        let again = prepare_data(query, &reader).unwrap();
+        assert_eq!(
+            column(again.data.get(&naming::layer_key(0)).unwrap(), "a"),
+            jittered
+        );
+
+        let nudge_df = result.data.get(&naming::layer_key(1)).unwrap();
+        assert_eq!(column(nudge_df, "a"), vec![1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(column(nudge_df, "b"), vec![11.5, 21.5, 31.5, 41.5]);
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_histogram_temporal_binwidth() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE events AS SELECT CAST(d AS DATE) AS day FROM (VALUES
+                    ('2024-01-01'), ('2024-01-03'), ('2024-01-08'), ('2024-01-14'), ('2024-01-15')
+                ) AS t(d)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM events
+            VISUALISE
+            DRAW histogram MAPPING day AS x SETTING binwidth => '1 week'
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        let stat_bin = naming::stat_column("bin");
+        let sorted = layer_df
+            .sort([stat_bin.as_str()], Default::default())
+            .unwrap();
+
+        // Bins keep the DATE type and start on Mondays
+        let bins = sorted.column(&stat_bin).unwrap();
+        assert_eq!(bins.dtype(), &polars::prelude::DataType::Date);
+        let bin_end = sorted.column(&naming::stat_column("bin_end")).unwrap();
+        assert_eq!(bin_end.dtype(), &polars::prelude::DataType::Date);
+        let days: Vec<String> = bins
+            .cast(&polars::prelude::DataType::String)
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(days, vec!["2024-01-01", "2024-01-08", "2024-01-15"]);
+        let counts: Vec<f64> = sorted
+            .column(&naming::stat_column("count"))
+            .unwrap()
+            .cast(&polars::prelude::DataType::Float64)
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        assert_eq!(counts, vec![2.0, 2.0, 1.0]);
+
+        // A numeric binwidth makes no sense for dates
+        let query = r#"
+            SELECT * FROM events
+            VISUALISE
+            DRAW histogram MAPPING day AS x SETTING binwidth => 7
+        "#;
+        assert!(prepare_data(query, &reader).is_err());
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_bar_unit_truncates_dates() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE orders AS SELECT CAST(d AS TIMESTAMP) AS placed, v AS amount FROM (VALUES
+                    ('2024-01-05 10:00:00', 1), ('2024-01-20 12:30:00', 2), ('2024-02-02 08:00:00', 4)
+                ) AS t(d, v)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM orders
+            VISUALISE
+            DRAW bar MAPPING placed AS x SETTING unit => 'month'
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        let sorted = layer_df.sort(["placed"], Default::default()).unwrap();
+        assert!(matches!(
+            sorted.column("placed").unwrap().dtype(),
+            polars::prelude::DataType::Datetime(_, _)
+        ));
+        let counts: Vec<f64> = sorted
+            .column(&naming::stat_column("count"))
+            .unwrap()
+            .cast(&polars::prelude::DataType::Float64)
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        assert_eq!(counts, vec![2.0, 1.0]);
+
+        // With y mapped the values are kept, only x is truncated
+        let query = r#"
+            SELECT * FROM orders
+            VISUALISE
+            DRAW bar MAPPING placed AS x, amount AS y SETTING unit => 'month'
+        "#;
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        let months: Vec<String> = layer_df
+            .column("placed")
+            .unwrap()
+            .cast(&polars::prelude::DataType::String)
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .map(|s| s[..10].to_string())
+            .collect();
+        assert_eq!(months.len(), 3);
+        assert!(months.iter().all(|m| m.ends_with("-01")));
+
+        // unit only applies to temporal x
+        let query = r#"
+            SELECT * FROM orders
+            VISUALISE
+            DRAW bar MAPPING amount AS x SETTING unit => 'month'
+        "#;
+        assert!(prepare_data(query, &reader).is_err());
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_histogram_bins_in_log_space() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        reader
+            .connection()
+            .execute(
+                "CREATE TABLE magnitudes AS SELECT * FROM (VALUES (1.0), (10.0), (100.0), (1000.0)) AS t(v)",
+                duckdb::params![],
+            )
+            .unwrap();
+
+        let query = r#"
+            SELECT * FROM magnitudes
+            VISUALISE
+            DRAW histogram MAPPING v AS x SETTING bins => 4
+            SCALE x SETTING type => 'log10'
+        "#;
+
+        let result = prepare_data(query, &reader).unwrap();
+        let layer_df = result.data.get(&naming::layer_key(0)).unwrap();
+        let column = |name: &str| -> Vec<f64> {
+            layer_df
+                .column(&naming::stat_column(name))
+                .unwrap()
+                .cast(&polars::prelude::DataType::Float64)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect()
+        };
+
+        // Bins have equal widths on the log scale, so each spans a factor of 10
+        let (bins, ends) = (column("bin"), column("bin_end"));
+        assert_eq!(bins.len(), 4);
+        for (bin, end) in bins.iter().zip(&ends) {
+            assert!((end / bin - 10.0).abs() < 1e-9);
+        }
+        assert_eq!(column("count"), vec![1.0; 4]);
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_log_scale_rejects_non_positive_values() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        let query = r#"
+            SELECT * FROM (VALUES (1, 0.0), (2, 5.0)) AS t(x, y)
+            VISUALISE x, y
+            DRAW point
+            SCALE y SETTING type => 'log'
+        "#;
+        let err = prepare_data(query, &reader).err().unwrap();
+        assert!(err.to_string().contains("requires positive values"));
+
+        let query = r#"
+            SELECT * FROM (VALUES (1, 0.0), (2, 5.0)) AS t(x, y)
+            VISUALISE x, y
+            DRAW point
+            SCALE y SETTING type => 'sqrt'
+        "#;
+        assert!(prepare_data(query, &reader).is_ok());
+
+        // Stats read the transformed column, so it is checked before they run
+        let query = r#"
+            SELECT * FROM (VALUES (0.0), (1.0), (10.0)) AS t(x)
+            VISUALISE x
+            DRAW histogram
+            SCALE x SETTING type => 'log10'
+        "#;
+        let err = prepare_data(query, &reader).err().unwrap();
+        assert!(matches!(err, GgsqlError::ValidationError(_)));
+        assert!(err
+            .to_string()
+            .contains("log10 scale of 'x' requires positive values"));
+
+        let query = r#"
+            SELECT * FROM (VALUES (-1.0), (4.0), (9.0)) AS t(x)
+            VISUALISE x
+            DRAW density
+            SCALE x SETTING type => 'sqrt'
+        "#;
+        let err = prepare_data(query, &reader).err().unwrap();
+        assert!(err.to_string().contains("requires non-negative values"));
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_scale_oob_censor_and_squish() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        let query = r#"
+            SELECT * FROM (VALUES (1, 0.0), (2, 5.0), (3, 10.0), (4, 15.0), (5, 20.0)) AS t(x, y)
+            VISUALISE x, y
+            DRAW point
+            SCALE x SETTING limits => [2, 4], oob => 'censor'
+            SCALE y SETTING limits => [5, 12], oob => 'squish'
+        "#;
+        let result = prepare_data(query, &reader).unwrap();
+        let df = result.data.get(&naming::layer_key(0)).unwrap();
+        let x: Vec<f64> = df
+            .column("x")
+            .unwrap()
+            .cast(&polars::prelude::DataType::Float64)
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect();
+        let y: Vec<f64> = df
+            .column("y")
+            .unwrap()
+            .cast(&polars::prelude::DataType::Float64)
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect();
+        assert_eq!(x, vec![2.0, 3.0, 4.0]);
+        assert_eq!(y, vec![5.0, 10.0, 12.0]);
+
+        // Censoring happens before stats, so the histogram only counts kept rows
+        let query = r#"
+            SELECT * FROM (VALUES (1.0), (2.0), (3.0), (100.0)) AS t(x)
+            VISUALISE x
+            DRAW histogram SETTING bins => 2
+            SCALE x SETTING limits => [0, 10], oob => 'censor'
+        "#;
+        let result = prepare_data(query, &reader).unwrap();
+        let df = result.data.get(&naming::layer_key(0)).unwrap();
+        let total: f64 = df
+            .column(&naming::stat_column("count"))
+            .unwrap()
+            .cast(&polars::prelude::DataType::Float64)
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .sum();
+        assert_eq!(total, 3.0);
+
+        let query = r#"
+            SELECT 1 AS x, 2 AS y
+            VISUALISE x, y
+            DRAW point
+            SCALE x SETTING oob => 'squish'
+        "#;
+        let err = prepare_data(query, &reader).err().unwrap();
+        assert!(err.to_string().contains("oob needs numeric limits"));
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_trained_scales() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        let query = r#"
+            SELECT * FROM (VALUES (1, 'a', 2.0), (4, 'b', 8.0)) AS t(x, g, y)
+            VISUALISE x, y
+            DRAW point MAPPING g AS color
+            DRAW line MAPPING y AS x
+        "#;
+        let result = prepare_data(query, &reader).unwrap();
+        let spec = &result.specs[0];
+        let x = spec.find_trained_scale("x").unwrap();
+        assert_eq!(x.kind, ScaleKind::Continuous);
+        assert_eq!(x.domain, Some(ScaleDomain::Range(1.0, 8.0)));
+        let color = spec.find_trained_scale("color").unwrap();
+        assert_eq!(color.kind, ScaleKind::Discrete);
+        assert_eq!(
+            color.domain,
+            Some(ScaleDomain::Values(vec!["a".to_string(), "b".to_string()]))
+        );
+
+        // Numbers in one layer and text in another can't share a scale
+        let query = r#"
+            SELECT * FROM (VALUES (1, 'a', 2.0), (4, 'b', 8.0)) AS t(x, g, y)
+            VISUALISE y
+            DRAW point MAPPING x AS x
+            DRAW point MAPPING g AS x
+        "#;
+        let err = prepare_data(query, &reader).err().unwrap();
+        assert!(err.to_string().contains(
+            "'x' is mapped to continuous data in layer 1 but to discrete data in layer 2"
+        ));
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_facet_layer_without_facet_variable() {
+        let reader = DuckDBReader::from_connection_string("duckdb://memory").unwrap();
+
+        // The histogram's data has no g, so it isn't grouped by it
+        let query = r#"
+            WITH all_x AS (SELECT x FROM (VALUES (1.0), (2.0), (4.0)) AS t(x))
+            SELECT * FROM (VALUES (1.0, 'a'), (4.0, 'b')) AS t(x, g)
+            VISUALISE
+            DRAW histogram MAPPING x AS x FROM all_x SETTING bins => 2
+            DRAW point MAPPING x AS x, x AS y
+            FACET WRAP g
+        "#;
+        let result = prepare_data(query, &reader).unwrap();
+        let histogram = result.data.get(&naming::layer_key(0)).unwrap();
+        assert!(histogram.column("g").is_err());
+        assert_eq!(histogram.height(), 2);
+    }
+
     #[cfg(feature = "duckdb")]
     #[test]
     fn test_bar_uses_y_when_mapped() {
@@ -73,6 +73,9 @@ pub enum GgsqlError {
     #[error("Output generation error: {0}")]
     WriterError(String),
 
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+
     #[error("Internal error: {0}")]
     InternalError(String),
 }
@@ -572,7 +575,7 @@ mod integration_tests {
         assert_eq!(vl_spec["layer"].as_array().unwrap().len(), 2);
 
         // Verify the color aesthetic is mapped to layer-indexed synthetic columns
-        let layer0_color = &vl_spec["layer"][0]["encoding"]["linetype"];
+        let layer0_color = &vl_spec["layer"][0]["encoding"]["strokeDash"];
         let layer1_color = &vl_spec["layer"][1]["encoding"]["shape"];
 
         // Constants should be field-mapped to layer-indexed columns
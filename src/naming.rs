@@ -8,8 +8,10 @@
 //! - **CTE tables**: Temp tables created from WITH clause CTEs (`__ggsql_cte_<name>_<uuid>__`)
 //! - **Constant columns**: Synthetic columns for literal values (`__ggsql_const_<aesthetic>__`)
 //! - **Stat columns**: Columns produced by statistical transforms (`__ggsql_stat__<name>`)
+//! - **Position columns**: Columns added by position adjustments (`__ggsql_pos_<name>__`)
 //! - **Data keys**: Keys for data sources in the data map (`__ggsql_global__`, `__ggsql_layer_<idx>__`)
 //! - **Ordering column**: Window function for preserving data order (`__ggsql_order__`)
+//! - **Group column**: Group index for stats computed outside SQL (`__ggsql_group__`)
 //! - **Session ID**: Process-wide UUID for temp table uniqueness
 
 use const_format::concatcp;
@@ -60,6 +62,9 @@ const CONST_PREFIX: &str = concatcp!(GGSQL_PREFIX, "const_");
 /// Full prefix for stat columns: `__ggsql_stat__`
 const STAT_PREFIX: &str = concatcp!(GGSQL_PREFIX, "stat_");
 
+/// Full prefix for position columns: `__ggsql_pos_`
+const POSITION_PREFIX: &str = concatcp!(GGSQL_PREFIX, "pos_");
+
 /// Full prefix for CTE tables: `__ggsql_cte_`
 const CTE_PREFIX: &str = concatcp!(GGSQL_PREFIX, "cte_");
 
@@ -71,9 +76,19 @@ const LAYER_PREFIX: &str = concatcp!(GGSQL_PREFIX, "layer_");
 /// This is NOT a SQL table name - use `global_table()` for SQL statements.
 pub const GLOBAL_DATA_KEY: &str = concatcp!(GGSQL_PREFIX, "global", GGSQL_SUFFIX);
 
+/// Key of the dataset combining the data of all layers when they are faceted
+/// together but come from different sources
+pub const FACET_DATA_KEY: &str = concatcp!(GGSQL_PREFIX, "facet_data", GGSQL_SUFFIX);
+
+/// Column naming the dataset each row of the combined facet data came from
+pub const SOURCE_COLUMN: &str = concatcp!(GGSQL_PREFIX, "source", GGSQL_SUFFIX);
+
 /// Column name for row ordering in Vega-Lite (used by Path geom)
 pub const ORDER_COLUMN: &str = concatcp!(GGSQL_PREFIX, "order", GGSQL_SUFFIX);
 
+/// Column name for the group index used to join stat results back onto their groups
+pub const GROUP_COLUMN: &str = concatcp!(GGSQL_PREFIX, "group", GGSQL_SUFFIX);
+
 /// Alias for schema extraction queries
 pub const SCHEMA_ALIAS: &str = concatcp!(GGSQL_SUFFIX, "schema", GGSQL_SUFFIX);
 
@@ -167,6 +182,47 @@ pub fn stat_column(stat_name: &str) -> String {
     format!("{}{}", STAT_PREFIX, stat_name)
 }
 
+/// Generate column name for a position adjustment output.
+///
+/// These columns are added by position adjustments like stack and dodge.
+///
+/// # Example
+/// ```
+/// use ggsql::naming;
+/// assert_eq!(naming::position_column("ymin"), "__ggsql_pos_ymin__");
+/// assert_eq!(naming::position_column("dodge"), "__ggsql_pos_dodge__");
+/// ```
+pub fn position_column(name: &str) -> String {
+    format!("{}{}{}", POSITION_PREFIX, name, GGSQL_SUFFIX)
+}
+
+/// Generate column name for the values of a secondary axis.
+///
+/// Used by the invisible layer that draws the secondary axis of a position scale.
+///
+/// # Example
+/// ```
+/// use ggsql::naming;
+/// assert_eq!(naming::secondary_axis_column("y"), "__ggsql_sec_y__");
+/// ```
+pub fn secondary_axis_column(channel: &str) -> String {
+    format!("{}sec_{}{}", GGSQL_PREFIX, channel, GGSQL_SUFFIX)
+}
+
+/// Generate column name for the panel labels of a facet.
+///
+/// Used when a facet row, column or wrap combines several variables or
+/// writes labels other than the plain values.
+///
+/// # Example
+/// ```
+/// use ggsql::naming;
+/// assert_eq!(naming::facet_column("row"), "__ggsql_facet_row__");
+/// ```
+pub fn facet_column(channel: &str) -> String {
+    format!("{}facet_{}{}", GGSQL_PREFIX, channel, GGSQL_SUFFIX)
+}
+
 /// Generate dataset key for layer-specific data.
 ///
 /// Used when a layer has its own data source (FROM clause, filter, etc.)
@@ -212,6 +268,18 @@ pub fn is_stat_column(name: &str) -> bool {
     name.starts_with(STAT_PREFIX)
 }
 
+/// Check if a column name is a position adjustment column.
+///
+/// # Example
+/// ```
+/// use ggsql::naming;
+/// assert!(naming::is_position_column("__ggsql_pos_ymin__"));
+/// assert!(!naming::is_position_column("__ggsql_stat_count"));
+/// ```
+pub fn is_position_column(name: &str) -> bool {
+    name.starts_with(POSITION_PREFIX)
+}
+
 /// Check if a column name is any synthetic ggsql column.
 ///
 /// # Example
@@ -219,10 +287,11 @@ pub fn is_stat_column(name: &str) -> bool {
 /// use ggsql::naming;
 /// assert!(naming::is_synthetic_column("__ggsql_const_color__"));
 /// assert!(naming::is_synthetic_column("__ggsql_stat_count"));
+/// assert!(naming::is_synthetic_column("__ggsql_pos_ymin__"));
 /// assert!(!naming::is_synthetic_column("revenue"));
 /// ```
 pub fn is_synthetic_column(name: &str) -> bool {
-    is_const_column(name) || is_stat_column(name)
+    is_const_column(name) || is_stat_column(name) || is_position_column(name)
 }
 
 /// Extract the stat name from a stat column (for display purposes).
@@ -341,7 +410,10 @@ mod tests {
     #[test]
     fn test_constants() {
         assert_eq!(GLOBAL_DATA_KEY, "__ggsql_global__");
+        assert_eq!(FACET_DATA_KEY, "__ggsql_facet_data__");
+        assert_eq!(SOURCE_COLUMN, "__ggsql_source__");
         assert_eq!(ORDER_COLUMN, "__ggsql_order__");
+        assert_eq!(GROUP_COLUMN, "__ggsql_group__");
         assert_eq!(SCHEMA_ALIAS, "__schema__");
     }
 
@@ -211,6 +211,8 @@ fn validate_scale_coord_conflicts(spec: &Plot) -> Result<()> {
             .properties
             .keys()
             .filter(|k| is_aesthetic_name(k))
+            // Under COORD trans, x and y name transforms rather than domains
+            .filter(|k| !(coord.coord_type == CoordType::Trans && (*k == "x" || *k == "y")))
             .cloned()
             .collect();
 
@@ -481,11 +483,12 @@ fn parse_parameter_assignment(node: &Node, source: &str) -> Result<(String, Para
     Ok((param_name, param_value.unwrap()))
 }
 
-/// Parse a parameter_value (string, number, or boolean)
+/// Parse a parameter_value (string, number, boolean, or array)
 fn parse_parameter_value(node: &Node, source: &str) -> Result<ParameterValue> {
     let mut cursor = node.walk();
     for child in node.children(&mut cursor) {
         match child.kind() {
+            "array" => return Ok(parse_array_value(&child, source)),
             "string" => {
                 let text = get_node_text(&child, source);
                 let unquoted = text.trim_matches(|c| c == '\'' || c == '"');
@@ -572,6 +575,19 @@ fn parse_geom_type(text: &str) -> Result<Geom> {
         "vline" => Ok(Geom::vline()),
         "abline" => Ok(Geom::abline()),
         "errorbar" => Ok(Geom::errorbar()),
+        "bin2d" => Ok(Geom::bin2d()),
+        "hex" => Ok(Geom::hex()),
+        "density2d" => Ok(Geom::density2d()),
+        "contour" => Ok(Geom::contour()),
+        "ecdf" => Ok(Geom::ecdf()),
+        "qq" => Ok(Geom::qq()),
+        "qqline" => Ok(Geom::qqline()),
+        "step" => Ok(Geom::step()),
+        "rect" => Ok(Geom::rect()),
+        "rug" => Ok(Geom::rug()),
+        "linerange" => Ok(Geom::linerange()),
+        "pointrange" => Ok(Geom::pointrange()),
+        "crossbar" => Ok(Geom::crossbar()),
         _ => Err(GgsqlError::ParseError(format!(
             "Unknown geom type: {}",
             text
@@ -681,20 +697,44 @@ fn build_scale(node: &Node, source: &str) -> Result<Scale> {
             }
             properties.insert("palette".to_string(), ParameterValue::Array(hex_codes));
         }
+        if let Some(ParameterValue::Map(entries)) = properties.get("values") {
+            let entries = entries
+                .iter()
+                .map(|(key, value)| match value {
+                    ArrayElement::String(color) => {
+                        (key.clone(), ArrayElement::String(color_to_hex(color)))
+                    }
+                    _ => (key.clone(), value.clone()),
+                })
+                .collect();
+            properties.insert("values".to_string(), ParameterValue::Map(entries));
+        }
     }
 
-    Ok(Scale {
+    let mut scale = Scale {
         aesthetic,
         scale_type,
         properties,
-    })
+    };
+    // Resolve named and implied palettes to explicit colours
+    if matches!(
+        scale.aesthetic.as_str(),
+        "stroke" | "colour" | "fill" | "color" | "col"
+    ) {
+        resolve_palette(&mut scale)?;
+    }
+    // Check the secondary axis transform early
+    SecondaryAxis::from_scale(&scale)?;
+    Ok(scale)
 }
 
 /// Parse scale type from text
 fn parse_scale_type(text: &str) -> Result<ScaleType> {
     match text.to_lowercase().as_str() {
         "linear" => Ok(ScaleType::Linear),
-        "log" | "log10" => Ok(ScaleType::Log),
+        "log10" => Ok(ScaleType::Log10),
+        "log" => Ok(ScaleType::Log),
+        "log2" => Ok(ScaleType::Log2),
         "sqrt" => Ok(ScaleType::Sqrt),
         "reverse" => Ok(ScaleType::Reverse),
         "categorical" => Ok(ScaleType::Categorical),
@@ -703,8 +743,14 @@ fn parse_scale_type(text: &str) -> Result<ScaleType> {
         "datetime" => Ok(ScaleType::DateTime),
         "viridis" => Ok(ScaleType::Viridis),
         "plasma" => Ok(ScaleType::Plasma),
+        "magma" => Ok(ScaleType::Magma),
+        "inferno" => Ok(ScaleType::Inferno),
+        "cividis" => Ok(ScaleType::Cividis),
         "diverging" => Ok(ScaleType::Diverging),
         "sequential" => Ok(ScaleType::Sequential),
+        "binned" | "quantize" => Ok(ScaleType::Binned),
+        "quantile" => Ok(ScaleType::Quantile),
+        "threshold" => Ok(ScaleType::Threshold),
         "identity" => Ok(ScaleType::Identity),
         "manual" => Ok(ScaleType::Manual),
         _ => Err(GgsqlError::ParseError(format!(
@@ -769,6 +815,32 @@ fn parse_scale_property_value(node: &Node, source: &str) -> Result<ParameterValu
                 }
                 return Ok(ParameterValue::Array(values));
             }
+            "map" => {
+                // Parse key-value pairs, keeping the order they were written in
+                let mut entries = Vec::new();
+                let mut map_cursor = child.walk();
+                for entry in child.children(&mut map_cursor) {
+                    if entry.kind() != "map_entry" {
+                        continue;
+                    }
+                    let key = entry
+                        .child_by_field_name("key")
+                        .and_then(|n| parse_array_element(&n, source));
+                    let value = entry
+                        .child_by_field_name("value")
+                        .and_then(|n| parse_array_element(&n, source));
+                    match (key, value) {
+                        (Some(key), Some(value)) => entries.push((key, value)),
+                        _ => {
+                            return Err(GgsqlError::ParseError(format!(
+                                "Invalid map entry: {}",
+                                get_node_text(&entry, source)
+                            )))
+                        }
+                    }
+                }
+                return Ok(ParameterValue::Map(entries));
+            }
             _ => {}
         }
     }
@@ -779,19 +851,46 @@ fn parse_scale_property_value(node: &Node, source: &str) -> Result<ParameterValu
     )))
 }
 
+/// Parse the value wrapped by an array_element node
+fn parse_array_element(node: &Node, source: &str) -> Option<ArrayElement> {
+    let mut cursor = node.walk();
+    let child = node.children(&mut cursor).next()?;
+    let text = get_node_text(&child, source);
+    match child.kind() {
+        "string" => Some(ArrayElement::String(
+            text.trim_matches(|c| c == '\'' || c == '"').to_string(),
+        )),
+        "number" => text.parse::<f64>().ok().map(ArrayElement::Number),
+        "boolean" => Some(ArrayElement::Boolean(text == "true")),
+        _ => None,
+    }
+}
+
+/// Parse an array node into an array of values, as used by COORD and SETTING
+fn parse_array_value(node: &Node, source: &str) -> ParameterValue {
+    let mut cursor = node.walk();
+    let values = node
+        .children(&mut cursor)
+        .filter(|child| child.kind() == "array_element")
+        .filter_map(|element| parse_array_element(&element, source))
+        .collect();
+    ParameterValue::Array(values)
+}
+
 /// Build a Facet from a facet_clause node
 fn build_facet(node: &Node, source: &str) -> Result<Facet> {
     let mut is_wrap = false;
     let mut row_vars = Vec::new();
     let mut col_vars = Vec::new();
     let mut scales = FacetScales::Fixed;
+    let mut properties = HashMap::new();
 
     let mut cursor = node.walk();
     let mut next_vars_are_cols = false;
 
     for child in node.children(&mut cursor) {
         match child.kind() {
-            "FACET" | "SETTING" | "=>" => continue,
+            "FACET" | "SETTING" | "=>" | "," => continue,
             "facet_wrap" => {
                 is_wrap = true;
             }
@@ -809,25 +908,57 @@ fn build_facet(node: &Node, source: &str) -> Result<Facet> {
                     row_vars = vars;
                 }
             }
-            "facet_scales" => {
-                scales = parse_facet_scales(&child, source)?;
+            "facet_property" => {
+                let mut prop_cursor = child.walk();
+                let mut prop_name = String::new();
+                let mut prop_value: Option<ParameterValue> = None;
+
+                for prop_child in child.children(&mut prop_cursor) {
+                    match prop_child.kind() {
+                        "facet_property_name" => {
+                            prop_name = get_node_text(&prop_child, source);
+                        }
+                        "facet_scales" | "string" | "number" | "boolean" => {
+                            prop_value = Some(parse_facet_property_value(&prop_child, source)?);
+                        }
+                        _ => {}
+                    }
+                }
+                match (prop_name.as_str(), prop_value) {
+                    ("scales", Some(ParameterValue::String(text))) => {
+                        scales = parse_facet_scales(&text)?;
+                    }
+                    ("scales", _) => {
+                        return Err(GgsqlError::ParseError(
+                            "FACET scales must be one of: fixed, free, free_x, free_y".to_string(),
+                        ))
+                    }
+                    (_, Some(value)) => {
+                        properties.insert(prop_name, value);
+                    }
+                    _ => {}
+                }
             }
             _ => {}
         }
     }
 
-    if is_wrap {
-        Ok(Facet::Wrap {
+    let facet = if is_wrap {
+        Facet::Wrap {
             variables: row_vars,
             scales,
-        })
+            properties,
+        }
     } else {
-        Ok(Facet::Grid {
+        Facet::Grid {
             rows: row_vars,
             cols: col_vars,
             scales,
-        })
-    }
+            properties,
+        }
+    };
+    facet.validate()?;
+    Ok(facet)
 }
 
 /// Parse facet variables from a facet_vars node
@@ -848,16 +979,44 @@ fn parse_facet_vars(node: &Node, source: &str) -> Result<Vec<String>> {
     Ok(vars)
 }
 
-/// Parse facet scales from a facet_scales node
-fn parse_facet_scales(node: &Node, source: &str) -> Result<FacetScales> {
-    let text = get_node_text(node, source);
-    match text.as_str() {
+/// Parse facet property value
+fn parse_facet_property_value(node: &Node, source: &str) -> Result<ParameterValue> {
+    match node.kind() {
+        // Unquoted scales option, e.g. `scales => free_y`
+        "facet_scales" => Ok(ParameterValue::String(get_node_text(node, source))),
+        "string" => {
+            let text = get_node_text(node, source);
+            let unquoted = text.trim_matches(|c| c == '\'' || c == '"');
+            Ok(ParameterValue::String(unquoted.to_string()))
+        }
+        "number" => {
+            let text = get_node_text(node, source);
+            let num = text.parse::<f64>().map_err(|e| {
+                GgsqlError::ParseError(format!("Failed to parse number '{}': {}", text, e))
+            })?;
+            Ok(ParameterValue::Number(num))
+        }
+        "boolean" => {
+            let text = get_node_text(node, source);
+            let bool_val = text == "true";
+            Ok(ParameterValue::Boolean(bool_val))
+        }
+        _ => Err(GgsqlError::ParseError(format!(
+            "Unexpected facet property value type: {}",
+            node.kind()
+        ))),
+    }
+}
+
+/// Parse facet scales from the value of the scales setting
+fn parse_facet_scales(text: &str) -> Result<FacetScales> {
+    match text {
         "fixed" => Ok(FacetScales::Fixed),
         "free" => Ok(FacetScales::Free),
         "free_x" => Ok(FacetScales::FreeX),
         "free_y" => Ok(FacetScales::FreeY),
         _ => Err(GgsqlError::ParseError(format!(
-            "Unknown facet scales: {}",
+            "Unknown facet scales: {}. Expected one of: fixed, free, free_x, free_y",
             text
         ))),
     }
@@ -893,10 +1052,12 @@ fn build_coord(node: &Node, source: &str) -> Result<Coord> {
     // Validate properties for this coord type
     validate_coord_properties(&coord_type, &properties)?;
 
-    Ok(Coord {
+    let coord = Coord {
         coord_type,
         properties,
-    })
+    };
+    coord.validate()?;
+    Ok(coord)
 }
 
 /// Parse a single coord_property node into (name, value)
@@ -957,33 +1118,48 @@ fn validate_coord_properties(
 ) -> Result<()> {
     for prop_name in properties.keys() {
         let valid = match coord_type {
-            CoordType::Cartesian => {
-                // Cartesian allows: xlim, ylim, aesthetic names
-                // Not allowed: theta
-                prop_name == "xlim" || prop_name == "ylim" || is_aesthetic_name(prop_name)
+            CoordType::Cartesian | CoordType::QuickMap | CoordType::Trans => {
+                // Cartesian allows: xlim, ylim, clip, aesthetic names
+                // (under trans, x and y name transforms)
+                // Not allowed: theta, ratio
+                prop_name == "xlim"
+                    || prop_name == "ylim"
+                    || prop_name == "clip"
+                    || is_aesthetic_name(prop_name)
+            }
+            CoordType::Fixed => {
+                // Fixed also allows the aspect ratio
+                prop_name == "xlim"
+                    || prop_name == "ylim"
+                    || prop_name == "clip"
+                    || prop_name == "ratio"
+                    || is_aesthetic_name(prop_name)
             }
             CoordType::Flip => {
-                // Flip allows: aesthetic names only
+                // Flip allows: clip, aesthetic names
                 // Not allowed: xlim, ylim, theta
-                is_aesthetic_name(prop_name)
+                prop_name == "clip" || is_aesthetic_name(prop_name)
             }
             CoordType::Polar => {
                 // Polar allows: theta, aesthetic names
                 // Not allowed: xlim, ylim
                 prop_name == "theta" || is_aesthetic_name(prop_name)
             }
-            _ => {
-                // Other coord types: allow all for now (future implementation)
+            CoordType::Map => {
+                // Rejected as a whole by Coord::validate
                 true
             }
         };
 
         if !valid {
             let valid_props = match coord_type {
-                CoordType::Cartesian => "xlim, ylim, <aesthetics>",
-                CoordType::Flip => "<aesthetics>",
+                CoordType::Cartesian | CoordType::QuickMap | CoordType::Trans => {
+                    "xlim, ylim, clip, <aesthetics>"
+                }
+                CoordType::Fixed => "ratio, xlim, ylim, clip, <aesthetics>",
+                CoordType::Flip => "clip, <aesthetics>",
                 CoordType::Polar => "theta, <aesthetics>",
-                _ => "<varies>",
+                CoordType::Map => "<none>",
             };
             return Err(GgsqlError::ParseError(format!(
                 "Property '{}' not valid for {:?} coordinates. Valid properties: {}",
@@ -1063,39 +1239,7 @@ fn parse_coord_property_value(node: &Node, source: &str) -> Result<ParameterValu
             let bool_val = text == "true";
             Ok(ParameterValue::Boolean(bool_val))
         }
-        "array" => {
-            // Parse array of values
-            let mut values = Vec::new();
-            let mut array_cursor = node.walk();
-            for array_child in node.children(&mut array_cursor) {
-                if array_child.kind() == "array_element" {
-                    // Array elements wrap the actual values
-                    let mut elem_cursor = array_child.walk();
-                    for elem_child in array_child.children(&mut elem_cursor) {
-                        match elem_child.kind() {
-                            "string" => {
-                                let text = get_node_text(&elem_child, source);
-                                let unquoted = text.trim_matches(|c| c == '\'' || c == '"');
-                                values.push(ArrayElement::String(unquoted.to_string()));
-                            }
-                            "number" => {
-                                let text = get_node_text(&elem_child, source);
-                                if let Ok(num) = text.parse::<f64>() {
-                                    values.push(ArrayElement::Number(num));
-                                }
-                            }
-                            "boolean" => {
-                                let text = get_node_text(&elem_child, source);
-                                let bool_val = text == "true";
-                                values.push(ArrayElement::Boolean(bool_val));
-                            }
-                            _ => continue,
-                        }
-                    }
-                }
-            }
-            Ok(ParameterValue::Array(values))
-        }
+        "array" => Ok(parse_array_value(node, source)),
         _ => Err(GgsqlError::ParseError(format!(
             "Unexpected coord property value type: {}",
             node.kind()
@@ -1598,6 +1742,54 @@ mod tests {
             .contains("Property 'ylim' not valid for Polar"));
     }
 
+    #[test]
+    fn test_coord_fixed_and_trans_settings() {
+        let query = r#"
+            VISUALISE
+            DRAW point MAPPING a AS x, b AS y
+            COORD fixed SETTING ratio => 2, clip => 'off'
+        "#;
+        let specs = parse_test_query(query).unwrap();
+        let coord = specs[0].coord.as_ref().unwrap();
+        assert_eq!(coord.coord_type, CoordType::Fixed);
+        assert_eq!(coord.ratio().unwrap(), 2.0);
+        assert_eq!(coord.clip().unwrap(), Some(false));
+
+        let query = r#"
+            VISUALISE
+            DRAW point MAPPING a AS x, b AS y
+            SCALE x SETTING domain => [1, 100]
+            COORD trans SETTING x => 'log10'
+        "#;
+        let specs = parse_test_query(query).unwrap();
+        let coord = specs[0].coord.as_ref().unwrap();
+        assert_eq!(coord.transform("x").unwrap(), CoordTransform::Log10);
+
+        for (setting, message) in [
+            (
+                "COORD cartesian SETTING ratio => 2",
+                "Property 'ratio' not valid",
+            ),
+            (
+                "COORD fixed SETTING ratio => 0",
+                "'ratio' must be a positive number",
+            ),
+            (
+                "COORD trans SETTING y => 'cube'",
+                "Unknown COORD trans transform 'cube'",
+            ),
+            (
+                "COORD flip SETTING clip => 'sometimes'",
+                "'clip' must be 'on' or 'off'",
+            ),
+            ("COORD map", "COORD map is not supported"),
+        ] {
+            let query = format!("VISUALISE DRAW point MAPPING a AS x, b AS y {}", setting);
+            let err = parse_test_query(&query).unwrap_err().to_string();
+            assert!(err.contains(message), "{}: {}", setting, err);
+        }
+    }
+
     // ========================================
     // SCALE/COORD Domain Conflict Tests
     // ========================================
@@ -3134,4 +3326,99 @@ mod tests {
         assert!(ok);
         eprintln!("{:?}", palette);
     }
+
+    #[test]
+    fn test_facet_settings() {
+        let query = r#"
+          VISUALISE x, y
+          DRAW point
+          FACET WRAP region, year SETTING scales => 'free_y', ncol => 3, label => 'both'
+        "#;
+        let specs = parse_test_query(query).unwrap();
+        let facet = specs[0].facet.as_ref().unwrap();
+        assert_eq!(facet.get_variables(), vec!["region", "year"]);
+        assert_eq!(facet.scales(), &FacetScales::FreeY);
+        assert_eq!(facet.layout_count("ncol"), Some(3));
+        assert_eq!(facet.label(), FacetLabel::Both);
+
+        // The unquoted form of scales is still accepted
+        let query = r#"
+          VISUALISE x, y
+          DRAW point
+          FACET region BY year SETTING scales => free
+        "#;
+        let specs = parse_test_query(query).unwrap();
+        assert_eq!(
+            specs[0].facet.as_ref().unwrap().scales(),
+            &FacetScales::Free
+        );
+
+        let query = r#"
+          VISUALISE x, y
+          DRAW point
+          FACET region BY year SETTING ncol => 2
+        "#;
+        let err = parse_test_query(query).unwrap_err().to_string();
+        assert!(err.contains("only applies to FACET WRAP"));
+    }
+
+    #[test]
+    fn test_scale_values_map() {
+        let query = r#"
+          VISUALISE foo AS x, bar AS color
+          SCALE color SETTING values => {'Adelie': 'red', 'Gentoo': '#1B9E77'}
+        "#;
+        let specs = parse_test_query(query).unwrap();
+
+        let values = specs[0].scales[0].properties.get("values").unwrap();
+        assert_eq!(
+            values,
+            &ParameterValue::Map(vec![
+                (
+                    ArrayElement::String("Adelie".to_string()),
+                    ArrayElement::String("#ff0000".to_string())
+                ),
+                (
+                    ArrayElement::String("Gentoo".to_string()),
+                    ArrayElement::String("#1b9e77".to_string())
+                ),
+            ])
+        );
+
+        let query = r#"
+          VISUALISE foo AS x, bar AS size
+          SCALE size SETTING values => {1: 2, 2: 6}
+        "#;
+        let specs = parse_test_query(query).unwrap();
+        assert_eq!(
+            specs[0].scales[0].properties.get("values"),
+            Some(&ParameterValue::Map(vec![
+                (ArrayElement::Number(1.0), ArrayElement::Number(2.0)),
+                (ArrayElement::Number(2.0), ArrayElement::Number(6.0)),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_colour_scale_named_palette() {
+        let query = r#"
+          VISUALISE foo AS x, bar AS fill
+          SCALE fill SETTING palette => 'Set2', direction => -1
+        "#;
+        let specs = parse_test_query(query).unwrap();
+
+        let scale = &specs[0].scales[0];
+        let Some(ParameterValue::Array(elems)) = scale.properties.get("palette") else {
+            panic!("palette was not resolved to colours");
+        };
+        assert_eq!(elems.len(), 8);
+        assert_eq!(elems[0], ArrayElement::String("#b3b3b3".to_string()));
+        assert!(!scale.properties.contains_key("direction"));
+
+        let query = r#"
+          VISUALISE foo AS x, bar AS fill
+          SCALE fill SETTING palette => 'not_a_palette'
+        "#;
+        assert!(parse_test_query(query).is_err());
+    }
 }
@@ -4,4 +4,4 @@
 
 mod types;
 
-pub use types::{Coord, CoordType};
+pub use types::{Coord, CoordTransform, CoordType};
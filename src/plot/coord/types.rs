@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use super::super::types::ParameterValue;
+use crate::{GgsqlError, Result};
 
 /// Coordinate system (from COORD clause)
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -27,3 +28,167 @@ pub enum CoordType {
     Map,
     QuickMap,
 }
+
+/// Transformation of a position axis by `COORD trans`
+///
+/// Unlike the transforms of `SCALE`, these are applied after statistics are
+/// computed, so they only change how the result is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoordTransform {
+    Identity,
+    Log10,
+    Log,
+    Log2,
+    Sqrt,
+    Reverse,
+}
+
+impl CoordTransform {
+    /// Parse a transform name such as `'log10'`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "identity" => Some(CoordTransform::Identity),
+            "log10" => Some(CoordTransform::Log10),
+            "log" => Some(CoordTransform::Log),
+            "log2" => Some(CoordTransform::Log2),
+            "sqrt" => Some(CoordTransform::Sqrt),
+            "reverse" => Some(CoordTransform::Reverse),
+            _ => None,
+        }
+    }
+
+    /// Whether the transform is only defined for positive values
+    pub fn is_log(&self) -> bool {
+        matches!(
+            self,
+            CoordTransform::Log10 | CoordTransform::Log | CoordTransform::Log2
+        )
+    }
+
+    /// Whether straight lines in the data become curves on the transformed axis
+    pub fn bends_lines(&self) -> bool {
+        !matches!(self, CoordTransform::Identity | CoordTransform::Reverse)
+    }
+}
+
+impl Coord {
+    /// Aspect ratio of `COORD fixed`: the length of one unit of y relative to one unit of x
+    pub fn ratio(&self) -> Result<f64> {
+        match self.properties.get("ratio") {
+            None => Ok(1.0),
+            Some(ParameterValue::Number(ratio)) if *ratio > 0.0 && ratio.is_finite() => Ok(*ratio),
+            Some(_) => Err(GgsqlError::ValidationError(
+                "COORD setting 'ratio' must be a positive number".to_string(),
+            )),
+        }
+    }
+
+    /// Whether marks are clipped to the panel (`clip => 'on'`), if set
+    pub fn clip(&self) -> Result<Option<bool>> {
+        match self.properties.get("clip") {
+            None => Ok(None),
+            Some(ParameterValue::String(clip)) if clip == "on" => Ok(Some(true)),
+            Some(ParameterValue::String(clip)) if clip == "off" => Ok(Some(false)),
+            Some(_) => Err(GgsqlError::ValidationError(
+                "COORD setting 'clip' must be 'on' or 'off'".to_string(),
+            )),
+        }
+    }
+
+    /// Transform of a position axis (`"x"` or `"y"`) under `COORD trans`
+    pub fn transform(&self, axis: &str) -> Result<CoordTransform> {
+        if self.coord_type != CoordType::Trans {
+            return Ok(CoordTransform::Identity);
+        }
+        match self.properties.get(axis) {
+            None => Ok(CoordTransform::Identity),
+            Some(ParameterValue::String(name)) => CoordTransform::parse(name).ok_or_else(|| {
+                GgsqlError::ValidationError(format!(
+                    "Unknown COORD trans transform '{}' for {}. \
+                     Expected one of: identity, log10, log, log2, sqrt, reverse",
+                    name, axis
+                ))
+            }),
+            Some(_) => Err(GgsqlError::ValidationError(format!(
+                "COORD trans setting '{}' must name a transform such as 'log10'",
+                axis
+            ))),
+        }
+    }
+
+    /// Check the values of the settings that apply to this coordinate system
+    pub fn validate(&self) -> Result<()> {
+        match self.coord_type {
+            CoordType::Map => {
+                return Err(GgsqlError::ValidationError(
+                    "COORD map is not supported. Use COORD quickmap to keep \
+                     longitude and latitude in proportion"
+                        .to_string(),
+                ))
+            }
+            CoordType::Fixed => {
+                self.ratio()?;
+            }
+            CoordType::Trans => {
+                self.transform("x")?;
+                self.transform("y")?;
+            }
+            _ => {}
+        }
+        self.clip()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coord(coord_type: CoordType, properties: &[(&str, ParameterValue)]) -> Coord {
+        Coord {
+            coord_type,
+            properties: properties
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_coord_settings() {
+        let fixed = coord(CoordType::Fixed, &[("ratio", ParameterValue::Number(2.0))]);
+        assert_eq!(fixed.ratio().unwrap(), 2.0);
+        assert_eq!(coord(CoordType::Fixed, &[]).ratio().unwrap(), 1.0);
+        assert!(
+            coord(CoordType::Fixed, &[("ratio", ParameterValue::Number(0.0))])
+                .validate()
+                .is_err()
+        );
+
+        let trans = coord(
+            CoordType::Trans,
+            &[
+                ("x", ParameterValue::String("log10".to_string())),
+                ("clip", ParameterValue::String("off".to_string())),
+            ],
+        );
+        assert!(trans.validate().is_ok());
+        assert_eq!(trans.transform("x").unwrap(), CoordTransform::Log10);
+        assert_eq!(trans.transform("y").unwrap(), CoordTransform::Identity);
+        assert_eq!(trans.clip().unwrap(), Some(false));
+        assert!(coord(
+            CoordType::Trans,
+            &[("y", ParameterValue::String("exp".to_string()))]
+        )
+        .validate()
+        .is_err());
+
+        assert!(coord(
+            CoordType::Cartesian,
+            &[("clip", ParameterValue::Boolean(false))]
+        )
+        .validate()
+        .is_err());
+        assert!(coord(CoordType::Map, &[]).validate().is_err());
+    }
+}
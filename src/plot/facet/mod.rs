@@ -4,4 +4,4 @@
 
 mod types;
 
-pub use types::{Facet, FacetScales};
+pub use types::{Facet, FacetLabel, FacetScales};
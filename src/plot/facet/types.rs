@@ -3,6 +3,10 @@
 //! This module defines faceting configuration for small multiples.
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::super::types::ParameterValue;
+use crate::{GgsqlError, Result};
 
 /// Faceting specification (from FACET clause)
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -11,12 +15,18 @@ pub enum Facet {
     Wrap {
         variables: Vec<String>,
         scales: FacetScales,
+        /// Other settings: ncol, nrow, dir, label and label_wrap
+        #[serde(default)]
+        properties: HashMap<String, ParameterValue>,
     },
     /// FACET rows BY cols
     Grid {
         rows: Vec<String>,
         cols: Vec<String>,
         scales: FacetScales,
+        /// Other settings: label and label_wrap
+        #[serde(default)]
+        properties: HashMap<String, ParameterValue>,
     },
 }
 
@@ -29,6 +39,27 @@ pub enum FacetScales {
     FreeY,
 }
 
+impl FacetScales {
+    /// Whether panels get their own scale for the x (`"x"`) or y (`"y"`) axis
+    pub fn is_free(&self, axis: &str) -> bool {
+        match self {
+            FacetScales::Fixed => false,
+            FacetScales::Free => true,
+            FacetScales::FreeX => axis == "x",
+            FacetScales::FreeY => axis == "y",
+        }
+    }
+}
+
+/// How panel labels are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FacetLabel {
+    /// Only the values, e.g. `North`
+    Value,
+    /// Variable names and values, e.g. `region: North`
+    Both,
+}
+
 impl Facet {
     /// Get all variables used for faceting
     ///
@@ -45,4 +76,141 @@ impl Facet {
             }
         }
     }
+
+    /// Scale sharing between panels
+    pub fn scales(&self) -> &FacetScales {
+        match self {
+            Facet::Wrap { scales, .. } | Facet::Grid { scales, .. } => scales,
+        }
+    }
+
+    /// Settings besides `scales`
+    pub fn properties(&self) -> &HashMap<String, ParameterValue> {
+        match self {
+            Facet::Wrap { properties, .. } | Facet::Grid { properties, .. } => properties,
+        }
+    }
+
+    /// Number of columns (`ncol`) or rows (`nrow`) of a wrapped layout
+    pub fn layout_count(&self, name: &str) -> Option<usize> {
+        match self.properties().get(name) {
+            Some(ParameterValue::Number(n)) => Some(*n as usize),
+            _ => None,
+        }
+    }
+
+    /// Whether wrapped panels fill columns first (`dir => 'v'`)
+    pub fn is_vertical(&self) -> bool {
+        matches!(self.properties().get("dir"), Some(ParameterValue::String(d)) if d == "v")
+    }
+
+    /// How panel labels are written
+    pub fn label(&self) -> FacetLabel {
+        match self.properties().get("label") {
+            Some(ParameterValue::String(l)) if l == "both" => FacetLabel::Both,
+            _ => FacetLabel::Value,
+        }
+    }
+
+    /// Width in characters at which panel labels are wrapped onto new lines
+    pub fn label_wrap(&self) -> Option<usize> {
+        match self.properties().get("label_wrap") {
+            Some(ParameterValue::Number(n)) => Some(*n as usize),
+            _ => None,
+        }
+    }
+
+    /// Check the settings of the facet
+    pub fn validate(&self) -> Result<()> {
+        let is_wrap = matches!(self, Facet::Wrap { .. });
+        let mut names: Vec<&String> = self.properties().keys().collect();
+        names.sort();
+        for name in names {
+            let value = &self.properties()[name];
+            let valid = match name.as_str() {
+                "ncol" | "nrow" | "dir" if !is_wrap => {
+                    return Err(GgsqlError::ValidationError(format!(
+                        "FACET setting '{}' only applies to FACET WRAP",
+                        name
+                    )))
+                }
+                "ncol" | "nrow" | "label_wrap" => {
+                    matches!(value, ParameterValue::Number(n) if *n >= 1.0 && n.fract() == 0.0)
+                }
+                "dir" => matches!(value, ParameterValue::String(d) if d == "h" || d == "v"),
+                "label" => {
+                    matches!(value, ParameterValue::String(l) if l == "value" || l == "both")
+                }
+                _ => {
+                    return Err(GgsqlError::ValidationError(format!(
+                        "Unknown FACET setting '{}'",
+                        name
+                    )))
+                }
+            };
+            if !valid {
+                let expected = match name.as_str() {
+                    "dir" => "'h' or 'v'",
+                    "label" => "'value' or 'both'",
+                    _ => "a positive whole number",
+                };
+                return Err(GgsqlError::ValidationError(format!(
+                    "FACET setting '{}' must be {}",
+                    name, expected
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap(properties: &[(&str, ParameterValue)]) -> Facet {
+        Facet::Wrap {
+            variables: vec!["a".to_string()],
+            scales: FacetScales::Fixed,
+            properties: properties
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_facet_settings() {
+        let facet = wrap(&[
+            ("ncol", ParameterValue::Number(3.0)),
+            ("dir", ParameterValue::String("v".to_string())),
+            ("label", ParameterValue::String("both".to_string())),
+        ]);
+        assert!(facet.validate().is_ok());
+        assert_eq!(facet.layout_count("ncol"), Some(3));
+        assert!(facet.is_vertical());
+        assert_eq!(facet.label(), FacetLabel::Both);
+        assert!(FacetScales::FreeY.is_free("y"));
+        assert!(!FacetScales::FreeY.is_free("x"));
+
+        for invalid in [
+            wrap(&[("ncol", ParameterValue::Number(0.0))]),
+            wrap(&[("nrow", ParameterValue::Number(1.5))]),
+            wrap(&[("dir", ParameterValue::String("up".to_string()))]),
+            wrap(&[("label", ParameterValue::Boolean(true))]),
+            wrap(&[("labels", ParameterValue::String("both".to_string()))]),
+        ] {
+            assert!(invalid.validate().is_err(), "{:?}", invalid);
+        }
+
+        let grid = Facet::Grid {
+            rows: vec!["a".to_string()],
+            cols: vec![],
+            scales: FacetScales::Fixed,
+            properties: [("ncol".to_string(), ParameterValue::Number(2.0))].into(),
+        };
+        let err = grid.validate().unwrap_err().to_string();
+        assert!(err.contains("only applies to FACET WRAP"));
+    }
 }
@@ -1,6 +1,7 @@
 //! Area geom implementation
 
 use super::{GeomAesthetics, GeomTrait, GeomType};
+use crate::plot::layer::Position;
 
 /// Area geom - filled area charts
 #[derive(Debug, Clone, Copy)]
@@ -18,6 +19,10 @@ impl GeomTrait for Area {
             hidden: &[],
         }
     }
+
+    fn default_position(&self) -> Position {
+        Position::Stack { reverse: false }
+    }
 }
 
 impl std::fmt::Display for Area {
@@ -3,14 +3,38 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 
-use super::types::get_column_name;
+use super::summary::{stat_summary, uses_summary_stat, validate_stat_param, SUMMARY_PARAMS};
+use super::temporal::{column_dtype, is_temporal, TimeInterval};
+use super::types::{get_column_name, get_string_param};
 use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
 use crate::naming;
+use crate::plot::layer::Position;
 use crate::plot::types::ParameterValue;
 use crate::{DataFrame, GgsqlError, Mappings, Result};
+use polars::prelude::DataType;
 
 use super::types::Schema;
 
+/// Bar width, the summary stat parameters and the temporal binning unit
+const BAR_PARAMS: [DefaultParam; 7] = {
+    let [stat, fun, fun_min, fun_max, level] = SUMMARY_PARAMS;
+    [
+        DefaultParam {
+            name: "width",
+            default: DefaultParamValue::Number(0.9),
+        },
+        stat,
+        fun,
+        fun_min,
+        fun_max,
+        level,
+        DefaultParam {
+            name: "unit",
+            default: DefaultParamValue::Null,
+        },
+    ]
+};
+
 /// Bar geom - bar charts with optional stat transform
 #[derive(Debug, Clone, Copy)]
 pub struct Bar;
@@ -35,25 +59,30 @@ impl GeomTrait for Bar {
     }
 
     fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
-        &[("count", "y"), ("x", "x")]
+        &[("count", "y"), ("x", "x"), ("y", "y")]
     }
 
     fn valid_stat_columns(&self) -> &'static [&'static str] {
-        &["count", "x", "proportion"]
+        &["count", "x", "proportion", "y", "ymin", "ymax"]
+    }
+
+    fn default_position(&self) -> Position {
+        Position::Stack { reverse: false }
     }
 
     fn default_params(&self) -> &'static [DefaultParam] {
-        &[DefaultParam {
-            name: "width",
-            default: DefaultParamValue::Number(0.9),
-        }]
+        &BAR_PARAMS
     }
 
     fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
         &["x", "y", "weight"]
     }
 
-    fn needs_stat_transform(&self, _aesthetics: &Mappings) -> bool {
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
         true // Bar stat decides COUNT vs identity based on y mapping
     }
 
@@ -63,10 +92,25 @@ impl GeomTrait for Bar {
         schema: &Schema,
         aesthetics: &Mappings,
         group_by: &[String],
-        _parameters: &HashMap<String, ParameterValue>,
-        _execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+        parameters: &HashMap<String, ParameterValue>,
+        execute_query: &dyn Fn(&str) -> Result<DataFrame>,
     ) -> Result<StatResult> {
-        stat_bar_count(query, schema, aesthetics, group_by)
+        validate_stat_param(parameters)?;
+        let truncated = truncate_x_query(query, aesthetics, parameters, execute_query)?;
+        let query = truncated.as_deref().unwrap_or(query);
+        if uses_summary_stat(parameters) {
+            return stat_summary(query, aesthetics, group_by, parameters, execute_query);
+        }
+        match stat_bar_count(query, schema, aesthetics, group_by)? {
+            // The truncated x still has to replace the original one
+            StatResult::Identity if truncated.is_some() => Ok(StatResult::Transformed {
+                query: query.to_string(),
+                stat_columns: vec![],
+                dummy_columns: vec![],
+                consumed_aesthetics: vec![],
+            }),
+            result => Ok(result),
+        }
     }
 }
 
@@ -76,6 +120,39 @@ impl std::fmt::Display for Bar {
     }
 }
 
+/// Truncate a DATE or TIMESTAMP x to the calendar interval of the `unit` setting
+///
+/// Like `date_trunc`, every x value is replaced by the start of the interval
+/// holding it (e.g. the first day of its month), so the bars count or
+/// summarise whole intervals. Returns `None` when `unit` is not set.
+fn truncate_x_query(
+    query: &str,
+    aesthetics: &Mappings,
+    parameters: &HashMap<String, ParameterValue>,
+    execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+) -> Result<Option<String>> {
+    let Some(unit) = get_string_param(parameters, "unit")? else {
+        return Ok(None);
+    };
+    let interval = TimeInterval::parse(unit)?;
+    let x_col = get_column_name(aesthetics, "x").ok_or_else(|| {
+        GgsqlError::ValidationError("Bar setting 'unit' requires an 'x' mapping".to_string())
+    })?;
+    let dtype = column_dtype(query, &x_col, execute_query)?;
+    if !is_temporal(&dtype) {
+        return Err(GgsqlError::ValidationError(format!(
+            "Bar setting 'unit' requires a DATE or TIMESTAMP x, but '{}' is {}",
+            x_col, dtype
+        )));
+    }
+    Ok(Some(format!(
+        "SELECT * REPLACE ({bucket} AS {x}) FROM ({query}) AS __unit_src__",
+        bucket = interval.bucket_sql(&x_col, matches!(dtype, DataType::Date)),
+        x = x_col,
+        query = query
+    )))
+}
+
 /// Statistical transformation for bar: COUNT/SUM vs identity based on y and weight mappings
 ///
 /// Uses pre-fetched schema to check column existence (avoiding redundant queries).
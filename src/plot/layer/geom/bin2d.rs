@@ -0,0 +1,367 @@
+//! Bin2d geom implementation
+
+use std::collections::HashMap;
+
+use super::histogram::{histogram_bin_index_expr, histogram_bin_width};
+use super::types::{column_to_f64, get_bool_param, get_column_name, get_xy_number_param};
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
+use crate::naming;
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+
+use super::types::Schema;
+
+/// Bin2d geom - counts of x/y pairs in a rectangular grid
+#[derive(Debug, Clone, Copy)]
+pub struct Bin2d;
+
+impl GeomTrait for Bin2d {
+    fn geom_type(&self) -> GeomType {
+        GeomType::Bin2d
+    }
+
+    fn aesthetics(&self) -> GeomAesthetics {
+        GeomAesthetics {
+            supported: &["x", "y", "weight", "stroke", "opacity"],
+            required: &["x", "y"],
+            // Cell edges and fill are produced by stat_bin2d
+            hidden: &["x2", "y2", "fill"],
+        }
+    }
+
+    fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("xbin", "x"),
+            ("xbin_end", "x2"),
+            ("ybin", "y"),
+            ("ybin_end", "y2"),
+            ("count", "fill"),
+        ]
+    }
+
+    fn valid_stat_columns(&self) -> &'static [&'static str] {
+        &["xbin", "xbin_end", "ybin", "ybin_end", "count", "density"]
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &[
+            DefaultParam {
+                name: "bins",
+                default: DefaultParamValue::Number(30.0),
+            },
+            DefaultParam {
+                name: "binwidth",
+                default: DefaultParamValue::Null,
+            },
+            DefaultParam {
+                name: "drop",
+                default: DefaultParamValue::Boolean(true),
+            },
+        ]
+    }
+
+    fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
+        &["x", "y", "weight"]
+    }
+
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
+        true
+    }
+
+    fn apply_stat_transform(
+        &self,
+        query: &str,
+        _schema: &Schema,
+        aesthetics: &Mappings,
+        group_by: &[String],
+        parameters: &HashMap<String, ParameterValue>,
+        execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<StatResult> {
+        stat_bin2d(query, aesthetics, group_by, parameters, execute_query)
+    }
+}
+
+impl std::fmt::Display for Bin2d {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bin2d")
+    }
+}
+
+/// Grid shared by the 2D binning stats
+///
+/// Bin widths follow `stat_histogram`: `bins` bins are centred on the range of
+/// each axis unless `binwidth` is given.
+pub struct BinGrid {
+    pub x_min: f64,
+    pub x_max: f64,
+    pub x_width: f64,
+    pub y_min: f64,
+    pub y_max: f64,
+    pub y_width: f64,
+    /// Keep only cells containing data (`drop => true`)
+    pub drop: bool,
+}
+
+impl BinGrid {
+    /// Compute the grid from the range of the data and the `bins`, `binwidth` and `drop` settings
+    pub fn from_data(
+        query: &str,
+        x_col: &str,
+        y_col: &str,
+        parameters: &HashMap<String, ParameterValue>,
+        execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<Self> {
+        let (x_bins, y_bins) = get_xy_number_param(parameters, "bins")?.unwrap_or((30.0, 30.0));
+        if x_bins < 1.0 || y_bins < 1.0 {
+            return Err(GgsqlError::ValidationError(
+                "Setting 'bins' must be at least 1".to_string(),
+            ));
+        }
+        let binwidth = get_xy_number_param(parameters, "binwidth")?;
+        if let Some((wx, wy)) = binwidth {
+            if !(wx > 0.0 && wy > 0.0) {
+                return Err(GgsqlError::ValidationError(
+                    "Setting 'binwidth' must be positive".to_string(),
+                ));
+            }
+        }
+        let drop = get_bool_param(parameters, "drop")?.unwrap_or(true);
+
+        let range_query = format!(
+            "SELECT MIN({x}) AS x_min, MAX({x}) AS x_max, MIN({y}) AS y_min, MAX({y}) AS y_max \
+             FROM ({query}) WHERE {x} IS NOT NULL AND {y} IS NOT NULL",
+            x = x_col,
+            y = y_col,
+            query = query
+        );
+        let range_df = execute_query(&range_query)?;
+        let value = |name: &str| -> Result<f64> {
+            column_to_f64(&range_df, name)?
+                .first()
+                .copied()
+                .flatten()
+                .ok_or_else(|| GgsqlError::ValidationError("No data to bin".to_string()))
+        };
+        let (x_min, x_max, y_min, y_max) = (
+            value("x_min")?,
+            value("x_max")?,
+            value("y_min")?,
+            value("y_max")?,
+        );
+
+        Ok(Self {
+            x_min: (x_min * 1e10).round() / 1e10,
+            x_max,
+            x_width: histogram_bin_width(x_min, x_max, x_bins as usize, binwidth.map(|b| b.0)),
+            y_min: (y_min * 1e10).round() / 1e10,
+            y_max,
+            y_width: histogram_bin_width(y_min, y_max, y_bins as usize, binwidth.map(|b| b.1)),
+            drop,
+        })
+    }
+}
+
+/// Column summed in each cell instead of counting rows, when `weight` is mapped
+pub fn weight_column(aesthetics: &Mappings) -> Result<Option<String>> {
+    match aesthetics.get("weight") {
+        Some(weight) if weight.is_literal() => Err(GgsqlError::ValidationError(
+            "Weight aesthetic must be a column, not a literal".to_string(),
+        )),
+        Some(weight) => Ok(weight.column_name().map(|col| col.to_string())),
+        None => Ok(None),
+    }
+}
+
+/// Build the CTEs counting x/y pairs per cell of a 2D grid
+///
+/// The index expressions compute integer cell indices from `__x__` and `__y__`.
+/// Produces the CTE `__bin_cells__` with the group columns, `__ix__`, `__iy__`
+/// and `__count__`. When `fill` is given as the largest indices and a parity
+/// flag, empty cells of the grid are added with a count of 0; with the parity
+/// flag only cells where `__ix__ + __iy__` is even exist.
+pub fn cell_counts_ctes(
+    query: &str,
+    (x_col, y_col): (&str, &str),
+    group_by: &[String],
+    (ix_expr, iy_expr): (&str, &str),
+    weight: Option<&str>,
+    fill: Option<(i64, i64, bool)>,
+) -> String {
+    let groups: String = group_by.iter().map(|g| format!("{}, ", g)).collect();
+    let (weight_select, agg_expr) = match weight {
+        Some(col) => (
+            format!(", CAST({} AS DOUBLE) AS __weight__", col),
+            "SUM(__weight__)",
+        ),
+        None => (String::new(), "COUNT(*)"),
+    };
+
+    let counted = format!(
+        "WITH __stat_src__ AS ({query}), \
+         __bin_points__ AS (\
+            SELECT {groups}CAST({x} AS DOUBLE) AS __x__, CAST({y} AS DOUBLE) AS __y__{weight} \
+            FROM __stat_src__ WHERE {x} IS NOT NULL AND {y} IS NOT NULL\
+         ), \
+         __bin_counted__ AS (\
+            SELECT {groups}CAST({ix} AS BIGINT) AS __ix__, CAST({iy} AS BIGINT) AS __iy__, {agg} AS __count__ \
+            FROM __bin_points__ GROUP BY {groups}__ix__, __iy__\
+         )",
+        query = query,
+        groups = groups,
+        x = x_col,
+        y = y_col,
+        weight = weight_select,
+        ix = ix_expr,
+        iy = iy_expr,
+        agg = agg_expr,
+    );
+
+    let Some((ix_max, iy_max, parity)) = fill else {
+        return format!(
+            "{}, __bin_cells__ AS (SELECT * FROM __bin_counted__)",
+            counted
+        );
+    };
+
+    let group_source = if group_by.is_empty() {
+        "(SELECT 1) AS __bin_groups__(__one__)".to_string()
+    } else {
+        format!(
+            "(SELECT DISTINCT {} FROM __bin_points__) AS __bin_groups__",
+            group_by.join(", ")
+        )
+    };
+    let group_select: String = group_by
+        .iter()
+        .map(|g| format!("__bin_groups__.{}, ", g))
+        .collect();
+    let group_join: String = group_by
+        .iter()
+        .map(|g| {
+            format!(
+                " AND __bin_counted__.{g} IS NOT DISTINCT FROM __bin_grid__.{g}",
+                g = g
+            )
+        })
+        .collect();
+    let parity_filter = if parity {
+        " WHERE (__xs__.__ix__ + __ys__.__iy__) % 2 = 0"
+    } else {
+        ""
+    };
+
+    format!(
+        "{counted}, \
+         __bin_grid__ AS (\
+            SELECT {group_select}__xs__.__ix__, __ys__.__iy__ FROM {group_source} \
+            CROSS JOIN generate_series(0, {ix_max}) AS __xs__(__ix__) \
+            CROSS JOIN generate_series(0, {iy_max}) AS __ys__(__iy__){parity}\
+         ), \
+         __bin_cells__ AS (\
+            SELECT __bin_grid__.*, COALESCE(__bin_counted__.__count__, 0) AS __count__ \
+            FROM __bin_grid__ LEFT JOIN __bin_counted__ \
+            ON __bin_counted__.__ix__ = __bin_grid__.__ix__ AND __bin_counted__.__iy__ = __bin_grid__.__iy__{group_join}\
+         )",
+        counted = counted,
+        group_select = group_select,
+        group_source = group_source,
+        ix_max = ix_max,
+        iy_max = iy_max,
+        parity = parity_filter,
+        group_join = group_join,
+    )
+}
+
+/// Statistical transformation for bin2d: count x/y pairs in a rectangular grid
+///
+/// Cells use the bin edges of `stat_histogram` on each axis. `density` is the
+/// proportion of the group's count falling in each cell.
+fn stat_bin2d(
+    query: &str,
+    aesthetics: &Mappings,
+    group_by: &[String],
+    parameters: &HashMap<String, ParameterValue>,
+    execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+) -> Result<StatResult> {
+    let x_col = get_column_name(aesthetics, "x").ok_or_else(|| {
+        GgsqlError::ValidationError("Bin2d requires 'x' aesthetic mapping".to_string())
+    })?;
+    let y_col = get_column_name(aesthetics, "y").ok_or_else(|| {
+        GgsqlError::ValidationError("Bin2d requires 'y' aesthetic mapping".to_string())
+    })?;
+    let weight = weight_column(aesthetics)?;
+    let grid = BinGrid::from_data(query, &x_col, &y_col, parameters, execute_query)?;
+
+    // Index of the last cell on each axis, for filling in empty cells
+    let last_index =
+        |min: f64, max: f64, width: f64| (((max - min) / width + 0.5).ceil() as i64 - 1).max(0);
+    let fill = (!grid.drop).then(|| {
+        (
+            last_index(grid.x_min, grid.x_max, grid.x_width),
+            last_index(grid.y_min, grid.y_max, grid.y_width),
+            false,
+        )
+    });
+
+    let cells = cell_counts_ctes(
+        query,
+        (&x_col, &y_col),
+        group_by,
+        (
+            &histogram_bin_index_expr("__x__", grid.x_min, grid.x_width, "right"),
+            &histogram_bin_index_expr("__y__", grid.y_min, grid.y_width, "right"),
+        ),
+        weight.as_deref(),
+        fill,
+    );
+
+    let groups: String = group_by.iter().map(|g| format!("{}, ", g)).collect();
+    let partition = if group_by.is_empty() {
+        String::new()
+    } else {
+        format!("PARTITION BY {}", group_by.join(", "))
+    };
+
+    let transformed_query = format!(
+        "{cells} \
+         SELECT {groups}\
+            __ix__ * {wx} + {x_min} - {wx} * 0.5 AS {xbin}, \
+            __ix__ * {wx} + {x_min} + {wx} * 0.5 AS {xbin_end}, \
+            __iy__ * {wy} + {y_min} - {wy} * 0.5 AS {ybin}, \
+            __iy__ * {wy} + {y_min} + {wy} * 0.5 AS {ybin_end}, \
+            __count__ AS {count}, \
+            __count__ * 1.0 / SUM(__count__) OVER ({partition}) AS {density} \
+         FROM __bin_cells__",
+        cells = cells,
+        groups = groups,
+        wx = grid.x_width,
+        x_min = grid.x_min,
+        wy = grid.y_width,
+        y_min = grid.y_min,
+        xbin = naming::stat_column("xbin"),
+        xbin_end = naming::stat_column("xbin_end"),
+        ybin = naming::stat_column("ybin"),
+        ybin_end = naming::stat_column("ybin_end"),
+        count = naming::stat_column("count"),
+        partition = partition,
+        density = naming::stat_column("density"),
+    );
+
+    Ok(StatResult::Transformed {
+        query: transformed_query,
+        stat_columns: vec![
+            "xbin".to_string(),
+            "xbin_end".to_string(),
+            "ybin".to_string(),
+            "ybin_end".to_string(),
+            "count".to_string(),
+            "density".to_string(),
+        ],
+        dummy_columns: vec![],
+        consumed_aesthetics: vec!["x".to_string(), "y".to_string(), "weight".to_string()],
+    })
+}
@@ -1,7 +1,14 @@
 //! Boxplot geom implementation
 
-use super::{GeomAesthetics, GeomTrait, GeomType};
-use crate::Mappings;
+use std::collections::HashMap;
+
+use super::types::{get_bool_param, get_column_name, get_number_param};
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
+use crate::naming;
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+
+use super::types::Schema;
 
 /// Boxplot geom - box and whisker plots
 #[derive(Debug, Clone, Copy)]
@@ -16,15 +23,62 @@ impl GeomTrait for Boxplot {
         GeomAesthetics {
             supported: &["x", "y", "color", "colour", "fill", "stroke", "opacity"],
             required: &["x", "y"],
-            hidden: &[],
+            // Box and whisker positions are produced by stat_boxplot
+            hidden: &["ymin", "lower", "middle", "upper", "ymax"],
         }
     }
 
-    fn needs_stat_transform(&self, _aesthetics: &Mappings) -> bool {
+    fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("lower", "ymin"),
+            ("q1", "lower"),
+            ("median", "middle"),
+            ("q3", "upper"),
+            ("upper", "ymax"),
+            ("outlier", "y"),
+        ]
+    }
+
+    fn valid_stat_columns(&self) -> &'static [&'static str] {
+        &["lower", "q1", "median", "q3", "upper", "outlier", "n"]
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &[
+            DefaultParam {
+                name: "coef",
+                default: DefaultParamValue::Number(1.5),
+            },
+            DefaultParam {
+                name: "varwidth",
+                default: DefaultParamValue::Boolean(false),
+            },
+        ]
+    }
+
+    fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
+        &["y"]
+    }
+
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
         true
     }
 
-    // Note: stat_boxplot not yet implemented - will return Identity for now
+    fn apply_stat_transform(
+        &self,
+        query: &str,
+        _schema: &Schema,
+        aesthetics: &Mappings,
+        group_by: &[String],
+        parameters: &HashMap<String, ParameterValue>,
+        _execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<StatResult> {
+        stat_boxplot(query, aesthetics, group_by, parameters)
+    }
 }
 
 impl std::fmt::Display for Boxplot {
@@ -32,3 +86,105 @@ impl std::fmt::Display for Boxplot {
         write!(f, "boxplot")
     }
 }
+
+/// Statistical transformation for boxplot: five-number summary and outliers per box
+///
+/// Produces one row per box with `outlier` set to NULL, followed by one row per
+/// outlier carrying the statistics of its box. Whiskers extend to the most
+/// extreme values within `coef` times the interquartile range of the box.
+fn stat_boxplot(
+    query: &str,
+    aesthetics: &Mappings,
+    group_by: &[String],
+    parameters: &HashMap<String, ParameterValue>,
+) -> Result<StatResult> {
+    let x_col = get_column_name(aesthetics, "x").ok_or_else(|| {
+        GgsqlError::ValidationError("Boxplot requires 'x' aesthetic mapping".to_string())
+    })?;
+    let y_col = get_column_name(aesthetics, "y").ok_or_else(|| {
+        GgsqlError::ValidationError("Boxplot requires 'y' aesthetic mapping".to_string())
+    })?;
+
+    let coef = get_number_param(parameters, "coef")?.unwrap_or(1.5);
+    if coef.is_nan() || coef < 0.0 {
+        return Err(GgsqlError::ValidationError(
+            "Setting 'coef' must be a non-negative number".to_string(),
+        ));
+    }
+    // varwidth only affects rendering, but reject values of the wrong type early
+    get_bool_param(parameters, "varwidth")?;
+
+    // Each box is one x value within each group
+    let mut keys = vec![x_col];
+    for col in group_by {
+        if !keys.contains(col) {
+            keys.push(col.clone());
+        }
+    }
+    let keys = keys.join(", ");
+
+    let stat_lower = naming::stat_column("lower");
+    let stat_q1 = naming::stat_column("q1");
+    let stat_median = naming::stat_column("median");
+    let stat_q3 = naming::stat_column("q3");
+    let stat_upper = naming::stat_column("upper");
+    let stat_outlier = naming::stat_column("outlier");
+    let stat_n = naming::stat_column("n");
+
+    // Stage 1: quartiles per box as window aggregates, keeping every value
+    // Stage 2: flag values beyond the fences
+    // Stage 3: whiskers are the extremes of the values that are not outliers
+    let transformed_query = format!(
+        "WITH __stat_src__ AS ({query}), \
+         __box_values__ AS (\
+            SELECT {keys}, CAST({y} AS DOUBLE) AS __value__ FROM __stat_src__ WHERE {y} IS NOT NULL\
+         ), \
+         __box_stats__ AS (\
+            SELECT *, \
+                QUANTILE_CONT(__value__, 0.25) OVER (PARTITION BY {keys}) AS {q1}, \
+                QUANTILE_CONT(__value__, 0.5) OVER (PARTITION BY {keys}) AS {median}, \
+                QUANTILE_CONT(__value__, 0.75) OVER (PARTITION BY {keys}) AS {q3}, \
+                COUNT(*) OVER (PARTITION BY {keys}) AS {n} \
+            FROM __box_values__\
+         ), \
+         __box_flagged__ AS (\
+            SELECT *, (__value__ < {q1} - {coef} * ({q3} - {q1}) OR __value__ > {q3} + {coef} * ({q3} - {q1})) AS __is_outlier__ \
+            FROM __box_stats__\
+         ), \
+         __box_whiskers__ AS (\
+            SELECT *, \
+                MIN(CASE WHEN NOT __is_outlier__ THEN __value__ END) OVER (PARTITION BY {keys}) AS {lower}, \
+                MAX(CASE WHEN NOT __is_outlier__ THEN __value__ END) OVER (PARTITION BY {keys}) AS {upper} \
+            FROM __box_flagged__\
+         ) \
+         SELECT DISTINCT {keys}, {lower}, {q1}, {median}, {q3}, {upper}, CAST(NULL AS DOUBLE) AS {outlier}, {n} FROM __box_whiskers__ \
+         UNION ALL \
+         SELECT {keys}, {lower}, {q1}, {median}, {q3}, {upper}, __value__ AS {outlier}, {n} FROM __box_whiskers__ WHERE __is_outlier__",
+        query = query,
+        keys = keys,
+        y = y_col,
+        coef = coef,
+        lower = stat_lower,
+        q1 = stat_q1,
+        median = stat_median,
+        q3 = stat_q3,
+        upper = stat_upper,
+        outlier = stat_outlier,
+        n = stat_n,
+    );
+
+    Ok(StatResult::Transformed {
+        query: transformed_query,
+        stat_columns: vec![
+            "lower".to_string(),
+            "q1".to_string(),
+            "median".to_string(),
+            "q3".to_string(),
+            "upper".to_string(),
+            "outlier".to_string(),
+            "n".to_string(),
+        ],
+        dummy_columns: vec![],
+        consumed_aesthetics: vec!["y".to_string()],
+    })
+}
@@ -0,0 +1,618 @@
+//! Contour geom implementation
+//!
+//! Also home to the contouring shared with the density2d geom: a surface
+//! sampled on a rectangular grid is cut at a set of levels with marching
+//! squares, producing either iso-lines or the filled regions above each level.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::types::{
+    column_to_f64, get_bool_param, get_column_name, get_number_param, group_index_expr,
+    grouped_values_query,
+};
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
+use crate::naming;
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+
+use super::types::Schema;
+
+/// Stat columns produced by contouring, in the order of the values in each row
+pub const CONTOUR_STAT_COLUMNS: [&str; 5] = ["x", "y", "level", "piece", "vertex"];
+
+/// Contour geom - contour lines or bands of a surface z over x and y
+#[derive(Debug, Clone, Copy)]
+pub struct Contour;
+
+impl GeomTrait for Contour {
+    fn geom_type(&self) -> GeomType {
+        GeomType::Contour
+    }
+
+    fn aesthetics(&self) -> GeomAesthetics {
+        GeomAesthetics {
+            supported: &[
+                "x",
+                "y",
+                "z",
+                "color",
+                "colour",
+                "stroke",
+                "linewidth",
+                "linetype",
+                "opacity",
+            ],
+            required: &["x", "y", "z"],
+            // Contours are coloured by the level produced by stat_contour
+            hidden: &["fill"],
+        }
+    }
+
+    fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
+        &[("x", "x"), ("y", "y"), ("level", "fill")]
+    }
+
+    fn valid_stat_columns(&self) -> &'static [&'static str] {
+        &CONTOUR_STAT_COLUMNS
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &[
+            DefaultParam {
+                name: "bins",
+                default: DefaultParamValue::Number(10.0),
+            },
+            DefaultParam {
+                name: "breaks",
+                default: DefaultParamValue::Null,
+            },
+            DefaultParam {
+                name: "filled",
+                default: DefaultParamValue::Boolean(false),
+            },
+        ]
+    }
+
+    fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
+        &["x", "y", "z"]
+    }
+
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
+        true
+    }
+
+    fn apply_stat_transform(
+        &self,
+        query: &str,
+        _schema: &Schema,
+        aesthetics: &Mappings,
+        group_by: &[String],
+        parameters: &HashMap<String, ParameterValue>,
+        execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<StatResult> {
+        stat_contour(query, aesthetics, group_by, parameters, execute_query)
+    }
+}
+
+impl std::fmt::Display for Contour {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "contour")
+    }
+}
+
+/// Settings controlling where a surface is cut
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContourOptions {
+    /// Approximate number of levels when no breaks are given
+    pub bins: usize,
+    /// Explicit levels
+    pub breaks: Option<Vec<f64>>,
+    /// Produce the filled regions above each level instead of lines
+    pub filled: bool,
+}
+
+impl ContourOptions {
+    /// Read the `bins`, `breaks` and `filled` settings
+    pub fn from_parameters(parameters: &HashMap<String, ParameterValue>) -> Result<Self> {
+        let bins = get_number_param(parameters, "bins")?.unwrap_or(10.0);
+        if bins < 1.0 {
+            return Err(GgsqlError::ValidationError(
+                "Setting 'bins' must be at least 1".to_string(),
+            ));
+        }
+        let breaks = match parameters.get("breaks") {
+            None => None,
+            Some(ParameterValue::Number(n)) => Some(vec![*n]),
+            Some(ParameterValue::Array(values)) => Some(
+                values
+                    .iter()
+                    .map(|v| v.as_number())
+                    .collect::<Option<Vec<f64>>>()
+                    .ok_or_else(|| {
+                        GgsqlError::ValidationError(
+                            "Setting 'breaks' must be an array of numbers".to_string(),
+                        )
+                    })?,
+            ),
+            Some(_) => {
+                return Err(GgsqlError::ValidationError(
+                    "Setting 'breaks' must be an array of numbers".to_string(),
+                ))
+            }
+        };
+        Ok(Self {
+            bins: bins as usize,
+            breaks,
+            filled: get_bool_param(parameters, "filled")?.unwrap_or(false),
+        })
+    }
+
+    /// Levels to cut a surface ranging from `min` to `max` at
+    ///
+    /// Without explicit breaks, levels are round numbers spaced like R's `pretty()`.
+    pub fn levels(&self, min: f64, max: f64) -> Vec<f64> {
+        if let Some(breaks) = &self.breaks {
+            let mut breaks = breaks.clone();
+            breaks.sort_by(|a, b| a.total_cmp(b));
+            breaks.dedup();
+            return breaks;
+        }
+        if !(min.is_finite() && max.is_finite()) || min >= max {
+            return vec![];
+        }
+        let step = nice_step((max - min) / self.bins as f64);
+        let first = (min / step).floor() as i64 + 1;
+        let last = (max / step).ceil() as i64 - 1;
+        (first..=last).map(|k| k as f64 * step).collect()
+    }
+}
+
+/// Round a step up to 1, 2, 5 or 10 times a power of ten
+fn nice_step(raw: f64) -> f64 {
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let fraction = raw / magnitude;
+    let nice = if fraction <= 1.0 {
+        1.0
+    } else if fraction <= 2.0 {
+        2.0
+    } else if fraction <= 5.0 {
+        5.0
+    } else {
+        10.0
+    };
+    nice * magnitude
+}
+
+/// A surface sampled on a rectangular grid
+///
+/// `z[i][j]` is the height at `(xs[i], ys[j])`; NaN marks missing values.
+#[derive(Debug, Clone)]
+pub struct Surface {
+    pub xs: Vec<f64>,
+    pub ys: Vec<f64>,
+    pub z: Vec<Vec<f64>>,
+}
+
+impl Surface {
+    /// Smallest and largest height
+    pub fn range(&self) -> (f64, f64) {
+        self.z
+            .iter()
+            .flatten()
+            .filter(|v| !v.is_nan())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(*v), hi.max(*v))
+            })
+    }
+
+    /// Surround the surface with a border below `floor`, so all contours close
+    fn padded(&self, floor: f64) -> Surface {
+        let pad = |values: &[f64]| {
+            let mut padded = Vec::with_capacity(values.len() + 2);
+            padded.push(values[0]);
+            padded.extend_from_slice(values);
+            padded.push(values[values.len() - 1]);
+            padded
+        };
+        let mut z = vec![vec![floor; self.ys.len() + 2]];
+        for column in &self.z {
+            let mut padded = vec![floor];
+            padded.extend(column.iter().map(|v| if v.is_nan() { floor } else { *v }));
+            padded.push(floor);
+            z.push(padded);
+        }
+        z.push(vec![floor; self.ys.len() + 2]);
+        Surface {
+            xs: pad(&self.xs),
+            ys: pad(&self.ys),
+            z,
+        }
+    }
+}
+
+/// Grid edge crossed by a contour: `H(i, j)` joins `(i, j)` and `(i + 1, j)`,
+/// `V(i, j)` joins `(i, j)` and `(i, j + 1)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Edge {
+    H(usize, usize),
+    V(usize, usize),
+}
+
+/// Trace the contour lines of `surface` at `level` with marching squares
+///
+/// Returns each line as its points and whether it is closed. Cells touching a
+/// missing value are skipped.
+fn isolines(surface: &Surface, level: f64) -> Vec<(Vec<(f64, f64)>, bool)> {
+    let (nx, ny) = (surface.xs.len(), surface.ys.len());
+    if nx < 2 || ny < 2 {
+        return vec![];
+    }
+    let z = &surface.z;
+    let inside = |i: usize, j: usize| z[i][j] >= level;
+
+    // Segments of every cell, as pairs of crossed edges
+    let mut segments: Vec<(Edge, Edge)> = Vec::new();
+    for i in 0..nx - 1 {
+        for j in 0..ny - 1 {
+            let corners = [z[i][j], z[i + 1][j], z[i + 1][j + 1], z[i][j + 1]];
+            if corners.iter().any(|v| v.is_nan()) {
+                continue;
+            }
+            let (bottom, right, top, left) = (
+                Edge::H(i, j),
+                Edge::V(i + 1, j),
+                Edge::H(i, j + 1),
+                Edge::V(i, j),
+            );
+            let case = (inside(i, j) as u8)
+                | (inside(i + 1, j) as u8) << 1
+                | (inside(i + 1, j + 1) as u8) << 2
+                | (inside(i, j + 1) as u8) << 3;
+            let centre_inside = corners.iter().sum::<f64>() / 4.0 >= level;
+            match case {
+                0 | 15 => {}
+                1 | 14 => segments.push((left, bottom)),
+                2 | 13 => segments.push((bottom, right)),
+                3 | 12 => segments.push((left, right)),
+                4 | 11 => segments.push((right, top)),
+                6 | 9 => segments.push((bottom, top)),
+                7 | 8 => segments.push((left, top)),
+                // Saddles: the centre decides which corners are connected
+                5 => {
+                    if centre_inside {
+                        segments.push((bottom, right));
+                        segments.push((top, left));
+                    } else {
+                        segments.push((left, bottom));
+                        segments.push((right, top));
+                    }
+                }
+                10 => {
+                    if centre_inside {
+                        segments.push((left, bottom));
+                        segments.push((right, top));
+                    } else {
+                        segments.push((bottom, right));
+                        segments.push((top, left));
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    let point = |edge: Edge| -> (f64, f64) {
+        let ((i1, j1), (i2, j2)) = match edge {
+            Edge::H(i, j) => ((i, j), (i + 1, j)),
+            Edge::V(i, j) => ((i, j), (i, j + 1)),
+        };
+        let (z1, z2) = (z[i1][j1], z[i2][j2]);
+        let t = if z1 == z2 {
+            0.5
+        } else {
+            (level - z1) / (z2 - z1)
+        };
+        (
+            surface.xs[i1] + t * (surface.xs[i2] - surface.xs[i1]),
+            surface.ys[j1] + t * (surface.ys[j2] - surface.ys[j1]),
+        )
+    };
+
+    // Chain segments sharing an edge into lines, starting from open ends
+    let mut at_edge: BTreeMap<Edge, Vec<usize>> = BTreeMap::new();
+    for (idx, (a, b)) in segments.iter().enumerate() {
+        at_edge.entry(*a).or_default().push(idx);
+        at_edge.entry(*b).or_default().push(idx);
+    }
+    let mut used = vec![false; segments.len()];
+    let follow = |start: Edge, used: &mut Vec<bool>| -> Vec<Edge> {
+        let mut line = vec![start];
+        let mut current = start;
+        while let Some(&next) = at_edge[&current].iter().find(|&&s| !used[s]) {
+            used[next] = true;
+            let (a, b) = segments[next];
+            current = if a == current { b } else { a };
+            line.push(current);
+        }
+        line
+    };
+
+    let open_ends: Vec<Edge> = at_edge
+        .iter()
+        .filter(|(_, segs)| segs.len() == 1)
+        .map(|(edge, _)| *edge)
+        .collect();
+    let mut lines = Vec::new();
+    for start in open_ends {
+        if at_edge[&start].iter().all(|&s| used[s]) {
+            continue;
+        }
+        let line = follow(start, &mut used);
+        lines.push((line.into_iter().map(point).collect(), false));
+    }
+    for idx in 0..segments.len() {
+        if used[idx] {
+            continue;
+        }
+        let line = follow(segments[idx].0, &mut used);
+        lines.push((line.into_iter().map(point).collect(), true));
+    }
+    lines
+}
+
+/// Contour `surface` at each level and append the rows of `CONTOUR_STAT_COLUMNS`
+///
+/// Lines get consecutive `piece` numbers starting at `next_piece`. Closed lines
+/// repeat their first point so they are drawn closed. Filled contours are the
+/// outlines of the regions at or above each level, to be drawn in increasing
+/// order of level. The outlines carry no holes, so a dip below a level inside
+/// its region is painted over in that level's colour.
+pub fn contour_rows(
+    group: usize,
+    surface: &Surface,
+    levels: &[f64],
+    filled: bool,
+    next_piece: &mut usize,
+    rows: &mut Vec<(usize, Vec<Option<f64>>)>,
+) {
+    let padded;
+    let surface = if filled {
+        let floor = levels.iter().copied().fold(surface.range().0, f64::min) - 1.0;
+        padded = surface.padded(floor);
+        &padded
+    } else {
+        surface
+    };
+
+    for &level in levels {
+        for (mut points, closed) in isolines(surface, level) {
+            if closed && !filled {
+                points.push(points[0]);
+            } else if filled && points.len() > 1 && points[0] == points[points.len() - 1] {
+                points.pop();
+            }
+            for (vertex, (x, y)) in points.into_iter().enumerate() {
+                rows.push((
+                    group,
+                    vec![
+                        Some(x),
+                        Some(y),
+                        Some(level),
+                        Some(*next_piece as f64),
+                        Some((vertex + 1) as f64),
+                    ],
+                ));
+            }
+            *next_piece += 1;
+        }
+    }
+}
+
+/// Build the stat query returning contour rows, ordered so that pieces are
+/// drawn in increasing order of level
+pub fn contour_query(
+    query: &str,
+    group_by: &[String],
+    rows: &[(usize, Vec<Option<f64>>)],
+) -> String {
+    format!(
+        "SELECT * FROM ({values} ORDER BY {level}, {piece}, {vertex})",
+        values = grouped_values_query(query, group_by, &CONTOUR_STAT_COLUMNS, rows),
+        level = naming::stat_column("level"),
+        piece = naming::stat_column("piece"),
+        vertex = naming::stat_column("vertex"),
+    )
+}
+
+/// Statistical transformation for contour: contour lines or bands of z over an x/y grid
+///
+/// The distinct x and y values of each group form the grid; grid points
+/// without a z value leave a gap in the contours. Levels are shared by all
+/// groups.
+fn stat_contour(
+    query: &str,
+    aesthetics: &Mappings,
+    group_by: &[String],
+    parameters: &HashMap<String, ParameterValue>,
+    execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+) -> Result<StatResult> {
+    let column = |aesthetic: &str| {
+        get_column_name(aesthetics, aesthetic).ok_or_else(|| {
+            GgsqlError::ValidationError(format!(
+                "Contour requires '{}' aesthetic mapping",
+                aesthetic
+            ))
+        })
+    };
+    let (x_col, y_col, z_col) = (column("x")?, column("y")?, column("z")?);
+    let options = ContourOptions::from_parameters(parameters)?;
+
+    let data_query = format!(
+        "WITH __stat_src__ AS ({query}) \
+         SELECT {rank} AS {group}, CAST({x} AS DOUBLE) AS x, CAST({y} AS DOUBLE) AS y, CAST({z} AS DOUBLE) AS z \
+         FROM __stat_src__ WHERE {x} IS NOT NULL AND {y} IS NOT NULL AND {z} IS NOT NULL",
+        query = query,
+        rank = group_index_expr(group_by),
+        group = naming::GROUP_COLUMN,
+        x = x_col,
+        y = y_col,
+        z = z_col
+    );
+    let df = execute_query(&data_query)?;
+    let groups = column_to_f64(&df, naming::GROUP_COLUMN)?;
+    let xs = column_to_f64(&df, "x")?;
+    let ys = column_to_f64(&df, "y")?;
+    let zs = column_to_f64(&df, "z")?;
+
+    let mut points: BTreeMap<usize, Vec<(f64, f64, f64)>> = BTreeMap::new();
+    for (((group, x), y), z) in groups.into_iter().zip(xs).zip(ys).zip(zs) {
+        if let (Some(group), Some(x), Some(y), Some(z)) = (group, x, y, z) {
+            points.entry(group as usize).or_default().push((x, y, z));
+        }
+    }
+
+    let surfaces: Vec<(usize, Surface)> = points
+        .into_iter()
+        .map(|(group, points)| (group, grid_surface(&points)))
+        .collect();
+    let (min, max) = surfaces
+        .iter()
+        .map(|(_, s)| s.range())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (a, b)| {
+            (lo.min(a), hi.max(b))
+        });
+    let levels = options.levels(min, max);
+
+    let mut rows = Vec::new();
+    let mut next_piece = 1;
+    for (group, surface) in &surfaces {
+        contour_rows(
+            *group,
+            surface,
+            &levels,
+            options.filled,
+            &mut next_piece,
+            &mut rows,
+        );
+    }
+
+    Ok(StatResult::Transformed {
+        query: contour_query(query, group_by, &rows),
+        stat_columns: CONTOUR_STAT_COLUMNS.iter().map(|s| s.to_string()).collect(),
+        dummy_columns: vec![],
+        consumed_aesthetics: vec!["x".to_string(), "y".to_string(), "z".to_string()],
+    })
+}
+
+/// Arrange scattered (x, y, z) values on the grid of their distinct x and y values
+fn grid_surface(points: &[(f64, f64, f64)]) -> Surface {
+    let distinct = |values: Vec<f64>| -> Vec<f64> {
+        values
+            .into_iter()
+            .map(f64::to_bits)
+            .collect::<BTreeSet<u64>>()
+            .into_iter()
+            .map(f64::from_bits)
+            .collect::<Vec<f64>>()
+    };
+    let mut xs = distinct(points.iter().map(|p| p.0).collect());
+    let mut ys = distinct(points.iter().map(|p| p.1).collect());
+    xs.sort_by(|a, b| a.total_cmp(b));
+    ys.sort_by(|a, b| a.total_cmp(b));
+
+    let mut z = vec![vec![f64::NAN; ys.len()]; xs.len()];
+    for (x, y, value) in points {
+        let i = xs.partition_point(|v| v < x);
+        let j = ys.partition_point(|v| v < y);
+        z[i][j] = *value;
+    }
+    Surface { xs, ys, z }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cone peaking at 1 in the centre of a 5x5 grid
+    fn cone() -> Surface {
+        let coords: Vec<f64> = (0..5).map(|v| v as f64).collect();
+        let z = coords
+            .iter()
+            .map(|x| {
+                coords
+                    .iter()
+                    .map(|y| 1.0 - ((x - 2.0).abs().max((y - 2.0).abs())) / 2.0)
+                    .collect()
+            })
+            .collect();
+        Surface {
+            xs: coords.clone(),
+            ys: coords,
+            z,
+        }
+    }
+
+    #[test]
+    fn test_isolines_closed_ring() {
+        let lines = isolines(&cone(), 0.75);
+        assert_eq!(lines.len(), 1);
+        let (points, closed) = &lines[0];
+        assert!(closed);
+        // The ring passes halfway between the peak and its neighbours
+        assert!(points.contains(&(2.0, 1.5)));
+        assert!(points.contains(&(2.5, 2.0)));
+    }
+
+    #[test]
+    fn test_isolines_open_at_border() {
+        // A ramp along x is cut by a straight line from bottom to top
+        let surface = Surface {
+            xs: vec![0.0, 1.0, 2.0],
+            ys: vec![0.0, 1.0],
+            z: vec![vec![0.0, 0.0], vec![1.0, 1.0], vec![2.0, 2.0]],
+        };
+        let lines = isolines(&surface, 1.5);
+        assert_eq!(lines.len(), 1);
+        let (points, closed) = &lines[0];
+        assert!(!closed);
+        assert_eq!(points.len(), 2);
+        assert!(points.iter().all(|(x, _)| *x == 1.5));
+    }
+
+    #[test]
+    fn test_filled_contours_close_at_border() {
+        let surface = Surface {
+            xs: vec![0.0, 1.0, 2.0],
+            ys: vec![0.0, 1.0],
+            z: vec![vec![0.0, 0.0], vec![1.0, 1.0], vec![2.0, 2.0]],
+        };
+        let mut rows = Vec::new();
+        let mut next_piece = 1;
+        contour_rows(1, &surface, &[1.5], true, &mut next_piece, &mut rows);
+        assert_eq!(next_piece, 2);
+        // The region x >= 1.5 is a rectangle along the right border
+        let xs: Vec<f64> = rows.iter().map(|(_, v)| v[0].unwrap()).collect();
+        assert!(xs.iter().all(|x| *x >= 1.5 && *x <= 2.0));
+        assert!(xs.contains(&2.0));
+    }
+
+    #[test]
+    fn test_pretty_levels() {
+        let options = ContourOptions {
+            bins: 10,
+            breaks: None,
+            filled: false,
+        };
+        assert_eq!(options.levels(0.0, 1.0).len(), 9);
+        assert_eq!(options.levels(0.0, 1.0)[0], 0.1);
+        let options = ContourOptions {
+            breaks: Some(vec![0.5, 0.2]),
+            ..options
+        };
+        assert_eq!(options.levels(0.0, 1.0), vec![0.2, 0.5]);
+    }
+}
@@ -0,0 +1,116 @@
+//! CrossBar geom implementation
+
+use std::collections::HashMap;
+
+use super::summary::{stat_summary, uses_summary_stat, validate_stat_param};
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, Mappings, Result};
+
+use super::types::Schema;
+
+/// Width of the box, followed by the parameters of the summary stat
+const CROSSBAR_PARAMS: [DefaultParam; 6] = [
+    DefaultParam {
+        name: "width",
+        default: DefaultParamValue::Number(0.9),
+    },
+    DefaultParam {
+        name: "stat",
+        default: DefaultParamValue::Null,
+    },
+    DefaultParam {
+        name: "fun",
+        default: DefaultParamValue::Null,
+    },
+    DefaultParam {
+        name: "fun_min",
+        default: DefaultParamValue::Null,
+    },
+    DefaultParam {
+        name: "fun_max",
+        default: DefaultParamValue::Null,
+    },
+    DefaultParam {
+        name: "level",
+        default: DefaultParamValue::Null,
+    },
+];
+
+/// CrossBar geom - box spanning an interval with a line at its estimate
+#[derive(Debug, Clone, Copy)]
+pub struct CrossBar;
+
+impl GeomTrait for CrossBar {
+    fn geom_type(&self) -> GeomType {
+        GeomType::CrossBar
+    }
+
+    fn aesthetics(&self) -> GeomAesthetics {
+        GeomAesthetics {
+            supported: &[
+                "x",
+                "y",
+                "ymin",
+                "ymax",
+                "xmin",
+                "xmax",
+                "color",
+                "colour",
+                "fill",
+                "stroke",
+                "linetype",
+                "linewidth",
+                "opacity",
+            ],
+            required: &["x", "y"],
+            hidden: &[],
+        }
+    }
+
+    fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
+        &[("y", "y"), ("ymin", "ymin"), ("ymax", "ymax")]
+    }
+
+    fn valid_stat_columns(&self) -> &'static [&'static str] {
+        &["y", "ymin", "ymax"]
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &CROSSBAR_PARAMS
+    }
+
+    fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
+        &["y"]
+    }
+
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
+        uses_summary_stat(parameters)
+    }
+
+    fn apply_stat_transform(
+        &self,
+        query: &str,
+        _schema: &Schema,
+        aesthetics: &Mappings,
+        group_by: &[String],
+        parameters: &HashMap<String, ParameterValue>,
+        execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<StatResult> {
+        validate_stat_param(parameters)?;
+        if !uses_summary_stat(parameters) {
+            return Ok(StatResult::Identity);
+        }
+        stat_summary(query, aesthetics, group_by, parameters, execute_query)
+    }
+}
+
+impl std::fmt::Display for CrossBar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "crossbar")
+    }
+}
@@ -1,7 +1,14 @@
 //! Density geom implementation
 
-use super::{GeomAesthetics, GeomTrait, GeomType};
-use crate::Mappings;
+use std::collections::HashMap;
+
+use super::types::{get_bool_param, get_column_name, get_number_param, get_string_param};
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
+use crate::naming;
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+
+use super::types::Schema;
 
 /// Density geom - kernel density estimation
 #[derive(Debug, Clone, Copy)]
@@ -16,15 +23,67 @@ impl GeomTrait for Density {
         GeomAesthetics {
             supported: &["x", "color", "colour", "fill", "stroke", "opacity"],
             required: &["x"],
-            hidden: &[],
+            // y is produced by stat_density but not valid for manual MAPPING
+            hidden: &["y"],
         }
     }
 
-    fn needs_stat_transform(&self, _aesthetics: &Mappings) -> bool {
+    fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
+        &[("x", "x"), ("density", "y")]
+    }
+
+    fn valid_stat_columns(&self) -> &'static [&'static str] {
+        &["x", "density", "scaled", "count"]
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &[
+            DefaultParam {
+                name: "bw",
+                default: DefaultParamValue::String("nrd0"),
+            },
+            DefaultParam {
+                name: "adjust",
+                default: DefaultParamValue::Number(1.0),
+            },
+            DefaultParam {
+                name: "kernel",
+                default: DefaultParamValue::String("gaussian"),
+            },
+            DefaultParam {
+                name: "n",
+                default: DefaultParamValue::Number(512.0),
+            },
+            DefaultParam {
+                name: "trim",
+                default: DefaultParamValue::Boolean(false),
+            },
+        ]
+    }
+
+    fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
+        &["x"]
+    }
+
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
         true
     }
 
-    // Note: stat_density not yet implemented - will return Identity for now
+    fn apply_stat_transform(
+        &self,
+        query: &str,
+        _schema: &Schema,
+        aesthetics: &Mappings,
+        group_by: &[String],
+        parameters: &HashMap<String, ParameterValue>,
+        _execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<StatResult> {
+        stat_density(query, aesthetics, group_by, parameters)
+    }
 }
 
 impl std::fmt::Display for Density {
@@ -32,3 +91,322 @@ impl std::fmt::Display for Density {
         write!(f, "density")
     }
 }
+
+/// Statistical transformation for density: kernel density estimate of x per group
+fn stat_density(
+    query: &str,
+    aesthetics: &Mappings,
+    group_by: &[String],
+    parameters: &HashMap<String, ParameterValue>,
+) -> Result<StatResult> {
+    let x_col = get_column_name(aesthetics, "x").ok_or_else(|| {
+        GgsqlError::ValidationError("Density requires 'x' aesthetic mapping".to_string())
+    })?;
+    let options = KdeOptions::from_parameters(parameters, false)?;
+
+    Ok(StatResult::Transformed {
+        query: kde_query(query, &x_col, group_by, "x", &options),
+        stat_columns: vec![
+            "x".to_string(),
+            "density".to_string(),
+            "scaled".to_string(),
+            "count".to_string(),
+        ],
+        dummy_columns: vec![],
+        consumed_aesthetics: vec!["x".to_string()],
+    })
+}
+
+/// Bandwidth selection rule for kernel density estimation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bandwidth {
+    /// Silverman's rule of thumb (R's `bw.nrd0`)
+    Nrd0,
+    /// Scott's variation of Silverman's rule (R's `bw.nrd`)
+    Nrd,
+    /// Scott's rule based on the standard deviation alone
+    Scott,
+    /// Fixed bandwidth
+    Fixed(f64),
+}
+
+/// Smoothing kernel for kernel density estimation
+///
+/// All kernels are scaled to unit standard deviation, so the bandwidth is the
+/// standard deviation of the kernel like in R's `density()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kernel {
+    Gaussian,
+    Epanechnikov,
+    Rectangular,
+    Triangular,
+    Biweight,
+    Cosine,
+    Optcosine,
+}
+
+impl Kernel {
+    fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "gaussian" => Ok(Kernel::Gaussian),
+            "epanechnikov" => Ok(Kernel::Epanechnikov),
+            "rectangular" => Ok(Kernel::Rectangular),
+            "triangular" => Ok(Kernel::Triangular),
+            "biweight" => Ok(Kernel::Biweight),
+            "cosine" => Ok(Kernel::Cosine),
+            "optcosine" => Ok(Kernel::Optcosine),
+            _ => Err(GgsqlError::ValidationError(format!(
+                "Unknown kernel '{}'. Expected one of 'gaussian', 'epanechnikov', 'rectangular', 'triangular', 'biweight', 'cosine' or 'optcosine'",
+                name
+            ))),
+        }
+    }
+
+    /// SQL expression for the kernel evaluated at the standardised distance `u`
+    fn sql(&self, u: &str) -> String {
+        use std::f64::consts::PI;
+        // Half-width of the bounded kernels at unit standard deviation
+        let bounded = |a: f64, body: String| {
+            format!(
+                "CASE WHEN ABS({u}) < {a:e} THEN {body} ELSE 0 END",
+                u = u,
+                a = a,
+                body = body
+            )
+        };
+        match self {
+            Kernel::Gaussian => format!(
+                "EXP(-0.5 * ({u}) * ({u})) / {c:e}",
+                u = u,
+                c = (2.0 * PI).sqrt()
+            ),
+            Kernel::Epanechnikov => {
+                let a = 5f64.sqrt();
+                bounded(
+                    a,
+                    format!(
+                        "{c:e} * (1 - POW(({u}) / {a:e}, 2))",
+                        c = 3.0 / (4.0 * a),
+                        u = u,
+                        a = a
+                    ),
+                )
+            }
+            Kernel::Rectangular => {
+                let a = 3f64.sqrt();
+                bounded(a, format!("{:e}", 1.0 / (2.0 * a)))
+            }
+            Kernel::Triangular => {
+                let a = 6f64.sqrt();
+                bounded(a, format!("(1 - ABS({u}) / {a:e}) / {a:e}", u = u, a = a))
+            }
+            Kernel::Biweight => {
+                let a = 7f64.sqrt();
+                bounded(
+                    a,
+                    format!(
+                        "{c:e} * POW(1 - POW(({u}) / {a:e}, 2), 2)",
+                        c = 15.0 / (16.0 * a),
+                        u = u,
+                        a = a
+                    ),
+                )
+            }
+            Kernel::Cosine => {
+                let a = 1.0 / (1.0 / 3.0 - 2.0 / (PI * PI)).sqrt();
+                bounded(
+                    a,
+                    format!(
+                        "(1 + COS(PI() * ({u}) / {a:e})) / {d:e}",
+                        u = u,
+                        a = a,
+                        d = 2.0 * a
+                    ),
+                )
+            }
+            Kernel::Optcosine => {
+                let a = 1.0 / (1.0 - 8.0 / (PI * PI)).sqrt();
+                bounded(
+                    a,
+                    format!(
+                        "{c:e} * COS(PI() * ({u}) / {d:e})",
+                        c = PI / (4.0 * a),
+                        u = u,
+                        d = 2.0 * a
+                    ),
+                )
+            }
+        }
+    }
+}
+
+/// Validated SETTING values for kernel density estimation
+#[derive(Debug, Clone)]
+pub struct KdeOptions {
+    pub bw: Bandwidth,
+    pub adjust: f64,
+    pub kernel: Kernel,
+    pub n: usize,
+    /// Evaluate each group over its own range instead of the range of the layer
+    pub trim: bool,
+}
+
+impl KdeOptions {
+    pub fn from_parameters(
+        parameters: &HashMap<String, ParameterValue>,
+        default_trim: bool,
+    ) -> Result<Self> {
+        let bw = match parameters.get("bw") {
+            None => Bandwidth::Nrd0,
+            Some(ParameterValue::Number(bw)) if *bw > 0.0 => Bandwidth::Fixed(*bw),
+            Some(ParameterValue::String(rule)) => match rule.as_str() {
+                "nrd0" => Bandwidth::Nrd0,
+                "nrd" => Bandwidth::Nrd,
+                "scott" => Bandwidth::Scott,
+                _ => {
+                    return Err(GgsqlError::ValidationError(format!(
+                        "Unknown bandwidth rule '{}'. Expected 'nrd0', 'nrd', 'scott' or a positive number",
+                        rule
+                    )))
+                }
+            },
+            Some(_) => {
+                return Err(GgsqlError::ValidationError(
+                    "Setting 'bw' must be 'nrd0', 'nrd', 'scott' or a positive number"
+                        .to_string(),
+                ))
+            }
+        };
+
+        let adjust = get_number_param(parameters, "adjust")?.unwrap_or(1.0);
+        if adjust.is_nan() || adjust <= 0.0 {
+            return Err(GgsqlError::ValidationError(
+                "Setting 'adjust' must be greater than 0".to_string(),
+            ));
+        }
+
+        let kernel =
+            Kernel::from_name(get_string_param(parameters, "kernel")?.unwrap_or("gaussian"))?;
+
+        let n = get_number_param(parameters, "n")?.unwrap_or(512.0);
+        if n < 2.0 || n.fract() != 0.0 {
+            return Err(GgsqlError::ValidationError(
+                "Setting 'n' must be an integer of at least 2".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            bw,
+            adjust,
+            kernel,
+            n: n as usize,
+            trim: get_bool_param(parameters, "trim")?.unwrap_or(default_trim),
+        })
+    }
+
+    /// SQL expression for the bandwidth, given per-group `__n__`, `__sd__` and `__iqr__` columns
+    fn bandwidth_sql(&self) -> String {
+        // R's bw.nrd0 falls back to the standard deviation, then to 1, for degenerate data
+        let spread = "COALESCE(NULLIF(LEAST(__sd__, __iqr__ / 1.34), 0), NULLIF(__sd__, 0), 1)";
+        let bw = match self.bw {
+            Bandwidth::Nrd0 => format!("0.9 * {} * POW(__n__, -0.2)", spread),
+            Bandwidth::Nrd => format!("1.06 * {} * POW(__n__, -0.2)", spread),
+            Bandwidth::Scott => {
+                "1.06 * COALESCE(NULLIF(__sd__, 0), 1) * POW(__n__, -0.2)".to_string()
+            }
+            Bandwidth::Fixed(bw) => format!("{:e}", bw),
+        };
+        format!("({}) * {:e}", bw, self.adjust)
+    }
+}
+
+/// Build the kernel density estimate of `value_col` for each group of `keys`.
+///
+/// The evaluation grid is generated in SQL and joined against the values of its
+/// group. The result has the key columns followed by the stat columns
+/// `<position>` (the grid), `density`, `scaled` (density relative to the group
+/// maximum) and `count` (density times the number of values).
+pub fn kde_query(
+    query: &str,
+    value_col: &str,
+    keys: &[String],
+    position: &str,
+    options: &KdeOptions,
+) -> String {
+    let key_list = |prefix: &str| -> String {
+        keys.iter()
+            .map(|k| format!("{}{}, ", prefix, k))
+            .collect::<String>()
+    };
+    let group_clause = if keys.is_empty() {
+        String::new()
+    } else {
+        format!(" GROUP BY {}", keys.join(", "))
+    };
+    let partition_clause = if keys.is_empty() {
+        String::new()
+    } else {
+        format!("PARTITION BY {}", keys.join(", "))
+    };
+    let join_clause = if keys.is_empty() {
+        "CROSS JOIN __kde_values__ v".to_string()
+    } else {
+        let conditions: Vec<String> = keys
+            .iter()
+            .map(|k| format!("g.{k} IS NOT DISTINCT FROM v.{k}", k = k))
+            .collect();
+        format!("JOIN __kde_values__ v ON {}", conditions.join(" AND "))
+    };
+
+    // Without trimming every group is evaluated over the range of the whole layer
+    let (range_lo, range_hi) = if options.trim {
+        ("__min__", "__max__")
+    } else {
+        ("MIN(__min__) OVER ()", "MAX(__max__) OVER ()")
+    };
+
+    format!(
+        "WITH __stat_src__ AS ({query}), \
+         __kde_values__ AS (\
+            SELECT {keys}CAST({value} AS DOUBLE) AS __value__ FROM __stat_src__ WHERE {value} IS NOT NULL\
+         ), \
+         __kde_groups__ AS (\
+            SELECT {keys}COUNT(*) AS __n__, MIN(__value__) AS __min__, MAX(__value__) AS __max__, \
+                STDDEV_SAMP(__value__) AS __sd__, \
+                QUANTILE_CONT(__value__, 0.75) - QUANTILE_CONT(__value__, 0.25) AS __iqr__ \
+            FROM __kde_values__{group_clause}\
+         ), \
+         __kde_bw__ AS (\
+            SELECT *, {bw} AS __bw__, {lo} AS __lo__, {hi} AS __hi__ FROM __kde_groups__\
+         ), \
+         __kde_grid__ AS (\
+            SELECT {keys}__n__, __bw__, __lo__ + (__hi__ - __lo__) * __i__ / {steps} AS __at__ \
+            FROM __kde_bw__ CROSS JOIN generate_series(0, {steps}) AS __steps__(__i__)\
+         ), \
+         __kde_density__ AS (\
+            SELECT {g_keys}g.__at__, g.__n__, SUM({kernel}) / (g.__n__ * g.__bw__) AS __density__ \
+            FROM __kde_grid__ g {join} \
+            GROUP BY {g_keys}g.__at__, g.__n__, g.__bw__\
+         ) \
+         SELECT {keys}__at__ AS {position}, __density__ AS {density}, \
+            __density__ / MAX(__density__) OVER ({partition}) AS {scaled}, \
+            __density__ * __n__ AS {count} \
+         FROM __kde_density__",
+        query = query,
+        keys = key_list(""),
+        g_keys = key_list("g."),
+        value = value_col,
+        group_clause = group_clause,
+        bw = options.bandwidth_sql(),
+        lo = range_lo,
+        hi = range_hi,
+        steps = options.n - 1,
+        kernel = options.kernel.sql("(g.__at__ - v.__value__) / g.__bw__"),
+        join = join_clause,
+        partition = partition_clause,
+        position = naming::stat_column(position),
+        density = naming::stat_column("density"),
+        scaled = naming::stat_column("scaled"),
+        count = naming::stat_column("count"),
+    )
+}
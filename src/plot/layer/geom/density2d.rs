@@ -0,0 +1,357 @@
+//! Density2d geom implementation
+
+use std::collections::HashMap;
+
+use super::contour::{contour_query, contour_rows, ContourOptions, Surface, CONTOUR_STAT_COLUMNS};
+use super::types::{
+    column_to_f64, get_column_name, get_number_param, get_xy_number_param, group_index_expr,
+};
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
+use crate::naming;
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+
+use super::types::Schema;
+
+/// Largest number of grid points along each axis of the density estimate
+const MAX_GRID_SIZE: usize = 500;
+
+/// Density2d geom - contours of a 2D kernel density estimate
+#[derive(Debug, Clone, Copy)]
+pub struct Density2d;
+
+impl GeomTrait for Density2d {
+    fn geom_type(&self) -> GeomType {
+        GeomType::Density2d
+    }
+
+    fn aesthetics(&self) -> GeomAesthetics {
+        GeomAesthetics {
+            supported: &[
+                "x",
+                "y",
+                "color",
+                "colour",
+                "stroke",
+                "linewidth",
+                "linetype",
+                "opacity",
+            ],
+            required: &["x", "y"],
+            // Contours are coloured by the level produced by stat_density2d
+            hidden: &["fill"],
+        }
+    }
+
+    fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
+        &[("x", "x"), ("y", "y"), ("level", "fill")]
+    }
+
+    fn valid_stat_columns(&self) -> &'static [&'static str] {
+        &CONTOUR_STAT_COLUMNS
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &[
+            DefaultParam {
+                name: "bins",
+                default: DefaultParamValue::Number(10.0),
+            },
+            DefaultParam {
+                name: "breaks",
+                default: DefaultParamValue::Null,
+            },
+            DefaultParam {
+                name: "filled",
+                default: DefaultParamValue::Boolean(false),
+            },
+            DefaultParam {
+                name: "h",
+                default: DefaultParamValue::Null,
+            },
+            DefaultParam {
+                name: "n",
+                default: DefaultParamValue::Number(100.0),
+            },
+        ]
+    }
+
+    fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
+        &["x", "y"]
+    }
+
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
+        true
+    }
+
+    fn apply_stat_transform(
+        &self,
+        query: &str,
+        _schema: &Schema,
+        aesthetics: &Mappings,
+        group_by: &[String],
+        parameters: &HashMap<String, ParameterValue>,
+        execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<StatResult> {
+        stat_density2d(query, aesthetics, group_by, parameters, execute_query)
+    }
+}
+
+impl std::fmt::Display for Density2d {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "density2d")
+    }
+}
+
+/// Statistical transformation for density2d: contour a bivariate Gaussian KDE
+///
+/// The density of each group is estimated on an `n` x `n` grid spanning the
+/// range of the whole layer, like `MASS::kde2d`. SQL snaps the points to their
+/// nearest grid point and counts them; the counts are then smoothed with a
+/// separable Gaussian kernel whose standard deviation is a quarter of the
+/// bandwidth `h`. Without `h`, each group uses `MASS::bandwidth.nrd` per axis.
+fn stat_density2d(
+    query: &str,
+    aesthetics: &Mappings,
+    group_by: &[String],
+    parameters: &HashMap<String, ParameterValue>,
+    execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+) -> Result<StatResult> {
+    let x_col = get_column_name(aesthetics, "x").ok_or_else(|| {
+        GgsqlError::ValidationError("Density2d requires 'x' aesthetic mapping".to_string())
+    })?;
+    let y_col = get_column_name(aesthetics, "y").ok_or_else(|| {
+        GgsqlError::ValidationError("Density2d requires 'y' aesthetic mapping".to_string())
+    })?;
+    let options = ContourOptions::from_parameters(parameters)?;
+    let n = get_number_param(parameters, "n")?.unwrap_or(100.0);
+    if !(2.0..=MAX_GRID_SIZE as f64).contains(&n) || n.fract() != 0.0 {
+        return Err(GgsqlError::ValidationError(format!(
+            "Setting 'n' must be an integer between 2 and {}",
+            MAX_GRID_SIZE
+        )));
+    }
+    let n = n as usize;
+    let h = get_xy_number_param(parameters, "h")?;
+    if let Some((hx, hy)) = h {
+        if !(hx > 0.0 && hy > 0.0) {
+            return Err(GgsqlError::ValidationError(
+                "Setting 'h' must be positive".to_string(),
+            ));
+        }
+    }
+
+    let points_cte = format!(
+        "WITH __stat_src__ AS ({query}), \
+         __density_points__ AS (\
+            SELECT {rank} AS {group}, CAST({x} AS DOUBLE) AS __x__, CAST({y} AS DOUBLE) AS __y__ \
+            FROM __stat_src__ WHERE {x} IS NOT NULL AND {y} IS NOT NULL\
+         )",
+        query = query,
+        rank = group_index_expr(group_by),
+        group = naming::GROUP_COLUMN,
+        x = x_col,
+        y = y_col
+    );
+
+    // Grid range over the whole layer, and bandwidth statistics per group
+    let stats_query = format!(
+        "{points} SELECT {group}, COUNT(*) AS n, \
+            STDDEV_SAMP(__x__) AS sd_x, QUANTILE_CONT(__x__, 0.75) - QUANTILE_CONT(__x__, 0.25) AS iqr_x, \
+            STDDEV_SAMP(__y__) AS sd_y, QUANTILE_CONT(__y__, 0.75) - QUANTILE_CONT(__y__, 0.25) AS iqr_y, \
+            MIN(MIN(__x__)) OVER () AS x_min, MAX(MAX(__x__)) OVER () AS x_max, \
+            MIN(MIN(__y__)) OVER () AS y_min, MAX(MAX(__y__)) OVER () AS y_max \
+         FROM __density_points__ GROUP BY {group} ORDER BY {group}",
+        points = points_cte,
+        group = naming::GROUP_COLUMN
+    );
+    let stats = execute_query(&stats_query)?;
+    let stat = |name: &str| column_to_f64(&stats, name);
+    let groups = stat(naming::GROUP_COLUMN)?;
+    let counts = stat("n")?;
+    let (sd_x, iqr_x, sd_y, iqr_y) = (stat("sd_x")?, stat("iqr_x")?, stat("sd_y")?, stat("iqr_y")?);
+    let range = |name: &str| -> Result<f64> {
+        stat(name)?
+            .first()
+            .copied()
+            .flatten()
+            .ok_or_else(|| GgsqlError::ValidationError("No data for density2d".to_string()))
+    };
+    let (x_min, x_max, y_min, y_max) = (
+        range("x_min")?,
+        range("x_max")?,
+        range("y_min")?,
+        range("y_max")?,
+    );
+
+    let step = |min: f64, max: f64| (max - min) / (n - 1) as f64;
+    let (dx, dy) = (step(x_min, x_max), step(y_min, y_max));
+    let axis = |min: f64, d: f64| (0..n).map(|k| min + k as f64 * d).collect::<Vec<f64>>();
+    let (xs, ys) = (axis(x_min, dx), axis(y_min, dy));
+
+    // Points snapped to their nearest grid point
+    let index = |col: &str, min: f64, d: f64| {
+        if d > 0.0 {
+            format!("CAST(ROUND(({} - {:e}) / {:e}) AS INTEGER)", col, min, d)
+        } else {
+            "0".to_string()
+        }
+    };
+    let binned_query = format!(
+        "{points} SELECT {group}, {ix} AS ix, {iy} AS iy, COUNT(*) AS count \
+         FROM __density_points__ GROUP BY ALL",
+        points = points_cte,
+        group = naming::GROUP_COLUMN,
+        ix = index("__x__", x_min, dx),
+        iy = index("__y__", y_min, dy)
+    );
+    let binned = execute_query(&binned_query)?;
+    let binned_groups = column_to_f64(&binned, naming::GROUP_COLUMN)?;
+    let binned_ix = column_to_f64(&binned, "ix")?;
+    let binned_iy = column_to_f64(&binned, "iy")?;
+    let binned_counts = column_to_f64(&binned, "count")?;
+
+    let mut surfaces: Vec<(usize, Surface)> = Vec::new();
+    for (i, group) in groups.iter().enumerate() {
+        let (Some(group), Some(count)) = (group, counts[i]) else {
+            continue;
+        };
+        let group = *group as usize;
+        let (hx, hy) = h.unwrap_or_else(|| {
+            (
+                bandwidth_nrd(sd_x[i], iqr_x[i], count),
+                bandwidth_nrd(sd_y[i], iqr_y[i], count),
+            )
+        });
+        // Without spread on an axis there is no density to contour
+        if !(hx > 0.0 && hy > 0.0 && dx > 0.0 && dy > 0.0) {
+            continue;
+        }
+
+        let mut grid = vec![vec![0.0; n]; n];
+        for (((g, ix), iy), c) in binned_groups
+            .iter()
+            .zip(&binned_ix)
+            .zip(&binned_iy)
+            .zip(&binned_counts)
+        {
+            if let (Some(g), Some(ix), Some(iy), Some(c)) = (g, ix, iy, c) {
+                if *g as usize == group {
+                    grid[*ix as usize][*iy as usize] += c / count;
+                }
+            }
+        }
+        surfaces.push((
+            group,
+            Surface {
+                xs: xs.clone(),
+                ys: ys.clone(),
+                z: smooth_grid(
+                    &grid,
+                    gaussian_weights(n, dx, hx / 4.0),
+                    gaussian_weights(n, dy, hy / 4.0),
+                ),
+            },
+        ));
+    }
+
+    let (min, max) = surfaces
+        .iter()
+        .map(|(_, s)| s.range())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (a, b)| {
+            (lo.min(a), hi.max(b))
+        });
+    let levels = options.levels(min, max);
+
+    let mut rows = Vec::new();
+    let mut next_piece = 1;
+    for (group, surface) in &surfaces {
+        contour_rows(
+            *group,
+            surface,
+            &levels,
+            options.filled,
+            &mut next_piece,
+            &mut rows,
+        );
+    }
+
+    Ok(StatResult::Transformed {
+        query: contour_query(query, group_by, &rows),
+        stat_columns: CONTOUR_STAT_COLUMNS.iter().map(|s| s.to_string()).collect(),
+        dummy_columns: vec![],
+        consumed_aesthetics: vec!["x".to_string(), "y".to_string()],
+    })
+}
+
+/// Normal reference bandwidth, as `MASS::bandwidth.nrd`
+fn bandwidth_nrd(sd: Option<f64>, iqr: Option<f64>, n: f64) -> f64 {
+    let sd = sd.unwrap_or(0.0);
+    let iqr = iqr.unwrap_or(0.0) / 1.34;
+    let spread = if iqr > 0.0 { sd.min(iqr) } else { sd };
+    4.0 * 1.06 * spread * n.powf(-0.2)
+}
+
+/// Gaussian kernel weights between grid points `k` steps apart
+fn gaussian_weights(n: usize, step: f64, sd: f64) -> Vec<f64> {
+    let norm = 1.0 / (sd * (2.0 * std::f64::consts::PI).sqrt());
+    (0..n)
+        .map(|k| {
+            let u = k as f64 * step / sd;
+            norm * (-0.5 * u * u).exp()
+        })
+        .collect()
+}
+
+/// Convolve a grid of point masses with a separable kernel
+fn smooth_grid(grid: &[Vec<f64>], wx: Vec<f64>, wy: Vec<f64>) -> Vec<Vec<f64>> {
+    let n = grid.len();
+    let mut along_x = vec![vec![0.0; n]; n];
+    for (a, row) in along_x.iter_mut().enumerate() {
+        for (i, column) in grid.iter().enumerate() {
+            let w = wx[a.abs_diff(i)];
+            for (value, mass) in row.iter_mut().zip(column) {
+                *value += w * mass;
+            }
+        }
+    }
+    along_x
+        .iter()
+        .map(|column| {
+            (0..n)
+                .map(|b| {
+                    column
+                        .iter()
+                        .enumerate()
+                        .map(|(j, value)| wy[b.abs_diff(j)] * value)
+                        .sum()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_size_is_validated() {
+        let mut aesthetics = Mappings::new();
+        aesthetics.insert("x", crate::AestheticValue::standard_column("x"));
+        aesthetics.insert("y", crate::AestheticValue::standard_column("y"));
+        let no_query = |_: &str| -> Result<DataFrame> { panic!("n must be checked first") };
+
+        for n in [1.0, 2.5, 1e5] {
+            let parameters = HashMap::from([("n".to_string(), ParameterValue::Number(n))]);
+            let err = stat_density2d("SELECT 1", &aesthetics, &[], &parameters, &no_query)
+                .err()
+                .unwrap();
+            assert!(err.to_string().contains("between 2 and 500"), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Ecdf geom implementation
+
+use std::collections::HashMap;
+
+use super::bin2d::weight_column;
+use super::types::{get_bool_param, get_column_name};
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
+use crate::naming;
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+
+use super::types::Schema;
+
+/// Ecdf geom - empirical cumulative distribution function drawn as a step line
+#[derive(Debug, Clone, Copy)]
+pub struct Ecdf;
+
+impl GeomTrait for Ecdf {
+    fn geom_type(&self) -> GeomType {
+        GeomType::Ecdf
+    }
+
+    fn aesthetics(&self) -> GeomAesthetics {
+        GeomAesthetics {
+            supported: &[
+                "x",
+                "weight",
+                "color",
+                "colour",
+                "stroke",
+                "linewidth",
+                "linetype",
+                "opacity",
+            ],
+            required: &["x"],
+            // y is produced by stat_ecdf but not valid for manual MAPPING
+            hidden: &["y"],
+        }
+    }
+
+    fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
+        &[("x", "x"), ("ecdf", "y")]
+    }
+
+    fn valid_stat_columns(&self) -> &'static [&'static str] {
+        &["x", "ecdf", "count"]
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &[DefaultParam {
+            name: "pad",
+            default: DefaultParamValue::Boolean(true),
+        }]
+    }
+
+    fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
+        &["x", "weight"]
+    }
+
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
+        true
+    }
+
+    fn apply_stat_transform(
+        &self,
+        query: &str,
+        _schema: &Schema,
+        aesthetics: &Mappings,
+        group_by: &[String],
+        parameters: &HashMap<String, ParameterValue>,
+        _execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<StatResult> {
+        stat_ecdf(query, aesthetics, group_by, parameters)
+    }
+}
+
+impl std::fmt::Display for Ecdf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ecdf")
+    }
+}
+
+/// Statistical transformation for ecdf: cumulative proportion of records up to each x
+///
+/// Returns one row per distinct x value within each group. With `pad`, each
+/// group also gets a row at its smallest x with a proportion of 0, so the step
+/// line rises from the bottom of the plot.
+fn stat_ecdf(
+    query: &str,
+    aesthetics: &Mappings,
+    group_by: &[String],
+    parameters: &HashMap<String, ParameterValue>,
+) -> Result<StatResult> {
+    let x_col = get_column_name(aesthetics, "x").ok_or_else(|| {
+        GgsqlError::ValidationError("Ecdf requires 'x' aesthetic mapping".to_string())
+    })?;
+    let weight = weight_column(aesthetics)?.unwrap_or_else(|| "1".to_string());
+    let pad = get_bool_param(parameters, "pad")?.unwrap_or(true);
+
+    let groups: String = group_by.iter().map(|g| format!("{}, ", g)).collect();
+    let partition = if group_by.is_empty() {
+        String::new()
+    } else {
+        format!("PARTITION BY {}", group_by.join(", "))
+    };
+    let stat_x = naming::stat_column("x");
+    let stat_ecdf = naming::stat_column("ecdf");
+    let stat_count = naming::stat_column("count");
+
+    let padding = if pad {
+        format!(
+            " UNION ALL SELECT {groups}MIN(__x__), 0, 0 FROM __ecdf_steps__{group_clause}",
+            groups = groups,
+            group_clause = if group_by.is_empty() {
+                String::new()
+            } else {
+                format!(" GROUP BY {}", group_by.join(", "))
+            }
+        )
+    } else {
+        String::new()
+    };
+
+    let transformed_query = format!(
+        "WITH __stat_src__ AS ({query}), \
+         __ecdf_steps__ AS (\
+            SELECT {groups}{x} AS __x__, SUM({weight}) AS __count__ \
+            FROM __stat_src__ WHERE {x} IS NOT NULL GROUP BY {groups}{x}\
+         ) \
+         SELECT {groups}__x__ AS {stat_x}, \
+            SUM(__count__) OVER ({partition} ORDER BY __x__) * 1.0 / SUM(__count__) OVER ({partition}) AS {stat_ecdf}, \
+            __count__ AS {stat_count} \
+         FROM __ecdf_steps__{padding}",
+        query = query,
+        groups = groups,
+        x = x_col,
+        weight = weight,
+        partition = partition,
+        stat_x = stat_x,
+        stat_ecdf = stat_ecdf,
+        stat_count = stat_count,
+        padding = padding,
+    );
+
+    Ok(StatResult::Transformed {
+        query: transformed_query,
+        stat_columns: vec!["x".to_string(), "ecdf".to_string(), "count".to_string()],
+        dummy_columns: vec![],
+        consumed_aesthetics: vec!["x".to_string(), "weight".to_string()],
+    })
+}
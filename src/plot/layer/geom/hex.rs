@@ -0,0 +1,221 @@
+//! Hex geom implementation
+
+use std::collections::HashMap;
+
+use super::bin2d::{cell_counts_ctes, weight_column, BinGrid};
+use super::types::get_column_name;
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
+use crate::naming;
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+
+use super::types::Schema;
+
+/// Hex geom - counts of x/y pairs in a hexagonal grid
+#[derive(Debug, Clone, Copy)]
+pub struct Hex;
+
+impl GeomTrait for Hex {
+    fn geom_type(&self) -> GeomType {
+        GeomType::Hex
+    }
+
+    fn aesthetics(&self) -> GeomAesthetics {
+        GeomAesthetics {
+            supported: &["x", "y", "weight", "stroke", "opacity"],
+            required: &["x", "y"],
+            // Fill is produced by stat_hex
+            hidden: &["fill"],
+        }
+    }
+
+    fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
+        &[("x", "x"), ("y", "y"), ("count", "fill")]
+    }
+
+    fn valid_stat_columns(&self) -> &'static [&'static str] {
+        &["x", "y", "count", "density", "hex", "vertex"]
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &[
+            DefaultParam {
+                name: "bins",
+                default: DefaultParamValue::Number(30.0),
+            },
+            DefaultParam {
+                name: "binwidth",
+                default: DefaultParamValue::Null,
+            },
+            DefaultParam {
+                name: "drop",
+                default: DefaultParamValue::Boolean(true),
+            },
+        ]
+    }
+
+    fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
+        &["x", "y", "weight"]
+    }
+
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
+        true
+    }
+
+    fn apply_stat_transform(
+        &self,
+        query: &str,
+        _schema: &Schema,
+        aesthetics: &Mappings,
+        group_by: &[String],
+        parameters: &HashMap<String, ParameterValue>,
+        execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<StatResult> {
+        stat_hex(query, aesthetics, group_by, parameters, execute_query)
+    }
+}
+
+impl std::fmt::Display for Hex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "hex")
+    }
+}
+
+/// Offsets of the six hexagon corners from its centre, in lattice units
+///
+/// Hexagons are pointy-topped: neighbours in a row are one unit apart and
+/// rows are half a unit apart, offset by half a unit. The y unit is `sqrt(3)`
+/// y bin widths, so hexagons are regular when both bin widths are drawn equally long.
+const HEX_CORNERS: [(f64, f64); 6] = [
+    (0.5, 1.0 / 6.0),
+    (0.0, 1.0 / 3.0),
+    (-0.5, 1.0 / 6.0),
+    (-0.5, -1.0 / 6.0),
+    (0.0, -1.0 / 3.0),
+    (0.5, -1.0 / 6.0),
+];
+
+/// Statistical transformation for hex: count x/y pairs in a hexagonal grid
+///
+/// Hexagon centres lie on two rectangular lattices, one offset by half a unit in
+/// both directions. Each point goes to the nearest centre, measured in lattice
+/// units with y stretched by `sqrt(3)` so that the cells are hexagons. Cells are indexed on
+/// a doubled grid (`__ix__ + __iy__` is always even) and returned as one row per
+/// corner, numbered by `vertex` within each `hex`.
+fn stat_hex(
+    query: &str,
+    aesthetics: &Mappings,
+    group_by: &[String],
+    parameters: &HashMap<String, ParameterValue>,
+    execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+) -> Result<StatResult> {
+    let x_col = get_column_name(aesthetics, "x").ok_or_else(|| {
+        GgsqlError::ValidationError("Hex requires 'x' aesthetic mapping".to_string())
+    })?;
+    let y_col = get_column_name(aesthetics, "y").ok_or_else(|| {
+        GgsqlError::ValidationError("Hex requires 'y' aesthetic mapping".to_string())
+    })?;
+    let weight = weight_column(aesthetics)?;
+    let grid = BinGrid::from_data(query, &x_col, &y_col, parameters, execute_query)?;
+    let y_unit = grid.y_width * 3f64.sqrt();
+
+    // Position in lattice units relative to the first centre
+    let u = format!("((__x__ - {}) / {})", grid.x_min, grid.x_width);
+    let v = format!("((__y__ - {}) / {})", grid.y_min, y_unit);
+    let nearest_is_even = format!(
+        "(POW({u} - ROUND({u}), 2) + 3 * POW({v} - ROUND({v}), 2) <= POW({u} - FLOOR({u}) - 0.5, 2) + 3 * POW({v} - FLOOR({v}) - 0.5, 2))",
+        u = u,
+        v = v
+    );
+    let index = |pos: &str| {
+        format!(
+            "CASE WHEN {even} THEN 2 * ROUND({pos}) ELSE 2 * FLOOR({pos}) + 1 END",
+            even = nearest_is_even,
+            pos = pos
+        )
+    };
+
+    // Largest doubled index on each axis, for filling in empty cells
+    let last_index = |min: f64, max: f64, width: f64| {
+        let extent = (max - min) / width;
+        (2.0 * extent.round()).max(2.0 * extent.floor() + 1.0) as i64
+    };
+    let fill = (!grid.drop).then(|| {
+        (
+            last_index(grid.x_min, grid.x_max, grid.x_width),
+            last_index(grid.y_min, grid.y_max, y_unit),
+            true,
+        )
+    });
+
+    let cells = cell_counts_ctes(
+        query,
+        (&x_col, &y_col),
+        group_by,
+        (&index(&u), &index(&v)),
+        weight.as_deref(),
+        fill,
+    );
+
+    let corners = HEX_CORNERS
+        .iter()
+        .enumerate()
+        .map(|(i, (du, dv))| format!("({}, {:e}, {:e})", i + 1, du, dv))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let groups: String = group_by.iter().map(|g| format!("{}, ", g)).collect();
+    let partition = if group_by.is_empty() {
+        String::new()
+    } else {
+        format!("PARTITION BY {}", group_by.join(", "))
+    };
+
+    let transformed_query = format!(
+        "{cells}, \
+         __hex_corners__ AS (SELECT * FROM (VALUES {corners}) AS t(__vertex__, __du__, __dv__)), \
+         __hex_counts__ AS (\
+            SELECT *, __count__ * 1.0 / SUM(__count__) OVER ({partition}) AS __density__ FROM __bin_cells__\
+         ) \
+         SELECT {groups}\
+            {x_min} + (__ix__ / 2.0 + __du__) * {wx} AS {x}, \
+            {y_min} + (__iy__ / 2.0 + __dv__) * {wy} AS {y}, \
+            __count__ AS {count}, \
+            __density__ AS {density}, \
+            __ix__ * {stride} + __iy__ AS {hex}, \
+            __vertex__ AS {vertex} \
+         FROM __hex_counts__ CROSS JOIN __hex_corners__",
+        cells = cells,
+        corners = corners,
+        partition = partition,
+        groups = groups,
+        x_min = grid.x_min,
+        wx = grid.x_width,
+        y_min = grid.y_min,
+        wy = y_unit,
+        stride = last_index(grid.y_min, grid.y_max, y_unit) + 1,
+        x = naming::stat_column("x"),
+        y = naming::stat_column("y"),
+        count = naming::stat_column("count"),
+        density = naming::stat_column("density"),
+        hex = naming::stat_column("hex"),
+        vertex = naming::stat_column("vertex"),
+    );
+
+    Ok(StatResult::Transformed {
+        query: transformed_query,
+        stat_columns: vec![
+            "x".to_string(),
+            "y".to_string(),
+            "count".to_string(),
+            "density".to_string(),
+            "hex".to_string(),
+            "vertex".to_string(),
+        ],
+        dummy_columns: vec![],
+        consumed_aesthetics: vec!["x".to_string(), "y".to_string(), "weight".to_string()],
+    })
+}
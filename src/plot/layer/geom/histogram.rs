@@ -2,11 +2,14 @@
 
 use std::collections::HashMap;
 
-use super::types::get_column_name;
+use super::temporal::{is_temporal, TimeInterval};
+use super::types::{column_to_f64, get_column_name};
 use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
 use crate::naming;
+use crate::plot::layer::Position;
 use crate::plot::types::ParameterValue;
 use crate::{DataFrame, GgsqlError, Mappings, Result};
+use polars::prelude::DataType;
 
 use super::types::Schema;
 
@@ -38,6 +41,10 @@ impl GeomTrait for Histogram {
         &["bin", "bin_end", "count", "density"]
     }
 
+    fn default_position(&self) -> Position {
+        Position::Stack { reverse: false }
+    }
+
     fn default_params(&self) -> &'static [DefaultParam] {
         &[
             DefaultParam {
@@ -59,7 +66,11 @@ impl GeomTrait for Histogram {
         &["x"]
     }
 
-    fn needs_stat_transform(&self, _aesthetics: &Mappings) -> bool {
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
         true
     }
 
@@ -113,12 +124,6 @@ fn stat_histogram(
         })
         .expect("closed is not the correct format. Expected a string");
 
-    // Get binwidth from parameters (default: None - use bins to calculate)
-    let explicit_binwidth = parameters.get("binwidth").and_then(|p| match p {
-        ParameterValue::Number(n) => Some(*n),
-        _ => None,
-    });
-
     // Query min/max to compute bin width
     let stats_query = format!(
         "SELECT MIN({x}) as min_val, MAX({x}) as max_val FROM ({query})",
@@ -126,40 +131,49 @@ fn stat_histogram(
         query = query
     );
     let stats_df = execute_query(&stats_query)?;
+    let x_dtype = stats_df
+        .column("min_val")
+        .map(|c| c.dtype().clone())
+        .map_err(|e| GgsqlError::InternalError(format!("Failed to read histogram range: {}", e)))?;
 
-    let (min_val, max_val) = extract_histogram_min_max(&stats_df)?;
-
-    // Compute bin width: use explicit binwidth if provided, otherwise calculate from bins
-    // Round to 10 decimal places to avoid SQL DECIMAL overflow issues
-    let bin_width = if let Some(bw) = explicit_binwidth {
-        bw
-    } else if min_val >= max_val {
-        1.0 // Fallback for edge case
+    let (bin_expr, bin_end_expr) = if is_temporal(&x_dtype) {
+        temporal_bin_exprs(
+            query,
+            &x_col,
+            &x_dtype,
+            bins,
+            parameters.get("binwidth"),
+            execute_query,
+        )?
     } else {
-        ((max_val - min_val) / (bins - 1) as f64 * 1e10).round() / 1e10
-    };
-    let min_val = (min_val * 1e10).round() / 1e10;
+        // Get binwidth from parameters (default: None - use bins to calculate)
+        let explicit_binwidth = match parameters.get("binwidth") {
+            Some(ParameterValue::Number(n)) => Some(*n),
+            Some(ParameterValue::String(s)) => {
+                return Err(GgsqlError::ValidationError(format!(
+                    "Histogram binwidth '{}' is a time interval, but '{}' is not a DATE or TIMESTAMP column",
+                    s, x_col
+                )))
+            }
+            _ => None,
+        };
 
-    // Build the bin expression (bin start)
-    let bin_expr = if closed == "left" {
-        // Left-closed [a, b): use FLOOR
-        format!(
-            "(FLOOR(({x} - {min} + {w} * 0.5) / {w})) * {w} + {min} - {w} * 0.5",
-            x = x_col,
-            min = min_val,
-            w = bin_width
-        )
-    } else {
-        // Right-closed (a, b]: use CEIL - 1 with GREATEST for min value
-        format!(
-            "(GREATEST(CEIL(({x} - {min} + {w} * 0.5) / {w}) - 1, 0)) * {w} + {min} - {w} * 0.5",
-            x = x_col,
+        let (min_val, max_val) = extract_histogram_min_max(&stats_df)?;
+
+        let bin_width = histogram_bin_width(min_val, max_val, bins, explicit_binwidth);
+        let min_val = (min_val * 1e10).round() / 1e10;
+
+        // Build the bin expression (bin start)
+        let bin_expr = format!(
+            "({index}) * {w} + {min} - {w} * 0.5",
+            index = histogram_bin_index_expr(&x_col, min_val, bin_width, closed),
             min = min_val,
             w = bin_width
-        )
+        );
+        // Build the bin end expression (bin start + bin width)
+        let bin_end_expr = format!("{expr} + {w}", expr = bin_expr, w = bin_width);
+        (bin_expr, bin_end_expr)
     };
-    // Build the bin end expression (bin start + bin width)
-    let bin_end_expr = format!("{expr} + {w}", expr = bin_expr, w = bin_width);
 
     // Build grouped columns (group_by includes partition_by + facet variables)
     let group_cols = if group_by.is_empty() {
@@ -245,6 +259,90 @@ fn stat_histogram(
     })
 }
 
+/// Bin start and end expressions for a DATE or TIMESTAMP `x`
+///
+/// Bins are calendar intervals given as a `binwidth` string such as `'1 week'`,
+/// or chosen from the span of the data and `bins`. They are always closed on
+/// the left, and both expressions keep the temporal type of `x`.
+fn temporal_bin_exprs(
+    query: &str,
+    x_col: &str,
+    x_dtype: &DataType,
+    bins: usize,
+    binwidth: Option<&ParameterValue>,
+    execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+) -> Result<(String, String)> {
+    let date = matches!(x_dtype, DataType::Date);
+    let interval = match binwidth {
+        Some(ParameterValue::String(s)) => TimeInterval::parse(s)?,
+        Some(ParameterValue::Number(_)) => {
+            return Err(GgsqlError::ValidationError(format!(
+                "Histogram binwidth for DATE or TIMESTAMP column '{}' must be a time interval such as '1 week'",
+                x_col
+            )))
+        }
+        _ => {
+            let span_query = format!(
+                "SELECT CAST(EPOCH(MAX({x})) - EPOCH(MIN({x})) AS DOUBLE) AS span FROM ({query})",
+                x = x_col,
+                query = query
+            );
+            let span = column_to_f64(&execute_query(&span_query)?, "span")?
+                .first()
+                .copied()
+                .flatten()
+                .unwrap_or(0.0);
+            TimeInterval::for_span(span, bins, date)
+        }
+    };
+    let bin_expr = interval.bucket_sql(x_col, date);
+    let bin_end_expr = interval.end_sql(&bin_expr, date);
+    Ok((bin_expr, bin_end_expr))
+}
+
+/// Compute the histogram bin width
+///
+/// Uses the explicit binwidth if provided, otherwise spreads `bins` bins over the
+/// range. Rounded to 10 decimal places to avoid SQL DECIMAL overflow issues.
+pub fn histogram_bin_width(
+    min_val: f64,
+    max_val: f64,
+    bins: usize,
+    explicit_binwidth: Option<f64>,
+) -> f64 {
+    if let Some(bw) = explicit_binwidth {
+        bw
+    } else if min_val >= max_val || bins < 2 {
+        1.0 // Fallback for edge case
+    } else {
+        ((max_val - min_val) / (bins - 1) as f64 * 1e10).round() / 1e10
+    }
+}
+
+/// Build the SQL expression for the zero-based bin index of `col`
+///
+/// Bins are centred on `min_val + k * bin_width`, so bin `k` starts at
+/// `k * bin_width + min_val - bin_width / 2`.
+pub fn histogram_bin_index_expr(col: &str, min_val: f64, bin_width: f64, closed: &str) -> String {
+    if closed == "left" {
+        // Left-closed [a, b): use FLOOR
+        format!(
+            "FLOOR(({x} - {min} + {w} * 0.5) / {w})",
+            x = col,
+            min = min_val,
+            w = bin_width
+        )
+    } else {
+        // Right-closed (a, b]: use CEIL - 1 with GREATEST for min value
+        format!(
+            "GREATEST(CEIL(({x} - {min} + {w} * 0.5) / {w}) - 1, 0)",
+            x = col,
+            min = min_val,
+            w = bin_width
+        )
+    }
+}
+
 /// Extract min and max from histogram stats DataFrame
 pub fn extract_histogram_min_max(df: &DataFrame) -> Result<(f64, f64)> {
     if df.height() == 0 {
@@ -0,0 +1,87 @@
+//! LineRange geom implementation
+
+use std::collections::HashMap;
+
+use super::summary::{stat_summary, uses_summary_stat, validate_stat_param, SUMMARY_PARAMS};
+use super::{DefaultParam, GeomAesthetics, GeomTrait, GeomType, StatResult};
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, Mappings, Result};
+
+use super::types::Schema;
+
+/// LineRange geom - vertical (or horizontal) line spanning an interval
+#[derive(Debug, Clone, Copy)]
+pub struct LineRange;
+
+impl GeomTrait for LineRange {
+    fn geom_type(&self) -> GeomType {
+        GeomType::LineRange
+    }
+
+    fn aesthetics(&self) -> GeomAesthetics {
+        GeomAesthetics {
+            supported: &[
+                "x",
+                "y",
+                "ymin",
+                "ymax",
+                "xmin",
+                "xmax",
+                "color",
+                "colour",
+                "stroke",
+                "linetype",
+                "linewidth",
+                "opacity",
+            ],
+            required: &[],
+            hidden: &[],
+        }
+    }
+
+    fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
+        &[("y", "y"), ("ymin", "ymin"), ("ymax", "ymax")]
+    }
+
+    fn valid_stat_columns(&self) -> &'static [&'static str] {
+        &["y", "ymin", "ymax"]
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &SUMMARY_PARAMS
+    }
+
+    fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
+        &["y"]
+    }
+
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
+        uses_summary_stat(parameters)
+    }
+
+    fn apply_stat_transform(
+        &self,
+        query: &str,
+        _schema: &Schema,
+        aesthetics: &Mappings,
+        group_by: &[String],
+        parameters: &HashMap<String, ParameterValue>,
+        execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<StatResult> {
+        validate_stat_param(parameters)?;
+        if !uses_summary_stat(parameters) {
+            return Ok(StatResult::Identity);
+        }
+        stat_summary(query, aesthetics, group_by, parameters, execute_query)
+    }
+}
+
+impl std::fmt::Display for LineRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "linerange")
+    }
+}
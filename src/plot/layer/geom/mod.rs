@@ -20,11 +20,13 @@
 //! assert!(point.aesthetics().required.contains(&"x"));
 //! ```
 
+use super::position::{Position, POSITION_PARAMS};
 use crate::{DataFrame, Mappings, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 
+pub mod temporal;
 pub mod types;
 
 // Geom implementations
@@ -32,19 +34,34 @@ mod abline;
 mod area;
 mod arrow;
 mod bar;
+mod bin2d;
 mod boxplot;
+mod contour;
+mod crossbar;
 mod density;
+mod density2d;
+mod ecdf;
 mod errorbar;
+mod hex;
 mod histogram;
 mod hline;
 mod label;
 mod line;
+mod linerange;
+mod numeric;
 mod path;
 mod point;
+mod pointrange;
 mod polygon;
+mod qq;
+mod qqline;
+mod rect;
 mod ribbon;
+mod rug;
 mod segment;
 mod smooth;
+mod step;
+mod summary;
 mod text;
 mod tile;
 mod violin;
@@ -58,19 +75,32 @@ pub use abline::AbLine;
 pub use area::Area;
 pub use arrow::Arrow;
 pub use bar::Bar;
+pub use bin2d::Bin2d;
 pub use boxplot::Boxplot;
+pub use contour::Contour;
+pub use crossbar::CrossBar;
 pub use density::Density;
+pub use density2d::Density2d;
+pub use ecdf::Ecdf;
 pub use errorbar::ErrorBar;
+pub use hex::Hex;
 pub use histogram::Histogram;
 pub use hline::HLine;
 pub use label::Label;
 pub use line::Line;
+pub use linerange::LineRange;
 pub use path::Path;
 pub use point::Point;
+pub use pointrange::PointRange;
 pub use polygon::Polygon;
+pub use qq::Qq;
+pub use qqline::QqLine;
+pub use rect::Rect;
 pub use ribbon::Ribbon;
+pub use rug::Rug;
 pub use segment::Segment;
 pub use smooth::Smooth;
+pub use step::Step;
 pub use text::Text;
 pub use tile::Tile;
 pub use violin::Violin;
@@ -103,6 +133,19 @@ pub enum GeomType {
     VLine,
     AbLine,
     ErrorBar,
+    Bin2d,
+    Hex,
+    Density2d,
+    Contour,
+    Ecdf,
+    Qq,
+    QqLine,
+    Step,
+    Rect,
+    Rug,
+    LineRange,
+    PointRange,
+    CrossBar,
 }
 
 impl std::fmt::Display for GeomType {
@@ -129,6 +172,19 @@ impl std::fmt::Display for GeomType {
             GeomType::VLine => "vline",
             GeomType::AbLine => "abline",
             GeomType::ErrorBar => "errorbar",
+            GeomType::Bin2d => "bin2d",
+            GeomType::Hex => "hex",
+            GeomType::Density2d => "density2d",
+            GeomType::Contour => "contour",
+            GeomType::Ecdf => "ecdf",
+            GeomType::Qq => "qq",
+            GeomType::QqLine => "qqline",
+            GeomType::Step => "step",
+            GeomType::Rect => "rect",
+            GeomType::Rug => "rug",
+            GeomType::LineRange => "linerange",
+            GeomType::PointRange => "pointrange",
+            GeomType::CrossBar => "crossbar",
         };
         write!(f, "{}", s)
     }
@@ -168,6 +224,16 @@ pub trait GeomTrait: std::fmt::Debug + std::fmt::Display + Send + Sync {
         &[]
     }
 
+    /// Check the values of the geom's own parameters.
+    ///
+    /// Names are checked against `valid_settings()`; this checks what they are set to.
+    fn validate_params(
+        &self,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> std::result::Result<(), String> {
+        Ok(())
+    }
+
     /// Returns aesthetics consumed as input by this geom's stat transform.
     ///
     /// Columns mapped to these aesthetics are used by the stat and don't need
@@ -176,8 +242,20 @@ pub trait GeomTrait: std::fmt::Debug + std::fmt::Display + Send + Sync {
         &[]
     }
 
+    /// Returns position axes the stat redraws on positions of its own.
+    ///
+    /// Only the primary aesthetic of these axes (e.g. `x`) trains the scale;
+    /// the stat's own positions (e.g. `xmin` and `xmax`) are left out.
+    fn stat_rewritten_axes(&self) -> &'static [&'static str] {
+        &[]
+    }
+
     /// Check if this geom requires a statistical transformation
-    fn needs_stat_transform(&self, _aesthetics: &Mappings) -> bool {
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
         false
     }
 
@@ -196,14 +274,25 @@ pub trait GeomTrait: std::fmt::Debug + std::fmt::Display + Send + Sync {
         Ok(StatResult::Identity)
     }
 
+    /// Returns the position adjustment used without a `position` setting.
+    fn default_position(&self) -> Position {
+        Position::Identity
+    }
+
     /// Returns valid parameter names for SETTING clause.
     ///
-    /// Combines supported aesthetics with non-aesthetic parameters.
+    /// Combines supported aesthetics with non-aesthetic parameters and the
+    /// position settings shared by all geoms.
     fn valid_settings(&self) -> Vec<&'static str> {
         let mut valid: Vec<&'static str> = self.aesthetics().supported.to_vec();
         for param in self.default_params() {
             valid.push(param.name);
         }
+        for param in POSITION_PARAMS {
+            if !valid.contains(&param) {
+                valid.push(param);
+            }
+        }
         valid
     }
 }
@@ -321,6 +410,71 @@ impl Geom {
         Self(Arc::new(ErrorBar))
     }
 
+    /// Create a Bin2d geom
+    pub fn bin2d() -> Self {
+        Self(Arc::new(Bin2d))
+    }
+
+    /// Create a Hex geom
+    pub fn hex() -> Self {
+        Self(Arc::new(Hex))
+    }
+
+    /// Create a Density2d geom
+    pub fn density2d() -> Self {
+        Self(Arc::new(Density2d))
+    }
+
+    /// Create a Contour geom
+    pub fn contour() -> Self {
+        Self(Arc::new(Contour))
+    }
+
+    /// Create an Ecdf geom
+    pub fn ecdf() -> Self {
+        Self(Arc::new(Ecdf))
+    }
+
+    /// Create a Qq geom
+    pub fn qq() -> Self {
+        Self(Arc::new(Qq))
+    }
+
+    /// Create a QqLine geom
+    pub fn qqline() -> Self {
+        Self(Arc::new(QqLine))
+    }
+
+    /// Create a Step geom
+    pub fn step() -> Self {
+        Self(Arc::new(Step))
+    }
+
+    /// Create a Rect geom
+    pub fn rect() -> Self {
+        Self(Arc::new(Rect))
+    }
+
+    /// Create a Rug geom
+    pub fn rug() -> Self {
+        Self(Arc::new(Rug))
+    }
+
+    /// Create a LineRange geom
+    pub fn linerange() -> Self {
+        Self(Arc::new(LineRange))
+    }
+
+    /// Create a PointRange geom
+    pub fn pointrange() -> Self {
+        Self(Arc::new(PointRange))
+    }
+
+    /// Create a CrossBar geom
+    pub fn crossbar() -> Self {
+        Self(Arc::new(CrossBar))
+    }
+
     /// Create a Geom from a GeomType
     pub fn from_type(t: GeomType) -> Self {
         match t {
@@ -345,6 +499,19 @@ impl Geom {
             GeomType::VLine => Self::vline(),
             GeomType::AbLine => Self::abline(),
             GeomType::ErrorBar => Self::errorbar(),
+            GeomType::Bin2d => Self::bin2d(),
+            GeomType::Hex => Self::hex(),
+            GeomType::Density2d => Self::density2d(),
+            GeomType::Contour => Self::contour(),
+            GeomType::Ecdf => Self::ecdf(),
+            GeomType::Qq => Self::qq(),
+            GeomType::QqLine => Self::qqline(),
+            GeomType::Step => Self::step(),
+            GeomType::Rect => Self::rect(),
+            GeomType::Rug => Self::rug(),
+            GeomType::LineRange => Self::linerange(),
+            GeomType::PointRange => Self::pointrange(),
+            GeomType::CrossBar => Self::crossbar(),
         }
     }
 
@@ -378,9 +545,18 @@ impl Geom {
         self.0.stat_consumed_aesthetics()
     }
 
+    /// Get position axes redrawn by the stat
+    pub fn stat_rewritten_axes(&self) -> &'static [&'static str] {
+        self.0.stat_rewritten_axes()
+    }
+
     /// Check if stat transform is needed
-    pub fn needs_stat_transform(&self, aesthetics: &Mappings) -> bool {
-        self.0.needs_stat_transform(aesthetics)
+    pub fn needs_stat_transform(
+        &self,
+        aesthetics: &Mappings,
+        parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
+        self.0.needs_stat_transform(aesthetics, parameters)
     }
 
     /// Apply stat transform
@@ -403,10 +579,23 @@ impl Geom {
         )
     }
 
+    /// Get default position adjustment
+    pub fn default_position(&self) -> Position {
+        self.0.default_position()
+    }
+
     /// Get valid settings
     pub fn valid_settings(&self) -> Vec<&'static str> {
         self.0.valid_settings()
     }
+
+    /// Check the values of the geom's own parameters
+    pub fn validate_params(
+        &self,
+        parameters: &HashMap<String, ParameterValue>,
+    ) -> std::result::Result<(), String> {
+        self.0.validate_params(parameters)
+    }
 }
 
 impl std::fmt::Debug for Geom {
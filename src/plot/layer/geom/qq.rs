@@ -0,0 +1,318 @@
+//! Qq geom implementation
+//!
+//! Also home to the theoretical distributions and sample quantiles shared with
+//! the qqline geom.
+
+use std::collections::HashMap;
+
+use super::numeric::{normal_quantile, normal_quantile_sql};
+use super::types::{get_column_name, get_string_param};
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
+use crate::naming;
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+
+use super::types::Schema;
+
+/// Settings selecting the theoretical distribution of the qq and qqline geoms
+pub const DISTRIBUTION_PARAMS: [DefaultParam; 2] = [
+    DefaultParam {
+        name: "distribution",
+        default: DefaultParamValue::String("normal"),
+    },
+    DefaultParam {
+        name: "dparams",
+        default: DefaultParamValue::Null,
+    },
+];
+
+/// Qq geom - sample quantiles against the quantiles of a theoretical distribution
+#[derive(Debug, Clone, Copy)]
+pub struct Qq;
+
+impl GeomTrait for Qq {
+    fn geom_type(&self) -> GeomType {
+        GeomType::Qq
+    }
+
+    fn aesthetics(&self) -> GeomAesthetics {
+        GeomAesthetics {
+            supported: &[
+                "sample", "color", "colour", "fill", "stroke", "size", "shape", "opacity",
+            ],
+            required: &["sample"],
+            // x and y are produced by stat_qq but not valid for manual MAPPING
+            hidden: &["x", "y"],
+        }
+    }
+
+    fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
+        &[("theoretical", "x"), ("sample", "y")]
+    }
+
+    fn valid_stat_columns(&self) -> &'static [&'static str] {
+        &["sample", "theoretical"]
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &DISTRIBUTION_PARAMS
+    }
+
+    fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
+        &["sample"]
+    }
+
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
+        true
+    }
+
+    fn apply_stat_transform(
+        &self,
+        query: &str,
+        _schema: &Schema,
+        aesthetics: &Mappings,
+        group_by: &[String],
+        parameters: &HashMap<String, ParameterValue>,
+        _execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<StatResult> {
+        stat_qq(query, aesthetics, group_by, parameters)
+    }
+}
+
+impl std::fmt::Display for Qq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "qq")
+    }
+}
+
+/// Theoretical distribution that samples are compared against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    Normal { mean: f64, sd: f64 },
+    Exponential { rate: f64 },
+    Uniform { min: f64, max: f64 },
+}
+
+impl Distribution {
+    /// Read the `distribution` and `dparams` settings
+    ///
+    /// `dparams` are the distribution parameters in order: mean and standard
+    /// deviation for normal (default 0 and 1), rate for exponential (default 1),
+    /// and minimum and maximum for uniform (default 0 and 1).
+    pub fn from_parameters(parameters: &HashMap<String, ParameterValue>) -> Result<Self> {
+        let name = get_string_param(parameters, "distribution")?.unwrap_or("normal");
+        let dparams: Option<Vec<f64>> = match parameters.get("dparams") {
+            None => None,
+            Some(ParameterValue::Number(n)) => Some(vec![*n]),
+            Some(ParameterValue::Array(values)) => Some(
+                values
+                    .iter()
+                    .map(|v| v.as_number())
+                    .collect::<Option<Vec<f64>>>()
+                    .ok_or_else(|| {
+                        GgsqlError::ValidationError(
+                            "Setting 'dparams' must be an array of numbers".to_string(),
+                        )
+                    })?,
+            ),
+            Some(_) => {
+                return Err(GgsqlError::ValidationError(
+                    "Setting 'dparams' must be an array of numbers".to_string(),
+                ))
+            }
+        };
+        let params = |defaults: &[f64]| -> Result<Vec<f64>> {
+            match &dparams {
+                None => Ok(defaults.to_vec()),
+                Some(values) if values.len() == defaults.len() => Ok(values.clone()),
+                Some(values) => Err(GgsqlError::ValidationError(format!(
+                    "The {} distribution takes {} parameter(s) in 'dparams', got {}",
+                    name,
+                    defaults.len(),
+                    values.len()
+                ))),
+            }
+        };
+
+        let distribution = match name {
+            "normal" => {
+                let p = params(&[0.0, 1.0])?;
+                Distribution::Normal {
+                    mean: p[0],
+                    sd: p[1],
+                }
+            }
+            "exponential" => Distribution::Exponential {
+                rate: params(&[1.0])?[0],
+            },
+            "uniform" => {
+                let p = params(&[0.0, 1.0])?;
+                Distribution::Uniform {
+                    min: p[0],
+                    max: p[1],
+                }
+            }
+            other => {
+                return Err(GgsqlError::ValidationError(format!(
+                    "Unknown distribution '{}'. Expected 'normal', 'exponential' or 'uniform'",
+                    other
+                )))
+            }
+        };
+
+        let valid = match distribution {
+            Distribution::Normal { sd, .. } => sd > 0.0,
+            Distribution::Exponential { rate } => rate > 0.0,
+            Distribution::Uniform { min, max } => min < max,
+        };
+        if !valid {
+            return Err(GgsqlError::ValidationError(format!(
+                "Invalid 'dparams' for the {} distribution",
+                name
+            )));
+        }
+        Ok(distribution)
+    }
+
+    /// Quantile of the probability `p`
+    pub fn quantile(&self, p: f64) -> f64 {
+        match self {
+            Distribution::Normal { mean, sd } => mean + sd * normal_quantile(p),
+            Distribution::Exponential { rate } => -(1.0 - p).ln() / rate,
+            Distribution::Uniform { min, max } => min + p * (max - min),
+        }
+    }
+
+    /// SQL expression for the quantile of the probability in column `p`
+    pub fn quantile_sql(&self, p: &str) -> String {
+        match self {
+            Distribution::Normal { mean, sd } => {
+                format!("({:e} + {:e} * {})", mean, sd, normal_quantile_sql(p))
+            }
+            Distribution::Exponential { rate } => format!("(-LN(1 - {}) / {:e})", p, rate),
+            Distribution::Uniform { min, max } => {
+                format!("({:e} + {} * {:e})", min, p, max - min)
+            }
+        }
+    }
+}
+
+/// Build the CTEs ranking the samples of each group
+///
+/// Produces `__qq_points__` with the group columns, the `__sample__` value and
+/// its plotting position `__p__`, following R's `ppoints()`.
+pub fn qq_points_ctes(query: &str, sample_col: &str, group_by: &[String]) -> String {
+    let groups: String = group_by.iter().map(|g| format!("{}, ", g)).collect();
+    let partition = if group_by.is_empty() {
+        String::new()
+    } else {
+        format!("PARTITION BY {}", group_by.join(", "))
+    };
+    format!(
+        "WITH __stat_src__ AS ({query}), \
+         __qq_ranks__ AS (\
+            SELECT {groups}CAST({sample} AS DOUBLE) AS __sample__, \
+            ROW_NUMBER() OVER ({partition} ORDER BY {sample}) AS __i__, \
+            COUNT(*) OVER ({partition}) AS __n__ \
+            FROM __stat_src__ WHERE {sample} IS NOT NULL\
+         ), \
+         __qq_offsets__ AS (\
+            SELECT *, CASE WHEN __n__ <= 10 THEN 0.375 ELSE 0.5 END AS __a__ FROM __qq_ranks__\
+         ), \
+         __qq_points__ AS (\
+            SELECT {groups}__sample__, (__i__ - __a__) / (__n__ + 1 - 2 * __a__) AS __p__ FROM __qq_offsets__\
+         )",
+        query = query,
+        groups = groups,
+        sample = sample_col,
+        partition = partition
+    )
+}
+
+/// Column holding the samples of a qq or qqline layer
+pub fn sample_column(aesthetics: &Mappings, geom: &str) -> Result<String> {
+    get_column_name(aesthetics, "sample").ok_or_else(|| {
+        GgsqlError::ValidationError(format!("{} requires 'sample' aesthetic mapping", geom))
+    })
+}
+
+/// Statistical transformation for qq: pair each sample with its theoretical quantile
+fn stat_qq(
+    query: &str,
+    aesthetics: &Mappings,
+    group_by: &[String],
+    parameters: &HashMap<String, ParameterValue>,
+) -> Result<StatResult> {
+    let sample_col = sample_column(aesthetics, "Qq")?;
+    let distribution = Distribution::from_parameters(parameters)?;
+    let groups: String = group_by.iter().map(|g| format!("{}, ", g)).collect();
+
+    let transformed_query = format!(
+        "{points} SELECT {groups}{theoretical} AS {stat_theoretical}, __sample__ AS {stat_sample} FROM __qq_points__",
+        points = qq_points_ctes(query, &sample_col, group_by),
+        groups = groups,
+        theoretical = distribution.quantile_sql("__p__"),
+        stat_theoretical = naming::stat_column("theoretical"),
+        stat_sample = naming::stat_column("sample"),
+    );
+
+    Ok(StatResult::Transformed {
+        query: transformed_query,
+        stat_columns: vec!["sample".to_string(), "theoretical".to_string()],
+        dummy_columns: vec![],
+        consumed_aesthetics: vec!["sample".to_string()],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribution_quantiles() {
+        let normal = Distribution::Normal {
+            mean: 10.0,
+            sd: 2.0,
+        };
+        assert!((normal.quantile(0.975) - 13.919_927_969).abs() < 1e-6);
+        let exponential = Distribution::Exponential { rate: 2.0 };
+        assert!((exponential.quantile(0.5) - 2f64.ln() / 2.0).abs() < 1e-12);
+        let uniform = Distribution::Uniform { min: 1.0, max: 3.0 };
+        assert_eq!(uniform.quantile(0.25), 1.5);
+    }
+
+    #[test]
+    fn test_distribution_parameters() {
+        let mut parameters = HashMap::new();
+        assert_eq!(
+            Distribution::from_parameters(&parameters).unwrap(),
+            Distribution::Normal { mean: 0.0, sd: 1.0 }
+        );
+
+        parameters.insert(
+            "distribution".to_string(),
+            ParameterValue::String("exponential".to_string()),
+        );
+        parameters.insert("dparams".to_string(), ParameterValue::Number(3.0));
+        assert_eq!(
+            Distribution::from_parameters(&parameters).unwrap(),
+            Distribution::Exponential { rate: 3.0 }
+        );
+
+        parameters.insert(
+            "distribution".to_string(),
+            ParameterValue::String("uniform".to_string()),
+        );
+        assert!(Distribution::from_parameters(&parameters).is_err());
+
+        parameters.insert(
+            "distribution".to_string(),
+            ParameterValue::String("cauchy".to_string()),
+        );
+        assert!(Distribution::from_parameters(&parameters).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+//! QqLine geom implementation
+
+use std::collections::HashMap;
+
+use super::qq::{qq_points_ctes, sample_column, Distribution};
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
+use crate::naming;
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+
+use super::types::Schema;
+
+/// QqLine geom - reference line through the quartiles of a quantile-quantile plot
+#[derive(Debug, Clone, Copy)]
+pub struct QqLine;
+
+impl GeomTrait for QqLine {
+    fn geom_type(&self) -> GeomType {
+        GeomType::QqLine
+    }
+
+    fn aesthetics(&self) -> GeomAesthetics {
+        GeomAesthetics {
+            supported: &[
+                "sample",
+                "color",
+                "colour",
+                "stroke",
+                "linewidth",
+                "linetype",
+                "opacity",
+            ],
+            required: &["sample"],
+            // x and y are produced by stat_qqline but not valid for manual MAPPING
+            hidden: &["x", "y"],
+        }
+    }
+
+    fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
+        &[("x", "x"), ("y", "y")]
+    }
+
+    fn valid_stat_columns(&self) -> &'static [&'static str] {
+        &["x", "y", "slope", "intercept"]
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &[
+            DefaultParam {
+                name: "distribution",
+                default: DefaultParamValue::String("normal"),
+            },
+            DefaultParam {
+                name: "dparams",
+                default: DefaultParamValue::Null,
+            },
+            DefaultParam {
+                name: "line_p",
+                default: DefaultParamValue::Null,
+            },
+        ]
+    }
+
+    fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
+        &["sample"]
+    }
+
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
+        true
+    }
+
+    fn apply_stat_transform(
+        &self,
+        query: &str,
+        _schema: &Schema,
+        aesthetics: &Mappings,
+        group_by: &[String],
+        parameters: &HashMap<String, ParameterValue>,
+        _execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<StatResult> {
+        stat_qqline(query, aesthetics, group_by, parameters)
+    }
+}
+
+impl std::fmt::Display for QqLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "qqline")
+    }
+}
+
+/// Read the `line_p` setting: the two probabilities the line passes through
+fn line_probabilities(parameters: &HashMap<String, ParameterValue>) -> Result<(f64, f64)> {
+    let invalid = || {
+        GgsqlError::ValidationError(
+            "Setting 'line_p' must be an array of two probabilities".to_string(),
+        )
+    };
+    let (p1, p2) = match parameters.get("line_p") {
+        None => return Ok((0.25, 0.75)),
+        Some(ParameterValue::Array(values)) => match values.as_slice() {
+            [a, b] => (
+                a.as_number().ok_or_else(invalid)?,
+                b.as_number().ok_or_else(invalid)?,
+            ),
+            _ => return Err(invalid()),
+        },
+        Some(_) => return Err(invalid()),
+    };
+    if !(p1 > 0.0 && p1 < 1.0 && p2 > 0.0 && p2 < 1.0) || p1 == p2 {
+        return Err(invalid());
+    }
+    Ok((p1, p2))
+}
+
+/// Statistical transformation for qqline: line through two quantile pairs
+///
+/// The line passes through the sample and theoretical quantiles at both
+/// `line_p` probabilities (the quartiles by default), like ggplot2's
+/// `stat_qq_line()`. It spans the theoretical quantiles of the qq points.
+fn stat_qqline(
+    query: &str,
+    aesthetics: &Mappings,
+    group_by: &[String],
+    parameters: &HashMap<String, ParameterValue>,
+) -> Result<StatResult> {
+    let sample_col = sample_column(aesthetics, "QqLine")?;
+    let distribution = Distribution::from_parameters(parameters)?;
+    let (p1, p2) = line_probabilities(parameters)?;
+
+    let groups: String = group_by.iter().map(|g| format!("{}, ", g)).collect();
+    let group_clause = if group_by.is_empty() {
+        String::new()
+    } else {
+        format!(" GROUP BY {}", group_by.join(", "))
+    };
+    let (x1, x2) = (distribution.quantile(p1), distribution.quantile(p2));
+    let end = |p: &str| {
+        format!(
+            "SELECT {groups}{x} AS {stat_x}, __intercept__ + __slope__ * {x} AS {stat_y}, \
+             __slope__ AS {stat_slope}, __intercept__ AS {stat_intercept} FROM __qq_line__",
+            groups = groups,
+            x = distribution.quantile_sql(p),
+            stat_x = naming::stat_column("x"),
+            stat_y = naming::stat_column("y"),
+            stat_slope = naming::stat_column("slope"),
+            stat_intercept = naming::stat_column("intercept"),
+        )
+    };
+
+    let transformed_query = format!(
+        "{points}, \
+         __qq_quantiles__ AS (\
+            SELECT {groups}MIN(__p__) AS __p_min__, MAX(__p__) AS __p_max__, \
+            QUANTILE_CONT(__sample__, {p1:e}) AS __y1__, QUANTILE_CONT(__sample__, {p2:e}) AS __y2__ \
+            FROM __qq_points__{group_clause}\
+         ), \
+         __qq_slopes__ AS (\
+            SELECT *, (__y2__ - __y1__) / {dx:e} AS __slope__ FROM __qq_quantiles__\
+         ), \
+         __qq_line__ AS (\
+            SELECT *, __y1__ - __slope__ * {x1:e} AS __intercept__ FROM __qq_slopes__\
+         ) \
+         {start} UNION ALL {finish}",
+        points = qq_points_ctes(query, &sample_col, group_by),
+        groups = groups,
+        p1 = p1,
+        p2 = p2,
+        group_clause = group_clause,
+        dx = x2 - x1,
+        x1 = x1,
+        start = end("__p_min__"),
+        finish = end("__p_max__"),
+    );
+
+    Ok(StatResult::Transformed {
+        query: transformed_query,
+        stat_columns: vec![
+            "x".to_string(),
+            "y".to_string(),
+            "slope".to_string(),
+            "intercept".to_string(),
+        ],
+        dummy_columns: vec![],
+        consumed_aesthetics: vec!["sample".to_string()],
+    })
+}
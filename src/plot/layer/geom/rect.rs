@@ -0,0 +1,39 @@
+//! Rect geom implementation
+
+use super::{GeomAesthetics, GeomTrait, GeomType};
+
+/// Rect geom - rectangles spanning xmin to xmax and ymin to ymax
+#[derive(Debug, Clone, Copy)]
+pub struct Rect;
+
+impl GeomTrait for Rect {
+    fn geom_type(&self) -> GeomType {
+        GeomType::Rect
+    }
+
+    fn aesthetics(&self) -> GeomAesthetics {
+        GeomAesthetics {
+            supported: &[
+                "xmin",
+                "xmax",
+                "ymin",
+                "ymax",
+                "color",
+                "colour",
+                "fill",
+                "stroke",
+                "linetype",
+                "linewidth",
+                "opacity",
+            ],
+            required: &["xmin", "xmax", "ymin", "ymax"],
+            hidden: &[],
+        }
+    }
+}
+
+impl std::fmt::Display for Rect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rect")
+    }
+}
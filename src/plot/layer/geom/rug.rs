@@ -0,0 +1,50 @@
+//! Rug geom implementation
+
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType};
+
+/// Rug geom - marginal ticks showing the distribution of x and/or y
+#[derive(Debug, Clone, Copy)]
+pub struct Rug;
+
+impl GeomTrait for Rug {
+    fn geom_type(&self) -> GeomType {
+        GeomType::Rug
+    }
+
+    fn aesthetics(&self) -> GeomAesthetics {
+        GeomAesthetics {
+            // Either x or y (or both) draw ticks; neither is required on its own
+            supported: &[
+                "x",
+                "y",
+                "color",
+                "colour",
+                "stroke",
+                "linetype",
+                "linewidth",
+                "opacity",
+            ],
+            required: &[],
+            hidden: &[],
+        }
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &[
+            DefaultParam {
+                name: "sides",
+                default: DefaultParamValue::String("bl"),
+            },
+            DefaultParam {
+                name: "length",
+                default: DefaultParamValue::Number(0.03),
+            },
+        ]
+    }
+}
+
+impl std::fmt::Display for Rug {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rug")
+    }
+}
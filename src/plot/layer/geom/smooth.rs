@@ -1,7 +1,27 @@
 //! Smooth geom implementation
 
-use super::{GeomAesthetics, GeomTrait, GeomType};
-use crate::Mappings;
+use std::collections::{BTreeMap, HashMap};
+
+use super::numeric::{solve_linear_system, t_quantile};
+use super::types::{
+    column_to_f64, get_bool_param, get_column_name, get_number_param, get_string_param,
+    group_index_expr, grouped_values_query,
+};
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
+use crate::naming;
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+
+use super::types::Schema;
+
+/// Without an explicit method, groups smaller than this are fitted with LOESS
+const LOESS_MAX_POINTS: usize = 1000;
+
+/// Largest group LOESS fits; each fit takes time quadratic in the group size
+const LOESS_POINT_LIMIT: usize = 10_000;
+
+/// Largest number of points the fit is evaluated at
+const MAX_GRID_SIZE: usize = 1000;
 
 /// Smooth geom - smoothed conditional means (regression, LOESS, etc.)
 #[derive(Debug, Clone, Copy)]
@@ -14,17 +34,75 @@ impl GeomTrait for Smooth {
 
     fn aesthetics(&self) -> GeomAesthetics {
         GeomAesthetics {
-            supported: &["x", "y", "color", "colour", "stroke", "linetype", "opacity"],
+            supported: &[
+                "x", "y", "color", "colour", "fill", "stroke", "linetype", "opacity",
+            ],
             required: &["x", "y"],
-            hidden: &[],
+            // ymin and ymax are the confidence band produced by stat_smooth
+            hidden: &["ymin", "ymax"],
         }
     }
 
-    fn needs_stat_transform(&self, _aesthetics: &Mappings) -> bool {
+    fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
+        &[("x", "x"), ("y", "y"), ("ymin", "ymin"), ("ymax", "ymax")]
+    }
+
+    fn valid_stat_columns(&self) -> &'static [&'static str] {
+        &["x", "y", "ymin", "ymax", "se"]
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &[
+            DefaultParam {
+                name: "method",
+                default: DefaultParamValue::Null,
+            },
+            DefaultParam {
+                name: "span",
+                default: DefaultParamValue::Number(0.75),
+            },
+            DefaultParam {
+                name: "degree",
+                default: DefaultParamValue::Number(2.0),
+            },
+            DefaultParam {
+                name: "se",
+                default: DefaultParamValue::Boolean(true),
+            },
+            DefaultParam {
+                name: "level",
+                default: DefaultParamValue::Number(0.95),
+            },
+            DefaultParam {
+                name: "n",
+                default: DefaultParamValue::Number(80.0),
+            },
+        ]
+    }
+
+    fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
+        &["x", "y"]
+    }
+
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
         true
     }
 
-    // Note: stat_smooth not yet implemented - will return Identity for now
+    fn apply_stat_transform(
+        &self,
+        query: &str,
+        _schema: &Schema,
+        aesthetics: &Mappings,
+        group_by: &[String],
+        parameters: &HashMap<String, ParameterValue>,
+        execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<StatResult> {
+        stat_smooth(query, aesthetics, group_by, parameters, execute_query)
+    }
 }
 
 impl std::fmt::Display for Smooth {
@@ -32,3 +110,583 @@ impl std::fmt::Display for Smooth {
         write!(f, "smooth")
     }
 }
+
+/// Fitting method for the smooth stat
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SmoothMethod {
+    /// Ordinary least squares straight line
+    Lm,
+    /// Ordinary least squares polynomial of the given degree
+    Poly,
+    /// Locally weighted polynomial regression
+    Loess,
+}
+
+/// Validated SETTING values for the smooth stat
+#[derive(Debug, Clone)]
+struct SmoothOptions {
+    /// Explicit method, or None to choose by group size
+    method: Option<SmoothMethod>,
+    span: f64,
+    degree: usize,
+    se: bool,
+    level: f64,
+    n: usize,
+}
+
+impl SmoothOptions {
+    fn from_parameters(parameters: &HashMap<String, ParameterValue>) -> Result<Self> {
+        let method = match get_string_param(parameters, "method")? {
+            None | Some("auto") => None,
+            Some("lm") => Some(SmoothMethod::Lm),
+            Some("poly") => Some(SmoothMethod::Poly),
+            Some("loess") => Some(SmoothMethod::Loess),
+            Some(other) => {
+                return Err(GgsqlError::ValidationError(format!(
+                    "Unknown smooth method '{}'. Expected 'lm', 'poly' or 'loess'",
+                    other
+                )))
+            }
+        };
+
+        let span = get_number_param(parameters, "span")?.unwrap_or(0.75);
+        if span.is_nan() || span <= 0.0 {
+            return Err(GgsqlError::ValidationError(
+                "Setting 'span' must be greater than 0".to_string(),
+            ));
+        }
+
+        let degree = get_number_param(parameters, "degree")?.unwrap_or(2.0);
+        if degree < 0.0 || degree.fract() != 0.0 {
+            return Err(GgsqlError::ValidationError(
+                "Setting 'degree' must be a non-negative integer".to_string(),
+            ));
+        }
+        let degree = degree as usize;
+        if method == Some(SmoothMethod::Poly) && degree == 0 {
+            return Err(GgsqlError::ValidationError(
+                "Setting 'degree' must be at least 1 for method 'poly'".to_string(),
+            ));
+        }
+        if matches!(method, None | Some(SmoothMethod::Loess)) && degree > 2 {
+            return Err(GgsqlError::ValidationError(
+                "Setting 'degree' must be 0, 1 or 2 for method 'loess'".to_string(),
+            ));
+        }
+
+        let level = get_number_param(parameters, "level")?.unwrap_or(0.95);
+        if !(level > 0.0 && level < 1.0) {
+            return Err(GgsqlError::ValidationError(
+                "Setting 'level' must be between 0 and 1".to_string(),
+            ));
+        }
+
+        let n = get_number_param(parameters, "n")?.unwrap_or(80.0);
+        if !(2.0..=MAX_GRID_SIZE as f64).contains(&n) || n.fract() != 0.0 {
+            return Err(GgsqlError::ValidationError(format!(
+                "Setting 'n' must be an integer between 2 and {}",
+                MAX_GRID_SIZE
+            )));
+        }
+
+        Ok(Self {
+            method,
+            span,
+            degree,
+            se: get_bool_param(parameters, "se")?.unwrap_or(true),
+            level,
+            n: n as usize,
+        })
+    }
+}
+
+/// Fitted values on the evaluation grid of one group
+struct Fit {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    /// Standard errors of the fit, if they could be estimated
+    se: Option<Vec<f64>>,
+    /// Residual degrees of freedom used for the confidence band
+    df: f64,
+}
+
+/// Statistical transformation for smooth: fit a curve per group and evaluate it on a grid
+///
+/// Linear and polynomial fits only need sums over the data, which are computed
+/// in SQL. The records themselves are only fetched for LOESS.
+fn stat_smooth(
+    query: &str,
+    aesthetics: &Mappings,
+    group_by: &[String],
+    parameters: &HashMap<String, ParameterValue>,
+    execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+) -> Result<StatResult> {
+    let x_col = get_column_name(aesthetics, "x").ok_or_else(|| {
+        GgsqlError::ValidationError("Smooth requires 'x' aesthetic mapping".to_string())
+    })?;
+    let y_col = get_column_name(aesthetics, "y").ok_or_else(|| {
+        GgsqlError::ValidationError("Smooth requires 'y' aesthetic mapping".to_string())
+    })?;
+    let options = SmoothOptions::from_parameters(parameters)?;
+
+    // The complete (x, y) pairs of every group, tagged with the group index
+    let points_query = format!(
+        "WITH __stat_src__ AS ({query}) SELECT {group}, x, y FROM (SELECT {rank} AS {group}, CAST({x} AS DOUBLE) AS x, CAST({y} AS DOUBLE) AS y FROM __stat_src__) WHERE x IS NOT NULL AND y IS NOT NULL AND isfinite(x) AND isfinite(y)",
+        query = query,
+        group = naming::GROUP_COLUMN,
+        rank = group_index_expr(group_by),
+        x = x_col,
+        y = y_col
+    );
+    let degree = match options.method {
+        Some(SmoothMethod::Poly) => options.degree,
+        _ => 1,
+    };
+    let sums = read_group_sums(
+        &execute_query(&group_sums_query(&points_query, degree))?,
+        degree,
+    )?;
+
+    // Like ggplot2, the automatic choice is made once for the whole layer
+    let largest = sums.values().map(|s| s.n).max().unwrap_or(0);
+    let method = options.method.unwrap_or(if largest < LOESS_MAX_POINTS {
+        SmoothMethod::Loess
+    } else {
+        SmoothMethod::Lm
+    });
+    let points = if method == SmoothMethod::Loess {
+        if largest > LOESS_POINT_LIMIT {
+            return Err(GgsqlError::ValidationError(format!(
+                "Method 'loess' fits groups of at most {} records, but a group has {}. Use method 'lm' or 'poly' instead",
+                LOESS_POINT_LIMIT, largest
+            )));
+        }
+        read_points(&execute_query(&points_query)?)?
+    } else {
+        BTreeMap::new()
+    };
+
+    let t_prob = (1.0 + options.level) / 2.0;
+    let mut rows = Vec::new();
+    for (group, sums) in &sums {
+        let grid = evaluation_grid(sums.min, sums.max, options.n);
+        let fit = match method {
+            SmoothMethod::Lm => fit_polynomial(sums, 1, grid, options.se),
+            SmoothMethod::Poly => fit_polynomial(sums, options.degree, grid, options.se),
+            SmoothMethod::Loess => {
+                let Some((xs, ys)) = points.get(group) else {
+                    continue;
+                };
+                fit_loess(xs, ys, options.span, options.degree, grid, options.se)
+            }
+        };
+
+        let t = t_quantile(t_prob, fit.df);
+        for (i, (x, y)) in fit.x.iter().zip(&fit.y).enumerate() {
+            let mut values = vec![Some(*x), Some(*y)];
+            if options.se {
+                let se = fit.se.as_ref().map(|se| se[i]);
+                values.push(se.map(|se| y - t * se));
+                values.push(se.map(|se| y + t * se));
+                values.push(se);
+            }
+            rows.push((*group, values));
+        }
+    }
+
+    let stat_columns: &[&str] = if options.se {
+        &["x", "y", "ymin", "ymax", "se"]
+    } else {
+        &["x", "y"]
+    };
+
+    Ok(StatResult::Transformed {
+        query: grouped_values_query(query, group_by, stat_columns, &rows),
+        stat_columns: stat_columns.iter().map(|s| s.to_string()).collect(),
+        dummy_columns: vec![],
+        consumed_aesthetics: vec!["x".to_string(), "y".to_string()],
+    })
+}
+
+/// Least squares sums of one group
+///
+/// x is centred and scaled to `z = (x - mean) / scale` to keep the normal
+/// equations well conditioned.
+#[derive(Debug, Clone)]
+struct GroupSums {
+    n: usize,
+    mean: f64,
+    scale: f64,
+    min: f64,
+    max: f64,
+    /// Sums of `z^k` for `k` up to twice the degree
+    zz: Vec<f64>,
+    /// Sums of `z^k * y` for `k` up to the degree
+    zy: Vec<f64>,
+    /// Sum of `y^2`
+    yy: f64,
+}
+
+/// Query for the [`GroupSums`] of every group of `points_query`, up to `degree`
+fn group_sums_query(points_query: &str, degree: usize) -> String {
+    let zz = (0..=2 * degree).map(|k| format!("SUM(POW(z, {k})) AS __zz{k}__", k = k));
+    let zy = (0..=degree).map(|k| format!("SUM(POW(z, {k}) * y) AS __zy{k}__", k = k));
+    format!(
+        "WITH __smooth_points__ AS ({points}), \
+         __smooth_scale__ AS (\
+            SELECT {group}, AVG(x) AS __mean__, COALESCE(NULLIF(STDDEV_POP(x), 0), 1) AS __scale__ \
+            FROM __smooth_points__ GROUP BY {group}\
+         ), \
+         __smooth_z__ AS (\
+            SELECT *, (x - __mean__) / __scale__ AS z \
+            FROM __smooth_points__ JOIN __smooth_scale__ USING ({group})\
+         ) \
+         SELECT {group}, COUNT(*) AS __n__, ANY_VALUE(__mean__) AS __mean__, ANY_VALUE(__scale__) AS __scale__, \
+            MIN(x) AS __min__, MAX(x) AS __max__, {sums}, SUM(y * y) AS __yy__ \
+         FROM __smooth_z__ GROUP BY {group}",
+        points = points_query,
+        group = naming::GROUP_COLUMN,
+        sums = zz.chain(zy).collect::<Vec<_>>().join(", ")
+    )
+}
+
+/// Read the result of [`group_sums_query`]
+fn read_group_sums(df: &DataFrame, degree: usize) -> Result<BTreeMap<usize, GroupSums>> {
+    let groups = column_to_f64(df, naming::GROUP_COLUMN)?;
+    let read = |name: &str| column_to_f64(df, name);
+    let n = read("__n__")?;
+    let mean = read("__mean__")?;
+    let scale = read("__scale__")?;
+    let min = read("__min__")?;
+    let max = read("__max__")?;
+    let yy = read("__yy__")?;
+    let zz = (0..=2 * degree)
+        .map(|k| read(&format!("__zz{}__", k)))
+        .collect::<Result<Vec<_>>>()?;
+    let zy = (0..=degree)
+        .map(|k| read(&format!("__zy{}__", k)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut sums = BTreeMap::new();
+    for (i, group) in groups.iter().enumerate() {
+        let value = |column: &[Option<f64>]| column[i].unwrap_or(f64::NAN);
+        let Some(group) = group else {
+            continue;
+        };
+        sums.insert(
+            *group as usize,
+            GroupSums {
+                n: value(&n) as usize,
+                mean: value(&mean),
+                scale: value(&scale),
+                min: value(&min),
+                max: value(&max),
+                zz: zz.iter().map(|c| value(c)).collect(),
+                zy: zy.iter().map(|c| value(c)).collect(),
+                yy: value(&yy),
+            },
+        );
+    }
+    Ok(sums)
+}
+
+/// The x and y values of the records of a group
+type Points = (Vec<f64>, Vec<f64>);
+
+/// Read the (x, y) pairs of every group from the points query
+fn read_points(df: &DataFrame) -> Result<BTreeMap<usize, Points>> {
+    let group_ids = column_to_f64(df, naming::GROUP_COLUMN)?;
+    let xs = column_to_f64(df, "x")?;
+    let ys = column_to_f64(df, "y")?;
+
+    let mut groups: BTreeMap<usize, Points> = BTreeMap::new();
+    for ((group, x), y) in group_ids.into_iter().zip(xs).zip(ys) {
+        if let (Some(group), Some(x), Some(y)) = (group, x, y) {
+            let entry = groups.entry(group as usize).or_default();
+            entry.0.push(x);
+            entry.1.push(y);
+        }
+    }
+    Ok(groups)
+}
+
+/// `n` evenly spaced x values from `min` to `max`
+fn evaluation_grid(min: f64, max: f64, n: usize) -> Vec<f64> {
+    if !(min.is_finite() && max.is_finite()) {
+        vec![]
+    } else if min >= max {
+        vec![min]
+    } else {
+        let step = (max - min) / (n - 1) as f64;
+        (0..n).map(|i| min + step * i as f64).collect()
+    }
+}
+
+/// Least squares polynomial fit from the sums of a group.
+///
+/// If the normal equations are singular (e.g. too few distinct x values), the
+/// degree is lowered until a fit is possible.
+fn fit_polynomial(sums: &GroupSums, degree: usize, grid: Vec<f64>, se: bool) -> Fit {
+    let n = sums.n;
+    let powers = |z: f64, p: usize| -> Vec<f64> { (0..p).map(|k| z.powi(k as i32)).collect() };
+
+    for p in (1..=(degree + 1).min(n)).rev() {
+        let xtx: Vec<Vec<f64>> = (0..p)
+            .map(|j| (0..p).map(|k| sums.zz[j + k]).collect())
+            .collect();
+        let xty = &sums.zy[..p];
+        let Some(beta) = solve_linear_system(&xtx, xty) else {
+            continue;
+        };
+
+        let predict = |z: f64| -> f64 { powers(z, p).iter().zip(&beta).map(|(v, b)| v * b).sum() };
+        let y: Vec<f64> = grid
+            .iter()
+            .map(|x| predict((x - sums.mean) / sums.scale))
+            .collect();
+
+        let df = (n - p) as f64;
+        let se = if se && df > 0.0 {
+            // rss = y'y - 2 b'X'y + b'X'X b
+            let mut rss = sums.yy;
+            for j in 0..p {
+                rss -= 2.0 * beta[j] * xty[j];
+                for k in 0..p {
+                    rss += beta[j] * beta[k] * xtx[j][k];
+                }
+            }
+            let sigma2 = rss.max(0.0) / df;
+            grid.iter()
+                .map(|x| {
+                    let v = powers((x - sums.mean) / sums.scale, p);
+                    let u = solve_linear_system(&xtx, &v)?;
+                    let var: f64 = v.iter().zip(&u).map(|(a, b)| a * b).sum();
+                    Some((sigma2 * var.max(0.0)).sqrt())
+                })
+                .collect()
+        } else {
+            None
+        };
+
+        return Fit { x: grid, y, se, df };
+    }
+
+    Fit {
+        x: vec![],
+        y: vec![],
+        se: None,
+        df: 0.0,
+    }
+}
+
+/// LOESS fit (local polynomial regression with tricube weights).
+///
+/// Each evaluation point uses the nearest `span` fraction of the data. The
+/// standard errors follow Cleveland's approximation: the residual variance is
+/// scaled by the equivalent number of parameters of the smoother matrix.
+fn fit_loess(xs: &[f64], ys: &[f64], span: f64, degree: usize, grid: Vec<f64>, se: bool) -> Fit {
+    let mut y = Vec::with_capacity(grid.len());
+    let mut spread = Vec::with_capacity(grid.len());
+    for x0 in &grid {
+        let l = loess_operator(xs, *x0, span, degree);
+        y.push(l.iter().zip(ys).map(|(l, y)| l * y).sum());
+        spread.push(l.iter().map(|l| l * l).sum::<f64>().sqrt());
+    }
+
+    let mut fit = Fit {
+        x: grid,
+        y,
+        se: None,
+        df: 0.0,
+    };
+    if !se {
+        return fit;
+    }
+
+    // Residual variance from the smoother matrix L evaluated at the data points:
+    // delta1 = tr((I - L)^T (I - L)) = n - 2 tr(L) + sum(L_ij^2)
+    let n = xs.len();
+    let mut rss = 0.0;
+    let mut delta1 = n as f64;
+    for (i, xi) in xs.iter().enumerate() {
+        let l = loess_operator(xs, *xi, span, degree);
+        let fitted: f64 = l.iter().zip(ys).map(|(l, y)| l * y).sum();
+        rss += (ys[i] - fitted).powi(2);
+        delta1 += l.iter().map(|l| l * l).sum::<f64>() - 2.0 * l[i];
+    }
+    if delta1 > 0.0 {
+        let sigma = (rss / delta1).sqrt();
+        fit.se = Some(spread.iter().map(|s| sigma * s).collect());
+        fit.df = delta1;
+    }
+    fit
+}
+
+/// Weights `l` such that the LOESS estimate at `x0` is `sum(l_i * y_i)`
+fn loess_operator(xs: &[f64], x0: f64, span: f64, degree: usize) -> Vec<f64> {
+    let n = xs.len();
+    let distances: Vec<f64> = xs.iter().map(|x| (x - x0).abs()).collect();
+
+    // Bandwidth: distance to the q-th nearest point, enlarged for span > 1
+    let q = ((span * n as f64).floor() as usize)
+        .max(degree + 1)
+        .clamp(1, n);
+    let mut sorted = distances.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mut h = sorted[q - 1];
+    if span > 1.0 {
+        h *= span;
+    }
+
+    let weights: Vec<f64> = distances
+        .iter()
+        .map(|d| {
+            if h <= 0.0 {
+                if *d == 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            } else if *d < h {
+                (1.0 - (d / h).powi(3)).powi(3)
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let scale = if h > 0.0 { h } else { 1.0 };
+    let us: Vec<f64> = xs.iter().map(|x| (x - x0) / scale).collect();
+
+    // Solve (X'WX) a = e1; the local intercept is then sum_i w_i (a . X_i) y_i
+    for p in (1..=degree + 1).rev() {
+        let mut xtwx = vec![vec![0.0; p]; p];
+        for (u, w) in us.iter().zip(&weights) {
+            if *w == 0.0 {
+                continue;
+            }
+            for (j, row) in xtwx.iter_mut().enumerate() {
+                for (k, cell) in row.iter_mut().enumerate() {
+                    *cell += w * u.powi((j + k) as i32);
+                }
+            }
+        }
+        let mut e1 = vec![0.0; p];
+        e1[0] = 1.0;
+        if let Some(a) = solve_linear_system(&xtwx, &e1) {
+            return us
+                .iter()
+                .zip(&weights)
+                .map(|(u, w)| w * (0..p).map(|k| a[k] * u.powi(k as i32)).sum::<f64>())
+                .collect();
+        }
+    }
+    vec![0.0; n]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The sums `group_sums_query` computes in SQL
+    fn sums(xs: &[f64], ys: &[f64], degree: usize) -> GroupSums {
+        let n = xs.len();
+        let mean = xs.iter().sum::<f64>() / n as f64;
+        let sd = (xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n as f64).sqrt();
+        let scale = if sd > 0.0 { sd } else { 1.0 };
+        let zs: Vec<f64> = xs.iter().map(|x| (x - mean) / scale).collect();
+        GroupSums {
+            n,
+            mean,
+            scale,
+            min: xs.iter().copied().fold(f64::INFINITY, f64::min),
+            max: xs.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            zz: (0..=2 * degree)
+                .map(|k| zs.iter().map(|z| z.powi(k as i32)).sum())
+                .collect(),
+            zy: (0..=degree)
+                .map(|k| zs.iter().zip(ys).map(|(z, y)| z.powi(k as i32) * y).sum())
+                .collect(),
+            yy: ys.iter().map(|y| y * y).sum(),
+        }
+    }
+
+    #[test]
+    fn test_fit_polynomial_recovers_line() {
+        let xs = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+        let ys: Vec<f64> = xs.iter().map(|x| 2.0 * x + 1.0).collect();
+        let fit = fit_polynomial(&sums(&xs, &ys, 1), 1, vec![0.0, 10.0], true);
+        assert!((fit.y[0] - 1.0).abs() < 1e-9);
+        assert!((fit.y[1] - 21.0).abs() < 1e-9);
+        assert_eq!(fit.df, 3.0);
+        // A perfect fit has no residual error
+        assert!(fit.se.unwrap().iter().all(|se| se.abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_fit_polynomial_standard_error() {
+        // Reference: predict(lm(y ~ x), data.frame(x = 3), se.fit = TRUE) in R
+        let xs = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+        let ys = vec![1.0, 3.0, 2.0, 5.0, 4.0];
+        let fit = fit_polynomial(&sums(&xs, &ys, 1), 1, vec![3.0], true);
+        assert!((fit.y[0] - 3.0).abs() < 1e-9);
+        assert!((fit.se.unwrap()[0] - 0.4898979).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fit_polynomial_lowers_degree_when_singular() {
+        // Two distinct x values cannot determine a quadratic
+        let xs = vec![1.0, 1.0, 2.0, 2.0];
+        let ys = vec![1.0, 3.0, 5.0, 7.0];
+        let fit = fit_polynomial(&sums(&xs, &ys, 2), 2, vec![1.0, 2.0], false);
+        assert!((fit.y[0] - 2.0).abs() < 1e-9);
+        assert!((fit.y[1] - 6.0).abs() < 1e-9);
+        assert!(fit.se.is_none());
+    }
+
+    #[test]
+    fn test_loess_reproduces_quadratic() {
+        // Local quadratic regression is exact for quadratic data
+        let xs: Vec<f64> = (0..20).map(|i| i as f64).collect();
+        let ys: Vec<f64> = xs.iter().map(|x| x * x - 3.0 * x).collect();
+        let fit = fit_loess(&xs, &ys, 0.5, 2, vec![0.0, 7.5, 19.0], true);
+        for (x, y) in fit.x.iter().zip(&fit.y) {
+            assert!((y - (x * x - 3.0 * x)).abs() < 1e-6);
+        }
+        assert!(fit.df > 0.0);
+    }
+
+    #[test]
+    fn test_evaluation_grid() {
+        assert_eq!(evaluation_grid(1.0, 3.0, 3), vec![1.0, 2.0, 3.0]);
+        assert_eq!(evaluation_grid(2.0, 2.0, 80), vec![2.0]);
+        assert!(evaluation_grid(f64::NAN, f64::NAN, 80).is_empty());
+    }
+
+    #[test]
+    fn test_smooth_options_validation() {
+        let mut params = HashMap::new();
+        params.insert(
+            "method".to_string(),
+            ParameterValue::String("spline".to_string()),
+        );
+        assert!(SmoothOptions::from_parameters(&params).is_err());
+
+        params.insert(
+            "method".to_string(),
+            ParameterValue::String("lm".to_string()),
+        );
+        params.insert("level".to_string(), ParameterValue::Number(1.5));
+        assert!(SmoothOptions::from_parameters(&params).is_err());
+
+        params.insert("level".to_string(), ParameterValue::Number(0.9));
+        let options = SmoothOptions::from_parameters(&params).unwrap();
+        assert_eq!(options.method, Some(SmoothMethod::Lm));
+        assert_eq!(options.n, 80);
+
+        for n in [1.0, 2.5, 1e9] {
+            params.insert("n".to_string(), ParameterValue::Number(n));
+            let err = SmoothOptions::from_parameters(&params).unwrap_err();
+            assert!(err.to_string().contains("between 2 and 1000"));
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Step geom implementation
+
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType};
+use crate::plot::types::ParameterValue;
+use std::collections::HashMap;
+
+/// Values of the `direction` setting: change y at (hv), before (vh) or halfway to (mid) the next x
+const DIRECTIONS: [&str; 3] = ["hv", "vh", "mid"];
+
+/// Step geom - stairstep line connecting points along x
+#[derive(Debug, Clone, Copy)]
+pub struct Step;
+
+impl GeomTrait for Step {
+    fn geom_type(&self) -> GeomType {
+        GeomType::Step
+    }
+
+    fn aesthetics(&self) -> GeomAesthetics {
+        GeomAesthetics {
+            supported: &[
+                "x",
+                "y",
+                "color",
+                "colour",
+                "stroke",
+                "linetype",
+                "linewidth",
+                "opacity",
+            ],
+            required: &["x", "y"],
+            hidden: &[],
+        }
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &[DefaultParam {
+            name: "direction",
+            default: DefaultParamValue::String("hv"),
+        }]
+    }
+
+    fn validate_params(
+        &self,
+        parameters: &HashMap<String, ParameterValue>,
+    ) -> std::result::Result<(), String> {
+        match parameters.get("direction") {
+            None => Ok(()),
+            Some(ParameterValue::String(direction)) if DIRECTIONS.contains(&direction.as_str()) => {
+                Ok(())
+            }
+            Some(ParameterValue::String(other)) => Err(format!(
+                "Setting 'direction' of step must be 'hv', 'vh' or 'mid', got '{}'",
+                other
+            )),
+            Some(_) => Err("Setting 'direction' of step must be 'hv', 'vh' or 'mid'".to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "step")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_direction_is_validated() {
+        let params = |value: ParameterValue| HashMap::from([("direction".to_string(), value)]);
+
+        assert!(Step.validate_params(&HashMap::new()).is_ok());
+        for direction in DIRECTIONS {
+            let ok = Step.validate_params(&params(ParameterValue::String(direction.to_string())));
+            assert!(ok.is_ok());
+        }
+
+        let err = Step
+            .validate_params(&params(ParameterValue::String("up".to_string())))
+            .unwrap_err();
+        assert!(err.contains("'hv', 'vh' or 'mid'"));
+        assert!(Step
+            .validate_params(&params(ParameterValue::Number(1.0)))
+            .is_err());
+    }
+}
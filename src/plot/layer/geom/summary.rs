@@ -0,0 +1,282 @@
+//! Summary stat shared by geoms that accept `SETTING stat => 'summary'`
+//!
+//! Summarises y for each x (and group) with a central value (`fun`) and a lower
+//! and upper bound (`fun_min`, `fun_max`), producing `y`, `ymin` and `ymax`.
+
+use std::collections::HashMap;
+
+use super::numeric::t_quantile;
+use super::types::{column_to_f64, get_column_name, get_number_param, get_string_param};
+use super::{DefaultParam, DefaultParamValue, StatResult};
+use crate::naming;
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+
+/// Parameters accepted by every geom supporting the summary stat
+pub const SUMMARY_PARAMS: [DefaultParam; 5] = [
+    DefaultParam {
+        name: "stat",
+        default: DefaultParamValue::Null,
+    },
+    DefaultParam {
+        name: "fun",
+        default: DefaultParamValue::Null,
+    },
+    DefaultParam {
+        name: "fun_min",
+        default: DefaultParamValue::Null,
+    },
+    DefaultParam {
+        name: "fun_max",
+        default: DefaultParamValue::Null,
+    },
+    DefaultParam {
+        name: "level",
+        default: DefaultParamValue::Null,
+    },
+];
+
+/// Check whether a layer asks for a stat other than identity
+///
+/// Unknown stats also count, so that `validate_stat_param` gets to reject them.
+pub fn uses_summary_stat(parameters: &HashMap<String, ParameterValue>) -> bool {
+    match parameters.get("stat") {
+        None => false,
+        Some(ParameterValue::String(s)) => s != "identity",
+        Some(_) => true,
+    }
+}
+
+/// Check the `stat` setting of a geom that only knows the summary stat
+pub fn validate_stat_param(parameters: &HashMap<String, ParameterValue>) -> Result<()> {
+    match get_string_param(parameters, "stat")? {
+        None | Some("identity") | Some("summary") => Ok(()),
+        Some(other) => Err(GgsqlError::ValidationError(format!(
+            "Unknown stat '{}'. Expected 'identity' or 'summary'",
+            other
+        ))),
+    }
+}
+
+/// Bound computed by `fun_min` / `fun_max`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SummaryBound {
+    /// Central value plus or minus one standard error
+    Se,
+    /// Central value plus or minus one standard deviation
+    Sd,
+    /// Central value plus or minus the t-based confidence interval half-width
+    Ci,
+    /// Lower or upper quantile enclosing `level` of the data
+    Quantile,
+    /// Smallest value, whichever bound asks for it
+    Min,
+    /// Largest value, whichever bound asks for it
+    Max,
+}
+
+impl SummaryBound {
+    fn from_param(parameters: &HashMap<String, ParameterValue>, name: &str) -> Result<Self> {
+        match get_string_param(parameters, name)? {
+            None | Some("se") => Ok(SummaryBound::Se),
+            Some("sd") => Ok(SummaryBound::Sd),
+            Some("ci") => Ok(SummaryBound::Ci),
+            Some("quantile") => Ok(SummaryBound::Quantile),
+            Some("min") => Ok(SummaryBound::Min),
+            Some("max") => Ok(SummaryBound::Max),
+            Some(other) => Err(GgsqlError::ValidationError(format!(
+                "Unknown value '{}' for '{}'. Expected 'se', 'sd', 'ci', 'quantile', 'min' or 'max'",
+                other, name
+            ))),
+        }
+    }
+
+    /// SQL for the bound; `sign` is -1 for the lower and 1 for the upper bound
+    fn sql(&self, sign: f64, level: f64, t_expr: &str) -> String {
+        let op = if sign < 0.0 { "-" } else { "+" };
+        match self {
+            SummaryBound::Se => format!("__center__ {} __sd__ / SQRT(__n__)", op),
+            SummaryBound::Sd => format!("__center__ {} __sd__", op),
+            SummaryBound::Ci => format!("__center__ {} {} * __sd__ / SQRT(__n__)", op, t_expr),
+            SummaryBound::Quantile => {
+                let p = if sign < 0.0 {
+                    (1.0 - level) / 2.0
+                } else {
+                    (1.0 + level) / 2.0
+                };
+                format!("QUANTILE_CONT(__value__, {})", p)
+            }
+            SummaryBound::Min => "MIN(__value__)".to_string(),
+            SummaryBound::Max => "MAX(__value__)".to_string(),
+        }
+    }
+
+    /// Whether the bound is an aggregate over the raw values
+    fn is_aggregate(&self) -> bool {
+        matches!(
+            self,
+            SummaryBound::Quantile | SummaryBound::Min | SummaryBound::Max
+        )
+    }
+}
+
+/// Statistical transformation for `stat => 'summary'`: summarise y for each x and group
+pub fn stat_summary(
+    query: &str,
+    aesthetics: &Mappings,
+    group_by: &[String],
+    parameters: &HashMap<String, ParameterValue>,
+    execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+) -> Result<StatResult> {
+    let y_col = get_column_name(aesthetics, "y").ok_or_else(|| {
+        GgsqlError::ValidationError(
+            "The summary stat requires a 'y' aesthetic mapped to a column".to_string(),
+        )
+    })?;
+
+    let center = match get_string_param(parameters, "fun")?.unwrap_or("mean") {
+        "mean" => "AVG(__value__)",
+        "median" => "MEDIAN(__value__)",
+        "min" => "MIN(__value__)",
+        "max" => "MAX(__value__)",
+        "sum" => "SUM(__value__)",
+        other => {
+            return Err(GgsqlError::ValidationError(format!(
+                "Unknown summary function '{}'. Expected 'mean', 'median', 'min', 'max' or 'sum'",
+                other
+            )))
+        }
+    };
+    let lower = SummaryBound::from_param(parameters, "fun_min")?;
+    let upper = SummaryBound::from_param(parameters, "fun_max")?;
+    let level = get_number_param(parameters, "level")?.unwrap_or(0.95);
+    if !(level > 0.0 && level < 1.0) {
+        return Err(GgsqlError::ValidationError(
+            "Setting 'level' must be between 0 and 1".to_string(),
+        ));
+    }
+
+    // Summaries are computed for each x value within each group
+    let mut keys: Vec<String> = get_column_name(aesthetics, "x").into_iter().collect();
+    for col in group_by {
+        if !keys.contains(col) {
+            keys.push(col.clone());
+        }
+    }
+    let key_select: String = keys.iter().map(|k| format!("{}, ", k)).collect();
+    let group_clause = if keys.is_empty() {
+        String::new()
+    } else {
+        format!(" GROUP BY {}", keys.join(", "))
+    };
+
+    let values_cte = format!(
+        "WITH __stat_src__ AS ({query}), __summary_values__ AS (SELECT {keys}CAST({y} AS DOUBLE) AS __value__ FROM __stat_src__ WHERE {y} IS NOT NULL)",
+        query = query,
+        keys = key_select,
+        y = y_col
+    );
+
+    // The t quantile depends on the size of each group, so look up the sizes first
+    let t_expr = if lower == SummaryBound::Ci || upper == SummaryBound::Ci {
+        let sizes_query = format!(
+            "{values} SELECT DISTINCT COUNT(*) AS n FROM __summary_values__{group}",
+            values = values_cte,
+            group = group_clause
+        );
+        let sizes = column_to_f64(&execute_query(&sizes_query)?, "n")?;
+        let cases: String = sizes
+            .into_iter()
+            .flatten()
+            .filter(|n| *n > 1.0)
+            .map(|n| {
+                format!(
+                    " WHEN {} THEN {:e}",
+                    n as i64,
+                    t_quantile((1.0 + level) / 2.0, n - 1.0)
+                )
+            })
+            .collect();
+        if cases.is_empty() {
+            "CAST(NULL AS DOUBLE)".to_string()
+        } else {
+            format!("(CASE __n__{} END)", cases)
+        }
+    } else {
+        String::new()
+    };
+
+    // Bounds that aggregate raw values are computed with the centre; the others derive from it
+    let aggregate_bound = |bound: SummaryBound, sign: f64, alias: &str| -> String {
+        if bound.is_aggregate() {
+            format!(", {} AS {}", bound.sql(sign, level, &t_expr), alias)
+        } else {
+            String::new()
+        }
+    };
+    let final_bound = |bound: SummaryBound, sign: f64, alias: &str| -> String {
+        if bound.is_aggregate() {
+            alias.to_string()
+        } else {
+            bound.sql(sign, level, &t_expr)
+        }
+    };
+
+    let stat_y = naming::stat_column("y");
+    let stat_ymin = naming::stat_column("ymin");
+    let stat_ymax = naming::stat_column("ymax");
+
+    let transformed_query = format!(
+        "{values}, \
+         __summary__ AS (\
+            SELECT {keys}{center} AS __center__, STDDEV_SAMP(__value__) AS __sd__, COUNT(*) AS __n__\
+            {agg_lower}{agg_upper} \
+            FROM __summary_values__{group}\
+         ) \
+         SELECT {keys}__center__ AS {y}, {lower} AS {ymin}, {upper} AS {ymax} FROM __summary__",
+        values = values_cte,
+        keys = key_select,
+        center = center,
+        agg_lower = aggregate_bound(lower, -1.0, "__lower__"),
+        agg_upper = aggregate_bound(upper, 1.0, "__upper__"),
+        group = group_clause,
+        y = stat_y,
+        lower = final_bound(lower, -1.0, "__lower__"),
+        ymin = stat_ymin,
+        upper = final_bound(upper, 1.0, "__upper__"),
+        ymax = stat_ymax,
+    );
+
+    Ok(StatResult::Transformed {
+        query: transformed_query,
+        stat_columns: vec!["y".to_string(), "ymin".to_string(), "ymax".to_string()],
+        dummy_columns: vec![],
+        consumed_aesthetics: vec!["y".to_string()],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounds_follow_the_requested_function() {
+        let mut parameters = HashMap::new();
+        parameters.insert(
+            "fun_min".to_string(),
+            ParameterValue::String("max".to_string()),
+        );
+        parameters.insert(
+            "fun_max".to_string(),
+            ParameterValue::String("min".to_string()),
+        );
+        let lower = SummaryBound::from_param(&parameters, "fun_min").unwrap();
+        let upper = SummaryBound::from_param(&parameters, "fun_max").unwrap();
+        assert_eq!(lower.sql(-1.0, 0.95, ""), "MAX(__value__)");
+        assert_eq!(upper.sql(1.0, 0.95, ""), "MIN(__value__)");
+        assert_eq!(
+            SummaryBound::from_param(&HashMap::new(), "fun_min").unwrap(),
+            SummaryBound::Se
+        );
+    }
+}
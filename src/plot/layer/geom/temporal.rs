@@ -0,0 +1,224 @@
+//! Calendar intervals for binning DATE and TIMESTAMP columns
+//!
+//! Temporal bins are computed with DuckDB's `time_bucket()`, which aligns them
+//! to calendar boundaries (weeks start on Monday, months on the 1st) and keeps
+//! the DATE or TIMESTAMP type of the binned column.
+
+use polars::prelude::DataType;
+
+use crate::{DataFrame, GgsqlError, Result};
+
+/// Calendar unit of a temporal bin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl TimeUnit {
+    /// Parse a unit name, accepting plurals (`"weeks"`)
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        let singular = name.strip_suffix('s').unwrap_or(&name);
+        match singular {
+            "minute" => Some(TimeUnit::Minute),
+            "hour" => Some(TimeUnit::Hour),
+            "day" => Some(TimeUnit::Day),
+            "week" => Some(TimeUnit::Week),
+            "month" => Some(TimeUnit::Month),
+            "quarter" => Some(TimeUnit::Quarter),
+            "year" => Some(TimeUnit::Year),
+            _ => None,
+        }
+    }
+
+    /// Approximate length in seconds, used to pick automatic intervals
+    fn seconds(&self) -> f64 {
+        const DAY: f64 = 86_400.0;
+        match self {
+            TimeUnit::Minute => 60.0,
+            TimeUnit::Hour => 3_600.0,
+            TimeUnit::Day => DAY,
+            TimeUnit::Week => 7.0 * DAY,
+            TimeUnit::Month => 30.436_875 * DAY,
+            TimeUnit::Quarter => 91.310_625 * DAY,
+            TimeUnit::Year => 365.2425 * DAY,
+        }
+    }
+
+    /// Whether the unit is shorter than a day
+    fn is_sub_day(&self) -> bool {
+        matches!(self, TimeUnit::Minute | TimeUnit::Hour)
+    }
+}
+
+/// A whole number of calendar units, such as `1 week` or `3 months`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeInterval {
+    pub count: u32,
+    pub unit: TimeUnit,
+}
+
+/// Intervals considered when the width of temporal bins is chosen automatically
+const NICE_INTERVALS: [(u32, TimeUnit); 13] = [
+    (1, TimeUnit::Minute),
+    (5, TimeUnit::Minute),
+    (15, TimeUnit::Minute),
+    (30, TimeUnit::Minute),
+    (1, TimeUnit::Hour),
+    (3, TimeUnit::Hour),
+    (6, TimeUnit::Hour),
+    (12, TimeUnit::Hour),
+    (1, TimeUnit::Day),
+    (1, TimeUnit::Week),
+    (1, TimeUnit::Month),
+    (1, TimeUnit::Quarter),
+    (1, TimeUnit::Year),
+];
+
+impl TimeInterval {
+    /// Parse an interval such as `"week"`, `"1 week"` or `"2 months"`
+    pub fn parse(text: &str) -> Result<Self> {
+        let invalid = || {
+            GgsqlError::ValidationError(format!(
+                "Invalid time interval '{}'. Expected a count and a unit such as '1 week', \
+                 with unit minute, hour, day, week, month, quarter or year",
+                text
+            ))
+        };
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        let (count, unit) = match parts.as_slice() {
+            [unit] => (1, *unit),
+            [count, unit] => (count.parse::<u32>().map_err(|_| invalid())?, *unit),
+            _ => return Err(invalid()),
+        };
+        if count == 0 {
+            return Err(invalid());
+        }
+        let unit = TimeUnit::parse(unit).ok_or_else(invalid)?;
+        Ok(TimeInterval { count, unit })
+    }
+
+    /// Smallest nice interval that splits `span_seconds` into at most `bins` bins
+    ///
+    /// DATE columns never get intervals shorter than a day.
+    pub fn for_span(span_seconds: f64, bins: usize, date: bool) -> Self {
+        let target = span_seconds / bins.max(1) as f64;
+        NICE_INTERVALS
+            .iter()
+            .map(|&(count, unit)| TimeInterval { count, unit })
+            .filter(|interval| !(date && interval.unit.is_sub_day()))
+            .find(|interval| interval.seconds() >= target)
+            .unwrap_or_else(|| TimeInterval {
+                count: (target / TimeUnit::Year.seconds()).ceil() as u32,
+                unit: TimeUnit::Year,
+            })
+    }
+
+    /// Approximate length in seconds
+    pub fn seconds(&self) -> f64 {
+        self.count as f64 * self.unit.seconds()
+    }
+
+    /// SQL interval literal, e.g. `INTERVAL '3 months'`
+    pub fn to_sql(&self) -> String {
+        let (count, unit) = match self.unit {
+            TimeUnit::Minute => (self.count, "minutes"),
+            TimeUnit::Hour => (self.count, "hours"),
+            TimeUnit::Day => (self.count, "days"),
+            TimeUnit::Week => (self.count * 7, "days"),
+            TimeUnit::Month => (self.count, "months"),
+            TimeUnit::Quarter => (self.count * 3, "months"),
+            TimeUnit::Year => (self.count, "years"),
+        };
+        format!("INTERVAL '{} {}'", count, unit)
+    }
+
+    /// SQL expression for the start of the bin holding `col`
+    ///
+    /// DATE columns binned by less than a day are promoted to TIMESTAMP.
+    pub fn bucket_sql(&self, col: &str, date: bool) -> String {
+        if date && self.unit.is_sub_day() {
+            format!("time_bucket({}, CAST({} AS TIMESTAMP))", self.to_sql(), col)
+        } else {
+            format!("time_bucket({}, {})", self.to_sql(), col)
+        }
+    }
+
+    /// SQL expression for the end of the bin starting at `start`
+    pub fn end_sql(&self, start: &str, date: bool) -> String {
+        if date && !self.unit.is_sub_day() {
+            format!("CAST({} + {} AS DATE)", start, self.to_sql())
+        } else {
+            format!("{} + {}", start, self.to_sql())
+        }
+    }
+}
+
+/// Whether a column type can be binned with calendar intervals
+pub fn is_temporal(dtype: &DataType) -> bool {
+    matches!(dtype, DataType::Date | DataType::Datetime(_, _))
+}
+
+/// Data type of `column` in the result of `query`
+pub fn column_dtype(
+    query: &str,
+    column: &str,
+    execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+) -> Result<DataType> {
+    let df = execute_query(&format!(
+        "SELECT {} FROM ({}) AS __dtype_src__ LIMIT 0",
+        column, query
+    ))?;
+    df.column(column)
+        .map(|c| c.dtype().clone())
+        .map_err(|e| GgsqlError::InternalError(format!("Failed to read column type: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(
+            TimeInterval::parse("week").unwrap(),
+            TimeInterval {
+                count: 1,
+                unit: TimeUnit::Week
+            }
+        );
+        assert_eq!(
+            TimeInterval::parse("2 Months").unwrap(),
+            TimeInterval {
+                count: 2,
+                unit: TimeUnit::Month
+            }
+        );
+        assert_eq!(
+            TimeInterval::parse("1 quarter").unwrap().to_sql(),
+            "INTERVAL '3 months'"
+        );
+        assert!(TimeInterval::parse("0 days").is_err());
+        assert!(TimeInterval::parse("1 fortnight").is_err());
+        assert!(TimeInterval::parse("1.5 days").is_err());
+    }
+
+    #[test]
+    fn test_interval_for_span() {
+        let day = 86_400.0;
+        let interval = TimeInterval::for_span(365.0 * day, 30, true);
+        assert_eq!(interval.unit, TimeUnit::Month);
+        let interval = TimeInterval::for_span(2.0 * day, 30, false);
+        assert_eq!((interval.count, interval.unit), (3, TimeUnit::Hour));
+        let interval = TimeInterval::for_span(2.0 * day, 30, true);
+        assert_eq!(interval.unit, TimeUnit::Day);
+        let interval = TimeInterval::for_span(100.0 * 365.0 * day, 30, true);
+        assert_eq!((interval.count, interval.unit), (4, TimeUnit::Year));
+    }
+}
@@ -2,7 +2,12 @@
 //!
 //! These types are used by all geom implementations and are shared across the module.
 
-use crate::Mappings;
+use std::collections::HashMap;
+
+use crate::naming;
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+use polars::prelude::DataType;
 
 /// Maps variant aesthetics to their primary aesthetic family.
 ///
@@ -18,6 +23,9 @@ pub const AESTHETIC_FAMILIES: &[(&str, &str)] = &[
     ("ymin", "y"),
     ("ymax", "y"),
     ("yend", "y"),
+    ("lower", "y"),
+    ("middle", "y"),
+    ("upper", "y"),
 ];
 
 /// Aesthetic information for a geom type
@@ -103,3 +111,180 @@ pub fn get_column_name(aesthetics: &Mappings, aesthetic: &str) -> Option<String>
         _ => None,
     })
 }
+
+/// Helper to read an optional numeric parameter, rejecting values of another type
+pub fn get_number_param(
+    parameters: &HashMap<String, ParameterValue>,
+    name: &str,
+) -> Result<Option<f64>> {
+    match parameters.get(name) {
+        None => Ok(None),
+        Some(value) => value.as_number().map(Some).ok_or_else(|| {
+            GgsqlError::ValidationError(format!("Setting '{}' must be a number", name))
+        }),
+    }
+}
+
+/// Helper to read an optional parameter given either as one number for both axes
+/// or as an array of two numbers for x and y
+pub fn get_xy_number_param(
+    parameters: &HashMap<String, ParameterValue>,
+    name: &str,
+) -> Result<Option<(f64, f64)>> {
+    match parameters.get(name) {
+        None => Ok(None),
+        Some(ParameterValue::Number(n)) => Ok(Some((*n, *n))),
+        Some(ParameterValue::Array(values)) => match values.as_slice() {
+            [x, y] => match (x.as_number(), y.as_number()) {
+                (Some(x), Some(y)) => Ok(Some((x, y))),
+                _ => Err(GgsqlError::ValidationError(format!(
+                    "Setting '{}' must contain numbers",
+                    name
+                ))),
+            },
+            _ => Err(GgsqlError::ValidationError(format!(
+                "Setting '{}' must be a number or an array of two numbers",
+                name
+            ))),
+        },
+        Some(_) => Err(GgsqlError::ValidationError(format!(
+            "Setting '{}' must be a number or an array of two numbers",
+            name
+        ))),
+    }
+}
+
+/// Helper to read an optional string parameter, rejecting values of another type
+pub fn get_string_param<'a>(
+    parameters: &'a HashMap<String, ParameterValue>,
+    name: &str,
+) -> Result<Option<&'a str>> {
+    match parameters.get(name) {
+        None => Ok(None),
+        Some(value) => value.as_str().map(Some).ok_or_else(|| {
+            GgsqlError::ValidationError(format!("Setting '{}' must be a string", name))
+        }),
+    }
+}
+
+/// Helper to read an optional boolean parameter, rejecting values of another type
+pub fn get_bool_param(
+    parameters: &HashMap<String, ParameterValue>,
+    name: &str,
+) -> Result<Option<bool>> {
+    match parameters.get(name) {
+        None => Ok(None),
+        Some(value) => value.as_bool().map(Some).ok_or_else(|| {
+            GgsqlError::ValidationError(format!("Setting '{}' must be true or false", name))
+        }),
+    }
+}
+
+/// SQL expression numbering the groups of `__stat_src__` as 1..n.
+///
+/// The numbering only depends on the group values, so it is stable across the
+/// separate queries a stat issues against the same source.
+pub fn group_index_expr(group_by: &[String]) -> String {
+    if group_by.is_empty() {
+        "1".to_string()
+    } else {
+        format!("DENSE_RANK() OVER (ORDER BY {})", group_by.join(", "))
+    }
+}
+
+/// Helper to extract a numeric DataFrame column as `f64` values (nulls preserved)
+pub fn column_to_f64(df: &DataFrame, name: &str) -> Result<Vec<Option<f64>>> {
+    let column = df
+        .column(name)
+        .and_then(|c| c.cast(&DataType::Float64))
+        .map_err(|e| {
+            GgsqlError::InternalError(format!("Failed to read stat column '{}': {}", name, e))
+        })?;
+    let values = column
+        .f64()
+        .map_err(|e| {
+            GgsqlError::InternalError(format!("Failed to read stat column '{}': {}", name, e))
+        })?
+        .into_iter()
+        .collect();
+    Ok(values)
+}
+
+/// Build a stat query from values computed outside of SQL.
+///
+/// Each row is `(group index, values)`, where the group index comes from
+/// [`group_index_expr`] and `values` line up with `stat_columns`. The values are
+/// inlined as a `VALUES` table and joined back onto the group columns of `query`,
+/// so grouping columns keep their original types. Values are emitted as DOUBLE;
+/// missing and non-finite values become NULL.
+pub fn grouped_values_query(
+    query: &str,
+    group_by: &[String],
+    stat_columns: &[&str],
+    rows: &[(usize, Vec<Option<f64>>)],
+) -> String {
+    let group_col = naming::GROUP_COLUMN;
+    let stat_cols: Vec<String> = stat_columns
+        .iter()
+        .map(|name| naming::stat_column(name))
+        .collect();
+
+    let null = "CAST(NULL AS DOUBLE)";
+    let value_rows: Vec<String> = if rows.is_empty() {
+        // Keep the column types with a placeholder row that is filtered out below
+        let nulls = vec![null; stat_cols.len()];
+        vec![format!("(NULL, {})", nulls.join(", "))]
+    } else {
+        rows.iter()
+            .map(|(group, values)| {
+                let values: Vec<String> = values
+                    .iter()
+                    .map(|v| match v {
+                        Some(v) if v.is_finite() => format!("{:e}", v),
+                        _ => null.to_string(),
+                    })
+                    .collect();
+                format!("({}, {})", group, values.join(", "))
+            })
+            .collect()
+    };
+
+    let stat_values = format!(
+        "__stat_values__ AS (SELECT * FROM (VALUES {rows}) AS t({group}, {cols}) WHERE {group} IS NOT NULL)",
+        rows = value_rows.join(", "),
+        group = group_col,
+        cols = stat_cols.join(", ")
+    );
+    let stat_select: Vec<String> = stat_cols
+        .iter()
+        .map(|c| format!("__stat_values__.{}", c))
+        .collect();
+
+    if group_by.is_empty() {
+        format!(
+            "WITH __stat_src__ AS ({query}), {values} SELECT {cols} FROM __stat_values__",
+            query = query,
+            values = stat_values,
+            cols = stat_select.join(", ")
+        )
+    } else {
+        let group_select: Vec<String> = group_by
+            .iter()
+            .map(|g| format!("__groups__.{}", g))
+            .collect();
+        format!(
+            "WITH __stat_src__ AS ({query}), \
+             __groups__ AS (SELECT DISTINCT {groups}, {rank} AS {group} FROM __stat_src__), \
+             {values} \
+             SELECT {group_cols}, {cols} FROM __stat_values__ \
+             JOIN __groups__ ON __stat_values__.{group} = __groups__.{group}",
+            query = query,
+            groups = group_by.join(", "),
+            rank = group_index_expr(group_by),
+            group = group_col,
+            values = stat_values,
+            group_cols = group_select.join(", "),
+            cols = stat_select.join(", ")
+        )
+    }
+}
@@ -1,7 +1,18 @@
 //! Violin geom implementation
 
-use super::{GeomAesthetics, GeomTrait, GeomType};
-use crate::Mappings;
+use std::collections::HashMap;
+
+use super::density::{kde_query, KdeOptions};
+use super::types::get_column_name;
+use super::{DefaultParam, DefaultParamValue, GeomAesthetics, GeomTrait, GeomType, StatResult};
+use crate::naming;
+use crate::plot::types::ParameterValue;
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+
+use super::types::Schema;
+
+/// Fraction of the space between two x positions taken up by the violins at that position
+const VIOLIN_WIDTH: f64 = 0.9;
 
 /// Violin geom - violin plots (mirrored density)
 #[derive(Debug, Clone, Copy)]
@@ -16,15 +27,81 @@ impl GeomTrait for Violin {
         GeomAesthetics {
             supported: &["x", "y", "color", "colour", "fill", "violin", "opacity"],
             required: &["x", "y"],
-            hidden: &[],
+            // The outline of each violin is produced by stat_violin
+            hidden: &["xmin", "xmax"],
         }
     }
 
-    fn needs_stat_transform(&self, _aesthetics: &Mappings) -> bool {
+    fn default_remappings(&self) -> &'static [(&'static str, &'static str)] {
+        &[("y", "y"), ("xmin", "xmin"), ("xmax", "xmax")]
+    }
+
+    fn valid_stat_columns(&self) -> &'static [&'static str] {
+        &[
+            "y",
+            "density",
+            "scaled",
+            "count",
+            "violinwidth",
+            "xpos",
+            "xmin",
+            "xmax",
+        ]
+    }
+
+    fn default_params(&self) -> &'static [DefaultParam] {
+        &[
+            DefaultParam {
+                name: "bw",
+                default: DefaultParamValue::String("nrd0"),
+            },
+            DefaultParam {
+                name: "adjust",
+                default: DefaultParamValue::Number(1.0),
+            },
+            DefaultParam {
+                name: "kernel",
+                default: DefaultParamValue::String("gaussian"),
+            },
+            DefaultParam {
+                name: "n",
+                default: DefaultParamValue::Number(512.0),
+            },
+            DefaultParam {
+                name: "trim",
+                default: DefaultParamValue::Boolean(true),
+            },
+        ]
+    }
+
+    fn stat_consumed_aesthetics(&self) -> &'static [&'static str] {
+        &["y"]
+    }
+
+    fn stat_rewritten_axes(&self) -> &'static [&'static str] {
+        // The outline is drawn around integer x positions, one per x value
+        &["x"]
+    }
+
+    fn needs_stat_transform(
+        &self,
+        _aesthetics: &Mappings,
+        _parameters: &HashMap<String, ParameterValue>,
+    ) -> bool {
         true
     }
 
-    // Note: stat_violin not yet implemented - will return Identity for now
+    fn apply_stat_transform(
+        &self,
+        query: &str,
+        _schema: &Schema,
+        aesthetics: &Mappings,
+        group_by: &[String],
+        parameters: &HashMap<String, ParameterValue>,
+        _execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<StatResult> {
+        stat_violin(query, aesthetics, group_by, parameters)
+    }
 }
 
 impl std::fmt::Display for Violin {
@@ -32,3 +109,104 @@ impl std::fmt::Display for Violin {
         write!(f, "violin")
     }
 }
+
+/// Statistical transformation for violin: kernel density of y for each x and group
+///
+/// Besides the density statistics, the outline of each violin is computed on a
+/// numeric x axis: `xpos` numbers the distinct x values from 1, groups sharing
+/// an x value are dodged side by side, and `xmin`/`xmax` mirror the density
+/// around the centre of each violin. Densities are scaled relative to the
+/// largest density in the layer (`violinwidth`), so all violins share one scale.
+fn stat_violin(
+    query: &str,
+    aesthetics: &Mappings,
+    group_by: &[String],
+    parameters: &HashMap<String, ParameterValue>,
+) -> Result<StatResult> {
+    let x_col = get_column_name(aesthetics, "x").ok_or_else(|| {
+        GgsqlError::ValidationError("Violin requires 'x' aesthetic mapping".to_string())
+    })?;
+    let y_col = get_column_name(aesthetics, "y").ok_or_else(|| {
+        GgsqlError::ValidationError("Violin requires 'y' aesthetic mapping".to_string())
+    })?;
+    let options = KdeOptions::from_parameters(parameters, true)?;
+
+    let groups: Vec<String> = group_by.iter().filter(|g| **g != x_col).cloned().collect();
+    let mut keys = vec![x_col.clone()];
+    keys.extend(groups.iter().cloned());
+
+    let density = kde_query(query, &y_col, &keys, "y", &options);
+
+    // Rank of each group within its x value
+    let dodge_rank = if groups.is_empty() {
+        "1".to_string()
+    } else {
+        format!(
+            "DENSE_RANK() OVER (PARTITION BY {x} ORDER BY {g})",
+            x = x_col,
+            g = groups.join(", ")
+        )
+    };
+
+    let stat_y = naming::stat_column("y");
+    let stat_density = naming::stat_column("density");
+    let stat_scaled = naming::stat_column("scaled");
+    let stat_count = naming::stat_column("count");
+    let stat_width = naming::stat_column("violinwidth");
+    let stat_xpos = naming::stat_column("xpos");
+    let stat_xmin = naming::stat_column("xmin");
+    let stat_xmax = naming::stat_column("xmax");
+
+    let transformed_query = format!(
+        "WITH __violin_density__ AS ({density}), \
+         __violin_dodged__ AS (\
+            SELECT *, \
+                DENSE_RANK() OVER (ORDER BY {x}) AS {xpos}, \
+                {dodge_rank} AS __dodge_rank__, \
+                {stat_density} / MAX({stat_density}) OVER () AS {width} \
+            FROM __violin_density__\
+         ), \
+         __violin_counted__ AS (\
+            SELECT *, MAX(__dodge_rank__) OVER (PARTITION BY {x}) AS __dodge_count__ FROM __violin_dodged__\
+         ), \
+         __violin_centred__ AS (\
+            SELECT *, \
+                {xpos} - {half} + {full} * (__dodge_rank__ - 0.5) / __dodge_count__ AS __centre__, \
+                {half} * {width} / __dodge_count__ AS __half_width__ \
+            FROM __violin_counted__\
+         ) \
+         SELECT {keys}, {y}, {stat_density}, {scaled}, {count}, {width}, {xpos}, \
+            __centre__ - __half_width__ AS {xmin}, __centre__ + __half_width__ AS {xmax} \
+         FROM __violin_centred__",
+        density = density,
+        x = x_col,
+        xpos = stat_xpos,
+        dodge_rank = dodge_rank,
+        stat_density = stat_density,
+        width = stat_width,
+        half = VIOLIN_WIDTH / 2.0,
+        full = VIOLIN_WIDTH,
+        keys = keys.join(", "),
+        y = stat_y,
+        scaled = stat_scaled,
+        count = stat_count,
+        xmin = stat_xmin,
+        xmax = stat_xmax,
+    );
+
+    Ok(StatResult::Transformed {
+        query: transformed_query,
+        stat_columns: vec![
+            "y".to_string(),
+            "density".to_string(),
+            "scaled".to_string(),
+            "count".to_string(),
+            "violinwidth".to_string(),
+            "xpos".to_string(),
+            "xmin".to_string(),
+            "xmax".to_string(),
+        ],
+        dummy_columns: vec![],
+        consumed_aesthetics: vec!["y".to_string()],
+    })
+}
@@ -8,11 +8,13 @@ use std::collections::HashMap;
 
 // Geom is now a submodule of layer
 pub mod geom;
+pub mod position;
 
 // Re-export geom types for convenience
 pub use geom::{
     DefaultParam, DefaultParamValue, Geom, GeomAesthetics, GeomTrait, GeomType, StatResult,
 };
+pub use position::Position;
 
 use crate::plot::types::{
     AestheticValue, DataSource, LiteralValue, Mappings, ParameterValue, SqlExpression,
@@ -38,13 +40,18 @@ pub struct Layer {
     pub order_by: Option<SqlExpression>,
     /// Columns for grouping/partitioning (from PARTITION BY clause)
     pub partition_by: Vec<String>,
+    /// Position adjustment (from the `position` setting, or the geom's default)
+    #[serde(default)]
+    pub position: Position,
+    /// Aesthetics used up as input by the stat transform during execution
+    #[serde(default)]
+    pub consumed_aesthetics: Vec<String>,
 }
 
 impl Layer {
     /// Create a new layer with the given geom
     pub fn new(geom: Geom) -> Self {
         Self {
-            geom,
             mappings: Mappings::new(),
             remappings: Mappings::new(),
             parameters: HashMap::new(),
@@ -52,6 +59,9 @@ impl Layer {
             filter: None,
             order_by: None,
             partition_by: Vec::new(),
+            position: geom.default_position(),
+            consumed_aesthetics: Vec::new(),
+            geom,
         }
     }
 
@@ -97,6 +107,12 @@ impl Layer {
         self
     }
 
+    /// Set the position adjustment
+    pub fn with_position(mut self, position: Position) -> Self {
+        self.position = position;
+        self
+    }
+
     /// Get a column reference from an aesthetic, if it's mapped to a column
     pub fn get_column(&self, aesthetic: &str) -> Option<&str> {
         match self.mappings.get(aesthetic) {
@@ -116,7 +132,10 @@ impl Layer {
     /// Check if this layer has the required aesthetics for its geom
     pub fn validate_required_aesthetics(&self) -> std::result::Result<(), String> {
         for aesthetic in self.geom.aesthetics().required {
-            if !self.mappings.contains_key(aesthetic) {
+            // A stat that has run may have replaced its inputs with its own columns
+            if !self.mappings.contains_key(aesthetic)
+                && !self.consumed_aesthetics.iter().any(|aes| aes == aesthetic)
+            {
                 return Err(format!(
                     "Geom '{}' requires aesthetic '{}' but it was not provided",
                     self.geom, aesthetic
@@ -144,7 +163,7 @@ impl Layer {
         }
     }
 
-    /// Validate that all SETTING parameters are valid for this layer's geom
+    /// Validate that all SETTING parameters are valid for this layer's geom, with valid values
     pub fn validate_settings(&self) -> std::result::Result<(), String> {
         let valid = self.geom.valid_settings();
         for param_name in self.parameters.keys() {
@@ -157,6 +176,6 @@ impl Layer {
                 ));
             }
         }
-        Ok(())
+        self.geom.validate_params(&self.parameters)
     }
 }
@@ -0,0 +1,645 @@
+//! Position adjustments for layers
+//!
+//! A position adjustment moves the rows of a layer so that overlapping geometry
+//! is stacked, dodged side by side, jittered or nudged. Adjustments are resolved
+//! in SQL by the execution pipeline, after any statistical transformation, so
+//! every writer receives the final positions.
+//!
+//! Stacking and dodging replace the positional columns in place and add
+//! synthetic position columns (see [`naming::position_column`]):
+//!
+//! - `stack` / `fill`: `y` becomes the top of each segment and a `ymin` column,
+//!   mapped to `y2`, holds its bottom
+//! - `dodge` on a discrete x axis: a `dodge` column holds the index of each group
+//!   within its x position, for writers to offset by
+//! - `jitter` on a discrete axis: `jitter_x` / `jitter_y` columns hold offsets as
+//!   a fraction of the band width
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::geom::types::{get_bool_param, get_column_name, get_number_param, get_string_param};
+use crate::naming;
+use crate::plot::types::{AestheticValue, ParameterValue};
+use crate::{DataFrame, GgsqlError, Mappings, Result};
+
+/// Settings accepted by every layer to control its position adjustment
+///
+/// They are prefixed by the position using them, so that they don't clash with
+/// the settings of a geom, such as the `width` of a bar.
+pub const POSITION_PARAMS: [&str; 9] = [
+    "position",
+    "reverse",
+    "dodge_width",
+    "padding",
+    "jitter_width",
+    "jitter_height",
+    "seed",
+    "nudge_x",
+    "nudge_y",
+];
+
+/// How the rows of a layer are positioned relative to each other
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Position {
+    /// Draw rows where their data puts them
+    #[default]
+    Identity,
+    /// Stack the groups at each x position on top of each other
+    Stack { reverse: bool },
+    /// Stack the groups at each x position and normalise each stack to 1
+    Fill { reverse: bool },
+    /// Place the groups at each x position side by side
+    Dodge {
+        width: f64,
+        padding: f64,
+        reverse: bool,
+    },
+    /// Add a small amount of deterministic random noise
+    ///
+    /// `None` uses 40% of the resolution of the data (or of the band on a
+    /// discrete axis).
+    Jitter {
+        width: Option<f64>,
+        height: Option<f64>,
+        seed: u64,
+    },
+    /// Shift every row by a fixed amount
+    Nudge { x: f64, y: f64 },
+}
+
+impl Position {
+    /// Name of the position as used by the `position` setting
+    pub fn name(&self) -> &'static str {
+        match self {
+            Position::Identity => "identity",
+            Position::Stack { .. } => "stack",
+            Position::Fill { .. } => "fill",
+            Position::Dodge { .. } => "dodge",
+            Position::Jitter { .. } => "jitter",
+            Position::Nudge { .. } => "nudge",
+        }
+    }
+
+    /// Read the `position` setting and its parameters
+    ///
+    /// Without a `position` setting the geom's `default` position is used, still
+    /// configured by the other position settings (e.g. `reverse => true` on a bar).
+    pub fn from_parameters(
+        parameters: &HashMap<String, ParameterValue>,
+        default: Position,
+    ) -> Result<Self> {
+        let name = get_string_param(parameters, "position")?.unwrap_or(default.name());
+        let reverse = get_bool_param(parameters, "reverse")?.unwrap_or(false);
+        let non_negative = |name: &str| -> Result<Option<f64>> {
+            match get_number_param(parameters, name)? {
+                Some(value) if value < 0.0 => Err(GgsqlError::ValidationError(format!(
+                    "Setting '{}' must not be negative",
+                    name
+                ))),
+                value => Ok(value),
+            }
+        };
+
+        let position = match name {
+            "identity" => Position::Identity,
+            "stack" => Position::Stack { reverse },
+            "fill" => Position::Fill { reverse },
+            "dodge" => {
+                let padding = non_negative("padding")?.unwrap_or(0.1);
+                if padding >= 1.0 {
+                    return Err(GgsqlError::ValidationError(
+                        "Setting 'padding' must be less than 1".to_string(),
+                    ));
+                }
+                Position::Dodge {
+                    width: non_negative("dodge_width")?.unwrap_or(0.9),
+                    padding,
+                    reverse,
+                }
+            }
+            "jitter" => {
+                let seed = match get_number_param(parameters, "seed")? {
+                    None => 1,
+                    Some(seed) if seed >= 0.0 && seed.fract() == 0.0 && seed <= u64::MAX as f64 => {
+                        seed as u64
+                    }
+                    Some(_) => {
+                        return Err(GgsqlError::ValidationError(
+                            "Setting 'seed' must be a non-negative integer".to_string(),
+                        ))
+                    }
+                };
+                Position::Jitter {
+                    width: non_negative("jitter_width")?,
+                    height: non_negative("jitter_height")?,
+                    seed,
+                }
+            }
+            "nudge" => Position::Nudge {
+                x: get_number_param(parameters, "nudge_x")?.unwrap_or(0.0),
+                y: get_number_param(parameters, "nudge_y")?.unwrap_or(0.0),
+            },
+            other => {
+                return Err(GgsqlError::ValidationError(format!(
+                    "Unknown position '{}'. Expected 'identity', 'stack', 'fill', 'dodge', 'jitter' or 'nudge'",
+                    other
+                )))
+            }
+        };
+        Ok(position)
+    }
+
+    /// Whether the position changes the data of a layer grouped by `groups`
+    ///
+    /// Stacking and dodging only rearrange groups, so they leave ungrouped
+    /// layers untouched.
+    pub fn adjusts(&self, groups: &[String]) -> bool {
+        match self {
+            Position::Identity => false,
+            Position::Stack { .. } | Position::Dodge { .. } => !groups.is_empty(),
+            Position::Fill { .. } | Position::Jitter { .. } => true,
+            Position::Nudge { x, y } => *x != 0.0 || *y != 0.0,
+        }
+    }
+
+    /// Wrap a layer query with the position adjustment
+    ///
+    /// `groups` are the grouping columns of the layer (from PARTITION BY and
+    /// discrete mappings) and `facets` the facet variables, which always separate
+    /// stacks and dodges. Mappings gain the synthetic position columns the
+    /// adjustment adds. The returned query has no ORDER BY.
+    pub fn apply(
+        &self,
+        query: &str,
+        mappings: &mut Mappings,
+        groups: &[String],
+        facets: &[String],
+        execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+    ) -> Result<String> {
+        if !self.adjusts(groups) {
+            return Ok(query.to_string());
+        }
+        let columns = query_columns(query, execute_query)?;
+        let axis = |aesthetic: &str| -> Option<Axis> {
+            get_column_name(mappings, aesthetic).map(|column| Axis {
+                numeric: columns
+                    .iter()
+                    .any(|(name, numeric)| *name == column && *numeric),
+                column,
+            })
+        };
+        let (x, x2, y) = (axis("x"), axis("x2"), axis("y"));
+
+        let adjusted = match *self {
+            Position::Identity => return Ok(query.to_string()),
+            Position::Stack { reverse } => {
+                stack_query(query, groups, facets, x, x2, y, reverse, false)?
+            }
+            Position::Fill { reverse } => {
+                stack_query(query, groups, facets, x, x2, y, reverse, true)?
+            }
+            Position::Dodge {
+                width,
+                padding,
+                reverse,
+            } => dodge_query(query, groups, facets, x, x2, width, padding, reverse)?,
+            Position::Jitter {
+                width,
+                height,
+                seed,
+            } => {
+                let all: Vec<String> = columns.iter().map(|(name, _)| name.clone()).collect();
+                jitter_query(query, &all, x, x2, y, axis("y2"), width, height, seed)
+            }
+            Position::Nudge { x: dx, y: dy } => nudge_query(
+                query,
+                [
+                    (x, dx, "x"),
+                    (x2, dx, "x"),
+                    (y, dy, "y"),
+                    (axis("y2"), dy, "y"),
+                ],
+            )?,
+        };
+
+        if matches!(self, Position::Stack { .. } | Position::Fill { .. }) {
+            mappings.insert(
+                "y2",
+                AestheticValue::standard_column(naming::position_column("ymin")),
+            );
+        }
+        Ok(adjusted)
+    }
+}
+
+/// A positional aesthetic mapped to a column
+struct Axis {
+    column: String,
+    numeric: bool,
+}
+
+/// Names of the columns produced by `query`, and whether each is numeric
+fn query_columns(
+    query: &str,
+    execute_query: &dyn Fn(&str) -> Result<DataFrame>,
+) -> Result<Vec<(String, bool)>> {
+    let df = execute_query(&format!(
+        "SELECT * FROM ({}) AS {} LIMIT 0",
+        query,
+        naming::SCHEMA_ALIAS
+    ))?;
+    Ok(df
+        .get_columns()
+        .iter()
+        .map(|col| (col.name().to_string(), col.dtype().is_numeric()))
+        .collect())
+}
+
+/// `PARTITION BY` clause over the given expressions, or nothing
+fn partition_clause(columns: &[String]) -> String {
+    if columns.is_empty() {
+        String::new()
+    } else {
+        format!("PARTITION BY {}", columns.join(", "))
+    }
+}
+
+/// Group columns sorted for stacking or dodging
+fn group_order(groups: &[String], descending: bool) -> String {
+    let direction = if descending { "DESC" } else { "ASC" };
+    groups
+        .iter()
+        .map(|g| format!("{} {}", g, direction))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Scalar subquery for the smallest gap between distinct values of a column
+///
+/// Like ggplot2's `resolution()`, falls back to 1 when there is no gap.
+fn resolution_sql(column: &str) -> String {
+    format!(
+        "(SELECT COALESCE(MIN(__gap__), 1) FROM (\
+            SELECT __value__ - LAG(__value__) OVER (ORDER BY __value__) AS __gap__ \
+            FROM (SELECT DISTINCT CAST({col} AS DOUBLE) AS __value__ FROM __position_src__ WHERE {col} IS NOT NULL)\
+         ) WHERE __gap__ > 0)",
+        col = column
+    )
+}
+
+/// Stack (or fill) the groups at each x position
+///
+/// Positive and negative values are stacked separately, away from zero. The
+/// first group ends up on top unless `reverse` is set.
+#[allow(clippy::too_many_arguments)]
+fn stack_query(
+    query: &str,
+    groups: &[String],
+    facets: &[String],
+    x: Option<Axis>,
+    x2: Option<Axis>,
+    y: Option<Axis>,
+    reverse: bool,
+    fill: bool,
+) -> Result<String> {
+    let name = if fill { "fill" } else { "stack" };
+    let y = y.filter(|y| y.numeric).ok_or_else(|| {
+        GgsqlError::ValidationError(format!(
+            "Position '{}' requires 'y' to be mapped to a numeric column",
+            name
+        ))
+    })?;
+
+    let mut partition: Vec<String> = x.into_iter().chain(x2).map(|a| a.column).collect();
+    partition.extend(facets.iter().cloned());
+    partition.push(format!("({} < 0)", y.column));
+    let partition = partition_clause(&partition);
+
+    let mut order = group_order(groups, !reverse);
+    if order.is_empty() {
+        order = "1".to_string();
+    }
+    let cumulative = format!(
+        "SUM({y}) OVER ({partition} ORDER BY {order} ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW)",
+        y = y.column,
+        partition = partition,
+        order = order
+    );
+    let (top, bottom) = if fill {
+        let total = format!(
+            "NULLIF(ABS(SUM({y}) OVER ({partition})), 0)",
+            y = y.column,
+            partition = partition
+        );
+        (
+            format!("{} / {}", cumulative, total),
+            format!("({} - {}) / {}", cumulative, y.column, total),
+        )
+    } else {
+        (cumulative.clone(), format!("{} - {}", cumulative, y.column))
+    };
+
+    Ok(format!(
+        "WITH __position_src__ AS ({query}) \
+         SELECT * REPLACE ({top} AS {y}), {bottom} AS {ymin} FROM __position_src__",
+        query = query,
+        top = top,
+        y = y.column,
+        bottom = bottom,
+        ymin = naming::position_column("ymin")
+    ))
+}
+
+/// Dodge the groups at each x position side by side
+///
+/// Every x position of a panel is split into as many slots as the panel has
+/// groups at any x position. On a discrete x axis the slot index is added as a
+/// column; on a numeric axis x (and x2) are moved into their slot within
+/// `width` times the resolution of x (or within the x to x2 interval).
+#[allow(clippy::too_many_arguments)]
+fn dodge_query(
+    query: &str,
+    groups: &[String],
+    facets: &[String],
+    x: Option<Axis>,
+    x2: Option<Axis>,
+    width: f64,
+    padding: f64,
+    reverse: bool,
+) -> Result<String> {
+    let x = x.ok_or_else(|| {
+        GgsqlError::ValidationError(
+            "Position 'dodge' requires 'x' to be mapped to a column".to_string(),
+        )
+    })?;
+
+    let mut slot_partition: Vec<String> = vec![x.column.clone()];
+    slot_partition.extend(x2.iter().map(|a| a.column.clone()));
+    slot_partition.extend(facets.iter().cloned());
+    let ctes = format!(
+        "WITH __position_src__ AS ({query}), \
+         __position_slots__ AS (\
+            SELECT *, DENSE_RANK() OVER ({slots} ORDER BY {order}) - 1 AS __slot__ FROM __position_src__\
+         ), \
+         __position_dodge__ AS (\
+            SELECT *, MAX(__slot__) OVER ({panels}) + 1 AS __slots__ FROM __position_slots__\
+         )",
+        query = query,
+        slots = partition_clause(&slot_partition),
+        order = group_order(groups, reverse),
+        panels = partition_clause(facets)
+    );
+
+    let select = if !x.numeric {
+        format!(
+            "SELECT * EXCLUDE (__slot__, __slots__), __slot__ AS {}",
+            naming::position_column("dodge")
+        )
+    } else if let Some(x2) = x2.filter(|a| a.numeric) {
+        let edge = |offset: f64| {
+            format!(
+                "{x} + ({x2} - {x}) * (__slot__ + {offset:e}) / __slots__",
+                x = x.column,
+                x2 = x2.column,
+                offset = offset
+            )
+        };
+        format!(
+            "SELECT * EXCLUDE (__slot__, __slots__) REPLACE ({start} AS {x}, {end} AS {x2})",
+            start = edge(padding / 2.0),
+            end = edge(1.0 - padding / 2.0),
+            x = x.column,
+            x2 = x2.column
+        )
+    } else {
+        format!(
+            "SELECT * EXCLUDE (__slot__, __slots__) REPLACE (\
+                {x} + {width:e} * {resolution} * ((__slot__ + 0.5) / __slots__ - 0.5) AS {x}\
+             )",
+            x = x.column,
+            width = width,
+            resolution = resolution_sql(&x.column)
+        )
+    };
+
+    Ok(format!("{} {} FROM __position_dodge__", ctes, select))
+}
+
+/// Jitter rows by uniform noise in both directions
+///
+/// The noise is a hash of `seed` and the row number with the rows sorted by all
+/// of their `columns`, so a plot renders the same way every time whatever order
+/// the rows come in. Numeric axes are jittered in the data; discrete axes get
+/// offset columns instead.
+#[allow(clippy::too_many_arguments)]
+fn jitter_query(
+    query: &str,
+    columns: &[String],
+    x: Option<Axis>,
+    x2: Option<Axis>,
+    y: Option<Axis>,
+    y2: Option<Axis>,
+    width: Option<f64>,
+    height: Option<f64>,
+    seed: u64,
+) -> String {
+    let noise = |k: u32| {
+        format!(
+            "(HASH(__row__, {seed}, {k}) % 1000001) / 500000.0 - 1",
+            seed = seed,
+            k = k
+        )
+    };
+
+    let order = columns
+        .iter()
+        .map(|c| format!("{} ASC NULLS FIRST", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut replace = Vec::new();
+    let mut extra = Vec::new();
+    let mut jitter =
+        |axis: Option<Axis>, end: Option<Axis>, amount: Option<f64>, k: u32, name: &str| {
+            let Some(axis) = axis else {
+                return;
+            };
+            if amount == Some(0.0) {
+                return;
+            }
+            if axis.numeric {
+                let amount = match amount {
+                    Some(amount) => format!("{:e}", amount),
+                    None => format!("0.4 * {}", resolution_sql(&axis.column)),
+                };
+                for a in std::iter::once(axis).chain(end.filter(|a| a.numeric)) {
+                    replace.push(format!(
+                        "{col} + {amount} * {noise} AS {col}",
+                        col = a.column,
+                        amount = amount,
+                        noise = noise(k)
+                    ));
+                }
+            } else {
+                extra.push(format!(
+                    "{:e} * {} AS {}",
+                    amount.unwrap_or(0.4),
+                    noise(k),
+                    naming::position_column(name)
+                ));
+            }
+        };
+    jitter(x, x2, width, 1, "jitter_x");
+    jitter(y, y2, height, 2, "jitter_y");
+
+    let replace = if replace.is_empty() {
+        String::new()
+    } else {
+        format!(" REPLACE ({})", replace.join(", "))
+    };
+    let extra: String = extra.iter().map(|e| format!(", {}", e)).collect();
+    format!(
+        "WITH __position_src__ AS ({query}), \
+         __position_rows__ AS (SELECT *, ROW_NUMBER() OVER (ORDER BY {order}) AS __row__ FROM __position_src__) \
+         SELECT * EXCLUDE (__row__){replace}{extra} FROM __position_rows__",
+        query = query,
+        order = order,
+        replace = replace,
+        extra = extra
+    )
+}
+
+/// Shift numeric positional columns by fixed amounts
+fn nudge_query(query: &str, shifts: [(Option<Axis>, f64, &str); 4]) -> Result<String> {
+    let mut replace = Vec::new();
+    for (axis, amount, name) in shifts {
+        let Some(axis) = axis else {
+            continue;
+        };
+        if amount == 0.0 {
+            continue;
+        }
+        if !axis.numeric {
+            return Err(GgsqlError::ValidationError(format!(
+                "Position 'nudge' cannot shift the discrete column '{}' (setting 'nudge_{}')",
+                axis.column, name
+            )));
+        }
+        replace.push(format!(
+            "{col} + {amount:e} AS {col}",
+            col = axis.column,
+            amount = amount
+        ));
+    }
+    if replace.is_empty() {
+        return Ok(query.to_string());
+    }
+    Ok(format!(
+        "WITH __position_src__ AS ({}) SELECT * REPLACE ({}) FROM __position_src__",
+        query,
+        replace.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameters(values: &[(&str, ParameterValue)]) -> HashMap<String, ParameterValue> {
+        values
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_position_from_parameters() {
+        let default = Position::Stack { reverse: false };
+        assert_eq!(
+            Position::from_parameters(&HashMap::new(), default).unwrap(),
+            default
+        );
+        assert_eq!(
+            Position::from_parameters(
+                &parameters(&[("reverse", ParameterValue::Boolean(true))]),
+                default
+            )
+            .unwrap(),
+            Position::Stack { reverse: true }
+        );
+        assert_eq!(
+            Position::from_parameters(
+                &parameters(&[
+                    ("position", ParameterValue::String("dodge".to_string())),
+                    ("dodge_width", ParameterValue::Number(0.5)),
+                ]),
+                default
+            )
+            .unwrap(),
+            Position::Dodge {
+                width: 0.5,
+                padding: 0.1,
+                reverse: false
+            }
+        );
+        assert!(Position::from_parameters(
+            &parameters(&[("position", ParameterValue::String("spread".to_string()))]),
+            default
+        )
+        .is_err());
+        assert!(Position::from_parameters(
+            &parameters(&[
+                ("position", ParameterValue::String("dodge".to_string())),
+                ("padding", ParameterValue::Number(1.0)),
+            ]),
+            default
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_jitter_parameters() {
+        let jitter = |values: &[(&str, ParameterValue)]| {
+            let mut values = values.to_vec();
+            values.push(("position", ParameterValue::String("jitter".to_string())));
+            Position::from_parameters(&parameters(&values), Position::Identity)
+        };
+        assert_eq!(
+            jitter(&[
+                ("jitter_width", ParameterValue::Number(0.2)),
+                ("seed", ParameterValue::Number(7.0)),
+            ])
+            .unwrap(),
+            Position::Jitter {
+                width: Some(0.2),
+                height: None,
+                seed: 7
+            }
+        );
+        for seed in [-1.0, 2.5] {
+            let err = jitter(&[("seed", ParameterValue::Number(seed))]).unwrap_err();
+            assert!(err.to_string().contains("non-negative integer"));
+        }
+
+        // The width of a geom is not the amount of jitter
+        assert_eq!(
+            jitter(&[("width", ParameterValue::Number(0.3))]).unwrap(),
+            Position::Jitter {
+                width: None,
+                height: None,
+                seed: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_position_adjusts() {
+        let groups = vec!["region".to_string()];
+        assert!(!Position::Identity.adjusts(&groups));
+        assert!(!Position::Stack { reverse: false }.adjusts(&[]));
+        assert!(Position::Stack { reverse: false }.adjusts(&groups));
+        assert!(Position::Fill { reverse: false }.adjusts(&[]));
+        assert!(!Position::Nudge { x: 0.0, y: 0.0 }.adjusts(&groups));
+    }
+}
@@ -16,7 +16,8 @@
 //! ├─ coord: Option<Coord>           (optional, from COORD clause)
 //! ├─ labels: Option<Labels>         (optional, merged from LABEL clauses)
 //! ├─ guides: Vec<Guide>             (0+ GuideNode, one per GUIDE clause)
-//! └─ theme: Option<Theme>           (optional, from THEME clause)
+//! ├─ theme: Option<Theme>           (optional, from THEME clause)
+//! └─ trained_scales: Vec<TrainedScale> (filled in from the data during execution)
 //! ```
 
 use crate::naming;
@@ -38,7 +39,7 @@ pub use super::layer::geom::{
 pub use super::layer::Layer;
 
 // Re-export Scale and Guide types from the scale module
-pub use super::scale::{Guide, GuideType, Scale, ScaleType};
+pub use super::scale::{Guide, GuideType, Scale, ScaleType, TrainedScale};
 
 // Re-export Coord types from the coord module
 pub use super::coord::{Coord, CoordType};
@@ -46,6 +47,9 @@ pub use super::coord::{Coord, CoordType};
 // Re-export Facet types from the facet module
 pub use super::facet::{Facet, FacetScales};
 
+// Re-export Theme from the theme module
+pub use super::theme::Theme;
+
 /// Complete ggsql visualization specification
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Plot {
@@ -67,6 +71,9 @@ pub struct Plot {
     pub guides: Vec<Guide>,
     /// Theme styling (from THEME clause)
     pub theme: Option<Theme>,
+    /// Scales trained on the layer data (filled in by `execute`)
+    #[serde(default)]
+    pub trained_scales: Vec<TrainedScale>,
 }
 
 /// Text labels (from LABELS clause)
@@ -76,15 +83,6 @@ pub struct Labels {
     pub labels: HashMap<String, String>,
 }
 
-/// Theme styling (from THEME clause)
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Theme {
-    /// Base theme style
-    pub style: Option<String>,
-    /// Theme property overrides
-    pub properties: HashMap<String, ParameterValue>,
-}
-
 impl Plot {
     /// Create a new empty Plot
     pub fn new() -> Self {
@@ -98,6 +96,7 @@ impl Plot {
             labels: None,
             guides: Vec::new(),
             theme: None,
+            trained_scales: Vec::new(),
         }
     }
 
@@ -113,6 +112,7 @@ impl Plot {
             labels: None,
             guides: Vec::new(),
             theme: None,
+            trained_scales: Vec::new(),
         }
     }
 
@@ -133,6 +133,13 @@ impl Plot {
             .find(|scale| scale.aesthetic == aesthetic)
     }
 
+    /// Find the trained scale of an aesthetic family
+    pub fn find_trained_scale(&self, aesthetic: &str) -> Option<&TrainedScale> {
+        self.trained_scales
+            .iter()
+            .find(|scale| scale.aesthetic == aesthetic)
+    }
+
     /// Find a guide for a specific aesthetic
     pub fn find_guide(&self, aesthetic: &str) -> Option<&Guide> {
         self.guides
@@ -164,8 +171,8 @@ impl Plot {
         for layer in &self.layers {
             for (aesthetic, value) in &layer.mappings.aesthetics {
                 if let AestheticValue::Column { name, .. } = value {
-                    // Skip synthetic constant columns
-                    if naming::is_const_column(name) {
+                    // Skip synthetic constant and position columns
+                    if naming::is_const_column(name) || naming::is_position_column(name) {
                         continue;
                     }
 
@@ -271,6 +278,12 @@ mod tests {
             .with_aesthetic("ymax".to_string(), AestheticValue::standard_column("ymax"));
 
         assert!(valid_ribbon.validate_required_aesthetics().is_ok());
+
+        // Aesthetics are only excused once a stat has consumed them
+        let mut contour = Layer::new(Geom::contour());
+        assert!(contour.validate_required_aesthetics().is_err());
+        contour.consumed_aesthetics = vec!["x".to_string(), "y".to_string(), "z".to_string()];
+        assert!(contour.validate_required_aesthetics().is_ok());
     }
 
     #[test]
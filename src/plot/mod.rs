@@ -8,18 +8,20 @@
 //!
 //! The module is organized into submodules:
 //!
-//! - `main` - Main Plot struct and Labels/Theme types
+//! - `main` - Main Plot struct and Labels type
 //! - `types` - Value types: Mappings, AestheticValue, ParameterValue, etc.
 //! - `layer` - Layer struct and Geom subsystem
 //! - `scale` - Scale and Guide types
 //! - `facet` - Facet types for small multiples
 //! - `coord` - Coordinate system types
+//! - `theme` - Theme types and the built-in themes
 
 pub mod coord;
 pub mod facet;
 pub mod layer;
 pub mod main;
 pub mod scale;
+pub mod theme;
 pub mod types;
 
 // Re-export all types for convenience
@@ -28,4 +30,5 @@ pub use facet::*;
 pub use layer::*;
 pub use main::*;
 pub use scale::*;
+pub use theme::*;
 pub use types::*;
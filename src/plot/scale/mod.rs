@@ -2,6 +2,12 @@
 //!
 //! This module defines scale and guide configuration for aesthetic mappings.
 
+mod palettes;
+mod sec_axis;
+mod training;
 mod types;
 
-pub use types::{Guide, GuideType, Scale, ScaleType};
+pub use palettes::{lookup_palette, palette_names, resolve_palette, sample_palette};
+pub use sec_axis::SecondaryAxis;
+pub use training::{column_kind, train_scales, ScaleDomain, ScaleKind, TrainedScale};
+pub use types::{Guide, GuideType, OutOfBounds, Scale, ScaleType};
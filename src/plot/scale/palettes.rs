@@ -0,0 +1,498 @@
+//! Built-in colour palettes
+//!
+//! Palettes are resolved to explicit hex codes when a plot is built, so every
+//! writer renders the same colours. The registry holds the viridis family, all
+//! ColorBrewer palettes (at their largest number of classes), Okabe-Ito and
+//! Tableau 10.
+
+use super::super::types::{ArrayElement, ParameterValue};
+use super::types::{Scale, ScaleType};
+use crate::{GgsqlError, Result};
+
+/// Palette names and their colours, looked up case-insensitively
+const PALETTES: &[(&str, &[&str])] = &[
+    // viridis family (10 evenly spaced samples, as viridisLite)
+    (
+        "viridis",
+        &[
+            "#440154", "#482878", "#3e4a89", "#31688e", "#26828e", "#1f9e89", "#35b779", "#6dcd59",
+            "#b4de2c", "#fde725",
+        ],
+    ),
+    (
+        "magma",
+        &[
+            "#000004", "#180f3e", "#451077", "#721f81", "#9f2f7f", "#cd4071", "#f1605d", "#fd9567",
+            "#fec98d", "#fcfdbf",
+        ],
+    ),
+    (
+        "inferno",
+        &[
+            "#000004", "#1b0c42", "#4b0c6b", "#781c6d", "#a52c60", "#cf4446", "#ed6925", "#fb9a06",
+            "#f7d03c", "#fcffa4",
+        ],
+    ),
+    (
+        "plasma",
+        &[
+            "#0d0887", "#47039f", "#7301a8", "#9c179e", "#bd3786", "#d8576b", "#ed7953", "#fa9e3b",
+            "#fdc926", "#f0f921",
+        ],
+    ),
+    (
+        "cividis",
+        &[
+            "#00204d", "#00336f", "#39486b", "#575c6d", "#707173", "#8a8779", "#a69d75", "#c4b56c",
+            "#e4cf5b", "#ffea46",
+        ],
+    ),
+    // ColorBrewer sequential
+    (
+        "Blues",
+        &[
+            "#f7fbff", "#deebf7", "#c6dbef", "#9ecae1", "#6baed6", "#4292c6", "#2171b5", "#08519c",
+            "#08306b",
+        ],
+    ),
+    (
+        "BuGn",
+        &[
+            "#f7fcfd", "#e5f5f9", "#ccece6", "#99d8c9", "#66c2a4", "#41ae76", "#238b45", "#006d2c",
+            "#00441b",
+        ],
+    ),
+    (
+        "BuPu",
+        &[
+            "#f7fcfd", "#e0ecf4", "#bfd3e6", "#9ebcda", "#8c96c6", "#8c6bb1", "#88419d", "#810f7c",
+            "#4d004b",
+        ],
+    ),
+    (
+        "GnBu",
+        &[
+            "#f7fcf0", "#e0f3db", "#ccebc5", "#a8ddb5", "#7bccc4", "#4eb3d3", "#2b8cbe", "#0868ac",
+            "#084081",
+        ],
+    ),
+    (
+        "Greens",
+        &[
+            "#f7fcf5", "#e5f5e0", "#c7e9c0", "#a1d99b", "#74c476", "#41ab5d", "#238b45", "#006d2c",
+            "#00441b",
+        ],
+    ),
+    (
+        "Greys",
+        &[
+            "#ffffff", "#f0f0f0", "#d9d9d9", "#bdbdbd", "#969696", "#737373", "#525252", "#252525",
+            "#000000",
+        ],
+    ),
+    (
+        "Oranges",
+        &[
+            "#fff5eb", "#fee6ce", "#fdd0a2", "#fdae6b", "#fd8d3c", "#f16913", "#d94801", "#a63603",
+            "#7f2704",
+        ],
+    ),
+    (
+        "OrRd",
+        &[
+            "#fff7ec", "#fee8c8", "#fdd49e", "#fdbb84", "#fc8d59", "#ef6548", "#d7301f", "#b30000",
+            "#7f0000",
+        ],
+    ),
+    (
+        "PuBu",
+        &[
+            "#fff7fb", "#ece7f2", "#d0d1e6", "#a6bddb", "#74a9cf", "#3690c0", "#0570b0", "#045a8d",
+            "#023858",
+        ],
+    ),
+    (
+        "PuBuGn",
+        &[
+            "#fff7fb", "#ece2f0", "#d0d1e6", "#a6bddb", "#67a9cf", "#3690c0", "#02818a", "#016c59",
+            "#014636",
+        ],
+    ),
+    (
+        "PuRd",
+        &[
+            "#f7f4f9", "#e7e1ef", "#d4b9da", "#c994c7", "#df65b0", "#e7298a", "#ce1256", "#980043",
+            "#67001f",
+        ],
+    ),
+    (
+        "Purples",
+        &[
+            "#fcfbfd", "#efedf5", "#dadaeb", "#bcbddc", "#9e9ac8", "#807dba", "#6a51a3", "#54278f",
+            "#3f007d",
+        ],
+    ),
+    (
+        "RdPu",
+        &[
+            "#fff7f3", "#fde0dd", "#fcc5c0", "#fa9fb5", "#f768a1", "#dd3497", "#ae017e", "#7a0177",
+            "#49006a",
+        ],
+    ),
+    (
+        "Reds",
+        &[
+            "#fff5f0", "#fee0d2", "#fcbba1", "#fc9272", "#fb6a4a", "#ef3b2c", "#cb181d", "#a50f15",
+            "#67000d",
+        ],
+    ),
+    (
+        "YlGn",
+        &[
+            "#ffffe5", "#f7fcb9", "#d9f0a3", "#addd8e", "#78c679", "#41ab5d", "#238443", "#006837",
+            "#004529",
+        ],
+    ),
+    (
+        "YlGnBu",
+        &[
+            "#ffffd9", "#edf8b1", "#c7e9b4", "#7fcdbb", "#41b6c4", "#1d91c0", "#225ea8", "#253494",
+            "#081d58",
+        ],
+    ),
+    (
+        "YlOrBr",
+        &[
+            "#ffffe5", "#fff7bc", "#fee391", "#fec44f", "#fe9929", "#ec7014", "#cc4c02", "#993404",
+            "#662506",
+        ],
+    ),
+    (
+        "YlOrRd",
+        &[
+            "#ffffcc", "#ffeda0", "#fed976", "#feb24c", "#fd8d3c", "#fc4e2a", "#e31a1c", "#bd0026",
+            "#800026",
+        ],
+    ),
+    // ColorBrewer diverging
+    (
+        "BrBG",
+        &[
+            "#543005", "#8c510a", "#bf812d", "#dfc27d", "#f6e8c3", "#f5f5f5", "#c7eae5", "#80cdc1",
+            "#35978f", "#01665e", "#003c30",
+        ],
+    ),
+    (
+        "PiYG",
+        &[
+            "#8e0152", "#c51b7d", "#de77ae", "#f1b6da", "#fde0ef", "#f7f7f7", "#e6f5d0", "#b8e186",
+            "#7fbc41", "#4d9221", "#276419",
+        ],
+    ),
+    (
+        "PRGn",
+        &[
+            "#40004b", "#762a83", "#9970ab", "#c2a5cf", "#e7d4e8", "#f7f7f7", "#d9f0d3", "#a6dba0",
+            "#5aae61", "#1b7837", "#00441b",
+        ],
+    ),
+    (
+        "PuOr",
+        &[
+            "#7f3b08", "#b35806", "#e08214", "#fdb863", "#fee0b6", "#f7f7f7", "#d8daeb", "#b2abd2",
+            "#8073ac", "#542788", "#2d004b",
+        ],
+    ),
+    (
+        "RdBu",
+        &[
+            "#67001f", "#b2182b", "#d6604d", "#f4a582", "#fddbc7", "#f7f7f7", "#d1e5f0", "#92c5de",
+            "#4393c3", "#2166ac", "#053061",
+        ],
+    ),
+    (
+        "RdGy",
+        &[
+            "#67001f", "#b2182b", "#d6604d", "#f4a582", "#fddbc7", "#ffffff", "#e0e0e0", "#bababa",
+            "#878787", "#4d4d4d", "#1a1a1a",
+        ],
+    ),
+    (
+        "RdYlBu",
+        &[
+            "#a50026", "#d73027", "#f46d43", "#fdae61", "#fee090", "#ffffbf", "#e0f3f8", "#abd9e9",
+            "#74add1", "#4575b4", "#313695",
+        ],
+    ),
+    (
+        "RdYlGn",
+        &[
+            "#a50026", "#d73027", "#f46d43", "#fdae61", "#fee08b", "#ffffbf", "#d9ef8b", "#a6d96a",
+            "#66bd63", "#1a9850", "#006837",
+        ],
+    ),
+    (
+        "Spectral",
+        &[
+            "#9e0142", "#d53e4f", "#f46d43", "#fdae61", "#fee08b", "#ffffbf", "#e6f598", "#abdda4",
+            "#66c2a5", "#3288bd", "#5e4fa2",
+        ],
+    ),
+    // ColorBrewer qualitative
+    (
+        "Accent",
+        &[
+            "#7fc97f", "#beaed4", "#fdc086", "#ffff99", "#386cb0", "#f0027f", "#bf5b17", "#666666",
+        ],
+    ),
+    (
+        "Dark2",
+        &[
+            "#1b9e77", "#d95f02", "#7570b3", "#e7298a", "#66a61e", "#e6ab02", "#a6761d", "#666666",
+        ],
+    ),
+    (
+        "Paired",
+        &[
+            "#a6cee3", "#1f78b4", "#b2df8a", "#33a02c", "#fb9a99", "#e31a1c", "#fdbf6f", "#ff7f00",
+            "#cab2d6", "#6a3d9a", "#ffff99", "#b15928",
+        ],
+    ),
+    (
+        "Pastel1",
+        &[
+            "#fbb4ae", "#b3cde3", "#ccebc5", "#decbe4", "#fed9a6", "#ffffcc", "#e5d8bd", "#fddaec",
+            "#f2f2f2",
+        ],
+    ),
+    (
+        "Pastel2",
+        &[
+            "#b3e2cd", "#fdcdac", "#cbd5e8", "#f4cae4", "#e6f5c9", "#fff2ae", "#f1e2cc", "#cccccc",
+        ],
+    ),
+    (
+        "Set1",
+        &[
+            "#e41a1c", "#377eb8", "#4daf4a", "#984ea3", "#ff7f00", "#ffff33", "#a65628", "#f781bf",
+            "#999999",
+        ],
+    ),
+    (
+        "Set2",
+        &[
+            "#66c2a5", "#fc8d62", "#8da0cb", "#e78ac3", "#a6d854", "#ffd92f", "#e5c494", "#b3b3b3",
+        ],
+    ),
+    (
+        "Set3",
+        &[
+            "#8dd3c7", "#ffffb3", "#bebada", "#fb8072", "#80b1d3", "#fdb462", "#b3de69", "#fccde5",
+            "#d9d9d9", "#bc80bd", "#ccebc5", "#ffed6f",
+        ],
+    ),
+    // Colour-blind friendly qualitative palettes
+    (
+        "okabe_ito",
+        &[
+            "#e69f00", "#56b4e9", "#009e73", "#f0e442", "#0072b2", "#d55e00", "#cc79a7", "#999999",
+            "#000000",
+        ],
+    ),
+    (
+        "tableau10",
+        &[
+            "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7",
+            "#9c755f", "#bab0ac",
+        ],
+    ),
+];
+
+/// Colours of a built-in palette
+///
+/// Names are case-insensitive and ignore `-` and `_`, so `'Okabe-Ito'` and
+/// `'okabe_ito'` are the same palette.
+pub fn lookup_palette(name: &str) -> Option<&'static [&'static str]> {
+    let normalise = |s: &str| {
+        s.chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_lowercase()
+    };
+    let name = normalise(name);
+    PALETTES
+        .iter()
+        .find(|(candidate, _)| normalise(candidate) == name)
+        .map(|(_, colours)| *colours)
+}
+
+/// Names of all built-in palettes
+pub fn palette_names() -> impl Iterator<Item = &'static str> {
+    PALETTES.iter().map(|(name, _)| *name)
+}
+
+/// Resolve the palette of a colour scale to an explicit array of hex codes
+///
+/// A palette given by name, or implied by a palette scale type such as
+/// `'viridis'`, is replaced by its colours. With `direction => -1` the colours
+/// are reversed and the `direction` setting is consumed, so writers don't
+/// reverse the scale a second time.
+pub fn resolve_palette(scale: &mut Scale) -> Result<()> {
+    let colours: Option<Vec<ArrayElement>> = match scale.properties.get("palette") {
+        Some(ParameterValue::String(name)) => {
+            let colours = lookup_palette(name).ok_or_else(|| {
+                GgsqlError::ParseError(format!(
+                    "Unknown palette '{}'. Available palettes: {}",
+                    name,
+                    palette_names().collect::<Vec<_>>().join(", ")
+                ))
+            })?;
+            Some(palette_elements(colours))
+        }
+        Some(ParameterValue::Array(colours)) => Some(colours.clone()),
+        Some(_) => {
+            return Err(GgsqlError::ParseError(format!(
+                "SCALE {} palette must be a palette name or an array of colours",
+                scale.aesthetic
+            )))
+        }
+        None => scale
+            .scale_type
+            .as_ref()
+            .and_then(ScaleType::default_palette)
+            .and_then(lookup_palette)
+            .map(palette_elements),
+    };
+    let Some(mut colours) = colours else {
+        return Ok(());
+    };
+
+    if let Some(ParameterValue::Number(direction)) = scale.properties.get("direction") {
+        if *direction < 0.0 {
+            colours.reverse();
+        }
+        scale.properties.remove("direction");
+    }
+    scale
+        .properties
+        .insert("palette".to_string(), ParameterValue::Array(colours));
+    Ok(())
+}
+
+/// Take `n` evenly spaced colours from a palette
+///
+/// Colours between the entries of the palette are interpolated in RGB, so a
+/// short palette can still provide many classes. Entries that aren't hex codes
+/// are picked as they are.
+pub fn sample_palette(colours: &[ArrayElement], n: usize) -> Vec<ArrayElement> {
+    if colours.len() < 2 || n < 2 {
+        return colours.iter().take(n.max(1)).cloned().collect();
+    }
+    let last = (colours.len() - 1) as f64;
+    (0..n)
+        .map(|i| {
+            let position = i as f64 * last / (n - 1) as f64;
+            let lower = position.floor() as usize;
+            let upper = position.ceil() as usize;
+            match (hex_to_rgb(&colours[lower]), hex_to_rgb(&colours[upper])) {
+                (Some(a), Some(b)) => {
+                    let t = position - lower as f64;
+                    let channel =
+                        |k: usize| (a[k] as f64 + (b[k] as f64 - a[k] as f64) * t).round();
+                    ArrayElement::String(format!(
+                        "#{:02x}{:02x}{:02x}",
+                        channel(0) as u8,
+                        channel(1) as u8,
+                        channel(2) as u8
+                    ))
+                }
+                _ => colours[position.round() as usize].clone(),
+            }
+        })
+        .collect()
+}
+
+fn hex_to_rgb(colour: &ArrayElement) -> Option<[u8; 3]> {
+    let ArrayElement::String(colour) = colour else {
+        return None;
+    };
+    let hex = colour.strip_prefix('#').filter(|h| h.len() == 6)?;
+    let channel = |k: usize| u8::from_str_radix(&hex[2 * k..2 * k + 2], 16).ok();
+    Some([channel(0)?, channel(1)?, channel(2)?])
+}
+
+fn palette_elements(colours: &[&str]) -> Vec<ArrayElement> {
+    colours
+        .iter()
+        .map(|c| ArrayElement::String(c.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_lookup_palette() {
+        assert_eq!(lookup_palette("set2").unwrap().len(), 8);
+        assert_eq!(lookup_palette("Okabe-Ito").unwrap()[0], "#e69f00");
+        assert_eq!(lookup_palette("Tableau10").unwrap().len(), 10);
+        assert!(lookup_palette("rainbow").is_none());
+        for name in palette_names() {
+            for colour in lookup_palette(name).unwrap() {
+                assert!(colour.starts_with('#') && colour.len() == 7, "{}", colour);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sample_palette() {
+        let colours = palette_elements(&["#000000", "#ffffff"]);
+        assert_eq!(
+            sample_palette(&colours, 3),
+            palette_elements(&["#000000", "#808080", "#ffffff"])
+        );
+        let viridis = palette_elements(lookup_palette("viridis").unwrap());
+        let sampled = sample_palette(&viridis, 4);
+        assert_eq!(sampled[0], viridis[0]);
+        assert_eq!(sampled[3], viridis[9]);
+        assert_eq!(sampled[1], viridis[3]);
+    }
+
+    #[test]
+    fn test_resolve_palette() {
+        let mut scale = Scale {
+            aesthetic: "fill".to_string(),
+            scale_type: Some(ScaleType::Viridis),
+            properties: HashMap::new(),
+        };
+        scale
+            .properties
+            .insert("direction".to_string(), ParameterValue::Number(-1.0));
+        resolve_palette(&mut scale).unwrap();
+        let Some(ParameterValue::Array(colours)) = scale.properties.get("palette") else {
+            panic!("palette was not resolved");
+        };
+        assert_eq!(colours[0], ArrayElement::String("#fde725".to_string()));
+        assert!(!scale.properties.contains_key("direction"));
+
+        scale.properties.insert(
+            "palette".to_string(),
+            ParameterValue::String("Blues".to_string()),
+        );
+        resolve_palette(&mut scale).unwrap();
+        assert_eq!(
+            scale.properties.get("palette"),
+            Some(&ParameterValue::Array(palette_elements(
+                lookup_palette("Blues").unwrap()
+            )))
+        );
+
+        scale.properties.insert(
+            "palette".to_string(),
+            ParameterValue::String("nope".to_string()),
+        );
+        assert!(resolve_palette(&mut scale).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+//! Secondary axes
+//!
+//! A secondary axis shows a position scale in a second unit, such as °F next
+//! to °C. It is given as a linear expression of `x`, e.g. `'x * 1.8 + 32'`.
+
+use super::super::types::ParameterValue;
+use super::types::Scale;
+use crate::{GgsqlError, Result};
+
+/// A secondary axis showing `slope * value + offset`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecondaryAxis {
+    pub slope: f64,
+    pub offset: f64,
+    /// Title of the secondary axis (`sec_name`)
+    pub name: Option<String>,
+}
+
+impl SecondaryAxis {
+    /// The secondary axis of a scale, if it has a `sec_axis` setting
+    pub fn from_scale(scale: &Scale) -> Result<Option<Self>> {
+        let expression = match scale.properties.get("sec_axis") {
+            None => return Ok(None),
+            Some(ParameterValue::String(expression)) => expression,
+            Some(_) => {
+                return Err(GgsqlError::ValidationError(format!(
+                    "SCALE {} sec_axis must be an expression such as 'x * 1.8 + 32'",
+                    scale.aesthetic
+                )))
+            }
+        };
+        if !matches!(scale.aesthetic.as_str(), "x" | "y") {
+            return Err(GgsqlError::ValidationError(format!(
+                "sec_axis is only supported on x and y scales, not {}",
+                scale.aesthetic
+            )));
+        }
+
+        let invalid = |reason: &str| {
+            GgsqlError::ValidationError(format!(
+                "Invalid sec_axis '{}' of SCALE {}: {}",
+                expression, scale.aesthetic, reason
+            ))
+        };
+        let mut parser = LinearParser {
+            tokens: tokenize(expression).map_err(|e| invalid(&e))?,
+            pos: 0,
+        };
+        let (slope, offset) = parser.expression().map_err(|e| invalid(&e))?;
+        if parser.pos != parser.tokens.len() {
+            return Err(invalid("unexpected trailing input"));
+        }
+        if slope == 0.0 || !slope.is_finite() || !offset.is_finite() {
+            return Err(invalid("the transform must depend on x"));
+        }
+
+        let name = match scale.properties.get("sec_name") {
+            None => None,
+            Some(ParameterValue::String(name)) => Some(name.clone()),
+            Some(_) => {
+                return Err(GgsqlError::ValidationError(format!(
+                    "SCALE {} sec_name must be a string",
+                    scale.aesthetic
+                )))
+            }
+        };
+        Ok(Some(SecondaryAxis {
+            slope,
+            offset,
+            name,
+        }))
+    }
+
+    /// Map a value of the primary axis onto the secondary axis
+    pub fn apply(&self, value: f64) -> f64 {
+        self.slope * value + self.offset
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Variable,
+    Operator(char),
+}
+
+fn tokenize(expression: &str) -> std::result::Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' => {
+                chars.next();
+            }
+            'x' | 'X' => {
+                chars.next();
+                tokens.push(Token::Variable);
+            }
+            '+' | '-' | '*' | '/' | '(' | ')' => {
+                chars.next();
+                tokens.push(Token::Operator(c));
+            }
+            '0'..='9' | '.' => {
+                let mut number = String::new();
+                while let Some(&d) = chars.peek() {
+                    if d.is_ascii_digit() || d == '.' {
+                        number.push(d);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let value = number
+                    .parse()
+                    .map_err(|_| format!("invalid number '{}'", number))?;
+                tokens.push(Token::Number(value));
+            }
+            _ => return Err(format!("unexpected character '{}'", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser evaluating an expression to `(slope, offset)`
+///
+/// Products and quotients of two terms that both depend on `x` are rejected,
+/// so every accepted expression is linear.
+struct LinearParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+type Linear = (f64, f64);
+
+impl LinearParser {
+    fn next_operator(&mut self, operators: &[char]) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Operator(op)) if operators.contains(op) => {
+                self.pos += 1;
+                Some(*op)
+            }
+            _ => None,
+        }
+    }
+
+    fn expression(&mut self) -> std::result::Result<Linear, String> {
+        let (mut slope, mut offset) = self.term()?;
+        while let Some(op) = self.next_operator(&['+', '-']) {
+            let (s, o) = self.term()?;
+            let sign = if op == '+' { 1.0 } else { -1.0 };
+            slope += sign * s;
+            offset += sign * o;
+        }
+        Ok((slope, offset))
+    }
+
+    fn term(&mut self) -> std::result::Result<Linear, String> {
+        let mut lhs = self.factor()?;
+        while let Some(op) = self.next_operator(&['*', '/']) {
+            let rhs = self.factor()?;
+            lhs = match op {
+                '*' if lhs.0 == 0.0 => (lhs.1 * rhs.0, lhs.1 * rhs.1),
+                '*' if rhs.0 == 0.0 => (lhs.0 * rhs.1, lhs.1 * rhs.1),
+                '/' if rhs.0 == 0.0 && rhs.1 != 0.0 => (lhs.0 / rhs.1, lhs.1 / rhs.1),
+                '/' if rhs.0 == 0.0 => return Err("division by zero".to_string()),
+                _ => return Err("the transform must be linear".to_string()),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn factor(&mut self) -> std::result::Result<Linear, String> {
+        if self.next_operator(&['-']).is_some() {
+            let (slope, offset) = self.factor()?;
+            return Ok((-slope, -offset));
+        }
+        if self.next_operator(&['+']).is_some() {
+            return self.factor();
+        }
+        if self.next_operator(&['(']).is_some() {
+            let inner = self.expression()?;
+            if self.next_operator(&[')']).is_none() {
+                return Err("missing closing parenthesis".to_string());
+            }
+            return Ok(inner);
+        }
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Number(n)) => Ok((0.0, n)),
+            Some(Token::Variable) => Ok((1.0, 0.0)),
+            _ => Err("expected a number, x or a parenthesis".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn scale_with(aesthetic: &str, sec_axis: &str) -> Scale {
+        let mut properties = HashMap::new();
+        properties.insert(
+            "sec_axis".to_string(),
+            ParameterValue::String(sec_axis.to_string()),
+        );
+        Scale {
+            aesthetic: aesthetic.to_string(),
+            scale_type: None,
+            properties,
+        }
+    }
+
+    #[test]
+    fn test_secondary_axis_expressions() {
+        let axis = SecondaryAxis::from_scale(&scale_with("y", "x * 1.8 + 32"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(axis.apply(100.0), 212.0);
+        assert_eq!(axis.name, None);
+
+        let axis = SecondaryAxis::from_scale(&scale_with("x", "(x - 32) / 1.8"))
+            .unwrap()
+            .unwrap();
+        assert!((axis.apply(212.0) - 100.0).abs() < 1e-9);
+
+        let axis = SecondaryAxis::from_scale(&scale_with("y", "-2 * (3 + x)"))
+            .unwrap()
+            .unwrap();
+        assert_eq!((axis.slope, axis.offset), (-2.0, -6.0));
+
+        for invalid in ["x * x", "1 / x", "5", "x +", "log(x)", "x / 0"] {
+            assert!(
+                SecondaryAxis::from_scale(&scale_with("y", invalid)).is_err(),
+                "{}",
+                invalid
+            );
+        }
+        assert!(SecondaryAxis::from_scale(&scale_with("color", "x")).is_err());
+    }
+}
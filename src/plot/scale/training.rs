@@ -0,0 +1,432 @@
+//! Scale training
+//!
+//! Before a plot is written, each scaled aesthetic is trained on the data of
+//! every layer: its kind (continuous, discrete, temporal or identity) is decided
+//! once for all layers and its domain is computed. Writers read the trained
+//! scales instead of guessing from individual columns.
+
+use std::collections::HashMap;
+
+use polars::prelude::{ChunkAgg, DataType, TimeUnit};
+use serde::{Deserialize, Serialize};
+
+use super::types::ScaleType;
+use crate::naming;
+use crate::plot::layer::geom::GeomAesthetics;
+use crate::plot::Plot;
+use crate::{DataFrame, GgsqlError, Result};
+
+/// Aesthetic families that are drawn through a scale
+const SCALED_AESTHETICS: &[&str] = &[
+    "x",
+    "y",
+    "color",
+    "colour",
+    "fill",
+    "stroke",
+    "opacity",
+    "size",
+    "shape",
+    "linetype",
+    "linewidth",
+];
+
+/// Kind of data a scale maps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScaleKind {
+    Continuous,
+    Discrete,
+    Temporal,
+    /// Data values are used as they are (`type => 'identity'`)
+    Identity,
+}
+
+impl ScaleKind {
+    fn describe(&self) -> &'static str {
+        match self {
+            ScaleKind::Continuous => "continuous",
+            ScaleKind::Discrete => "discrete",
+            ScaleKind::Temporal => "temporal",
+            ScaleKind::Identity => "identity",
+        }
+    }
+}
+
+/// Values spanned by a trained scale
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ScaleDomain {
+    /// Smallest and largest value; milliseconds since the epoch for temporal scales
+    Range(f64, f64),
+    /// Distinct values in sorted order
+    Values(Vec<String>),
+}
+
+/// A scale resolved from the data of all layers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrainedScale {
+    /// The aesthetic family the scale applies to
+    pub aesthetic: String,
+    pub kind: ScaleKind,
+    /// The domain, if any layer has data for the aesthetic
+    pub domain: Option<ScaleDomain>,
+}
+
+/// Train the scales of all aesthetics mapped to columns in any layer
+///
+/// `data` holds the layer data as prepared by `execute`: layers without their
+/// own entry use the global data. Axes a layer's stat redraws on positions of
+/// its own are trained from their primary aesthetic only. Returns an error when
+/// layers map data of different kinds to the same aesthetic, e.g. numbers in
+/// one layer and text in another.
+pub fn train_scales(spec: &Plot, data: &HashMap<String, DataFrame>) -> Result<Vec<TrainedScale>> {
+    let mut trained: Vec<(TrainedScale, usize)> = Vec::new();
+
+    for (idx, layer) in spec.layers.iter().enumerate() {
+        let Some(df) = data
+            .get(&naming::layer_key(idx))
+            .or_else(|| data.get(naming::GLOBAL_DATA_KEY))
+        else {
+            continue;
+        };
+
+        let mut aesthetics: Vec<&String> = layer.mappings.aesthetics.keys().collect();
+        aesthetics.sort();
+        for aesthetic in aesthetics {
+            let value = &layer.mappings.aesthetics[aesthetic];
+            let family = GeomAesthetics::primary_aesthetic(aesthetic);
+            if !SCALED_AESTHETICS.contains(&family) || value.is_dummy() {
+                continue;
+            }
+            if family != aesthetic.as_str() && layer.geom.stat_rewritten_axes().contains(&family) {
+                continue;
+            }
+            let Some(column) = value.column_name().and_then(|c| df.column(c).ok()) else {
+                continue;
+            };
+
+            let kind = match spec.find_scale(family).and_then(|s| s.scale_type.as_ref()) {
+                Some(scale_type) => explicit_kind(scale_type, column).map_err(|e| {
+                    GgsqlError::ValidationError(format!(
+                        "SCALE {} in layer {}: {}",
+                        family,
+                        idx + 1,
+                        e
+                    ))
+                })?,
+                None => column_kind(column)?,
+            };
+            let domain = column_domain(column, kind)?;
+
+            match trained.iter_mut().find(|(s, _)| s.aesthetic == family) {
+                Some((scale, first_layer)) => {
+                    if scale.kind != kind {
+                        return Err(GgsqlError::ValidationError(format!(
+                            "Aesthetic '{}' is mapped to {} data in layer {} but to {} data in layer {}",
+                            family,
+                            scale.kind.describe(),
+                            *first_layer + 1,
+                            kind.describe(),
+                            idx + 1
+                        )));
+                    }
+                    scale.domain = merge_domains(scale.domain.take(), domain);
+                }
+                None => trained.push((
+                    TrainedScale {
+                        aesthetic: family.to_string(),
+                        kind,
+                        domain,
+                    },
+                    idx,
+                )),
+            }
+        }
+    }
+
+    Ok(trained.into_iter().map(|(scale, _)| scale).collect())
+}
+
+/// Kind of scale with an explicit type, checked against the column it maps
+///
+/// Continuous, palette and binned types need numbers, temporal types need dates
+/// or text to parse them from. Any column can be drawn on a discrete scale.
+fn explicit_kind(
+    scale_type: &ScaleType,
+    column: &polars::prelude::Column,
+) -> std::result::Result<ScaleKind, String> {
+    let data = column_kind(column).map_err(|e| e.to_string())?;
+    let required = match scale_type {
+        ScaleType::Identity => return Ok(ScaleKind::Identity),
+        ScaleType::Ordinal | ScaleType::Categorical | ScaleType::Manual => {
+            return Ok(ScaleKind::Discrete)
+        }
+        ScaleType::Date | ScaleType::DateTime | ScaleType::Time => {
+            if data == ScaleKind::Temporal || column.dtype() == &DataType::String {
+                return Ok(ScaleKind::Temporal);
+            }
+            ScaleKind::Temporal
+        }
+        ScaleType::Linear
+        | ScaleType::Log10
+        | ScaleType::Log
+        | ScaleType::Log2
+        | ScaleType::Sqrt
+        | ScaleType::Reverse
+        | ScaleType::Viridis
+        | ScaleType::Plasma
+        | ScaleType::Magma
+        | ScaleType::Inferno
+        | ScaleType::Cividis
+        | ScaleType::Diverging
+        | ScaleType::Sequential
+        | ScaleType::Binned
+        | ScaleType::Quantile
+        | ScaleType::Threshold => {
+            if data == ScaleKind::Continuous {
+                return Ok(ScaleKind::Continuous);
+            }
+            ScaleKind::Continuous
+        }
+    };
+    Err(format!(
+        "type '{}' needs {} data, but column '{}' holds {} data",
+        scale_type.name(),
+        required.describe(),
+        column.name(),
+        data.describe()
+    ))
+}
+
+/// Kind of scale suited to the data of a column
+///
+/// Text columns holding only numbers are continuous. This is the only place
+/// deciding how a column is drawn when no scale type says otherwise.
+pub fn column_kind(column: &polars::prelude::Column) -> Result<ScaleKind> {
+    Ok(match column.dtype() {
+        dtype if dtype.is_numeric() => ScaleKind::Continuous,
+        DataType::Date | DataType::Datetime(_, _) | DataType::Time => ScaleKind::Temporal,
+        DataType::String => {
+            let values = column.str().map_err(|e| {
+                GgsqlError::InternalError(format!("Failed to read text column: {}", e))
+            })?;
+            let mut values = values.into_iter().flatten().peekable();
+            if values.peek().is_some() && values.all(|v| v.trim().parse::<f64>().is_ok()) {
+                ScaleKind::Continuous
+            } else {
+                ScaleKind::Discrete
+            }
+        }
+        _ => ScaleKind::Discrete,
+    })
+}
+
+/// Domain of a column for a scale of the given kind
+fn column_domain(column: &polars::prelude::Column, kind: ScaleKind) -> Result<Option<ScaleDomain>> {
+    let internal = |e: polars::prelude::PolarsError| {
+        GgsqlError::InternalError(format!("Failed to train scale: {}", e))
+    };
+    match kind {
+        ScaleKind::Continuous | ScaleKind::Temporal => {
+            let column = match column.dtype() {
+                DataType::Date | DataType::Datetime(_, _) => column
+                    .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+                    .and_then(|c| c.cast(&DataType::Int64))
+                    .map_err(internal)?,
+                DataType::Time => column.cast(&DataType::Int64).map_err(internal)?,
+                _ => column.clone(),
+            };
+            // Columns that can't be read as numbers have no range
+            let Ok(values) = column.cast(&DataType::Float64) else {
+                return Ok(None);
+            };
+            let values = values.f64().map_err(internal)?;
+            Ok(match (values.min(), values.max()) {
+                (Some(lo), Some(hi)) => Some(ScaleDomain::Range(lo, hi)),
+                _ => None,
+            })
+        }
+        ScaleKind::Discrete => {
+            let values = column
+                .cast(&DataType::String)
+                .and_then(|c| c.unique())
+                .map_err(internal)?;
+            let mut values: Vec<String> = values
+                .str()
+                .map_err(internal)?
+                .into_iter()
+                .flatten()
+                .map(|v| v.to_string())
+                .collect();
+            values.sort();
+            Ok(Some(ScaleDomain::Values(values)))
+        }
+        ScaleKind::Identity => Ok(None),
+    }
+}
+
+fn merge_domains(a: Option<ScaleDomain>, b: Option<ScaleDomain>) -> Option<ScaleDomain> {
+    match (a, b) {
+        (Some(ScaleDomain::Range(a_lo, a_hi)), Some(ScaleDomain::Range(b_lo, b_hi))) => {
+            Some(ScaleDomain::Range(a_lo.min(b_lo), a_hi.max(b_hi)))
+        }
+        (Some(ScaleDomain::Values(mut a)), Some(ScaleDomain::Values(b))) => {
+            for value in b {
+                if let Err(pos) = a.binary_search(&value) {
+                    a.insert(pos, value);
+                }
+            }
+            Some(ScaleDomain::Values(a))
+        }
+        (a, None) => a,
+        (None, b) => b,
+        (a, _) => a,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot::{AestheticValue, Layer, Scale};
+    use crate::Geom;
+    use polars::prelude::*;
+
+    fn point_layer(x: &str, y: &str) -> Layer {
+        Layer::new(Geom::point())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column(x.to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column(y.to_string()),
+            )
+    }
+
+    #[test]
+    fn test_train_scales_across_layers() {
+        let mut spec = Plot::new();
+        spec.layers.push(point_layer("a", "b"));
+        spec.layers.push(point_layer("a", "c"));
+
+        let mut data = HashMap::new();
+        data.insert(
+            naming::GLOBAL_DATA_KEY.to_string(),
+            df! {"a" => &[1.0, 4.0], "b" => &["p", "q"], "c" => &["r", "p"]}.unwrap(),
+        );
+        data.insert(
+            naming::layer_key(1),
+            df! {"a" => &[-2, 3], "c" => &["r", "p"]}.unwrap(),
+        );
+
+        let scales = train_scales(&spec, &data).unwrap();
+        let x = scales.iter().find(|s| s.aesthetic == "x").unwrap();
+        assert_eq!(x.kind, ScaleKind::Continuous);
+        assert_eq!(x.domain, Some(ScaleDomain::Range(-2.0, 4.0)));
+        let y = scales.iter().find(|s| s.aesthetic == "y").unwrap();
+        assert_eq!(y.kind, ScaleKind::Discrete);
+        assert_eq!(
+            y.domain,
+            Some(ScaleDomain::Values(vec![
+                "p".to_string(),
+                "q".to_string(),
+                "r".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_numeric_text_is_continuous() {
+        let mut spec = Plot::new();
+        spec.layers.push(point_layer("a", "b"));
+        let mut data = HashMap::new();
+        // Only the last value makes the column non-numeric
+        data.insert(
+            naming::GLOBAL_DATA_KEY.to_string(),
+            df! {
+                "a" => &["1", "2", "3", "4", "5", "6"],
+                "b" => &["1", "2", "3", "4", "5", "x"],
+            }
+            .unwrap(),
+        );
+        let scales = train_scales(&spec, &data).unwrap();
+        assert_eq!(scales[0].kind, ScaleKind::Continuous);
+        assert_eq!(scales[1].kind, ScaleKind::Discrete);
+    }
+
+    #[test]
+    fn test_stat_positions_skip_training() {
+        let mut spec = Plot::new();
+        spec.layers.push(
+            Layer::new(Geom::violin())
+                .with_aesthetic("x".to_string(), AestheticValue::standard_column("a"))
+                .with_aesthetic("xmin".to_string(), AestheticValue::standard_column("lo"))
+                .with_aesthetic("xmax".to_string(), AestheticValue::standard_column("hi")),
+        );
+        spec.layers.push(point_layer("a", "b"));
+        let mut data = HashMap::new();
+        data.insert(
+            naming::GLOBAL_DATA_KEY.to_string(),
+            df! {"a" => &["p", "q"], "b" => &[1.0, 2.0], "lo" => &[0.6, 1.6], "hi" => &[1.4, 2.4]}
+                .unwrap(),
+        );
+        let scales = train_scales(&spec, &data).unwrap();
+        assert_eq!(scales[0].aesthetic, "x");
+        assert_eq!(scales[0].kind, ScaleKind::Discrete);
+    }
+
+    #[test]
+    fn test_explicit_types_check_the_data() {
+        let mut data = HashMap::new();
+        data.insert(
+            naming::GLOBAL_DATA_KEY.to_string(),
+            df! {"a" => &[1.0, 2.0], "b" => &["p", "q"]}.unwrap(),
+        );
+        let mut spec = Plot::new();
+        spec.layers.push(point_layer("a", "b"));
+
+        let scale = |aesthetic: &str, scale_type: ScaleType| Scale {
+            aesthetic: aesthetic.to_string(),
+            scale_type: Some(scale_type),
+            properties: HashMap::new(),
+        };
+
+        // Numbers can be drawn on a discrete scale, text can't be drawn in colour ramps
+        for (aesthetic, scale_type, ok) in [
+            ("x", ScaleType::Categorical, true),
+            ("x", ScaleType::Viridis, true),
+            ("y", ScaleType::Viridis, false),
+            ("y", ScaleType::Binned, false),
+            ("y", ScaleType::Log10, false),
+            ("y", ScaleType::Date, true),
+        ] {
+            spec.scales = vec![scale(aesthetic, scale_type.clone())];
+            assert_eq!(train_scales(&spec, &data).is_ok(), ok, "{:?}", scale_type);
+        }
+
+        spec.scales = vec![scale("y", ScaleType::Viridis)];
+        let err = train_scales(&spec, &data).unwrap_err().to_string();
+        assert!(
+            err.contains(
+                "type 'viridis' needs continuous data, but column 'b' holds discrete data"
+            ),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_mixed_layers_are_rejected() {
+        let mut spec = Plot::new();
+        spec.layers.push(point_layer("a", "b"));
+        spec.layers.push(point_layer("b", "a"));
+        let mut data = HashMap::new();
+        data.insert(
+            naming::GLOBAL_DATA_KEY.to_string(),
+            df! {"a" => &[1, 2], "b" => &["p", "q"]}.unwrap(),
+        );
+        let err = train_scales(&spec, &data).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("mapped to continuous data in layer 1 but to discrete data in layer 2"));
+    }
+}
@@ -5,7 +5,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use super::super::types::ParameterValue;
+use super::super::types::{ArrayElement, ParameterValue};
+use crate::{GgsqlError, Result};
 
 /// Scale configuration (from SCALE clause)
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -18,6 +19,49 @@ pub struct Scale {
     pub properties: HashMap<String, ParameterValue>,
 }
 
+impl Scale {
+    /// Numeric limits of the scale, from its `limits` or `domain` setting
+    pub fn limits(&self) -> Option<(f64, f64)> {
+        ["limits", "domain"]
+            .iter()
+            .find_map(|key| match self.properties.get(*key)?.as_array()? {
+                [ArrayElement::Number(a), ArrayElement::Number(b)] => Some((a.min(*b), a.max(*b))),
+                _ => None,
+            })
+    }
+
+    /// How data outside the limits of the scale is treated (`oob` setting)
+    pub fn oob(&self) -> Result<OutOfBounds> {
+        match self.properties.get("oob") {
+            None => Ok(OutOfBounds::Keep),
+            Some(ParameterValue::String(oob)) => match oob.to_lowercase().as_str() {
+                "keep" => Ok(OutOfBounds::Keep),
+                "censor" => Ok(OutOfBounds::Censor),
+                "squish" => Ok(OutOfBounds::Squish),
+                _ => Err(GgsqlError::ValidationError(format!(
+                    "Unknown oob '{}' of SCALE {}. Use 'censor', 'squish' or 'keep'",
+                    oob, self.aesthetic
+                ))),
+            },
+            Some(_) => Err(GgsqlError::ValidationError(format!(
+                "SCALE {} oob must be 'censor', 'squish' or 'keep'",
+                self.aesthetic
+            ))),
+        }
+    }
+}
+
+/// Treatment of data outside the limits of a scale
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutOfBounds {
+    /// Data is drawn as it is, possibly beyond the axis
+    Keep,
+    /// Values outside the limits are removed
+    Censor,
+    /// Values outside the limits are moved onto the nearest limit
+    Squish,
+}
+
 /// Scale types
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ScaleType {
@@ -48,10 +92,155 @@ pub enum ScaleType {
     Diverging,
     Sequential,
 
+    // Binned scales
+    Binned,
+    Quantile,
+    Threshold,
+
     // Special
     Identity,
 }
 
+impl ScaleType {
+    /// Name of the type, as written in `SETTING type => ...`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScaleType::Linear => "linear",
+            ScaleType::Log10 => "log10",
+            ScaleType::Log => "log",
+            ScaleType::Log2 => "log2",
+            ScaleType::Sqrt => "sqrt",
+            ScaleType::Reverse => "reverse",
+            ScaleType::Ordinal => "ordinal",
+            ScaleType::Categorical => "categorical",
+            ScaleType::Manual => "manual",
+            ScaleType::Date => "date",
+            ScaleType::DateTime => "datetime",
+            ScaleType::Time => "time",
+            ScaleType::Viridis => "viridis",
+            ScaleType::Plasma => "plasma",
+            ScaleType::Magma => "magma",
+            ScaleType::Inferno => "inferno",
+            ScaleType::Cividis => "cividis",
+            ScaleType::Diverging => "diverging",
+            ScaleType::Sequential => "sequential",
+            ScaleType::Binned => "binned",
+            ScaleType::Quantile => "quantile",
+            ScaleType::Threshold => "threshold",
+            ScaleType::Identity => "identity",
+        }
+    }
+
+    /// Name of a continuous transform, as written in `SETTING type => ...`
+    pub fn transform_name(&self) -> Option<&'static str> {
+        match self {
+            ScaleType::Log10 => Some("log10"),
+            ScaleType::Log => Some("log"),
+            ScaleType::Log2 => Some("log2"),
+            ScaleType::Sqrt => Some("sqrt"),
+            _ => None,
+        }
+    }
+
+    /// SQL expression mapping `expr` from data space into the transformed space
+    ///
+    /// Returns `None` for scales that don't transform their data.
+    pub fn transform_sql(&self, expr: &str) -> Option<String> {
+        match self {
+            ScaleType::Log10 => Some(format!("LOG10({})", expr)),
+            ScaleType::Log => Some(format!("LN({})", expr)),
+            ScaleType::Log2 => Some(format!("LOG2({})", expr)),
+            ScaleType::Sqrt => Some(format!("SQRT({})", expr)),
+            _ => None,
+        }
+    }
+
+    /// SQL expression mapping `expr` from the transformed space back into data space
+    pub fn inverse_sql(&self, expr: &str) -> Option<String> {
+        match self {
+            ScaleType::Log10 => Some(format!("POW(10, {})", expr)),
+            ScaleType::Log => Some(format!("EXP({})", expr)),
+            ScaleType::Log2 => Some(format!("POW(2, {})", expr)),
+            ScaleType::Sqrt => Some(format!("POW({}, 2)", expr)),
+            _ => None,
+        }
+    }
+
+    /// Map `value` into the transformed space (unchanged for other scales)
+    pub fn transform(&self, value: f64) -> f64 {
+        match self {
+            ScaleType::Log10 => value.log10(),
+            ScaleType::Log => value.ln(),
+            ScaleType::Log2 => value.log2(),
+            ScaleType::Sqrt => value.sqrt(),
+            _ => value,
+        }
+    }
+
+    /// Map `value` back from the transformed space
+    pub fn inverse(&self, value: f64) -> f64 {
+        match self {
+            ScaleType::Log10 => 10f64.powf(value),
+            ScaleType::Log => value.exp(),
+            ScaleType::Log2 => 2f64.powf(value),
+            ScaleType::Sqrt => value * value,
+            _ => value,
+        }
+    }
+
+    /// Name of the built-in palette used by a palette scale type
+    pub fn default_palette(&self) -> Option<&'static str> {
+        match self {
+            ScaleType::Viridis => Some("viridis"),
+            ScaleType::Plasma => Some("plasma"),
+            ScaleType::Magma => Some("magma"),
+            ScaleType::Inferno => Some("inferno"),
+            ScaleType::Cividis => Some("cividis"),
+            ScaleType::Diverging => Some("RdBu"),
+            ScaleType::Sequential => Some("Blues"),
+            ScaleType::Binned | ScaleType::Quantile | ScaleType::Threshold => Some("viridis"),
+            _ => None,
+        }
+    }
+
+    /// Whether the scale maps continuous data into a few discrete classes
+    pub fn is_binned(&self) -> bool {
+        matches!(
+            self,
+            ScaleType::Binned | ScaleType::Quantile | ScaleType::Threshold
+        )
+    }
+
+    /// Whether `value` lies in the domain of the transform
+    pub fn accepts(&self, value: f64) -> bool {
+        match self {
+            ScaleType::Log10 | ScaleType::Log | ScaleType::Log2 => value > 0.0,
+            ScaleType::Sqrt => value >= 0.0,
+            _ => true,
+        }
+    }
+
+    /// SQL condition that holds when `expr` lies in the domain of the transform
+    ///
+    /// Returns `None` for scales that don't transform their data.
+    pub fn domain_sql(&self, expr: &str) -> Option<String> {
+        match self {
+            ScaleType::Log10 | ScaleType::Log | ScaleType::Log2 => Some(format!("{} > 0", expr)),
+            ScaleType::Sqrt => Some(format!("{} >= 0", expr)),
+            _ => None,
+        }
+    }
+
+    /// Description of the values in the domain of the transform, for error messages
+    pub fn domain_description(&self) -> &'static str {
+        match self {
+            ScaleType::Log10 | ScaleType::Log | ScaleType::Log2 => "positive",
+            ScaleType::Sqrt => "non-negative",
+            _ => "any",
+        }
+    }
+}
+
 /// Guide configuration (from GUIDE clause)
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Guide {
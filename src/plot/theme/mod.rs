@@ -0,0 +1,10 @@
+//! Theme types for ggsql visualization specifications
+//!
+//! This module defines the THEME clause, the built-in themes it refers to and
+//! the registry of themes loaded from theme files.
+
+mod registry;
+mod types;
+
+pub use registry::{ThemeRegistry, THEME_PATH_VAR};
+pub use types::{Theme, ThemeStyle, BUILTIN_THEMES};
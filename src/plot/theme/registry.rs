@@ -0,0 +1,289 @@
+//! Themes defined outside of queries
+//!
+//! A house style can be written once as a JSON theme file and used in any
+//! query with `THEME <name>`:
+//!
+//! ```json
+//! {
+//!   "name": "acme",
+//!   "inherits": "minimal",
+//!   "text_family": "Inter",
+//!   "panel_grid": false
+//! }
+//! ```
+//!
+//! All keys besides `name` and `inherits` are `THEME SETTING` properties. The
+//! name defaults to the file name, and `inherits` names a built-in or another
+//! registered theme (`grey` if omitted).
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde_json::Value;
+
+use super::types::{ThemeStyle, BUILTIN_THEMES};
+use crate::plot::ParameterValue;
+use crate::{GgsqlError, Result};
+
+/// Environment variable holding directories to load theme files from
+pub const THEME_PATH_VAR: &str = "GGSQL_THEME_PATH";
+
+/// A registered theme, resolved against its parent when used
+#[derive(Debug, Clone, PartialEq)]
+struct ThemeDefinition {
+    inherits: Option<String>,
+    properties: Vec<(String, ParameterValue)>,
+}
+
+/// Themes available to `THEME` besides the built-in ones
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ThemeRegistry {
+    themes: HashMap<String, ThemeDefinition>,
+}
+
+impl ThemeRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with the theme files found in the directories of `GGSQL_THEME_PATH`
+    pub fn from_env() -> Result<Self> {
+        let mut registry = Self::new();
+        if let Some(paths) = std::env::var_os(THEME_PATH_VAR) {
+            for dir in std::env::split_paths(&paths) {
+                if dir.is_dir() {
+                    registry.load_dir(&dir)?;
+                }
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Register every `.json` theme file in a directory
+    pub fn load_dir(&mut self, dir: &Path) -> Result<()> {
+        let entries = std::fs::read_dir(dir).map_err(|e| {
+            GgsqlError::ConfigError(format!(
+                "Failed to read theme directory '{}': {}",
+                dir.display(),
+                e
+            ))
+        })?;
+        let mut files: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+        for file in files {
+            self.load_file(&file)?;
+        }
+        Ok(())
+    }
+
+    /// Register the theme in a JSON theme file, returning its name
+    pub fn load_file(&mut self, path: &Path) -> Result<String> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            GgsqlError::ConfigError(format!(
+                "Failed to read theme file '{}': {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.load_json(&text, &stem).map_err(|e| match e {
+            GgsqlError::ValidationError(msg) => GgsqlError::ValidationError(format!(
+                "Invalid theme file '{}': {}",
+                path.display(),
+                msg
+            )),
+            e => e,
+        })
+    }
+
+    /// Register a theme from the JSON text of a theme file
+    ///
+    /// `default_name` is used when the theme has no `name` key.
+    pub fn load_json(&mut self, text: &str, default_name: &str) -> Result<String> {
+        let json: Value = serde_json::from_str(text)
+            .map_err(|e| GgsqlError::ValidationError(format!("Invalid JSON: {}", e)))?;
+        let Value::Object(entries) = json else {
+            return Err(GgsqlError::ValidationError(
+                "A theme must be a JSON object".to_string(),
+            ));
+        };
+
+        let mut name = default_name.to_string();
+        let mut inherits = None;
+        let mut properties = Vec::new();
+        for (key, value) in entries {
+            match (key.as_str(), value) {
+                ("name", Value::String(s)) => name = s,
+                ("inherits", Value::String(s)) => inherits = Some(s),
+                ("name" | "inherits", _) => {
+                    return Err(GgsqlError::ValidationError(format!(
+                        "'{}' must be a string",
+                        key
+                    )))
+                }
+                (_, Value::String(s)) => properties.push((key, ParameterValue::String(s))),
+                (_, Value::Number(n)) => {
+                    properties.push((key, ParameterValue::Number(n.as_f64().unwrap_or_default())))
+                }
+                (_, Value::Bool(b)) => properties.push((key, ParameterValue::Boolean(b))),
+                _ => {
+                    return Err(GgsqlError::ValidationError(format!(
+                        "Theme property '{}' must be a string, number or boolean",
+                        key
+                    )))
+                }
+            }
+        }
+        self.register(&name, inherits.as_deref(), properties)?;
+        Ok(name)
+    }
+
+    /// Register a theme overriding `properties` of the theme it inherits from
+    ///
+    /// Registering a name again replaces the earlier theme. Built-in themes
+    /// can't be replaced.
+    pub fn register(
+        &mut self,
+        name: &str,
+        inherits: Option<&str>,
+        properties: Vec<(String, ParameterValue)>,
+    ) -> Result<()> {
+        let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(GgsqlError::ValidationError(format!(
+                "Invalid theme name '{}'. Theme names may only contain letters, digits and underscores",
+                name
+            )));
+        }
+        if ThemeStyle::builtin(name).is_some() {
+            return Err(GgsqlError::ValidationError(format!(
+                "Theme '{}' is a built-in theme and can't be redefined",
+                name
+            )));
+        }
+        // Check the properties up front rather than when the theme is used
+        let mut style = ThemeStyle::grey();
+        for (property, value) in &properties {
+            style.set(property, value)?;
+        }
+        self.themes.insert(
+            name.to_string(),
+            ThemeDefinition {
+                inherits: inherits.map(|s| s.to_string()),
+                properties,
+            },
+        );
+        Ok(())
+    }
+
+    /// Names of the registered themes, in sorted order
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.themes.keys().map(|s| s.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    /// The style of a registered or built-in theme
+    ///
+    /// Unknown names are a configuration error.
+    pub fn resolve(&self, name: &str) -> Result<ThemeStyle> {
+        self.resolve_chain(name, &mut Vec::new())
+    }
+
+    fn resolve_chain<'a>(&'a self, name: &'a str, chain: &mut Vec<&'a str>) -> Result<ThemeStyle> {
+        if let Some(style) = ThemeStyle::builtin(name) {
+            return Ok(style);
+        }
+        let Some(definition) = self.themes.get(name) else {
+            let mut available: Vec<&str> = BUILTIN_THEMES.to_vec();
+            available.extend(self.names());
+            return Err(GgsqlError::ConfigError(format!(
+                "Unknown theme '{}'. Available themes: {}",
+                name,
+                available.join(", ")
+            )));
+        };
+        if chain.contains(&name) {
+            chain.push(name);
+            return Err(GgsqlError::ValidationError(format!(
+                "Themes inherit from each other in a cycle: {}",
+                chain.join(" -> ")
+            )));
+        }
+        chain.push(name);
+
+        let mut style = match &definition.inherits {
+            Some(parent) => self.resolve_chain(parent, chain)?,
+            None => ThemeStyle::grey(),
+        };
+        for (property, value) in &definition.properties {
+            style.set(property, value)?;
+        }
+        Ok(style)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_inheritance() {
+        let mut registry = ThemeRegistry::new();
+        let name = registry
+            .load_json(
+                r#"{"inherits": "minimal", "text_family": "Inter", "panel_grid": false}"#,
+                "acme",
+            )
+            .unwrap();
+        assert_eq!(name, "acme");
+        registry
+            .load_json(
+                r#"{"name": "acme_dark", "inherits": "acme", "background": "black"}"#,
+                "ignored",
+            )
+            .unwrap();
+
+        let style = registry.resolve("acme_dark").unwrap();
+        assert_eq!(style.background, "black");
+        assert_eq!(style.text_family, "Inter");
+        assert_eq!(style.panel_grid, None);
+        // Inherited from minimal
+        assert_eq!(style.panel_border, None);
+        assert_eq!(registry.names(), vec!["acme", "acme_dark"]);
+
+        // Unknown names are not taken for an empty theme
+        let err = registry.resolve("nope").unwrap_err();
+        assert!(matches!(err, GgsqlError::ConfigError(_)));
+        assert!(err.to_string().contains("acme, acme_dark"), "{}", err);
+    }
+
+    #[test]
+    fn test_invalid_themes() {
+        let mut registry = ThemeRegistry::new();
+        assert!(registry.load_json(r#"{"text_size": -2}"#, "a").is_err());
+        assert!(registry.load_json(r#"{"unknown": 1}"#, "a").is_err());
+        assert!(registry.load_json(r#"{"panel_grid": [1]}"#, "a").is_err());
+        assert!(registry.load_json("[]", "a").is_err());
+        assert!(registry.load_json("{}", "minimal").is_err());
+        assert!(registry.load_json("{}", "my-theme").is_err());
+
+        registry.register("a", Some("b"), vec![]).unwrap();
+        registry.register("b", Some("a"), vec![]).unwrap();
+        let err = registry.resolve("a").unwrap_err().to_string();
+        assert!(err.contains("a -> b -> a"), "{}", err);
+
+        let err = registry
+            .load_file(Path::new("/nonexistent/acme.json"))
+            .unwrap_err();
+        assert!(matches!(err, GgsqlError::ConfigError(_)));
+    }
+}
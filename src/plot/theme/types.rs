@@ -0,0 +1,349 @@
+//! Theme types for ggsql visualization specifications
+//!
+//! A theme controls the parts of a plot that don't show data: backgrounds,
+//! grid lines, axes, text and legends. `THEME` names one of the built-in themes
+//! and can override individual properties of it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::super::types::ParameterValue;
+use super::registry::ThemeRegistry;
+use crate::{GgsqlError, Result};
+
+/// Names of the built-in themes
+pub const BUILTIN_THEMES: &[&str] = &[
+    "grey", "gray", "bw", "light", "dark", "minimal", "classic", "void",
+];
+
+/// Theme styling (from THEME clause)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Theme {
+    /// Base theme style
+    pub style: Option<String>,
+    /// Theme property overrides
+    pub properties: HashMap<String, ParameterValue>,
+}
+
+impl Theme {
+    /// The concrete style of the theme, with its property overrides applied
+    ///
+    /// The name is looked up in `themes` and the built-in themes. Without a
+    /// name the overrides apply to the `grey` theme.
+    pub fn resolve(&self, themes: &ThemeRegistry) -> Result<ThemeStyle> {
+        let mut style = match &self.style {
+            Some(name) => themes.resolve(name)?,
+            None => ThemeStyle::grey(),
+        };
+        let mut names: Vec<&String> = self.properties.keys().collect();
+        names.sort();
+        for name in names {
+            style.set(name, &self.properties[name])?;
+        }
+        Ok(style)
+    }
+}
+
+/// The concrete styling of a plot
+///
+/// Colours are CSS colours and `None` means the element is not drawn. Sizes
+/// are in pixels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThemeStyle {
+    /// Background of the whole plot
+    pub background: String,
+    /// Background of the plotting area
+    pub panel_background: Option<String>,
+    /// Border around the plotting area
+    pub panel_border: Option<String>,
+    /// Major grid lines
+    pub panel_grid: Option<String>,
+    /// Axis lines
+    pub axis_line: Option<String>,
+    pub axis_line_width: f64,
+    /// Axis tick marks
+    pub axis_ticks: Option<String>,
+    /// Whether axes are drawn at all
+    pub axes: bool,
+    /// Colour of all text
+    pub text_colour: String,
+    pub text_family: String,
+    /// Size of axis and legend titles
+    pub text_size: f64,
+    /// Size of the plot title
+    pub title_size: f64,
+    /// Size of axis and legend labels
+    pub axis_text_size: f64,
+    pub legend_background: Option<String>,
+    /// `right`, `left`, `top`, `bottom` or `none`
+    pub legend_position: String,
+    /// `vertical` or `horizontal`, or `None` to follow the position
+    pub legend_direction: Option<String>,
+    /// Space around the plot
+    pub plot_margin: f64,
+    /// Space between facet panels
+    pub panel_spacing: f64,
+}
+
+impl ThemeStyle {
+    /// A built-in theme by name
+    pub fn builtin(name: &str) -> Option<Self> {
+        Some(match name.to_lowercase().as_str() {
+            "grey" | "gray" => Self::grey(),
+            "bw" => Self::bw(),
+            "light" => Self {
+                panel_grid: Some("#dedede".to_string()),
+                panel_border: Some("#b3b3b3".to_string()),
+                axis_ticks: Some("#b3b3b3".to_string()),
+                ..Self::bw()
+            },
+            "dark" => Self {
+                panel_background: Some("#7f7f7f".to_string()),
+                panel_grid: Some("#6b6b6b".to_string()),
+                ..Self::grey()
+            },
+            "minimal" => Self {
+                panel_background: None,
+                panel_border: None,
+                axis_ticks: None,
+                ..Self::bw()
+            },
+            "classic" => Self {
+                panel_grid: None,
+                panel_border: None,
+                axis_line: Some("black".to_string()),
+                axis_ticks: Some("black".to_string()),
+                ..Self::bw()
+            },
+            "void" => Self {
+                panel_background: None,
+                panel_border: None,
+                panel_grid: None,
+                axis_ticks: None,
+                axes: false,
+                ..Self::bw()
+            },
+            _ => return None,
+        })
+    }
+
+    /// The default theme: a grey panel with white grid lines
+    pub fn grey() -> Self {
+        Self {
+            background: "white".to_string(),
+            panel_background: Some("#ebebeb".to_string()),
+            panel_border: None,
+            panel_grid: Some("white".to_string()),
+            axis_line: None,
+            axis_line_width: 1.0,
+            axis_ticks: Some("#333333".to_string()),
+            axes: true,
+            text_colour: "#333333".to_string(),
+            text_family: "sans-serif".to_string(),
+            text_size: 11.0,
+            title_size: 13.0,
+            axis_text_size: 9.0,
+            legend_background: None,
+            legend_position: "right".to_string(),
+            legend_direction: None,
+            plot_margin: 5.0,
+            panel_spacing: 10.0,
+        }
+    }
+
+    /// A white panel with grey grid lines and a dark border
+    fn bw() -> Self {
+        Self {
+            panel_background: Some("white".to_string()),
+            panel_border: Some("#333333".to_string()),
+            panel_grid: Some("#ebebeb".to_string()),
+            ..Self::grey()
+        }
+    }
+
+    /// Override a single property, as given in `THEME SETTING`
+    pub fn set(&mut self, name: &str, value: &ParameterValue) -> Result<()> {
+        match name {
+            "background" => self.background = string_value(name, value)?,
+            "panel_background" => {
+                self.panel_background = element(name, value, &self.panel_background, "white")?
+            }
+            "panel_border" => {
+                self.panel_border = element(name, value, &self.panel_border, "#333333")?
+            }
+            "panel_grid" | "panel_grid_major" => {
+                self.panel_grid = element(name, value, &self.panel_grid, "#ebebeb")?
+            }
+            "panel_grid_minor" => {
+                if element(name, value, &None, "#ebebeb")?.is_some() {
+                    return Err(GgsqlError::ValidationError(
+                        "THEME panel_grid_minor can only be turned off, minor grid lines are not drawn"
+                            .to_string(),
+                    ));
+                }
+            }
+            "axis_line" => self.axis_line = element(name, value, &self.axis_line, "black")?,
+            "axis_line_width" => self.axis_line_width = size(name, value)?,
+            "text_family" => self.text_family = string_value(name, value)?,
+            "text_size" => self.text_size = size(name, value)?,
+            "title_size" => self.title_size = size(name, value)?,
+            "axis_text_size" => self.axis_text_size = size(name, value)?,
+            "plot_margin" => self.plot_margin = size(name, value)?,
+            "panel_spacing" => self.panel_spacing = size(name, value)?,
+            "legend_background" => {
+                self.legend_background = element(name, value, &self.legend_background, "white")?
+            }
+            "legend_position" => {
+                self.legend_position =
+                    one_of(name, value, &["right", "left", "top", "bottom", "none"])?
+            }
+            "legend_direction" => {
+                self.legend_direction = Some(one_of(name, value, &["vertical", "horizontal"])?)
+            }
+            _ => {
+                return Err(GgsqlError::ValidationError(format!(
+                    "Unknown THEME property '{}'",
+                    name
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A colour for an element that can be turned off
+///
+/// `false` and `'none'` turn the element off; `true` turns it on in its
+/// current colour, or `default` if it was off.
+fn element(
+    name: &str,
+    value: &ParameterValue,
+    current: &Option<String>,
+    default: &str,
+) -> Result<Option<String>> {
+    match value {
+        ParameterValue::Boolean(false) => Ok(None),
+        ParameterValue::Boolean(true) => {
+            Ok(Some(current.clone().unwrap_or_else(|| default.to_string())))
+        }
+        ParameterValue::String(s) if matches!(s.as_str(), "none" | "blank") => Ok(None),
+        ParameterValue::String(s) => Ok(Some(s.clone())),
+        _ => Err(GgsqlError::ValidationError(format!(
+            "THEME {} must be a colour, 'none' or a boolean",
+            name
+        ))),
+    }
+}
+
+fn string_value(name: &str, value: &ParameterValue) -> Result<String> {
+    match value {
+        ParameterValue::String(s) => Ok(s.clone()),
+        _ => Err(GgsqlError::ValidationError(format!(
+            "THEME {} must be a string",
+            name
+        ))),
+    }
+}
+
+fn size(name: &str, value: &ParameterValue) -> Result<f64> {
+    match value {
+        ParameterValue::Number(n) if *n >= 0.0 => Ok(*n),
+        _ => Err(GgsqlError::ValidationError(format!(
+            "THEME {} must be a non-negative number",
+            name
+        ))),
+    }
+}
+
+fn one_of(name: &str, value: &ParameterValue, options: &[&str]) -> Result<String> {
+    match value {
+        ParameterValue::String(s) if options.contains(&s.as_str()) => Ok(s.clone()),
+        _ => Err(GgsqlError::ValidationError(format!(
+            "THEME {} must be one of: {}",
+            name,
+            options.join(", ")
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn theme(style: Option<&str>, properties: &[(&str, ParameterValue)]) -> Theme {
+        Theme {
+            style: style.map(|s| s.to_string()),
+            properties: properties
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_theme() {
+        for name in BUILTIN_THEMES {
+            assert!(theme(Some(name), &[])
+                .resolve(&ThemeRegistry::new())
+                .is_ok());
+        }
+
+        let style = theme(Some("classic"), &[])
+            .resolve(&ThemeRegistry::new())
+            .unwrap();
+        assert_eq!(style.panel_grid, None);
+        assert_eq!(style.axis_line.as_deref(), Some("black"));
+
+        let style = theme(
+            Some("minimal"),
+            &[
+                ("panel_grid", ParameterValue::Boolean(false)),
+                ("axis_line", ParameterValue::Boolean(true)),
+                ("text_family", ParameterValue::String("serif".to_string())),
+                (
+                    "legend_position",
+                    ParameterValue::String("bottom".to_string()),
+                ),
+            ],
+        )
+        .resolve(&ThemeRegistry::new())
+        .unwrap();
+        assert_eq!(style.panel_grid, None);
+        assert_eq!(style.axis_line.as_deref(), Some("black"));
+        assert_eq!(style.text_family, "serif");
+        assert_eq!(style.legend_position, "bottom");
+
+        // Properties without a theme name apply to the default theme
+        let style = theme(
+            None,
+            &[("background", ParameterValue::String("#fafafa".into()))],
+        )
+        .resolve(&ThemeRegistry::new())
+        .unwrap();
+        assert_eq!(style.panel_background.as_deref(), Some("#ebebeb"));
+        assert_eq!(style.background, "#fafafa");
+
+        assert!(theme(Some("fancy"), &[])
+            .resolve(&ThemeRegistry::new())
+            .is_err());
+        for (name, value) in [
+            (
+                "legend_position",
+                ParameterValue::String("middle".to_string()),
+            ),
+            ("text_size", ParameterValue::Number(-1.0)),
+            (
+                "panel_grid_minor",
+                ParameterValue::String("grey".to_string()),
+            ),
+        ] {
+            assert!(
+                theme(None, &[(name, value)])
+                    .resolve(&ThemeRegistry::new())
+                    .is_err(),
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -205,6 +205,8 @@ pub enum ParameterValue {
     Number(f64),
     Boolean(bool),
     Array(Vec<ArrayElement>),
+    /// Key-value pairs such as `{'Adelie': 'red', 'Gentoo': 'blue'}`, in the order written
+    Map(Vec<(ArrayElement, ArrayElement)>),
 }
 
 /// Elements in arrays (shared type for property values)
@@ -216,6 +218,14 @@ pub enum ArrayElement {
 }
 
 impl ArrayElement {
+    /// Try to extract as a number value
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            ArrayElement::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
     /// Convert to a serde_json::Value
     pub fn to_json(&self) -> serde_json::Value {
         match self {
@@ -224,6 +234,15 @@ impl ArrayElement {
             ArrayElement::Boolean(b) => serde_json::Value::Bool(*b),
         }
     }
+
+    /// Text of the element, as used for the keys of JSON objects
+    pub fn to_key_string(&self) -> String {
+        match self {
+            ArrayElement::String(s) => s.clone(),
+            ArrayElement::Number(n) => n.to_string(),
+            ArrayElement::Boolean(b) => b.to_string(),
+        }
+    }
 }
 
 impl ParameterValue {
@@ -236,6 +255,12 @@ impl ParameterValue {
             ParameterValue::Array(arr) => {
                 serde_json::Value::Array(arr.iter().map(|e| e.to_json()).collect())
             }
+            ParameterValue::Map(entries) => serde_json::Value::Object(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.to_key_string(), value.to_json()))
+                    .collect(),
+            ),
         }
     }
 
@@ -270,6 +295,14 @@ impl ParameterValue {
             _ => None,
         }
     }
+
+    /// Try to extract as key-value pairs
+    pub fn as_map(&self) -> Option<&[(ArrayElement, ArrayElement)]> {
+        match self {
+            ParameterValue::Map(entries) => Some(entries),
+            _ => None,
+        }
+    }
 }
 
 // =============================================================================
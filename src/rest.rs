@@ -31,6 +31,7 @@ use tower_http::cors::{Any, CorsLayer};
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+use ggsql::plot::ThemeRegistry;
 use ggsql::{parser, GgsqlError, VERSION};
 
 #[cfg(feature = "duckdb")]
@@ -68,6 +69,11 @@ struct Cli {
     /// Example: --load-data data.csv --load-data other.parquet
     #[arg(long = "load-data")]
     load_data_files: Vec<String>,
+
+    /// Load JSON theme file(s), in addition to those on GGSQL_THEME_PATH
+    /// Example: --theme-file acme.json
+    #[arg(long = "theme-file")]
+    theme_files: Vec<String>,
 }
 
 /// Shared application state
@@ -77,6 +83,8 @@ struct AppState {
     /// Wrapped in Arc<Mutex> since DuckDB Connection is not Sync
     #[cfg(feature = "duckdb")]
     reader: Option<std::sync::Arc<std::sync::Mutex<DuckDBReader>>>,
+    /// Themes available to THEME besides the built-in ones
+    themes: ThemeRegistry,
 }
 
 // ============================================================================
@@ -195,6 +203,7 @@ impl From<GgsqlError> for ApiErrorResponse {
             GgsqlError::ValidationError(_) => (StatusCode::BAD_REQUEST, "ValidationError"),
             GgsqlError::ReaderError(_) => (StatusCode::BAD_REQUEST, "ReaderError"),
             GgsqlError::WriterError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "WriterError"),
+            GgsqlError::ConfigError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "ConfigError"),
             GgsqlError::InternalError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "InternalError"),
         };
 
@@ -485,7 +494,7 @@ async fn query_handler(
         // Generate visualization output using writer
         #[cfg(feature = "vegalite")]
         if request.writer == "vegalite" {
-            let writer = VegaLiteWriter::new();
+            let writer = VegaLiteWriter::new().with_themes(state.themes.clone());
             let json_output = writer.write(first_spec, &prepared.data)?;
             let spec_value: serde_json::Value = serde_json::from_str(&json_output)
                 .map_err(|e| GgsqlError::WriterError(format!("Failed to parse JSON: {}", e)))?;
@@ -634,10 +643,18 @@ async fn main() -> anyhow::Result<()> {
     #[cfg(not(feature = "duckdb"))]
     let reader = None;
 
+    // Load themes from GGSQL_THEME_PATH and --theme-file
+    let mut themes = ThemeRegistry::from_env()?;
+    for file in &cli.theme_files {
+        let name = themes.load_file(std::path::Path::new(file))?;
+        info!("Loaded theme '{}' from {}", name, file);
+    }
+
     // Create application state
     let state = AppState {
         #[cfg(feature = "duckdb")]
         reader,
+        themes,
     };
 
     // Configure CORS
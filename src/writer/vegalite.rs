@@ -21,8 +21,13 @@
 //! ```
 
 use crate::naming;
+use crate::plot::layer::geom::temporal::{TimeInterval, TimeUnit as IntervalUnit};
 use crate::plot::layer::geom::{GeomAesthetics, GeomType};
-use crate::plot::{ArrayElement, Coord, CoordType, LiteralValue, ParameterValue};
+use crate::plot::{
+    column_kind, sample_palette, ArrayElement, Coord, CoordTransform, CoordType, Facet, FacetLabel,
+    Layer, LiteralValue, ParameterValue, Position, Scale, ScaleDomain, ScaleKind, ScaleType,
+    SecondaryAxis, ThemeRegistry, ThemeStyle,
+};
 use crate::writer::Writer;
 use crate::{AestheticValue, DataFrame, Geom, GgsqlError, Plot, Result};
 use polars::prelude::*;
@@ -35,6 +40,8 @@ use std::collections::HashMap;
 pub struct VegaLiteWriter {
     /// Vega-Lite schema version
     schema: String,
+    /// Themes available to THEME besides the built-in ones
+    themes: ThemeRegistry,
 }
 
 impl VegaLiteWriter {
@@ -42,11 +49,25 @@ impl VegaLiteWriter {
     pub fn new() -> Self {
         Self {
             schema: "https://vega.github.io/schema/vega-lite/v6.json".to_string(),
+            themes: ThemeRegistry::new(),
         }
     }
 
+    /// Use the themes of `themes` in addition to the built-in ones
+    pub fn with_themes(mut self, themes: ThemeRegistry) -> Self {
+        self.themes = themes;
+        self
+    }
+
     /// Convert Polars DataFrame to Vega-Lite data values (array of objects)
-    fn dataframe_to_values(&self, df: &DataFrame) -> Result<Vec<Value>> {
+    ///
+    /// Timestamps of the columns in `timezones` are written as wall-clock times
+    /// in their display time zone; see `series_value_at`.
+    fn dataframe_to_values(
+        &self,
+        df: &DataFrame,
+        timezones: &HashMap<String, chrono_tz::Tz>,
+    ) -> Result<Vec<Value>> {
         let mut values = Vec::new();
         let height = df.height();
         let column_names = df.get_column_names();
@@ -60,7 +81,9 @@ impl VegaLiteWriter {
                 })?;
 
                 // Get value from series and convert to JSON Value
-                let value = self.series_value_at(column.as_materialized_series(), row_idx)?;
+                let timezone = timezones.get(col_name.as_str()).copied();
+                let value =
+                    self.series_value_at(column.as_materialized_series(), row_idx, timezone)?;
                 row_obj.insert(col_name.to_string(), value);
             }
 
@@ -71,7 +94,17 @@ impl VegaLiteWriter {
     }
 
     /// Get a single value from a series at a given index as JSON Value
-    fn series_value_at(&self, series: &Series, idx: usize) -> Result<Value> {
+    ///
+    /// Timestamps with a time zone keep their UTC offset. With a display
+    /// `timezone`, timestamps are instead converted to that zone and written
+    /// without an offset, so Vega-Lite shows their wall-clock time there; naive
+    /// timestamps are taken to be UTC in that case.
+    fn series_value_at(
+        &self,
+        series: &Series,
+        idx: usize,
+        timezone: Option<chrono_tz::Tz>,
+    ) -> Result<Value> {
         use DataType::*;
 
         match series.dtype() {
@@ -145,7 +178,7 @@ impl VegaLiteWriter {
                     Ok(Value::Null)
                 }
             }
-            Datetime(time_unit, _) => {
+            Datetime(time_unit, column_tz) => {
                 // Convert timestamp to ISO datetime: "YYYY-MM-DDTHH:MM:SS.sssZ"
                 let ca = series.datetime().map_err(|e| {
                     GgsqlError::WriterError(format!("Failed to cast to datetime: {}", e))
@@ -163,7 +196,21 @@ impl VegaLiteWriter {
                         .unwrap_or_else(|| {
                             chrono::DateTime::<chrono::Utc>::from_timestamp(0, 0).unwrap()
                         });
-                    Ok(json!(dt.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()))
+                    let column_tz = column_tz
+                        .as_ref()
+                        .and_then(|tz| tz.parse::<chrono_tz::Tz>().ok());
+                    let formatted = match (timezone, column_tz) {
+                        (Some(display), _) => dt
+                            .with_timezone(&display)
+                            .format("%Y-%m-%dT%H:%M:%S%.3f")
+                            .to_string(),
+                        (None, Some(tz)) => dt
+                            .with_timezone(&tz)
+                            .format("%Y-%m-%dT%H:%M:%S%.3f%:z")
+                            .to_string(),
+                        (None, None) => dt.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+                    };
+                    Ok(json!(formatted))
                 } else {
                     Ok(Value::Null)
                 }
@@ -208,52 +255,130 @@ impl VegaLiteWriter {
             GeomType::Ribbon => "area",
             GeomType::Histogram => "bar",
             GeomType::Density => "area",
+            GeomType::Smooth => "line",
             GeomType::Boxplot => "boxplot",
+            GeomType::Violin => "area",
+            GeomType::ErrorBar => "errorbar",
             GeomType::Text => "text",
             GeomType::Label => "text",
-            _ => "point", // Default fallback
+            GeomType::Bin2d => "rect",
+            GeomType::Hex => "line",
+            GeomType::Density2d => "line",
+            GeomType::Contour => "line",
+            GeomType::Ecdf => "line",
+            GeomType::Qq => "point",
+            GeomType::QqLine => "line",
+            GeomType::Polygon => "line",
+            GeomType::Segment => "rule",
+            GeomType::Arrow => "rule",
+            GeomType::HLine => "rule",
+            GeomType::VLine => "rule",
+            GeomType::AbLine => "rule",
+            GeomType::Step => "line",
+            GeomType::Rect => "rect",
+            GeomType::Rug => "rule",
+            GeomType::LineRange => "rule",
+            GeomType::PointRange => "point",
+            GeomType::CrossBar => "bar",
         }
         .to_string()
     }
 
-    /// Check if a string column contains numeric values
-    fn is_numeric_string_column(&self, series: &Series) -> bool {
-        if let Ok(ca) = series.str() {
-            // Check first few non-null values to see if they're numeric
-            for val in ca.into_iter().flatten().take(5) {
-                if val.parse::<f64>().is_err() {
-                    return false;
-                }
+    /// Draw one line per value of the `piece` stat column, through its vertices in order
+    ///
+    /// Used for the outlines produced by stats, e.g. hexagons and contours.
+    fn apply_outline_encoding(
+        &self,
+        layer: &Layer,
+        piece: &str,
+        encoding: &mut Map<String, Value>,
+    ) {
+        let mut detail_fields = vec![naming::stat_column(piece)];
+        detail_fields.extend(layer.partition_by.iter().cloned());
+        if let Some(detail) = self.build_detail_encoding(&detail_fields) {
+            encoding.insert("detail".to_string(), detail);
+        }
+        encoding.insert(
+            "order".to_string(),
+            json!({
+                "field": naming::stat_column("vertex"),
+                "type": "quantitative"
+            }),
+        );
+    }
+
+    /// Translate a layer's position adjustment into encodings
+    ///
+    /// Stacks are computed by the execution pipeline as `y` and `y2`, so
+    /// Vega-Lite's implicit stacking of bars and areas is turned off unless the
+    /// layer is an ungrouped stack that the pipeline left alone. Rows dodged or
+    /// jittered on a discrete axis are moved by `xOffset` / `yOffset`.
+    fn apply_position_encoding(
+        &self,
+        layer: &Layer,
+        df: &DataFrame,
+        encoding: &mut Map<String, Value>,
+    ) {
+        let stackable = matches!(
+            layer.geom.geom_type(),
+            GeomType::Bar | GeomType::Histogram | GeomType::Area
+        );
+        let implicit_stack =
+            matches!(layer.position, Position::Stack { .. }) && !encoding.contains_key("y2");
+        if stackable && !implicit_stack {
+            if let Some(y) = encoding.get_mut("y").and_then(|y| y.as_object_mut()) {
+                y.insert("stack".to_string(), Value::Null);
             }
-            true
-        } else {
-            false
-        }
-    }
-
-    /// Infer Vega-Lite field type from DataFrame column
-    fn infer_field_type(&self, df: &DataFrame, field: &str) -> String {
-        if let Ok(column) = df.column(field) {
-            use DataType::*;
-            match column.dtype() {
-                Int8 | Int16 | Int32 | Int64 | UInt8 | UInt16 | UInt32 | UInt64 | Float32
-                | Float64 => "quantitative",
-                Boolean => "nominal",
-                String => {
-                    // Check if string column contains numeric values
-                    if self.is_numeric_string_column(column.as_materialized_series()) {
-                        "quantitative"
-                    } else {
-                        "nominal"
-                    }
-                }
-                Date | Datetime(_, _) | Time => "temporal",
-                _ => "nominal",
+        }
+
+        if let Position::Dodge { padding, .. } = layer.position {
+            let dodge = naming::position_column("dodge");
+            if df.column(&dodge).is_ok() {
+                encoding.insert(
+                    "xOffset".to_string(),
+                    json!({
+                        "field": dodge,
+                        "type": "nominal",
+                        "scale": {"paddingInner": padding}
+                    }),
+                );
             }
-            .to_string()
-        } else {
-            "nominal".to_string()
         }
+
+        // Jitter offsets are fractions of the band, centred on its middle
+        for (channel, name) in [("xOffset", "jitter_x"), ("yOffset", "jitter_y")] {
+            let jitter = naming::position_column(name);
+            if df.column(&jitter).is_ok() {
+                encoding.insert(
+                    channel.to_string(),
+                    json!({
+                        "field": jitter,
+                        "type": "quantitative",
+                        "scale": {"domain": [-0.5, 0.5]}
+                    }),
+                );
+            }
+        }
+    }
+
+    /// Vega-Lite field type for a kind of scale
+    fn kind_field_type(&self, kind: ScaleKind) -> &'static str {
+        match kind {
+            ScaleKind::Continuous => "quantitative",
+            ScaleKind::Temporal => "temporal",
+            ScaleKind::Discrete | ScaleKind::Identity => "nominal",
+        }
+    }
+
+    /// Vega-Lite field type of a column without a trained scale
+    ///
+    /// Uses the same rules as scale training, so both agree on the kind of data.
+    fn column_field_type(&self, df: &DataFrame, field: &str) -> Result<String> {
+        let kind = match df.column(field) {
+            Ok(column) => column_kind(column)?,
+            Err(_) => ScaleKind::Discrete,
+        };
+        Ok(self.kind_field_type(kind).to_string())
     }
 
     /// Build encoding channel from aesthetic mapping
@@ -274,14 +399,19 @@ impl VegaLiteWriter {
                 is_dummy,
                 ..
             } => {
-                // Check if there's a scale specification for this aesthetic
-                let inferred = self.infer_field_type(df, col);
+                // Prefer the kind of scale trained on all layers over this column alone
+                let trained = spec
+                    .find_trained_scale(GeomAesthetics::primary_aesthetic(aesthetic))
+                    .filter(|_| !*is_dummy);
+                let inferred = match trained.map(|s| s.kind) {
+                    Some(ScaleKind::Identity) | None => self.column_field_type(df, col)?,
+                    Some(kind) => self.kind_field_type(kind).to_string(),
+                };
                 let mut identity_scale = false;
 
                 let field_type = if let Some(scale) = spec.find_scale(aesthetic) {
                     // Use scale type if explicitly specified
                     if let Some(scale_type) = &scale.scale_type {
-                        use crate::plot::ScaleType;
                         match scale_type {
                             ScaleType::Linear
                             | ScaleType::Log10
@@ -300,12 +430,18 @@ impl VegaLiteWriter {
                             | ScaleType::Cividis
                             | ScaleType::Diverging
                             | ScaleType::Sequential => "quantitative", // Color scales
+                            ScaleType::Binned | ScaleType::Quantile | ScaleType::Threshold => {
+                                "quantitative"
+                            }
                             ScaleType::Identity => {
                                 identity_scale = true;
                                 inferred.as_str()
                             }
                         }
                         .to_string()
+                    } else if scale.properties.contains_key("values") {
+                        // Explicit value mappings make a manual, discrete scale
+                        "nominal".to_string()
                     } else if scale.properties.contains_key("domain") {
                         // If domain is specified without explicit type:
                         // - For size/opacity: keep quantitative (domain sets range, not categories)
@@ -323,6 +459,13 @@ impl VegaLiteWriter {
                     // No scale specification, infer from data
                     inferred
                 };
+                // Shapes and line types only come in discrete sets
+                let field_type =
+                    if matches!(aesthetic, "shape" | "linetype") && field_type == "quantitative" {
+                        "ordinal".to_string()
+                    } else {
+                        field_type
+                    };
 
                 let mut encoding = json!({
                     "field": col,
@@ -344,11 +487,42 @@ impl VegaLiteWriter {
 
                 if let Some(scale) = spec.find_scale(aesthetic) {
                     // Apply scale properties from SCALE if specified
-                    use crate::plot::{ArrayElement, ParameterValue};
+                    // Apply continuous transforms
+                    match scale.scale_type {
+                        Some(ScaleType::Log10) => {
+                            scale_obj.insert("type".to_string(), json!("log"));
+                        }
+                        Some(ScaleType::Log) => {
+                            scale_obj.insert("type".to_string(), json!("log"));
+                            scale_obj.insert("base".to_string(), json!(std::f64::consts::E));
+                        }
+                        Some(ScaleType::Log2) => {
+                            scale_obj.insert("type".to_string(), json!("log"));
+                            scale_obj.insert("base".to_string(), json!(2));
+                        }
+                        Some(ScaleType::Sqrt) => {
+                            scale_obj.insert("type".to_string(), json!("sqrt"));
+                        }
+                        Some(ScaleType::Reverse) => {
+                            scale_obj.insert("reverse".to_string(), json!(true));
+                        }
+                        _ => {}
+                    }
 
-                    // Apply domain
-                    if let Some(ParameterValue::Array(domain_values)) =
-                        scale.properties.get("domain")
+                    // A direction of -1 reverses the scale, e.g. to flip a palette
+                    if let Some(ParameterValue::Number(direction)) =
+                        scale.properties.get("direction")
+                    {
+                        if *direction < 0.0 {
+                            scale_obj.insert("reverse".to_string(), json!(true));
+                        }
+                    }
+
+                    // Apply domain (`limits` is an alias of `domain`)
+                    if let Some(ParameterValue::Array(domain_values)) = scale
+                        .properties
+                        .get("domain")
+                        .or_else(|| scale.properties.get("limits"))
                     {
                         let domain_json: Vec<Value> = domain_values
                             .iter()
@@ -388,6 +562,56 @@ impl VegaLiteWriter {
                             .collect();
                         scale_obj.insert("range".to_string(), json!(range_json));
                     }
+
+                    // Manual values map each category to its output and take
+                    // precedence over domain, range and palette
+                    match scale.properties.get("values") {
+                        None => {}
+                        Some(ParameterValue::Map(entries)) => {
+                            let (domain, range): (Vec<Value>, Vec<Value>) = entries
+                                .iter()
+                                .map(|(key, value)| (key.to_json(), value.to_json()))
+                                .unzip();
+                            scale_obj.insert("domain".to_string(), json!(domain));
+                            scale_obj.insert("range".to_string(), json!(range));
+                        }
+                        Some(ParameterValue::Array(values)) => {
+                            let range: Vec<Value> =
+                                values.iter().map(ArrayElement::to_json).collect();
+                            scale_obj.insert("range".to_string(), json!(range));
+                        }
+                        Some(_) => {
+                            return Err(GgsqlError::ValidationError(format!(
+                                "SCALE {} values must be a map such as {{'a': 'red'}} or an array",
+                                aesthetic
+                            )))
+                        }
+                    }
+
+                    if scale.scale_type.as_ref().is_some_and(ScaleType::is_binned) {
+                        self.apply_binned_scale(&mut scale_obj, scale)?;
+                    }
+                }
+                // Shapes, line types, line widths and sizes are given the ggplot2 way.
+                // Shapes are translated with the mark (see apply_point_shapes) and
+                // sizes once the mark is known (see apply_size_units)
+                if matches!(aesthetic, "shape" | "linetype" | "linewidth")
+                    || (aesthetic == "size" && field_type == "quantitative")
+                {
+                    let range = match scale_obj.remove("range") {
+                        Some(Value::Array(range)) => range,
+                        Some(range) => vec![range],
+                        None => default_range(aesthetic),
+                    };
+                    let range = if matches!(aesthetic, "linetype" | "linewidth") {
+                        range
+                            .iter()
+                            .map(|value| aesthetic_value(aesthetic, value))
+                            .collect::<Result<Vec<_>>>()?
+                    } else {
+                        range
+                    };
+                    scale_obj.insert("range".to_string(), json!(range));
                 }
                 // We don't automatically want to include 0 in our position scales
                 if aesthetic == "x" || aesthetic == "y" {
@@ -405,6 +629,8 @@ impl VegaLiteWriter {
                 // Hide axis for dummy columns (e.g., x when bar chart has no x mapped)
                 if *is_dummy {
                     encoding["axis"] = json!(null);
+                } else if let Some(scale) = spec.find_scale(aesthetic) {
+                    self.apply_scale_guide(&mut encoding, aesthetic, col, scale)?;
                 }
 
                 Ok(encoding)
@@ -416,7 +642,7 @@ impl VegaLiteWriter {
                     LiteralValue::Number(n) => json!(n),
                     LiteralValue::Boolean(b) => json!(b),
                 };
-                Ok(json!({"value": val}))
+                Ok(json!({"value": aesthetic_value(aesthetic, &val)?}))
             }
         }
     }
@@ -425,1000 +651,3876 @@ impl VegaLiteWriter {
     fn map_aesthetic_name(&self, aesthetic: &str) -> String {
         match aesthetic {
             "fill" => "color",
+            "linetype" => "strokeDash",
+            "linewidth" => "strokeWidth",
             _ => aesthetic,
         }
         .to_string()
     }
 
-    /// Apply guide configurations to encoding channels
-    fn apply_guides_to_encoding(&self, encoding: &mut Map<String, Value>, spec: &Plot) {
-        use crate::plot::GuideType;
+    /// Build the confidence band layer for a smooth layer.
+    ///
+    /// Moves the `ymin`/`ymax` channels out of the line encoding into an area
+    /// layer spanning `y` to `y2`. The band is filled from the `fill` aesthetic,
+    /// or drawn in translucent grey. Returns None when the stat produced no band
+    /// (`se => false`).
+    fn build_smooth_band(
+        &self,
+        layer: &Layer,
+        layer_spec: &Value,
+        encoding: &mut Map<String, Value>,
+        df: &DataFrame,
+        spec: &Plot,
+        titled_families: &mut std::collections::HashSet<String>,
+    ) -> Result<Option<Value>> {
+        let (Some(mut ymin), Some(ymax)) = (encoding.remove("ymin"), encoding.remove("ymax"))
+        else {
+            return Ok(None);
+        };
 
-        for guide in &spec.guides {
-            let channel_name = self.map_aesthetic_name(&guide.aesthetic);
+        // The y family title may have landed on ymin; share it between both layers
+        let title = ymin
+            .get("title")
+            .or_else(|| encoding.get("y").and_then(|y| y.get("title")))
+            .cloned();
+        if let Some(title) = title {
+            ymin["title"] = title.clone();
+            if let Some(y) = encoding.get_mut("y") {
+                y["title"] = title;
+            }
+        }
 
-            // Skip if this channel doesn't exist in the encoding
-            if !encoding.contains_key(&channel_name) {
-                continue;
+        let mut band_encoding = encoding.clone();
+        for channel in ["color", "stroke", "strokeDash", "opacity"] {
+            band_encoding.remove(channel);
+        }
+        band_encoding.insert("y".to_string(), ymin);
+        band_encoding.insert("y2".to_string(), json!({"field": ymax["field"]}));
+
+        if let Some(fill) = layer.mappings.get("fill") {
+            let channel = self.build_encoding_channel("fill", fill, df, spec, titled_families)?;
+            band_encoding.insert("color".to_string(), channel);
+        } else if let Some(fill) = layer.parameters.get("fill") {
+            band_encoding.insert("color".to_string(), json!({"value": fill.to_json()}));
+        }
+
+        let mut band = layer_spec.clone();
+        band["mark"] = json!({
+            "type": "area",
+            "color": "#999999",
+            "opacity": 0.4
+        });
+        band["encoding"] = Value::Object(band_encoding);
+        Ok(Some(band))
+    }
+
+    /// Build the layers drawing a boxplot from precomputed statistics.
+    ///
+    /// Box rows (no outlier value) are drawn as a whisker rule from `ymin` to
+    /// `ymax`, a box from `lower` to `upper` and a median tick at `middle`.
+    /// Outlier rows are drawn as points at `y`. With `varwidth => true` the box
+    /// width is proportional to the square root of the number of observations.
+    fn build_boxplot_layers(
+        &self,
+        layer: &Layer,
+        layer_spec: &Value,
+        mut encoding: Map<String, Value>,
+    ) -> Vec<Value> {
+        let ymin = encoding.remove("ymin");
+        let lower = encoding.remove("lower");
+        let middle = encoding.remove("middle");
+        let upper = encoding.remove("upper");
+        let ymax = encoding.remove("ymax");
+        let outlier = encoding.remove("y");
+
+        // The y family title may have landed on any of the statistics
+        let title = [&ymin, &lower, &middle, &upper, &ymax, &outlier]
+            .iter()
+            .filter_map(|channel| channel.as_ref().and_then(|c| c.get("title")))
+            .next()
+            .cloned();
+        let y_channel = |channel: &Option<Value>| -> Option<Value> {
+            let mut channel = channel.clone()?;
+            if let Some(title) = &title {
+                channel["title"] = title.clone();
             }
+            Some(channel)
+        };
+        let y2_channel =
+            |channel: &Option<Value>| channel.as_ref().map(|c| json!({"field": c["field"]}));
+
+        // Outlier rows carry a value in the outlier column, box rows don't
+        let is_box = outlier
+            .as_ref()
+            .and_then(|o| o["field"].as_str())
+            .map(|field| {
+                (
+                    json!({"filter": format!("!isValid(datum[{}])", json!(field))}),
+                    json!({"filter": format!("isValid(datum[{}])", json!(field))}),
+                )
+            });
 
-            // Handle guide type
-            match &guide.guide_type {
-                Some(GuideType::None) => {
-                    // Remove legend for this channel
-                    if let Some(channel) = encoding.get_mut(&channel_name) {
-                        channel["legend"] = json!(null);
-                    }
-                }
-                Some(GuideType::Legend) => {
-                    // Apply legend properties
-                    if let Some(channel) = encoding.get_mut(&channel_name) {
-                        let mut legend = json!({});
+        // Lines use the stroke colour; only boxes and outliers use the fill colour
+        let mut line_encoding = encoding.clone();
+        line_encoding.remove("color");
+        if let Some(stroke) = line_encoding.remove("stroke") {
+            line_encoding.insert("color".to_string(), stroke);
+        }
 
-                        for (prop_name, prop_value) in &guide.properties {
-                            let value = prop_value.to_json();
+        let part = |mark: Value, encoding: Map<String, Value>, box_rows: bool| -> Value {
+            let mut part = layer_spec.clone();
+            part["mark"] = mark;
+            if let Some((box_filter, outlier_filter)) = &is_box {
+                let filter = if box_rows { box_filter } else { outlier_filter };
+                let mut transforms = part
+                    .get("transform")
+                    .and_then(|t| t.as_array().cloned())
+                    .unwrap_or_default();
+                transforms.push(filter.clone());
+                part["transform"] = json!(transforms);
+            }
+            part["encoding"] = Value::Object(encoding);
+            part
+        };
 
-                            // Map property names to Vega-Lite legend properties
-                            match prop_name.as_str() {
-                                "title" => legend["title"] = value,
-                                "position" => legend["orient"] = value,
-                                "direction" => legend["direction"] = value,
-                                "nrow" => legend["rowPadding"] = value,
-                                "ncol" => legend["columnPadding"] = value,
-                                "title_position" => legend["titleAnchor"] = value,
-                                _ => {
-                                    // Pass through other properties
-                                    legend[prop_name] = value;
-                                }
-                            }
-                        }
+        let mut parts = Vec::new();
 
-                        if !legend.as_object().unwrap().is_empty() {
-                            channel["legend"] = legend;
-                        }
-                    }
-                }
-                Some(GuideType::ColorBar) => {
-                    // For color bars, similar to legend but with gradient
-                    if let Some(channel) = encoding.get_mut(&channel_name) {
-                        let mut legend = json!({"type": "gradient"});
+        if let (Some(y), Some(y2)) = (y_channel(&ymin), y2_channel(&ymax)) {
+            let mut whisker = line_encoding.clone();
+            whisker.insert("y".to_string(), y);
+            whisker.insert("y2".to_string(), y2);
+            parts.push(part(
+                json!({"type": "rule", "color": "black"}),
+                whisker,
+                true,
+            ));
+        }
 
-                        for (prop_name, prop_value) in &guide.properties {
-                            let value = prop_value.to_json();
+        let varwidth = layer
+            .parameters
+            .get("varwidth")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let mut box_encoding = encoding.clone();
+        box_encoding.remove("stroke");
+        if let (Some(y), Some(y2)) = (y_channel(&lower), y2_channel(&upper)) {
+            box_encoding.insert("y".to_string(), y);
+            box_encoding.insert("y2".to_string(), y2);
+        }
+        let box_mark = if varwidth {
+            box_encoding.insert(
+                "size".to_string(),
+                json!({
+                    "field": naming::stat_column("n"),
+                    "type": "quantitative",
+                    "scale": {"type": "sqrt", "zero": true},
+                    "legend": null
+                }),
+            );
+            json!({"type": "bar", "stroke": "black"})
+        } else {
+            json!({"type": "bar", "width": {"band": 0.75}, "stroke": "black"})
+        };
+        parts.push(part(box_mark, box_encoding, true));
+
+        if let Some(y) = y_channel(&middle) {
+            let mut median = line_encoding.clone();
+            median.insert("y".to_string(), y);
+            parts.push(part(
+                json!({"type": "tick", "color": "black", "thickness": 2}),
+                median,
+                true,
+            ));
+        }
 
-                            match prop_name.as_str() {
-                                "title" => legend["title"] = value,
-                                "position" => legend["orient"] = value,
-                                _ => legend[prop_name] = value,
-                            }
-                        }
+        if let Some(y) = y_channel(&outlier) {
+            let mut points = encoding;
+            points.remove("stroke");
+            points.insert("y".to_string(), y);
+            parts.push(part(json!({"type": "point"}), points, false));
+        }
 
-                        channel["legend"] = legend;
-                    }
-                }
-                Some(GuideType::Axis) => {
-                    // Apply axis properties
-                    if let Some(channel) = encoding.get_mut(&channel_name) {
-                        let mut axis = json!({});
+        parts
+    }
 
-                        for (prop_name, prop_value) in &guide.properties {
-                            let value = prop_value.to_json();
+    /// Turn `ymin`/`ymax` (or `xmin`/`xmax`) into the `y`/`y2` (or `x`/`x2`)
+    /// channels of a range mark such as an error bar.
+    ///
+    /// The centre value on the same axis is dropped, since the range replaces it.
+    /// Returns false, leaving the encoding untouched, when no complete range is mapped.
+    fn apply_range_encoding(&self, encoding: &mut Map<String, Value>) -> bool {
+        self.apply_axis_range_encoding(encoding, "y")
+            || self.apply_axis_range_encoding(encoding, "x")
+    }
 
-                            // Map property names to Vega-Lite axis properties
-                            match prop_name.as_str() {
-                                "title" => axis["title"] = value,
-                                "text_angle" => axis["labelAngle"] = value,
-                                "text_size" => axis["labelFontSize"] = value,
-                                _ => axis[prop_name] = value,
-                            }
-                        }
+    /// Turn `<axis>min`/`<axis>max` into the `<axis>`/`<axis>2` channels.
+    ///
+    /// Returns false, leaving the encoding untouched, when the range is not mapped.
+    fn apply_axis_range_encoding(&self, encoding: &mut Map<String, Value>, axis: &str) -> bool {
+        let (min, max) = (format!("{}min", axis), format!("{}max", axis));
+        if !encoding.contains_key(&min) || !encoding.contains_key(&max) {
+            return false;
+        }
+        let mut lower = encoding.remove(&min).unwrap();
+        let upper = encoding.remove(&max).unwrap();
+        let centre = encoding.remove(axis);
+
+        // The axis title may have landed on any of the three channels
+        let title = [lower.get("title"), upper.get("title")]
+            .into_iter()
+            .chain(std::iter::once(
+                centre.as_ref().and_then(|c| c.get("title")),
+            ))
+            .flatten()
+            .next()
+            .cloned();
+        if let Some(title) = title {
+            lower["title"] = title;
+        }
+        encoding.insert(axis.to_string(), lower);
+        encoding.insert(format!("{}2", axis), json!({"field": upper["field"]}));
+        true
+    }
 
-                        if !axis.as_object().unwrap().is_empty() {
-                            channel["axis"] = axis;
-                        }
-                    }
-                }
-                None => {
-                    // No specific guide type, just apply properties generically
-                    if let Some(channel) = encoding.get_mut(&channel_name) {
-                        for (prop_name, prop_value) in &guide.properties {
-                            channel[prop_name] = prop_value.to_json();
-                        }
-                    }
+    /// Rename the channels of geoms whose aesthetics differ from Vega-Lite's.
+    ///
+    /// Segments run from `x`/`y` to `xend`/`yend`, and reference lines are
+    /// positioned by their intercept alone.
+    fn rename_line_channels(&self, geom_type: GeomType, encoding: &mut Map<String, Value>) {
+        let renames: &[(&str, &str)] = match geom_type {
+            GeomType::Segment | GeomType::Arrow => &[("xend", "x2"), ("yend", "y2")],
+            GeomType::HLine => &[("yintercept", "y")],
+            GeomType::VLine => &[("xintercept", "x")],
+            _ => &[],
+        };
+        for (from, to) in renames {
+            if let Some(mut channel) = encoding.remove(*from) {
+                // Secondary channels only take a field; they share the primary scale
+                if to.ends_with('2') {
+                    channel = json!({"field": channel["field"]});
                 }
+                encoding.insert(to.to_string(), channel);
             }
         }
     }
 
-    /// Validate column references for a single layer against its specific DataFrame
-    fn validate_layer_columns(
+    /// Split a pointrange or crossbar layer into its interval and its estimate.
+    ///
+    /// The interval from `ymin` to `ymax` (or `xmin` to `xmax`) is drawn as a
+    /// rule for pointranges and as a box for crossbars; the estimate at `y` (or
+    /// `x`) as a point or a line across the box.
+    fn build_interval_layers(
         &self,
-        layer: &crate::plot::Layer,
-        data: &DataFrame,
-        layer_idx: usize,
-    ) -> Result<()> {
-        let available_columns: Vec<String> = data
-            .get_column_names()
-            .iter()
-            .map(|s| s.to_string())
-            .collect();
+        layer: &Layer,
+        layer_spec: &Value,
+        encoding: Map<String, Value>,
+    ) -> Vec<Value> {
+        let mut range = encoding.clone();
+        if !self.apply_range_encoding(&mut range) {
+            let mut part = layer_spec.clone();
+            part["encoding"] = Value::Object(encoding);
+            return vec![part];
+        }
+        let horizontal = range.contains_key("x2");
+        let mut estimate = encoding;
+        for channel in ["ymin", "ymax", "xmin", "xmax"] {
+            estimate.remove(channel);
+        }
 
-        for (aesthetic, value) in &layer.mappings.aesthetics {
-            if let AestheticValue::Column { name: col, .. } = value {
-                if !available_columns.contains(col) {
-                    let source_desc = if let Some(src) = &layer.source {
-                        format!(" (source: {})", src.as_str())
-                    } else {
-                        " (global data)".to_string()
-                    };
-                    return Err(GgsqlError::ValidationError(format!(
-                        "Column '{}' referenced in aesthetic '{}' (layer {}{}) does not exist.\nAvailable columns: {}",
-                        col,
-                        aesthetic,
-                        layer_idx + 1,
-                        source_desc,
-                        available_columns.join(", ")
-                    )));
-                }
+        let part = |mark: Value, encoding: Map<String, Value>| -> Value {
+            let mut part = layer_spec.clone();
+            part["mark"] = mark;
+            part["encoding"] = Value::Object(encoding);
+            part
+        };
+
+        if layer.geom.geom_type() == GeomType::CrossBar {
+            let width = layer
+                .parameters
+                .get("width")
+                .and_then(|w| w.as_number())
+                .unwrap_or(0.9);
+            let size = if horizontal { "height" } else { "width" };
+            let mut box_mark = json!({"type": "bar", "stroke": "black"});
+            box_mark[size] = json!({"band": width});
+            let mut middle = json!({"type": "tick", "color": "black", "thickness": 2});
+            if horizontal {
+                middle["orient"] = json!("vertical");
             }
+            estimate.remove("stroke");
+            vec![part(box_mark, range), part(middle, estimate)]
+        } else {
+            range.remove("shape");
+            range.remove("size");
+            vec![
+                part(json!({"type": "rule"}), range),
+                part(json!({"type": "point", "filled": true}), estimate),
+            ]
         }
+    }
 
-        // Check partition_by columns
-        for col in &layer.partition_by {
-            if !available_columns.contains(col) {
-                let source_desc = if let Some(src) = &layer.source {
-                    format!(" (source: {})", src.as_str())
-                } else {
-                    " (global data)".to_string()
-                };
-                return Err(GgsqlError::ValidationError(format!(
-                    "Column '{}' referenced in PARTITION BY (layer {}{}) does not exist.\nAvailable columns: {}",
-                    col,
-                    layer_idx + 1,
-                    source_desc,
-                    available_columns.join(", ")
-                )));
+    /// Split a rug layer into one layer of ticks per side of the plot.
+    ///
+    /// `sides` holds any of `b`, `t`, `l` and `r`. Bottom and top ticks mark `x`
+    /// values, left and right ticks `y` values; each tick is `length` times the
+    /// height (or width) of the plot long.
+    fn build_rug_layers(
+        &self,
+        layer: &Layer,
+        layer_spec: &Value,
+        encoding: Map<String, Value>,
+    ) -> Result<Vec<Value>> {
+        let sides = layer
+            .parameters
+            .get("sides")
+            .and_then(|s| s.as_str())
+            .unwrap_or("bl");
+        if sides.is_empty() || !sides.chars().all(|c| "btlr".contains(c)) {
+            return Err(GgsqlError::ValidationError(format!(
+                "Setting 'sides' of rug must combine 'b', 't', 'l' and 'r', got '{}'",
+                sides
+            )));
+        }
+        let length = layer
+            .parameters
+            .get("length")
+            .and_then(|l| l.as_number())
+            .unwrap_or(0.03);
+
+        let mut parts = Vec::new();
+        for side in sides.chars() {
+            // Ticks along x are anchored to the bottom or top edge, and vice versa
+            let (axis, cross, extent, at_start) = match side {
+                'b' => ("x", "y", "height", false),
+                't' => ("x", "y", "height", true),
+                'l' => ("y", "x", "width", true),
+                _ => ("y", "x", "width", false),
+            };
+            if !encoding.contains_key(axis) {
+                continue;
             }
+            let (edge, inner) = if at_start {
+                (
+                    json!(0),
+                    json!({"expr": format!("{} * {}", extent, length)}),
+                )
+            } else {
+                (
+                    json!(extent),
+                    json!({"expr": format!("{} * {}", extent, 1.0 - length)}),
+                )
+            };
+            let mut ticks = encoding.clone();
+            ticks.remove(cross);
+            ticks.insert(cross.to_string(), json!({"value": edge}));
+            ticks.insert(format!("{}2", cross), json!({"value": inner}));
+            let mut part = layer_spec.clone();
+            part["encoding"] = Value::Object(ticks);
+            parts.push(part);
         }
-
-        Ok(())
+        Ok(parts)
     }
-}
 
-impl Default for VegaLiteWriter {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-// Coordinate transformation methods
-impl VegaLiteWriter {
-    /// Apply coordinate transformations to the spec and data
-    /// Returns (possibly transformed DataFrame, possibly modified spec)
-    fn apply_coord_transforms(
+    /// Move a violin layer onto the numeric x axis computed by stat_violin.
+    ///
+    /// The outline spans `xmin` to `xmax` at each `y`, and the axis is labelled
+    /// with the original x value at each integer `xpos`. Returns false, leaving
+    /// the encoding untouched, when the stat columns are not present.
+    fn apply_violin_encoding(
         &self,
-        spec: &Plot,
-        data: &DataFrame,
-        vl_spec: &mut Value,
-    ) -> Result<Option<DataFrame>> {
-        if let Some(ref coord) = spec.coord {
-            match coord.coord_type {
-                CoordType::Cartesian => {
-                    self.apply_cartesian_coord(coord, vl_spec, data)?;
-                    Ok(None) // No DataFrame transformation needed
-                }
-                CoordType::Flip => {
-                    self.apply_flip_coord(vl_spec)?;
-                    Ok(None) // No DataFrame transformation needed
-                }
-                CoordType::Polar => {
-                    // Polar requires DataFrame transformation for percentages
-                    let transformed_df = self.apply_polar_coord(coord, spec, data, vl_spec)?;
-                    Ok(Some(transformed_df))
-                }
-                _ => {
-                    // Other coord types not yet implemented
-                    Ok(None)
+        layer: &Layer,
+        df: &DataFrame,
+        encoding: &mut Map<String, Value>,
+    ) -> Result<bool> {
+        if !encoding.contains_key("xmin") || !encoding.contains_key("xmax") {
+            return Ok(false);
+        }
+        let xmin = encoding.remove("xmin").unwrap();
+        let xmax = encoding.remove("xmax").unwrap();
+        let x = encoding.remove("x");
+        let x_field = x
+            .as_ref()
+            .and_then(|x| x["field"].as_str())
+            .map(|f| f.to_string());
+
+        // Label each position with the x value it was computed from
+        let mut labels: std::collections::BTreeMap<i64, String> = std::collections::BTreeMap::new();
+        if let (Some(x_field), Ok(xpos)) = (&x_field, df.column(&naming::stat_column("xpos"))) {
+            let xpos = xpos.cast(&DataType::Int64).map_err(|e| {
+                GgsqlError::WriterError(format!("Failed to read violin positions: {}", e))
+            })?;
+            let xpos = xpos.i64().map_err(|e| {
+                GgsqlError::WriterError(format!("Failed to read violin positions: {}", e))
+            })?;
+            let values = df.column(x_field).map_err(|e| {
+                GgsqlError::WriterError(format!("Failed to read violin x values: {}", e))
+            })?;
+            for (idx, pos) in xpos.into_iter().enumerate() {
+                if let Some(pos) = pos {
+                    if let std::collections::btree_map::Entry::Vacant(entry) = labels.entry(pos) {
+                        let label = match self.series_value_at(
+                            values.as_materialized_series(),
+                            idx,
+                            None,
+                        )? {
+                            Value::String(s) => s,
+                            other => other.to_string(),
+                        };
+                        entry.insert(label);
+                    }
                 }
             }
-        } else {
-            Ok(None)
         }
+
+        let label_expr = labels
+            .iter()
+            .map(|(pos, label)| format!("datum.value === {} ? {} : ", pos, json!(label)))
+            .collect::<String>()
+            + "''";
+        let positions: Vec<i64> = labels.keys().copied().collect();
+
+        let mut x_encoding = json!({
+            "field": xmin["field"],
+            "type": "quantitative",
+            "scale": {
+                "domain": [0.5, positions.len() as f64 + 0.5],
+                "nice": false,
+                "zero": false
+            },
+            "axis": {
+                "values": positions,
+                "labelExpr": label_expr,
+                "grid": false
+            }
+        });
+        if let Some(title) = x.as_ref().and_then(|x| x.get("title")) {
+            x_encoding["title"] = title.clone();
+        } else if let Some(field) = &x_field {
+            x_encoding["title"] = json!(field);
+        }
+        encoding.insert("x".to_string(), x_encoding);
+        encoding.insert("x2".to_string(), json!({"field": xmax["field"]}));
+
+        // Each x value and group is its own violin
+        let mut detail_fields: Vec<String> = x_field.into_iter().collect();
+        for col in &layer.partition_by {
+            if !detail_fields.contains(col) {
+                detail_fields.push(col.clone());
+            }
+        }
+        if let Some(detail) = self.build_detail_encoding(&detail_fields) {
+            encoding.insert("detail".to_string(), detail);
+        }
+
+        Ok(true)
     }
 
-    /// Apply Cartesian coordinate properties (xlim, ylim, aesthetic domains)
-    fn apply_cartesian_coord(
+    /// Turn the scale of a binned colour scale into a discretizing Vega-Lite scale
+    ///
+    /// `'binned'` scales cut the data range into `n` equal bins (`quantize`), or
+    /// at the given `breaks` (`threshold`). `'quantile'` scales put an equal
+    /// number of observations in each of `n` classes. The palette is sampled to
+    /// one colour per class.
+    fn apply_binned_scale(&self, scale_obj: &mut Map<String, Value>, scale: &Scale) -> Result<()> {
+        let n = match scale.properties.get("n") {
+            None => 5,
+            Some(ParameterValue::Number(n)) if *n >= 1.0 && n.fract() == 0.0 => *n as usize,
+            Some(_) => {
+                return Err(GgsqlError::ValidationError(format!(
+                    "SCALE {} n must be a positive whole number",
+                    scale.aesthetic
+                )))
+            }
+        };
+        let breaks = match scale.properties.get("breaks") {
+            None => None,
+            Some(ParameterValue::Array(values))
+                if values.iter().all(|v| matches!(v, ArrayElement::Number(_))) =>
+            {
+                Some(values)
+            }
+            Some(_) => {
+                return Err(GgsqlError::ValidationError(format!(
+                    "SCALE {} breaks of a binned scale must be an array of numbers",
+                    scale.aesthetic
+                )))
+            }
+        };
+
+        let (vl_type, classes) = match (&scale.scale_type, breaks) {
+            (Some(ScaleType::Quantile), None) => ("quantile", n),
+            (Some(ScaleType::Quantile), Some(_)) => {
+                return Err(GgsqlError::ValidationError(format!(
+                    "SCALE {} of type 'quantile' takes n, not breaks; use type 'threshold' to bin at given breaks",
+                    scale.aesthetic
+                )))
+            }
+            (_, Some(breaks)) => {
+                let domain: Vec<Value> = breaks.iter().map(ArrayElement::to_json).collect();
+                scale_obj.insert("domain".to_string(), json!(domain));
+                ("threshold", breaks.len() + 1)
+            }
+            (Some(ScaleType::Threshold), None) => {
+                return Err(GgsqlError::ValidationError(format!(
+                    "SCALE {} of type 'threshold' needs breaks",
+                    scale.aesthetic
+                )))
+            }
+            _ => ("quantize", n),
+        };
+        scale_obj.insert("type".to_string(), json!(vl_type));
+
+        if !scale.properties.contains_key("range") {
+            if let Some(ParameterValue::Array(palette)) = scale.properties.get("palette") {
+                let range: Vec<Value> = sample_palette(palette, classes)
+                    .iter()
+                    .map(ArrayElement::to_json)
+                    .collect();
+                scale_obj.insert("range".to_string(), json!(range));
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the breaks, labels and NA value of a scale to its encoding channel
+    ///
+    /// Breaks and labels end up on the axis of position aesthetics and on the
+    /// legend of all others. Missing values are drawn in the `na_value` colour
+    /// through a condition on the channel.
+    fn apply_scale_guide(
         &self,
-        coord: &Coord,
-        vl_spec: &mut Value,
-        _data: &DataFrame,
+        encoding: &mut Value,
+        aesthetic: &str,
+        field: &str,
+        scale: &Scale,
     ) -> Result<()> {
-        // Apply xlim/ylim to scale domains
-        for (prop_name, prop_value) in &coord.properties {
-            match prop_name.as_str() {
-                "xlim" => {
-                    if let Some(limits) = self.extract_limits(prop_value)? {
-                        self.apply_axis_limits(vl_spec, "x", limits)?;
-                    }
-                }
-                "ylim" => {
-                    if let Some(limits) = self.extract_limits(prop_value)? {
-                        self.apply_axis_limits(vl_spec, "y", limits)?;
-                    }
-                }
-                _ if self.is_aesthetic_name(prop_name) => {
-                    // Aesthetic domain specification
-                    if let Some(domain) = self.extract_domain(prop_value)? {
-                        self.apply_aesthetic_domain(vl_spec, prop_name, domain)?;
-                    }
-                }
-                _ => {
-                    // ratio, clip - not yet implemented (TODO comments added by validation)
-                }
+        let is_position = matches!(GeomAesthetics::primary_aesthetic(aesthetic), "x" | "y");
+        let is_temporal = encoding["type"] == "temporal";
+        let mut guide = Map::new();
+
+        let breaks = match scale.properties.get("breaks") {
+            None => None,
+            // The breaks of binned scales are class boundaries, not legend entries
+            Some(_) if scale.scale_type.as_ref().is_some_and(ScaleType::is_binned) => None,
+            Some(ParameterValue::Array(values)) => Some(values),
+            // Date and time breaks are calendar intervals such as '1 month'
+            Some(ParameterValue::String(interval)) if is_temporal => {
+                let interval = TimeInterval::parse(interval)?;
+                let (unit, step) = match interval.unit {
+                    IntervalUnit::Minute => ("minute", interval.count),
+                    IntervalUnit::Hour => ("hour", interval.count),
+                    IntervalUnit::Day => ("day", interval.count),
+                    IntervalUnit::Week => ("week", interval.count),
+                    IntervalUnit::Month => ("month", interval.count),
+                    IntervalUnit::Quarter => ("month", interval.count * 3),
+                    IntervalUnit::Year => ("year", interval.count),
+                };
+                guide.insert(
+                    "tickCount".to_string(),
+                    json!({"interval": unit, "step": step}),
+                );
+                None
+            }
+            Some(_) => {
+                return Err(GgsqlError::ValidationError(format!(
+                "SCALE {} breaks must be an array of values, or an interval for dates and times",
+                scale.aesthetic
+            )))
+            }
+        };
+        if let Some(breaks) = breaks {
+            let values: Vec<Value> = breaks.iter().map(ArrayElement::to_json).collect();
+            guide.insert("values".to_string(), json!(values));
+        }
+
+        match scale.properties.get("labels") {
+            None => {}
+            Some(ParameterValue::String(format)) if is_temporal => {
+                guide.insert("format".to_string(), json!(time_format(format)?));
+                guide.insert("formatType".to_string(), json!("time"));
+            }
+            Some(ParameterValue::String(format)) => {
+                guide.insert("format".to_string(), json!(number_format(format)));
+            }
+            Some(ParameterValue::Array(labels)) => {
+                let breaks = breaks.filter(|b| b.len() == labels.len()).ok_or_else(|| {
+                    GgsqlError::ValidationError(format!(
+                        "SCALE {} labels given as an array need breaks of the same length",
+                        scale.aesthetic
+                    ))
+                })?;
+                guide.insert("labelExpr".to_string(), json!(label_expr(breaks, labels)));
+            }
+            Some(_) => {
+                return Err(GgsqlError::ValidationError(format!(
+                    "SCALE {} labels must be a format name or an array of strings",
+                    scale.aesthetic
+                )))
             }
         }
 
+        if !guide.is_empty() {
+            let key = if is_position { "axis" } else { "legend" };
+            merge_object(encoding, key, guide);
+        }
+
+        if let Some(na_value) = scale.properties.get("na_value") {
+            // Rows without a position are left out, there is nothing to draw them in
+            if is_position {
+                return Err(GgsqlError::ValidationError(format!(
+                    "SCALE {} does not support na_value, missing positions are not drawn",
+                    scale.aesthetic
+                )));
+            }
+            encoding["condition"] = json!({
+                "test": format!("!isValid(datum[{}])", json!(field)),
+                "value": na_value.to_json()
+            });
+        }
         Ok(())
     }
 
-    /// Apply Flip coordinate transformation (swap x and y)
-    fn apply_flip_coord(&self, vl_spec: &mut Value) -> Result<()> {
-        // Handle single layer
-        if let Some(encoding) = vl_spec.get_mut("encoding") {
-            if let Some(enc_obj) = encoding.as_object_mut() {
-                // Swap x and y encodings
-                if let (Some(x), Some(y)) = (enc_obj.remove("x"), enc_obj.remove("y")) {
-                    enc_obj.insert("x".to_string(), y);
-                    enc_obj.insert("y".to_string(), x);
-                }
+    /// Expand the domains of continuous position scales with an `expand` setting
+    ///
+    /// Like ggplot2, `expand` is a multiplicative factor of the range or an array
+    /// of a multiplicative and an additive term. The range is taken from the
+    /// scale's `domain`, or else from the data of all layers, and expanded in the
+    /// transformed space of the scale.
+    fn apply_scale_expansion(
+        &self,
+        spec: &Plot,
+        data: &HashMap<String, DataFrame>,
+        layer_data_keys: &[String],
+        vl_spec: &mut Value,
+    ) -> Result<()> {
+        for scale in &spec.scales {
+            if !matches!(scale.aesthetic.as_str(), "x" | "y") {
+                continue;
             }
-        }
+            let Some(expand) = scale.properties.get("expand") else {
+                continue;
+            };
+            let (mult, add) = match expand {
+                ParameterValue::Number(m) => (*m, 0.0),
+                ParameterValue::Array(values) => match values.as_slice() {
+                    [ArrayElement::Number(m), ArrayElement::Number(a)] => (*m, *a),
+                    _ => {
+                        return Err(GgsqlError::ValidationError(format!(
+                            "SCALE {} expand must be a number or an array of two numbers",
+                            scale.aesthetic
+                        )))
+                    }
+                },
+                _ => {
+                    return Err(GgsqlError::ValidationError(format!(
+                        "SCALE {} expand must be a number or an array of two numbers",
+                        scale.aesthetic
+                    )))
+                }
+            };
 
-        // Handle multi-layer
-        if let Some(layers) = vl_spec.get_mut("layer") {
-            if let Some(layers_arr) = layers.as_array_mut() {
-                for layer in layers_arr {
-                    if let Some(encoding) = layer.get_mut("encoding") {
-                        if let Some(enc_obj) = encoding.as_object_mut() {
-                            if let (Some(x), Some(y)) = (enc_obj.remove("x"), enc_obj.remove("y")) {
-                                enc_obj.insert("x".to_string(), y);
-                                enc_obj.insert("y".to_string(), x);
-                            }
-                        }
+            let Some((min, max)) = self.scale_range(spec, scale, data, layer_data_keys) else {
+                continue;
+            };
+            let scale_type = scale.scale_type.clone().unwrap_or(ScaleType::Linear);
+            let (lo, hi) = (scale_type.transform(min), scale_type.transform(max));
+            let pad = (hi - lo) * mult + add;
+            let domain = json!([scale_type.inverse(lo - pad), scale_type.inverse(hi + pad)]);
+
+            if let Some(layers) = vl_spec.get_mut("layer").and_then(|l| l.as_array_mut()) {
+                for layer in layers {
+                    if let Some(channel) = layer
+                        .get_mut("encoding")
+                        .and_then(|e| e.get_mut(&scale.aesthetic))
+                        .filter(|c| c.get("field").is_some())
+                    {
+                        merge_object(
+                            channel,
+                            "scale",
+                            Map::from_iter([("domain".to_string(), domain.clone())]),
+                        );
                     }
                 }
             }
         }
-
         Ok(())
     }
 
-    /// Apply Polar coordinate transformation (bar→arc, point→arc with radius)
-    fn apply_polar_coord(
+    /// Draw the secondary axes of position scales
+    ///
+    /// The primary domain is fixed (without rounding to nice values) so that an
+    /// invisible extra layer with the transformed domain lines up with it. Its
+    /// independent scale puts the axis on the opposite side of the plot.
+    fn apply_secondary_axes(
         &self,
-        coord: &Coord,
         spec: &Plot,
-        _data: &DataFrame,
+        data: &HashMap<String, DataFrame>,
+        layer_data_keys: &[String],
         vl_spec: &mut Value,
-    ) -> Result<DataFrame> {
-        // Get theta field (defaults to 'y')
-        let theta_field = coord
-            .properties
-            .get("theta")
-            .and_then(|v| match v {
-                ParameterValue::String(s) => Some(s.clone()),
-                _ => None,
-            })
-            .unwrap_or_else(|| "y".to_string());
+    ) -> Result<()> {
+        let flipped = spec
+            .coord
+            .as_ref()
+            .is_some_and(|c| c.coord_type == CoordType::Flip);
+        let mut secondary_layers = Vec::new();
+        let mut independent = Map::new();
+        for scale in &spec.scales {
+            let Some(axis) = SecondaryAxis::from_scale(scale)? else {
+                continue;
+            };
+            if spec.facet.is_some() {
+                return Err(GgsqlError::ValidationError(
+                    "sec_axis cannot be combined with FACET".to_string(),
+                ));
+            }
+            if spec
+                .coord
+                .as_ref()
+                .is_some_and(|c| c.coord_type == CoordType::Polar)
+            {
+                return Err(GgsqlError::ValidationError(
+                    "sec_axis cannot be combined with polar coordinates".to_string(),
+                ));
+            }
+            if let Some(transform) = scale
+                .scale_type
+                .as_ref()
+                .and_then(ScaleType::transform_name)
+            {
+                return Err(GgsqlError::ValidationError(format!(
+                    "sec_axis cannot be combined with a {} scale",
+                    transform
+                )));
+            }
+            let channel = match (scale.aesthetic.as_str(), flipped) {
+                ("x", true) => "y",
+                ("y", true) => "x",
+                (aesthetic, _) => aesthetic,
+            };
 
-        // Convert geoms to polar equivalents
-        self.convert_geoms_to_polar(spec, vl_spec, &theta_field)?;
+            let Some(layers) = vl_spec.get_mut("layer").and_then(|l| l.as_array_mut()) else {
+                continue;
+            };
+            let existing = layers.iter().find_map(|layer| {
+                match layer["encoding"][channel]["scale"]["domain"]
+                    .as_array()?
+                    .as_slice()
+                {
+                    [lo, hi] => Some((lo.as_f64()?, hi.as_f64()?)),
+                    _ => None,
+                }
+            });
+            let (lo, hi) = match existing {
+                Some(domain) => domain,
+                None => {
+                    let Some((min, max)) = self.scale_range(spec, scale, data, layer_data_keys)
+                    else {
+                        continue;
+                    };
+                    // The default expansion of ggplot2
+                    let pad = (max - min) * 0.05;
+                    (min - pad, max + pad)
+                }
+            };
 
-        // No DataFrame transformation needed - Vega-Lite handles polar math
-        Ok(_data.clone())
+            let mut fixed_scale = Map::new();
+            fixed_scale.insert("domain".to_string(), json!([lo, hi]));
+            fixed_scale.insert("nice".to_string(), json!(false));
+            for layer in layers.iter_mut() {
+                if let Some(encoding) = layer
+                    .get_mut("encoding")
+                    .and_then(|e| e.get_mut(channel))
+                    .filter(|c| c.get("field").is_some())
+                {
+                    merge_object(encoding, "scale", fixed_scale.clone());
+                }
+            }
+
+            let field = naming::secondary_axis_column(channel);
+            let domain = json!([axis.apply(lo), axis.apply(hi)]);
+            let mut encoding = Map::new();
+            encoding.insert(
+                channel.to_string(),
+                json!({
+                    "field": field,
+                    "type": "quantitative",
+                    "title": axis.name,
+                    "scale": {"domain": domain, "nice": false, "zero": false},
+                }),
+            );
+            let values: Vec<Value> = [lo, hi]
+                .iter()
+                .map(|v| Value::Object(Map::from_iter([(field.clone(), json!(axis.apply(*v)))])))
+                .collect();
+            secondary_layers.push(json!({
+                "data": {"values": values},
+                "mark": {"type": "point", "opacity": 0},
+                "encoding": encoding,
+            }));
+            independent.insert(channel.to_string(), json!("independent"));
+        }
+        if secondary_layers.is_empty() {
+            return Ok(());
+        }
+
+        // The primary layers share their scales in a group of their own, so that
+        // only the group and the secondary layers get independent scales
+        let mut group = json!({"layer": vl_spec["layer"].take()});
+        if let Some(resolve) = vl_spec.as_object_mut().and_then(|o| o.remove("resolve")) {
+            group["resolve"] = resolve;
+        }
+        let mut layers = vec![group];
+        layers.extend(secondary_layers);
+        vl_spec["layer"] = json!(layers);
+        vl_spec["resolve"] = json!({"scale": independent});
+        Ok(())
     }
 
-    /// Convert geoms to polar equivalents (bar→arc, point→arc with radius)
-    fn convert_geoms_to_polar(
+    /// Turn the layered spec into a faceted one
+    ///
+    /// A facet dimension with a single variable and plain labels facets by the
+    /// column itself. Otherwise each row of the facet data gets the label of its
+    /// panel, combining all variables of the dimension, as an extra field.
+    ///
+    /// Faceted layers can't have data of their own, so layers from different
+    /// sources are combined into one dataset (see `combine_facet_data`).
+    fn apply_facet(
         &self,
         spec: &Plot,
+        facet: &Facet,
+        data: &HashMap<String, DataFrame>,
         vl_spec: &mut Value,
-        theta_field: &str,
     ) -> Result<()> {
-        // Determine which aesthetic (x or y) maps to theta
-        // Default: y maps to theta (pie chart style)
-        let theta_aesthetic = theta_field;
-
-        // Handle single layer
-        if let Some(mark) = vl_spec.get_mut("mark") {
-            *mark = self.convert_mark_to_polar(mark, spec)?;
-
-            // Update encoding for polar
-            if let Some(encoding) = vl_spec.get_mut("encoding") {
-                self.update_encoding_for_polar(encoding, theta_aesthetic)?;
+        let layers = vl_spec["layer"]
+            .as_array_mut()
+            .ok_or_else(|| GgsqlError::InternalError("Faceted spec has no layers".to_string()))?;
+        let mut data_keys: Vec<String> = Vec::new();
+        for layer in layers.iter() {
+            if let Some(key) = layer["data"]["name"].as_str() {
+                if !data_keys.iter().any(|k| k == key) {
+                    data_keys.push(key.to_string());
+                }
             }
         }
 
-        // Handle multi-layer
-        if let Some(layers) = vl_spec.get_mut("layer") {
-            if let Some(layers_arr) = layers.as_array_mut() {
-                for layer in layers_arr {
-                    if let Some(mark) = layer.get_mut("mark") {
-                        *mark = self.convert_mark_to_polar(mark, spec)?;
+        let variables = facet.get_variables();
+        for variable in &variables {
+            let found = data_keys
+                .iter()
+                .any(|key| data.get(key).is_some_and(|df| df.column(variable).is_ok()));
+            if !found {
+                return Err(GgsqlError::ValidationError(format!(
+                    "Facet variable '{}' not found in the data",
+                    variable
+                )));
+            }
+        }
 
-                        if let Some(encoding) = layer.get_mut("encoding") {
-                            self.update_encoding_for_polar(encoding, theta_aesthetic)?;
-                        }
+        let facet_data_key = match data_keys.as_slice() {
+            [key] => {
+                for layer in layers.iter_mut() {
+                    if layer["data"]["name"] == json!(key) {
+                        layer.as_object_mut().unwrap().remove("data");
                     }
                 }
+                key.clone()
             }
+            _ => {
+                for layer in layers.iter_mut() {
+                    let Some(key) = layer["data"]["name"].as_str().map(str::to_string) else {
+                        continue;
+                    };
+                    layer.as_object_mut().unwrap().remove("data");
+                    let mut transforms = vec![json!({
+                        "filter": {"field": naming::SOURCE_COLUMN, "equal": key}
+                    })];
+                    if let Some(existing) = layer["transform"].as_array() {
+                        transforms.extend(existing.iter().cloned());
+                    }
+                    layer["transform"] = json!(transforms);
+                }
+                combine_facet_data(&data_keys, &variables, data, vl_spec)?;
+                naming::FACET_DATA_KEY.to_string()
+            }
+        };
+        // Frame used to infer the type of a facet variable
+        let frame_with = |variable: &str| {
+            data_keys
+                .iter()
+                .filter_map(|key| data.get(key))
+                .find(|df| df.column(variable).is_ok())
+                .unwrap()
+        };
+
+        let dimensions: Vec<(&str, &Vec<String>)> = match facet {
+            Facet::Wrap { variables, .. } => vec![("facet", variables)],
+            Facet::Grid { rows, cols, .. } => vec![("row", rows), ("column", cols)],
+        };
+        let mut facet_spec = Map::new();
+        for (channel, variables) in dimensions {
+            if variables.is_empty() {
+                continue;
+            }
+            let (mut field, panels) = self.facet_field(
+                facet,
+                channel,
+                variables,
+                frame_with(&variables[0]),
+                &facet_data_key,
+                vl_spec,
+            )?;
+            if channel == "facet" {
+                apply_wrap_layout(facet, &mut field, &panels, vl_spec)?;
+            }
+            facet_spec.insert(channel.to_string(), field);
         }
+        if facet_spec.is_empty() {
+            return Ok(());
+        }
+        vl_spec["facet"] = match facet_spec.remove("facet") {
+            Some(field) => field,
+            None => Value::Object(facet_spec),
+        };
 
+        // Free scales are resolved per panel
+        let flipped = spec
+            .coord
+            .as_ref()
+            .is_some_and(|c| c.coord_type == CoordType::Flip);
+        for axis in ["x", "y"] {
+            if facet.scales().is_free(axis) {
+                let channel = match (flipped, axis) {
+                    (true, "x") => "y",
+                    (true, _) => "x",
+                    (false, axis) => axis,
+                };
+                vl_spec["resolve"]["scale"][channel] = json!("independent");
+            }
+        }
+
+        // Set top-level data reference for faceting
+        vl_spec["data"] = json!({"name": facet_data_key});
+
+        // Move layer into spec, keep datasets at top level
+        let mut spec_inner = json!({});
+        if let Some(layer) = vl_spec.get("layer") {
+            spec_inner["layer"] = layer.clone();
+        }
+
+        // Panels with a fixed aspect ratio (COORD fixed) keep their size
+        for key in ["width", "height"] {
+            if vl_spec[key].is_number() {
+                spec_inner[key] = vl_spec.as_object_mut().unwrap().remove(key).unwrap();
+            }
+        }
+
+        vl_spec["spec"] = spec_inner;
+        vl_spec.as_object_mut().unwrap().remove("layer");
         Ok(())
     }
 
-    /// Convert a mark type to its polar equivalent
-    fn convert_mark_to_polar(&self, mark: &Value, _spec: &Plot) -> Result<Value> {
-        let mark_str = if mark.is_string() {
-            mark.as_str().unwrap()
-        } else if let Some(mark_type) = mark.get("type") {
-            mark_type.as_str().unwrap_or("bar")
-        } else {
-            "bar"
+    /// Facet field of one dimension, with its panels in sorted order
+    fn facet_field(
+        &self,
+        facet: &Facet,
+        channel: &str,
+        variables: &[String],
+        df: &DataFrame,
+        data_key: &str,
+        vl_spec: &mut Value,
+    ) -> Result<(Value, Vec<Value>)> {
+        let label = facet.label();
+        let wrap_width = facet.label_wrap();
+        let rows = vl_spec["datasets"][data_key]
+            .as_array_mut()
+            .ok_or_else(|| GgsqlError::InternalError(format!("Missing dataset '{}'", data_key)))?;
+        let combination = |row: &Value| -> Vec<Value> {
+            variables
+                .iter()
+                .map(|v| row.get(v).cloned().unwrap_or(Value::Null))
+                .collect()
         };
 
-        // Convert geom types to polar equivalents
-        match mark_str {
-            "bar" | "col" => {
-                // Bar/col in polar becomes arc (pie/donut slices)
-                Ok(json!("arc"))
-            }
-            "point" => {
-                // Points in polar can stay as points or become arcs with radius
-                // For now, keep as points (they'll plot at radius based on value)
-                Ok(json!("point"))
-            }
-            "line" => {
-                // Lines in polar become circular/spiral lines
-                Ok(json!("line"))
-            }
-            "area" => {
-                // Area in polar becomes arc with radius
-                Ok(json!("arc"))
-            }
-            _ => {
-                // Other geoms: keep as-is or convert to arc
-                Ok(json!("arc"))
+        // Distinct combinations of the variables
+        let mut panels: Vec<Vec<Value>> = Vec::new();
+        for row in rows.iter() {
+            let panel = combination(row);
+            if !panels.contains(&panel) {
+                panels.push(panel);
             }
         }
-    }
+        panels.sort_by(|a, b| {
+            a.iter()
+                .zip(b)
+                .map(|(a, b)| compare_json(a, b))
+                .find(|order| order.is_ne())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
 
-    /// Update encoding channels for polar coordinates
-    fn update_encoding_for_polar(&self, encoding: &mut Value, theta_aesthetic: &str) -> Result<()> {
-        let enc_obj = encoding
-            .as_object_mut()
-            .ok_or_else(|| GgsqlError::WriterError("Encoding is not an object".to_string()))?;
+        if variables.len() == 1 && label == FacetLabel::Value && wrap_width.is_none() {
+            let field = json!({
+                "field": variables[0],
+                "type": self.column_field_type(df, &variables[0])?,
+            });
+            return Ok((field, panels.into_iter().flatten().collect()));
+        }
 
-        // Map the theta aesthetic to theta channel
-        if theta_aesthetic == "y" {
-            // Standard pie chart: y → theta, x → color/category
-            if let Some(y_enc) = enc_obj.remove("y") {
-                enc_obj.insert("theta".to_string(), y_enc);
-            }
-            // Map x to color if not already mapped, and remove x from positional encoding
-            if !enc_obj.contains_key("color") {
-                if let Some(x_enc) = enc_obj.remove("x") {
-                    enc_obj.insert("color".to_string(), x_enc);
-                }
-            } else {
-                // If color is already mapped, just remove x from positional encoding
-                enc_obj.remove("x");
+        let panel_label = |panel: &[Value]| -> String {
+            let parts: Vec<String> = variables
+                .iter()
+                .zip(panel)
+                .map(|(variable, value)| match label {
+                    FacetLabel::Value => json_label(value),
+                    FacetLabel::Both => format!("{}: {}", variable, json_label(value)),
+                })
+                .collect();
+            let text = parts.join(", ");
+            match wrap_width {
+                Some(width) => wrap_label(&text, width),
+                None => text,
             }
-        } else if theta_aesthetic == "x" {
-            // Reversed: x → theta, y → radius
-            if let Some(x_enc) = enc_obj.remove("x") {
-                enc_obj.insert("theta".to_string(), x_enc);
+        };
+        let column = naming::facet_column(channel);
+        for row in rows.iter_mut() {
+            let text = panel_label(&combination(row));
+            row[column.as_str()] = json!(text);
+        }
+        let labels: Vec<Value> = panels.iter().map(|p| json!(panel_label(p))).collect();
+
+        let mut header = json!({
+            "title": match label {
+                FacetLabel::Value => json!(variables.join(", ")),
+                // The labels name their variables
+                FacetLabel::Both => Value::Null,
             }
-            if let Some(y_enc) = enc_obj.remove("y") {
-                enc_obj.insert("radius".to_string(), y_enc);
+        });
+        if wrap_width.is_some() {
+            header["labelExpr"] = json!("split(datum.value, '\\n')");
+        }
+        let field = json!({
+            "field": column,
+            "type": "nominal",
+            "sort": labels,
+            "header": header,
+        });
+        Ok((field, labels))
+    }
+
+    /// Numeric range of a position scale: its `domain`, or the range of its data
+    fn scale_range(
+        &self,
+        spec: &Plot,
+        scale: &Scale,
+        data: &HashMap<String, DataFrame>,
+        layer_data_keys: &[String],
+    ) -> Option<(f64, f64)> {
+        if let Some(ParameterValue::Array(domain)) = scale.properties.get("domain") {
+            if let [ArrayElement::Number(a), ArrayElement::Number(b)] = domain.as_slice() {
+                return Some((a.min(*b), a.max(*b)));
             }
         }
+        if let Some(trained) = spec.find_trained_scale(&scale.aesthetic) {
+            return match (trained.kind, &trained.domain) {
+                (ScaleKind::Continuous, Some(ScaleDomain::Range(lo, hi))) => Some((*lo, *hi)),
+                _ => None,
+            };
+        }
 
-        Ok(())
+        self.data_range(spec, &scale.aesthetic, data, layer_data_keys)
     }
 
-    // Helper methods
-
-    fn extract_limits(&self, value: &ParameterValue) -> Result<Option<(f64, f64)>> {
-        match value {
-            ParameterValue::Array(arr) => {
-                if arr.len() != 2 {
-                    return Err(GgsqlError::WriterError(format!(
-                        "xlim/ylim must be exactly 2 numbers, got {}",
-                        arr.len()
-                    )));
+    /// Columns of the layer data mapped to a scale's aesthetic
+    fn mapped_columns<'a>(
+        &self,
+        spec: &Plot,
+        aesthetic: &str,
+        data: &'a HashMap<String, DataFrame>,
+        layer_data_keys: &[String],
+    ) -> Vec<&'a Column> {
+        let mut columns = Vec::new();
+        for (layer, key) in spec.layers.iter().zip(layer_data_keys) {
+            let Some(df) = data.get(key) else {
+                continue;
+            };
+            for (name, value) in &layer.mappings.aesthetics {
+                if GeomAesthetics::primary_aesthetic(name) != aesthetic || value.is_dummy() {
+                    continue;
+                }
+                if let Some(column) = value.column_name().and_then(|c| df.column(c).ok()) {
+                    columns.push(column);
                 }
-                let min = match &arr[0] {
-                    ArrayElement::Number(n) => *n,
-                    _ => {
-                        return Err(GgsqlError::WriterError(
-                            "xlim/ylim values must be numbers".to_string(),
-                        ))
-                    }
-                };
-                let max = match &arr[1] {
-                    ArrayElement::Number(n) => *n,
-                    _ => {
-                        return Err(GgsqlError::WriterError(
-                            "xlim/ylim values must be numbers".to_string(),
-                        ))
-                    }
-                };
-
-                // Auto-swap if reversed
-                let (min, max) = if min > max { (max, min) } else { (min, max) };
-
-                Ok(Some((min, max)))
             }
-            _ => Err(GgsqlError::WriterError(
-                "xlim/ylim must be an array".to_string(),
-            )),
         }
+        columns
     }
 
-    fn extract_domain(&self, value: &ParameterValue) -> Result<Option<Vec<Value>>> {
-        match value {
-            ParameterValue::Array(arr) => {
-                let domain: Vec<Value> = arr
-                    .iter()
-                    .map(|elem| match elem {
-                        ArrayElement::String(s) => json!(s),
-                        ArrayElement::Number(n) => json!(n),
-                        ArrayElement::Boolean(b) => json!(b),
-                    })
-                    .collect();
-                Ok(Some(domain))
+    /// Range of the numeric columns mapped to a scale's aesthetic
+    fn data_range(
+        &self,
+        spec: &Plot,
+        aesthetic: &str,
+        data: &HashMap<String, DataFrame>,
+        layer_data_keys: &[String],
+    ) -> Option<(f64, f64)> {
+        let mut range: Option<(f64, f64)> = None;
+        for column in self.mapped_columns(spec, aesthetic, data, layer_data_keys) {
+            if !column.dtype().is_numeric() {
+                continue;
+            }
+            let Ok(column) = column.cast(&DataType::Float64) else {
+                continue;
+            };
+            let Ok(values) = column.f64() else {
+                continue;
+            };
+            if let (Some(lo), Some(hi)) = (values.min(), values.max()) {
+                range = Some(match range {
+                    Some((a, b)) => (a.min(lo), b.max(hi)),
+                    None => (lo, hi),
+                });
             }
-            _ => Ok(None),
         }
+        range
     }
 
-    fn apply_axis_limits(&self, vl_spec: &mut Value, axis: &str, limits: (f64, f64)) -> Result<()> {
-        let domain = json!([limits.0, limits.1]);
+    /// Apply guide configurations to encoding channels
+    fn apply_guides_to_encoding(&self, encoding: &mut Map<String, Value>, spec: &Plot) {
+        use crate::plot::GuideType;
 
-        // Apply to encoding if present
-        if let Some(encoding) = vl_spec.get_mut("encoding") {
-            if let Some(axis_enc) = encoding.get_mut(axis) {
-                axis_enc["scale"] = json!({"domain": domain});
+        for guide in &spec.guides {
+            let channel_name = self.map_aesthetic_name(&guide.aesthetic);
+
+            // Skip if this channel doesn't exist in the encoding
+            if !encoding.contains_key(&channel_name) {
+                continue;
             }
-        }
 
-        // Apply to layers if present
-        if let Some(layers) = vl_spec.get_mut("layer") {
-            if let Some(layers_arr) = layers.as_array_mut() {
-                for layer in layers_arr {
-                    if let Some(encoding) = layer.get_mut("encoding") {
-                        if let Some(axis_enc) = encoding.get_mut(axis) {
-                            axis_enc["scale"] = json!({"domain": domain});
-                        }
+            // Handle guide type
+            match &guide.guide_type {
+                Some(GuideType::None) => {
+                    // Remove legend for this channel
+                    if let Some(channel) = encoding.get_mut(&channel_name) {
+                        channel["legend"] = json!(null);
                     }
                 }
-            }
-        }
+                Some(GuideType::Legend) => {
+                    // Apply legend properties
+                    if let Some(channel) = encoding.get_mut(&channel_name) {
+                        let mut legend = json!({});
 
-        Ok(())
-    }
+                        for (prop_name, prop_value) in &guide.properties {
+                            let value = prop_value.to_json();
 
-    fn apply_aesthetic_domain(
-        &self,
-        vl_spec: &mut Value,
-        aesthetic: &str,
-        domain: Vec<Value>,
-    ) -> Result<()> {
-        let domain_json = json!(domain);
+                            // Map property names to Vega-Lite legend properties
+                            match prop_name.as_str() {
+                                "title" => legend["title"] = value,
+                                "position" => legend["orient"] = value,
+                                "direction" => legend["direction"] = value,
+                                "nrow" => legend["rowPadding"] = value,
+                                "ncol" => legend["columnPadding"] = value,
+                                "title_position" => legend["titleAnchor"] = value,
+                                _ => {
+                                    // Pass through other properties
+                                    legend[prop_name] = value;
+                                }
+                            }
+                        }
 
-        // Apply to encoding if present
-        if let Some(encoding) = vl_spec.get_mut("encoding") {
-            if let Some(aes_enc) = encoding.get_mut(aesthetic) {
-                aes_enc["scale"] = json!({"domain": domain_json});
-            }
-        }
+                        if let Value::Object(legend) = legend {
+                            if !legend.is_empty() {
+                                merge_object(channel, "legend", legend);
+                            }
+                        }
+                    }
+                }
+                Some(GuideType::ColorBar) => {
+                    // For color bars, similar to legend but with gradient
+                    if let Some(channel) = encoding.get_mut(&channel_name) {
+                        let mut legend = json!({"type": "gradient"});
 
-        // Apply to layers if present
-        if let Some(layers) = vl_spec.get_mut("layer") {
-            if let Some(layers_arr) = layers.as_array_mut() {
-                for layer in layers_arr {
-                    if let Some(encoding) = layer.get_mut("encoding") {
-                        if let Some(aes_enc) = encoding.get_mut(aesthetic) {
-                            aes_enc["scale"] = json!({"domain": domain_json});
+                        for (prop_name, prop_value) in &guide.properties {
+                            let value = prop_value.to_json();
+
+                            match prop_name.as_str() {
+                                "title" => legend["title"] = value,
+                                "position" => legend["orient"] = value,
+                                _ => legend[prop_name] = value,
+                            }
+                        }
+
+                        if let Value::Object(legend) = legend {
+                            merge_object(channel, "legend", legend);
                         }
                     }
                 }
-            }
-        }
+                Some(GuideType::Axis) => {
+                    // Apply axis properties
+                    if let Some(channel) = encoding.get_mut(&channel_name) {
+                        let mut axis = json!({});
 
-        Ok(())
-    }
+                        for (prop_name, prop_value) in &guide.properties {
+                            let value = prop_value.to_json();
 
-    fn is_aesthetic_name(&self, name: &str) -> bool {
-        matches!(
-            name,
-            "x" | "y"
-                | "xmin"
-                | "xmax"
-                | "ymin"
-                | "ymax"
-                | "xend"
-                | "yend"
-                | "color"
-                | "colour"
-                | "fill"
-                | "stroke"
-                | "opacity"
-                | "size"
-                | "shape"
-                | "linetype"
-                | "linewidth"
-                | "width"
-                | "height"
-                | "label"
-                | "family"
-                | "fontface"
-                | "hjust"
-                | "vjust"
-        )
+                            // Map property names to Vega-Lite axis properties
+                            match prop_name.as_str() {
+                                "title" => axis["title"] = value,
+                                "text_angle" => axis["labelAngle"] = value,
+                                "text_size" => axis["labelFontSize"] = value,
+                                _ => axis[prop_name] = value,
+                            }
+                        }
+
+                        if let Value::Object(axis) = axis {
+                            if !axis.is_empty() {
+                                merge_object(channel, "axis", axis);
+                            }
+                        }
+                    }
+                }
+                None => {
+                    // No specific guide type, just apply properties generically
+                    if let Some(channel) = encoding.get_mut(&channel_name) {
+                        for (prop_name, prop_value) in &guide.properties {
+                            channel[prop_name] = prop_value.to_json();
+                        }
+                    }
+                }
+            }
+        }
     }
 
-    /// Build detail encoding from partition_by columns
-    /// Maps partition_by columns to Vega-Lite's detail channel for grouping
-    fn build_detail_encoding(&self, partition_by: &[String]) -> Option<Value> {
-        if partition_by.is_empty() {
-            return None;
+    /// Validate column references for a single layer against its specific DataFrame
+    fn validate_layer_columns(
+        &self,
+        layer: &crate::plot::Layer,
+        data: &DataFrame,
+        layer_idx: usize,
+    ) -> Result<()> {
+        let available_columns: Vec<String> = data
+            .get_column_names()
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        for (aesthetic, value) in &layer.mappings.aesthetics {
+            if let AestheticValue::Column { name: col, .. } = value {
+                if !available_columns.contains(col) {
+                    let source_desc = if let Some(src) = &layer.source {
+                        format!(" (source: {})", src.as_str())
+                    } else {
+                        " (global data)".to_string()
+                    };
+                    return Err(GgsqlError::ValidationError(format!(
+                        "Column '{}' referenced in aesthetic '{}' (layer {}{}) does not exist.\nAvailable columns: {}",
+                        col,
+                        aesthetic,
+                        layer_idx + 1,
+                        source_desc,
+                        available_columns.join(", ")
+                    )));
+                }
+            }
         }
 
-        if partition_by.len() == 1 {
-            // Single column: simple object
-            Some(json!({
-                "field": partition_by[0],
-                "type": "nominal"
-            }))
-        } else {
-            // Multiple columns: array of detail specifications
-            let details: Vec<Value> = partition_by
-                .iter()
-                .map(|col| {
-                    json!({
-                        "field": col,
-                        "type": "nominal"
-                    })
-                })
-                .collect();
-            Some(json!(details))
+        // Check partition_by columns
+        for col in &layer.partition_by {
+            if !available_columns.contains(col) {
+                let source_desc = if let Some(src) = &layer.source {
+                    format!(" (source: {})", src.as_str())
+                } else {
+                    " (global data)".to_string()
+                };
+                return Err(GgsqlError::ValidationError(format!(
+                    "Column '{}' referenced in PARTITION BY (layer {}{}) does not exist.\nAvailable columns: {}",
+                    col,
+                    layer_idx + 1,
+                    source_desc,
+                    available_columns.join(", ")
+                )));
+            }
         }
+
+        Ok(())
     }
 }
 
-impl Writer for VegaLiteWriter {
-    fn write(&self, spec: &Plot, data: &HashMap<String, DataFrame>) -> Result<String> {
-        // Validate spec before processing
-        self.validate(spec)?;
+/// Insert the entries of `entries` into the object at `channel[key]`
+///
+/// Creates the object if it is missing or not an object, so settings from
+/// scales and guides can be combined on the same axis or legend.
+fn merge_object(channel: &mut Value, key: &str, entries: Map<String, Value>) {
+    match channel.get_mut(key) {
+        Some(Value::Object(existing)) => existing.extend(entries),
+        _ => channel[key] = Value::Object(entries),
+    }
+}
 
-        // Determine which dataset key each layer should use
-        // A layer uses __layer_{idx}__ if:
-        // - It has an explicit source (FROM clause), OR
-        // - It has constants injected (no source but constants were added)
-        // Otherwise, use __global__
-        let layer_data_keys: Vec<String> = spec
-            .layers
-            .iter()
-            .enumerate()
-            .map(|(idx, _layer)| {
-                let layer_key = naming::layer_key(idx);
-                if data.contains_key(&layer_key) {
-                    layer_key
-                } else {
-                    naming::GLOBAL_DATA_KEY.to_string()
-                }
-            })
-            .collect();
+/// Longer side of a panel with a fixed aspect ratio, in pixels
+const FIXED_PANEL_SIZE: f64 = 400.0;
 
-        // Validate all required datasets exist and validate column references
-        for (layer_idx, (layer, key)) in spec.layers.iter().zip(layer_data_keys.iter()).enumerate()
-        {
-            let df = data.get(key).ok_or_else(|| {
-                GgsqlError::WriterError(format!(
-                    "Missing data source '{}' for layer {}",
-                    key,
-                    layer_idx + 1
-                ))
-            })?;
-            self.validate_layer_columns(layer, df, layer_idx)?;
+/// Clip the marks of all layers to the panel, or let them extend beyond it
+///
+/// Walks nested layers and facet specs, so layers added for secondary axes or
+/// faceting are clipped alike.
+fn set_mark_clip(vl_spec: &mut Value, clip: bool) {
+    if let Some(mark) = vl_spec["mark"].as_str().map(str::to_string) {
+        vl_spec["mark"] = json!({"type": mark});
+    }
+    if vl_spec["mark"].is_object() {
+        vl_spec["mark"]["clip"] = json!(clip);
+    }
+    if let Some(spec) = vl_spec.get_mut("spec") {
+        set_mark_clip(spec, clip);
+    }
+    if let Some(layers) = vl_spec.get_mut("layer").and_then(Value::as_array_mut) {
+        for layer in layers {
+            set_mark_clip(layer, clip);
         }
+    }
+}
 
-        // Build the base Vega-Lite spec
-        let mut vl_spec = json!({
-            "$schema": self.schema
-        });
+/// Whether a geom joins points with lines that need not be parallel to an axis
+fn draws_sloped_lines(geom: GeomType) -> bool {
+    matches!(
+        geom,
+        GeomType::Line
+            | GeomType::Path
+            | GeomType::Area
+            | GeomType::Polygon
+            | GeomType::Ribbon
+            | GeomType::Density
+            | GeomType::Smooth
+            | GeomType::Violin
+            | GeomType::Segment
+            | GeomType::Arrow
+            | GeomType::AbLine
+            | GeomType::Density2d
+            | GeomType::Contour
+            | GeomType::QqLine
+    )
+}
 
-        // Responsive plot sizing
-        vl_spec["width"] = json!("container");
-        vl_spec["height"] = json!("container");
+/// Line widths are given in millimetres, as in ggplot2, and drawn in points
+const POINTS_PER_MM: f64 = 72.27 / 25.4;
+
+/// Dash patterns of the ggplot2 line types, in the order of their codes 0-6
+const LINETYPES: [(&str, &str); 7] = [
+    ("blank", "0"),
+    ("solid", ""),
+    ("dashed", "44"),
+    ("dotted", "13"),
+    ("dotdash", "1343"),
+    ("longdash", "73"),
+    ("twodash", "2262"),
+];
+
+/// Vega-Lite shape and fill of the ggplot2 point shapes 0-25
+const SHAPE_CODES: [(&str, bool); 26] = [
+    ("square", false),
+    ("circle", false),
+    ("triangle-up", false),
+    ("cross", false),
+    ("M-1,-1L1,1M1,-1L-1,1", false),
+    ("diamond", false),
+    ("triangle-down", false),
+    ("M-1,-1H1V1H-1ZM-1,-1L1,1M1,-1L-1,1", false),
+    ("M-1,-1L1,1M1,-1L-1,1M0,-1.4L0,1.4", false),
+    ("diamond", false),
+    ("circle", false),
+    ("triangle-up", false),
+    ("square", false),
+    ("circle", false),
+    ("square", false),
+    ("square", true),
+    ("circle", true),
+    ("triangle-up", true),
+    ("diamond", true),
+    ("circle", true),
+    ("circle", true),
+    ("circle", true),
+    ("square", true),
+    ("diamond", true),
+    ("triangle-up", true),
+    ("triangle-down", true),
+];
+
+/// Default outputs of shape, line type, line width and size scales, as in ggplot2
+fn default_range(aesthetic: &str) -> Vec<Value> {
+    match aesthetic {
+        "shape" => [16, 17, 15, 3, 7, 8].iter().map(|c| json!(c)).collect(),
+        "linetype" => LINETYPES[1..].iter().map(|(name, _)| json!(name)).collect(),
+        "linewidth" | "size" => vec![json!(1.0), json!(6.0)],
+        _ => Vec::new(),
+    }
+}
 
-        // Add title if present
-        if let Some(labels) = &spec.labels {
-            if let Some(title) = labels.labels.get("title") {
-                vl_spec["title"] = json!(title);
-            }
-        }
+/// Translate a ggplot2-style aesthetic value into its Vega-Lite equivalent
+///
+/// Shapes are codes 0-25 or names such as `'triangle open'`, line types are
+/// codes 0-6, names such as `'dashed'` or hex dash patterns such as `'44'`,
+/// and line widths are in millimetres. Sizes are converted by
+/// `apply_size_units`, as they depend on the mark. Other aesthetics pass
+/// through unchanged.
+fn aesthetic_value(aesthetic: &str, value: &Value) -> Result<Value> {
+    match aesthetic {
+        "shape" => match shape_value(value) {
+            Some((shape, _)) => Ok(json!(shape)),
+            None if value.is_string() => Ok(value.clone()),
+            None => Err(GgsqlError::ValidationError(format!(
+                "{} is not a shape. Use a whole number from 0 to 25 or a shape name",
+                value
+            ))),
+        },
+        "linetype" => linetype_dash(value).map(|dash| json!(dash)),
+        "linewidth" => match value.as_f64() {
+            Some(width) => Ok(json!(width * POINTS_PER_MM)),
+            None => Err(GgsqlError::ValidationError(format!(
+                "linewidth must be a number of millimetres, got {}",
+                value
+            ))),
+        },
+        _ => Ok(value.clone()),
+    }
+}
 
-        // Build datasets - convert all DataFrames to Vega-Lite format
-        let mut datasets = Map::new();
-        for (key, df) in data {
-            let values = self.dataframe_to_values(df)?;
-            datasets.insert(key.clone(), json!(values));
-        }
-        vl_spec["datasets"] = Value::Object(datasets);
+/// Translate the shapes of a mapped shape scale and draw them open or filled
+///
+/// Whether a shape is filled is a property of the point mark. When the shapes
+/// of a scale disagree, the mark is filled and the open shapes are given a
+/// transparent fill through a `fillOpacity` channel on the same field.
+fn apply_point_shapes(layer_spec: &mut Value, encoding: &mut Map<String, Value>) -> Result<()> {
+    let Some(shape) = encoding.get_mut("shape") else {
+        return Ok(());
+    };
+    let Some(range) = shape
+        .get_mut("scale")
+        .and_then(|scale| scale.get_mut("range"))
+        .and_then(|range| range.as_array_mut())
+    else {
+        return Ok(());
+    };
+    // Vega-Lite shape names and SVG paths are drawn like Vega-Lite draws them
+    let filled: Vec<bool> = range
+        .iter()
+        .map(|value| shape_value(value).is_some_and(|(_, filled)| filled))
+        .collect();
+    for value in range.iter_mut() {
+        *value = aesthetic_value("shape", value)?;
+    }
+    if layer_spec["mark"] != "point" || filled.is_empty() {
+        return Ok(());
+    }
 
-        // Determine if faceting requires unified data (no per-layer data entries)
-        let faceting_mode = spec.facet.is_some();
+    if filled.iter().all(|f| *f == filled[0]) {
+        layer_spec["mark"] = json!({"type": "point", "filled": filled[0]});
+        return Ok(());
+    }
+    let mut fill_opacity = json!({
+        "field": shape["field"],
+        "type": shape["type"],
+        "scale": {"range": filled.iter().map(|f| if *f { 1.0 } else { 0.0 }).collect::<Vec<_>>()},
+        "legend": null
+    });
+    if let Some(domain) = shape["scale"].get("domain") {
+        fill_opacity["scale"]["domain"] = domain.clone();
+    }
+    encoding.insert("fillOpacity".to_string(), fill_opacity);
+    layer_spec["mark"] = json!({"type": "point", "filled": true});
+    Ok(())
+}
 
-        // If faceting, validate all layers use the same data source
-        if faceting_mode {
-            let unique_keys: std::collections::HashSet<_> = layer_data_keys.iter().collect();
-            if unique_keys.len() > 1 {
-                return Err(GgsqlError::ValidationError(
-                    "Faceting requires all layers to use the same data source. \
-                     Layers with different FROM sources cannot be faceted."
-                        .to_string(),
-                ));
-            }
+/// Convert sizes from millimetres, as in ggplot2, into Vega-Lite sizes
+///
+/// Text is sized by its font size in points. Other marks are sized by the
+/// area of their bounding box in square pixels, the square of the size.
+fn apply_size_units(encoding: &mut Map<String, Value>, is_text: bool) -> Result<()> {
+    let Some(size) = encoding.get_mut("size") else {
+        return Ok(());
+    };
+    let convert = |value: &Value| -> Result<Value> {
+        let mm = value.as_f64().ok_or_else(|| {
+            GgsqlError::ValidationError(format!(
+                "size must be a number of millimetres, got {}",
+                value
+            ))
+        })?;
+        let points = mm * POINTS_PER_MM;
+        Ok(json!(if is_text { points } else { points * points }))
+    };
+    if let Some(value) = size.get("value") {
+        size["value"] = convert(value)?;
+    } else if let Some(range) = size
+        .get_mut("scale")
+        .and_then(|scale| scale.get_mut("range"))
+        .and_then(|range| range.as_array_mut())
+    {
+        for value in range.iter_mut() {
+            *value = convert(value)?;
         }
+    }
+    Ok(())
+}
 
-        // Build layers array
-        let mut layers = Vec::new();
-        for (layer_idx, layer) in spec.layers.iter().enumerate() {
-            let data_key = &layer_data_keys[layer_idx];
-            let df = data.get(data_key).unwrap();
-
-            let mut layer_spec = if faceting_mode {
-                // No per-layer data when faceting - uses top-level data
-                json!({
-                    "mark": self.geom_to_mark(&layer.geom)
-                })
-            } else {
-                json!({
-                    "data": {"name": data_key},
-                    "mark": self.geom_to_mark(&layer.geom)
-                })
-            };
+/// Vega-Lite shape and fill of a ggplot2 shape code or name
+///
+/// Returns `None` for values that aren't ggplot2 shapes, which are passed on
+/// as Vega-Lite shape names or SVG paths.
+fn shape_value(value: &Value) -> Option<(&'static str, bool)> {
+    if let Some(code) = value.as_f64() {
+        let is_code = code.fract() == 0.0 && (0.0..SHAPE_CODES.len() as f64).contains(&code);
+        return is_code.then(|| SHAPE_CODES[code as usize]);
+    }
+    let name = value.as_str()?.to_lowercase();
+    let filled = !name.ends_with(" open");
+    let base = name
+        .trim_end_matches(" open")
+        .trim_end_matches(" filled")
+        .trim();
+    let shape = match base {
+        "circle" | "bullet" => "circle",
+        "square" => "square",
+        "diamond" => "diamond",
+        "triangle" => "triangle-up",
+        "triangle down" => "triangle-down",
+        "plus" => return Some(("cross", false)),
+        "cross" => return Some((SHAPE_CODES[4].0, false)),
+        "asterisk" => return Some((SHAPE_CODES[8].0, false)),
+        _ => return None,
+    };
+    Some((shape, filled))
+}
 
-            // For Bar geom, set mark with width parameter
-            if layer.geom.geom_type() == GeomType::Bar {
-                use crate::plot::ParameterValue;
-                let width = layer
-                    .parameters
-                    .get("width")
-                    .and_then(|p| match p {
-                        ParameterValue::Number(n) => Some(*n),
-                        _ => None,
-                    })
-                    .unwrap_or(0.9);
-                layer_spec["mark"] = json!({
-                    "type": "bar",
-                    "width": {"band": width}
-                });
-            }
+/// Vega-Lite dash array of a ggplot2 line type
+///
+/// Hex dash patterns give the lengths of dashes and gaps in units of the
+/// default line width of 2 pixels.
+fn linetype_dash(value: &Value) -> Result<Vec<f64>> {
+    let pattern = match value {
+        Value::Number(code) => code
+            .as_u64()
+            .and_then(|c| LINETYPES.get(c as usize))
+            .map(|(_, pattern)| pattern.to_string()),
+        Value::String(name) => LINETYPES
+            .iter()
+            .find(|(candidate, _)| candidate.eq_ignore_ascii_case(name))
+            .map(|(_, pattern)| pattern.to_string())
+            .or_else(|| {
+                let is_pattern = matches!(name.len(), 2 | 4 | 6 | 8)
+                    && name.chars().all(|c| c.is_ascii_hexdigit() && c != '0');
+                is_pattern.then(|| name.clone())
+            }),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        GgsqlError::ValidationError(format!(
+            "{} is not a line type. Use blank, solid, dashed, dotted, dotdash, longdash, \
+             twodash, a code from 0 to 6 or a hex dash pattern such as '44'",
+            value
+        ))
+    })?;
+    Ok(match pattern.as_str() {
+        "" => vec![1.0, 0.0],
+        "0" => vec![0.0, 1.0],
+        pattern => pattern
+            .chars()
+            .filter_map(|c| c.to_digit(16))
+            .map(|d| 2.0 * d as f64)
+            .collect(),
+    })
+}
 
-            // Add window transform for Path geoms to preserve data order
-            // (Line geom uses Vega-Lite's default x-axis sorting)
-            if layer.geom.geom_type() == GeomType::Path {
-                let mut window_transform = json!({
-                    "window": [{"op": "row_number", "as": naming::ORDER_COLUMN}]
-                });
+/// d3 format string for a named SCALE label format
+///
+/// Unknown names are assumed to be d3 format strings already.
+fn number_format(name: &str) -> &str {
+    match name {
+        "percent" => ".0%",
+        "comma" => ",",
+        "currency" | "dollar" => "$,.2f",
+        "si" => ".3~s",
+        "scientific" => ".2e",
+        other => other,
+    }
+}
 
-                // Add groupby if partition_by is present (restarts numbering per group)
-                if !layer.partition_by.is_empty() {
-                    window_transform["groupby"] = json!(layer.partition_by);
+/// Display time zones of the columns of a dataset, from the `timezone` setting
+/// of the scales the columns are mapped to
+fn display_timezones(
+    spec: &Plot,
+    data_key: &str,
+    layer_data_keys: &[String],
+) -> Result<HashMap<String, chrono_tz::Tz>> {
+    let mut timezones = HashMap::new();
+    for scale in &spec.scales {
+        let timezone = match scale.properties.get("timezone") {
+            None => continue,
+            Some(ParameterValue::String(name)) => name.parse::<chrono_tz::Tz>().map_err(|_| {
+                GgsqlError::ValidationError(format!(
+                    "Unknown time zone '{}' in SCALE {}",
+                    name, scale.aesthetic
+                ))
+            })?,
+            Some(_) => {
+                return Err(GgsqlError::ValidationError(format!(
+                    "SCALE {} timezone must be a time zone name such as 'Europe/Oslo'",
+                    scale.aesthetic
+                )))
+            }
+        };
+        let layers = spec
+            .layers
+            .iter()
+            .zip(layer_data_keys)
+            .filter(|(_, key)| key.as_str() == data_key);
+        for (layer, _) in layers {
+            for (aesthetic, value) in &layer.mappings.aesthetics {
+                if GeomAesthetics::primary_aesthetic(aesthetic) != scale.aesthetic {
+                    continue;
+                }
+                if let Some(column) = value.column_name() {
+                    timezones.insert(column.to_string(), timezone);
                 }
-
-                layer_spec["transform"] = json!([window_transform]);
             }
-
-            // Build encoding for this layer
-            // Track which aesthetic families have been titled to ensure only one title per family
-            let mut encoding = Map::new();
-            let mut titled_families: std::collections::HashSet<String> =
-                std::collections::HashSet::new();
-            for (aesthetic, value) in &layer.mappings.aesthetics {
-                let channel_name = self.map_aesthetic_name(aesthetic);
-                let channel_encoding =
-                    self.build_encoding_channel(aesthetic, value, df, spec, &mut titled_families)?;
-                encoding.insert(channel_name, channel_encoding);
+        }
+    }
+    Ok(timezones)
+}
+
+/// Translate a strftime-style format into a d3 time format
+///
+/// d3 shares most directives with strftime; the composite ones it lacks are
+/// expanded. Time zone names (`%Z`) can't be shown by d3.
+fn time_format(format: &str) -> Result<String> {
+    let mut translated = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            translated.push(c);
+            continue;
+        }
+        let mut directive = chars.next().ok_or_else(|| {
+            GgsqlError::ValidationError(format!("Incomplete directive in date format '{}'", format))
+        })?;
+        // Padding modifiers are shared by strftime and d3
+        let mut padding = String::new();
+        if matches!(directive, '-' | '_' | '0') {
+            padding.push(directive);
+            directive = chars.next().ok_or_else(|| {
+                GgsqlError::ValidationError(format!(
+                    "Incomplete directive in date format '{}'",
+                    format
+                ))
+            })?;
+        }
+        match directive {
+            'F' => translated.push_str("%Y-%m-%d"),
+            'T' => translated.push_str("%H:%M:%S"),
+            'R' => translated.push_str("%H:%M"),
+            'D' => translated.push_str("%m/%d/%y"),
+            'h' => translated.push_str("%b"),
+            'k' => translated.push_str("%_H"),
+            'l' => translated.push_str("%_I"),
+            'P' => translated.push_str("%p"),
+            'z' => translated.push_str("%Z"),
+            'n' => translated.push('\n'),
+            't' => translated.push('\t'),
+            'Z' => {
+                return Err(GgsqlError::ValidationError(format!(
+                "Time zone names (%Z) are not supported in date format '{}', use %z for the offset",
+                format
+            )))
             }
+            other => {
+                translated.push('%');
+                translated.push_str(&padding);
+                translated.push(other);
+            }
+        }
+    }
+    Ok(translated)
+}
 
-            // Also add aesthetic parameters from SETTING as literal encodings
-            // (e.g., SETTING color => 'red' becomes {"color": {"value": "red"}})
-            // Only parameters that are supported aesthetics for this geom type are included
-            let supported_aesthetics = layer.geom.aesthetics().supported;
-            for (param_name, param_value) in &layer.parameters {
-                if supported_aesthetics.contains(&param_name.as_str()) {
-                    let channel_name = self.map_aesthetic_name(param_name);
-                    // Only add if not already set by MAPPING (MAPPING takes precedence)
-                    if !encoding.contains_key(&channel_name) {
-                        encoding.insert(channel_name, json!({"value": param_value.to_json()}));
+/// Vega expression labelling each break with its label, other values as usual
+fn label_expr(breaks: &[ArrayElement], labels: &[ArrayElement]) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"));
+    let mut expr = String::new();
+    for (value, label) in breaks.iter().zip(labels) {
+        let value = match value {
+            ArrayElement::String(s) => quote(s),
+            other => other.to_json().to_string(),
+        };
+        let label = match label {
+            ArrayElement::String(s) => quote(s),
+            other => quote(&other.to_json().to_string()),
+        };
+        expr.push_str(&format!("datum.value === {} ? {} : ", value, label));
+    }
+    expr.push_str("datum.label");
+    expr
+}
+
+/// Combine the datasets of layers from different sources for faceting
+///
+/// Each row names its dataset in the source column, which the layers filter
+/// on. Rows of a dataset missing facet variables are repeated in every panel
+/// of those variables, as ggplot2 does.
+fn combine_facet_data(
+    data_keys: &[String],
+    variables: &[String],
+    data: &HashMap<String, DataFrame>,
+    vl_spec: &mut Value,
+) -> Result<()> {
+    let has_column = |key: &String, variable: &String| {
+        data.get(key).is_some_and(|df| df.column(variable).is_ok())
+    };
+    let rows_of = |key: &str| -> Result<&Vec<Value>> {
+        vl_spec["datasets"][key]
+            .as_array()
+            .ok_or_else(|| GgsqlError::InternalError(format!("Missing dataset '{}'", key)))
+    };
+    // Distinct combinations of `variables` in the rows of `keys`
+    let combinations = |keys: &[&String], variables: &[&String]| -> Result<Vec<Vec<Value>>> {
+        let mut combinations: Vec<Vec<Value>> = Vec::new();
+        for key in keys {
+            for row in rows_of(key)? {
+                let combination: Vec<Value> = variables
+                    .iter()
+                    .map(|v| row.get(v.as_str()).cloned().unwrap_or(Value::Null))
+                    .collect();
+                if !combinations.contains(&combination) {
+                    combinations.push(combination);
+                }
+            }
+        }
+        Ok(combinations)
+    };
+
+    let mut combined = Vec::new();
+    for key in data_keys {
+        let missing: Vec<&String> = variables.iter().filter(|v| !has_column(key, v)).collect();
+        // Panels of the missing variables: the combinations seen in datasets
+        // having all of them, otherwise every combination of their values
+        let sources: Vec<&String> = data_keys
+            .iter()
+            .filter(|k| missing.iter().all(|v| has_column(k, v)))
+            .collect();
+        let panels = if missing.is_empty() {
+            vec![Vec::new()]
+        } else if !sources.is_empty() {
+            combinations(&sources, &missing)?
+        } else {
+            let mut panels = vec![Vec::new()];
+            for variable in &missing {
+                let sources: Vec<&String> = data_keys
+                    .iter()
+                    .filter(|k| has_column(k, variable))
+                    .collect();
+                let values = combinations(&sources, &[*variable])?;
+                panels = panels
+                    .iter()
+                    .flat_map(|panel| {
+                        values.iter().map(move |value| {
+                            let mut panel: Vec<Value> = panel.clone();
+                            panel.extend(value.iter().cloned());
+                            panel
+                        })
+                    })
+                    .collect();
+            }
+            panels
+        };
+
+        for row in rows_of(key)? {
+            for panel in &panels {
+                let mut row = row.clone();
+                for (variable, value) in missing.iter().zip(panel) {
+                    row[variable.as_str()] = value.clone();
+                }
+                row[naming::SOURCE_COLUMN] = json!(key);
+                combined.push(row);
+            }
+        }
+    }
+    // The combined dataset replaces those of the layers
+    let datasets = vl_spec["datasets"].as_object_mut().unwrap();
+    for key in data_keys {
+        datasets.remove(key);
+    }
+    datasets.insert(naming::FACET_DATA_KEY.to_string(), Value::Array(combined));
+    Ok(())
+}
+
+/// Lay out wrapped panels in `ncol` columns or `nrow` rows
+///
+/// Vega-Lite fills rows first, so panels filling columns first (`dir => 'v'`)
+/// are put in the matching order. That's only possible if no gaps are left
+/// before the last panel.
+fn apply_wrap_layout(
+    facet: &Facet,
+    field: &mut Value,
+    panels: &[Value],
+    vl_spec: &mut Value,
+) -> Result<()> {
+    let n = panels.len();
+    let ncol = facet.layout_count("ncol");
+    let nrow = facet.layout_count("nrow");
+    if let (Some(ncol), Some(nrow)) = (ncol, nrow) {
+        if ncol * nrow < n {
+            return Err(GgsqlError::ValidationError(format!(
+                "FACET WRAP has {} panels, more than fit in {} rows of {} columns",
+                n, nrow, ncol
+            )));
+        }
+    }
+    let columns = match (ncol, nrow) {
+        (Some(ncol), _) => ncol,
+        (None, Some(nrow)) => n.div_ceil(nrow).max(1),
+        (None, None) if facet.is_vertical() => ((n as f64).sqrt().ceil() as usize).max(1),
+        (None, None) => return Ok(()),
+    };
+    vl_spec["columns"] = json!(columns);
+
+    if facet.is_vertical() {
+        let rows = nrow.unwrap_or_else(|| n.div_ceil(columns));
+        let mut placed: Vec<(usize, &Value)> = panels
+            .iter()
+            .enumerate()
+            .map(|(i, panel)| ((i % rows) * columns + i / rows, panel))
+            .collect();
+        placed.sort_by_key(|(position, _)| *position);
+        if placed
+            .iter()
+            .enumerate()
+            .any(|(i, (position, _))| i != *position)
+        {
+            return Err(GgsqlError::ValidationError(format!(
+                "FACET WRAP can't fill {} rows of {} columns with {} panels column by column \
+                 without leaving gaps. Change ncol or nrow",
+                rows, columns, n
+            )));
+        }
+        field["sort"] = json!(placed
+            .into_iter()
+            .map(|(_, panel)| panel.clone())
+            .collect::<Vec<_>>());
+    }
+    Ok(())
+}
+
+/// Order of two data values: numbers by value, missing values last
+fn compare_json(a: &Value, b: &Value) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        _ => json_label(a).cmp(&json_label(b)),
+    }
+}
+
+/// Text of a data value in a panel label
+fn json_label(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "NA".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Break a label into lines of at most `width` characters at spaces
+fn wrap_label(text: &str, width: usize) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines.join("\n")
+}
+
+/// Vega-Lite `config` drawing a plot in the given theme style
+fn theme_config(style: &ThemeStyle) -> Value {
+    let text = json!({
+        "titleFontSize": style.text_size,
+        "titleColor": style.text_colour,
+        "labelFontSize": style.axis_text_size,
+        "labelColor": style.text_colour,
+    });
+
+    let mut axis = text.clone();
+    if !style.axes {
+        axis["disable"] = json!(true);
+    }
+    axis["grid"] = json!(style.panel_grid.is_some());
+    if let Some(colour) = &style.panel_grid {
+        axis["gridColor"] = json!(colour);
+    }
+    axis["domain"] = json!(style.axis_line.is_some());
+    if let Some(colour) = &style.axis_line {
+        axis["domainColor"] = json!(colour);
+        axis["domainWidth"] = json!(style.axis_line_width);
+    }
+    axis["ticks"] = json!(style.axis_ticks.is_some());
+    if let Some(colour) = &style.axis_ticks {
+        axis["tickColor"] = json!(colour);
+    }
+
+    let mut legend = text.clone();
+    if style.legend_position == "none" {
+        legend["disable"] = json!(true);
+    } else {
+        legend["orient"] = json!(style.legend_position);
+    }
+    if let Some(direction) = &style.legend_direction {
+        legend["direction"] = json!(direction);
+    }
+    if let Some(colour) = &style.legend_background {
+        legend["fillColor"] = json!(colour);
+    }
+
+    json!({
+        "background": style.background,
+        "font": style.text_family,
+        "padding": style.plot_margin,
+        "view": {
+            "fill": style.panel_background,
+            "stroke": style.panel_border,
+        },
+        "axis": axis,
+        "legend": legend,
+        "header": {
+            "titleFontSize": style.text_size,
+            "titleColor": style.text_colour,
+            "labelFontSize": style.text_size,
+            "labelColor": style.text_colour,
+        },
+        "title": {
+            "fontSize": style.title_size,
+            "color": style.text_colour,
+            "subtitleColor": style.text_colour,
+        },
+        "facet": {"spacing": style.panel_spacing},
+        "concat": {"spacing": style.panel_spacing},
+    })
+}
+
+impl Default for VegaLiteWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Coordinate transformation methods
+impl VegaLiteWriter {
+    /// Apply coordinate transformations to the spec and data
+    /// Returns (possibly transformed DataFrame, possibly modified spec)
+    fn apply_coord_transforms(
+        &self,
+        spec: &Plot,
+        layer_data: &HashMap<String, DataFrame>,
+        layer_data_keys: &[String],
+        vl_spec: &mut Value,
+    ) -> Result<Option<DataFrame>> {
+        let data = layer_data.get(&layer_data_keys[0]).unwrap();
+        if let Some(ref coord) = spec.coord {
+            coord.validate()?;
+            let transformed_df = match coord.coord_type {
+                CoordType::Cartesian => {
+                    self.apply_cartesian_coord(coord, vl_spec, data)?;
+                    None // No DataFrame transformation needed
+                }
+                CoordType::Flip => {
+                    self.apply_flip_coord(vl_spec)?;
+                    None // No DataFrame transformation needed
+                }
+                CoordType::Polar => {
+                    // Polar requires DataFrame transformation for percentages
+                    Some(self.apply_polar_coord(coord, spec, data, vl_spec)?)
+                }
+                CoordType::Fixed | CoordType::QuickMap => {
+                    self.apply_cartesian_coord(coord, vl_spec, data)?;
+                    self.apply_fixed_coord(spec, coord, layer_data, layer_data_keys, vl_spec)?;
+                    None
+                }
+                CoordType::Trans => {
+                    self.apply_cartesian_coord(coord, vl_spec, data)?;
+                    self.apply_trans_coord(spec, coord, vl_spec)?;
+                    None
+                }
+                // Rejected by Coord::validate
+                CoordType::Map => None,
+            };
+            Ok(transformed_df)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Apply Cartesian coordinate properties (xlim, ylim, aesthetic domains)
+    fn apply_cartesian_coord(
+        &self,
+        coord: &Coord,
+        vl_spec: &mut Value,
+        _data: &DataFrame,
+    ) -> Result<()> {
+        // Apply xlim/ylim to scale domains
+        for (prop_name, prop_value) in &coord.properties {
+            match prop_name.as_str() {
+                "xlim" => {
+                    if let Some(limits) = self.extract_limits(prop_value)? {
+                        self.apply_axis_limits(vl_spec, "x", limits)?;
+                    }
+                }
+                "ylim" => {
+                    if let Some(limits) = self.extract_limits(prop_value)? {
+                        self.apply_axis_limits(vl_spec, "y", limits)?;
+                    }
+                }
+                // Under COORD trans, x and y name transforms; see apply_trans_coord
+                "x" | "y" if coord.coord_type == CoordType::Trans => {}
+                _ if self.is_aesthetic_name(prop_name) => {
+                    // Aesthetic domain specification
+                    if let Some(domain) = self.extract_domain(prop_value)? {
+                        self.apply_aesthetic_domain(vl_spec, prop_name, domain)?;
                     }
                 }
+                _ => {
+                    // ratio is applied by apply_coord_transforms, clip by write
+                }
             }
+        }
 
-            // Add detail encoding for partition_by columns (grouping)
-            if let Some(detail) = self.build_detail_encoding(&layer.partition_by) {
-                encoding.insert("detail".to_string(), detail);
+        Ok(())
+    }
+
+    /// Keep the aspect ratio of the data for `COORD fixed` and `COORD quickmap`
+    ///
+    /// One unit of y is drawn `ratio` times as long as one unit of x. The panel
+    /// gets a fixed size instead of filling its container, and position scales
+    /// without a domain get one so that the drawn ranges match the computed ones.
+    /// `quickmap` takes x and y as longitude and latitude and derives the ratio
+    /// from the latitude in the middle of the plot.
+    fn apply_fixed_coord(
+        &self,
+        spec: &Plot,
+        coord: &Coord,
+        data: &HashMap<String, DataFrame>,
+        layer_data_keys: &[String],
+        vl_spec: &mut Value,
+    ) -> Result<()> {
+        let name = if coord.coord_type == CoordType::QuickMap {
+            "quickmap"
+        } else {
+            "fixed"
+        };
+        if spec
+            .facet
+            .as_ref()
+            .is_some_and(|f| f.scales().is_free("x") || f.scales().is_free("y"))
+        {
+            return Err(GgsqlError::ValidationError(format!(
+                "COORD {} can't be combined with free FACET scales",
+                name
+            )));
+        }
+
+        let (x_lo, x_hi) = self.axis_span(spec, "x", name, data, layer_data_keys, vl_spec)?;
+        let (y_lo, y_hi) = self.axis_span(spec, "y", name, data, layer_data_keys, vl_spec)?;
+        let ratio = match coord.coord_type {
+            CoordType::QuickMap => 1.0 / ((y_lo + y_hi) / 2.0).to_radians().cos(),
+            _ => coord.ratio()?,
+        };
+        let span = |lo: f64, hi: f64| if hi > lo { hi - lo } else { 1.0 };
+        let aspect = ratio * span(y_lo, y_hi) / span(x_lo, x_hi);
+        if !aspect.is_finite() || aspect <= 0.0 {
+            return Err(GgsqlError::ValidationError(format!(
+                "COORD {} can't keep the aspect ratio of this data",
+                name
+            )));
+        }
+
+        let (width, height) = if aspect <= 1.0 {
+            (FIXED_PANEL_SIZE, FIXED_PANEL_SIZE * aspect)
+        } else {
+            (FIXED_PANEL_SIZE / aspect, FIXED_PANEL_SIZE)
+        };
+        vl_spec["width"] = json!(width.round());
+        vl_spec["height"] = json!(height.round());
+        Ok(())
+    }
+
+    /// Range of a position axis as drawn
+    ///
+    /// This is the domain set on the axis, or else the range of its trained
+    /// scale, which is then set as the domain. Without a trained scale the range
+    /// comes from the data mapped to the axis. Discrete axes span one unit per
+    /// value.
+    fn axis_span(
+        &self,
+        spec: &Plot,
+        axis: &str,
+        coord_name: &str,
+        data: &HashMap<String, DataFrame>,
+        layer_data_keys: &[String],
+        vl_spec: &mut Value,
+    ) -> Result<(f64, f64)> {
+        let mut channels: Vec<&mut Value> = vl_spec["layer"]
+            .as_array_mut()
+            .into_iter()
+            .flatten()
+            .filter_map(|layer| layer.get_mut("encoding").and_then(|e| e.get_mut(axis)))
+            .filter(|channel| channel.get("field").is_some())
+            .collect();
+        for channel in &channels {
+            if let Some([lo, hi]) = channel["scale"]["domain"].as_array().map(|d| d.as_slice()) {
+                if let (Some(lo), Some(hi)) = (lo.as_f64(), hi.as_f64()) {
+                    return Ok((lo.min(hi), lo.max(hi)));
+                }
+            }
+        }
+
+        let (lo, hi) = match spec.find_trained_scale(axis) {
+            Some(trained) => match (&trained.kind, &trained.domain) {
+                (ScaleKind::Discrete, Some(ScaleDomain::Values(values))) => {
+                    return Ok((0.0, values.len() as f64))
+                }
+                (ScaleKind::Continuous, Some(ScaleDomain::Range(lo, hi)))
+                | (ScaleKind::Temporal, Some(ScaleDomain::Range(lo, hi))) => (*lo, *hi),
+                _ => {
+                    return Err(GgsqlError::ValidationError(format!(
+                        "COORD {} needs a continuous or discrete {} axis",
+                        coord_name, axis
+                    )))
+                }
+            },
+            None => match self.data_range(spec, axis, data, layer_data_keys) {
+                Some(range) => range,
+                None => {
+                    let columns = self.mapped_columns(spec, axis, data, layer_data_keys);
+                    if columns.is_empty() {
+                        return Err(GgsqlError::ValidationError(format!(
+                            "COORD {} needs data mapped to both x and y",
+                            coord_name
+                        )));
+                    }
+                    let mut values = std::collections::HashSet::new();
+                    for column in columns {
+                        if let Ok(column) = column.cast(&DataType::String) {
+                            if let Ok(strings) = column.str() {
+                                values.extend(strings.into_iter().flatten().map(str::to_string));
+                            }
+                        }
+                    }
+                    return Ok((0.0, values.len() as f64));
+                }
+            },
+        };
+        for channel in channels.iter_mut() {
+            merge_object(
+                channel,
+                "scale",
+                Map::from_iter([
+                    ("domain".to_string(), json!([lo, hi])),
+                    ("nice".to_string(), json!(false)),
+                ]),
+            );
+        }
+        Ok((lo, hi))
+    }
+
+    /// Transform position scales for `COORD trans`
+    ///
+    /// The data, and any statistics computed from it, stay untransformed; only
+    /// the scales drawing them change. This draws points, rectangles and
+    /// axis-parallel lines exactly as a transformed coordinate system would, but
+    /// Vega-Lite still joins points with straight lines where the transformed
+    /// system bends them, so layers drawing such lines are rejected.
+    fn apply_trans_coord(&self, spec: &Plot, coord: &Coord, vl_spec: &mut Value) -> Result<()> {
+        for axis in ["x", "y"] {
+            let transform = coord.transform(axis)?;
+            if transform.bends_lines() {
+                if let Some(layer) = spec
+                    .layers
+                    .iter()
+                    .find(|layer| draws_sloped_lines(layer.geom.geom_type()))
+                {
+                    return Err(GgsqlError::ValidationError(format!(
+                        "COORD trans can't transform {} for a {} layer, whose lines it would \
+                         have to bend. Transform the SCALE of {} instead",
+                        axis,
+                        layer.geom.geom_type(),
+                        axis
+                    )));
+                }
             }
+            let scale = match transform {
+                CoordTransform::Identity => continue,
+                CoordTransform::Log10 => json!({"type": "log", "base": 10}),
+                CoordTransform::Log => json!({"type": "log", "base": std::f64::consts::E}),
+                CoordTransform::Log2 => json!({"type": "log", "base": 2}),
+                CoordTransform::Sqrt => json!({"type": "sqrt"}),
+                CoordTransform::Reverse => json!({"reverse": true}),
+            };
 
-            // Add y2 baseline when x2 is present (for histogram bars)
-            // Vega-Lite requires y2 when using x2 for bar marks
-            if encoding.contains_key("x2") && !encoding.contains_key("y2") {
-                encoding.insert("y2".to_string(), json!({"datum": 0}));
+            let trained = spec.find_trained_scale(axis);
+            if trained.is_some_and(|t| t.kind != ScaleKind::Continuous) {
+                return Err(GgsqlError::ValidationError(format!(
+                    "COORD trans can only transform a continuous {} axis",
+                    axis
+                )));
+            }
+            if transform.is_log() {
+                if let Some(ScaleDomain::Range(lo, _)) = trained.and_then(|t| t.domain.as_ref()) {
+                    if *lo <= 0.0 {
+                        return Err(GgsqlError::ValidationError(format!(
+                            "COORD trans can't log-transform {}: it has values of 0 or less",
+                            axis
+                        )));
+                    }
+                }
             }
 
-            // Add order encoding for Path geoms (preserves data order instead of x-axis sorting)
-            if layer.geom.geom_type() == GeomType::Path {
-                encoding.insert(
-                    "order".to_string(),
-                    json!({
-                        "field": naming::ORDER_COLUMN,
-                        "type": "quantitative"
-                    }),
-                );
-            }
+            let Some(layers) = vl_spec["layer"].as_array_mut() else {
+                continue;
+            };
+            for layer in layers {
+                let Some(channel) = layer
+                    .get_mut("encoding")
+                    .and_then(|e| e.get_mut(axis))
+                    .filter(|c| c.get("field").is_some())
+                else {
+                    continue;
+                };
+                if channel["scale"].get("type").is_some_and(|t| *t != "linear") {
+                    return Err(GgsqlError::ValidationError(format!(
+                        "{} is transformed by both its SCALE and COORD trans",
+                        axis
+                    )));
+                }
+                merge_object(channel, "scale", scale.as_object().unwrap().clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply Flip coordinate transformation (swap x and y)
+    fn apply_flip_coord(&self, vl_spec: &mut Value) -> Result<()> {
+        // Handle single layer
+        if let Some(encoding) = vl_spec.get_mut("encoding") {
+            if let Some(enc_obj) = encoding.as_object_mut() {
+                // Swap x and y encodings
+                if let (Some(x), Some(y)) = (enc_obj.remove("x"), enc_obj.remove("y")) {
+                    enc_obj.insert("x".to_string(), y);
+                    enc_obj.insert("y".to_string(), x);
+                }
+            }
+        }
+
+        // Handle multi-layer
+        if let Some(layers) = vl_spec.get_mut("layer") {
+            if let Some(layers_arr) = layers.as_array_mut() {
+                for layer in layers_arr {
+                    if let Some(encoding) = layer.get_mut("encoding") {
+                        if let Some(enc_obj) = encoding.as_object_mut() {
+                            if let (Some(x), Some(y)) = (enc_obj.remove("x"), enc_obj.remove("y")) {
+                                enc_obj.insert("x".to_string(), y);
+                                enc_obj.insert("y".to_string(), x);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply Polar coordinate transformation (bar→arc, point→arc with radius)
+    fn apply_polar_coord(
+        &self,
+        coord: &Coord,
+        spec: &Plot,
+        _data: &DataFrame,
+        vl_spec: &mut Value,
+    ) -> Result<DataFrame> {
+        // Get theta field (defaults to 'y')
+        let theta_field = coord
+            .properties
+            .get("theta")
+            .and_then(|v| match v {
+                ParameterValue::String(s) => Some(s.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| "y".to_string());
+
+        // Convert geoms to polar equivalents
+        self.convert_geoms_to_polar(spec, vl_spec, &theta_field)?;
+
+        // No DataFrame transformation needed - Vega-Lite handles polar math
+        Ok(_data.clone())
+    }
+
+    /// Convert geoms to polar equivalents (bar→arc, point→arc with radius)
+    fn convert_geoms_to_polar(
+        &self,
+        spec: &Plot,
+        vl_spec: &mut Value,
+        theta_field: &str,
+    ) -> Result<()> {
+        // Determine which aesthetic (x or y) maps to theta
+        // Default: y maps to theta (pie chart style)
+        let theta_aesthetic = theta_field;
+
+        // Handle single layer
+        if let Some(mark) = vl_spec.get_mut("mark") {
+            *mark = self.convert_mark_to_polar(mark, spec)?;
+
+            // Update encoding for polar
+            if let Some(encoding) = vl_spec.get_mut("encoding") {
+                self.update_encoding_for_polar(encoding, theta_aesthetic)?;
+            }
+        }
+
+        // Handle multi-layer
+        if let Some(layers) = vl_spec.get_mut("layer") {
+            if let Some(layers_arr) = layers.as_array_mut() {
+                for layer in layers_arr {
+                    if let Some(mark) = layer.get_mut("mark") {
+                        *mark = self.convert_mark_to_polar(mark, spec)?;
+
+                        if let Some(encoding) = layer.get_mut("encoding") {
+                            self.update_encoding_for_polar(encoding, theta_aesthetic)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Convert a mark type to its polar equivalent
+    fn convert_mark_to_polar(&self, mark: &Value, _spec: &Plot) -> Result<Value> {
+        let mark_str = if mark.is_string() {
+            mark.as_str().unwrap()
+        } else if let Some(mark_type) = mark.get("type") {
+            mark_type.as_str().unwrap_or("bar")
+        } else {
+            "bar"
+        };
+
+        // Convert geom types to polar equivalents
+        match mark_str {
+            "bar" | "col" => {
+                // Bar/col in polar becomes arc (pie/donut slices)
+                Ok(json!("arc"))
+            }
+            "point" => {
+                // Points in polar can stay as points or become arcs with radius
+                // For now, keep as points (they'll plot at radius based on value)
+                Ok(json!("point"))
+            }
+            "line" => {
+                // Lines in polar become circular/spiral lines
+                Ok(json!("line"))
+            }
+            "area" => {
+                // Area in polar becomes arc with radius
+                Ok(json!("arc"))
+            }
+            _ => {
+                // Other geoms: keep as-is or convert to arc
+                Ok(json!("arc"))
+            }
+        }
+    }
+
+    /// Update encoding channels for polar coordinates
+    fn update_encoding_for_polar(&self, encoding: &mut Value, theta_aesthetic: &str) -> Result<()> {
+        let enc_obj = encoding
+            .as_object_mut()
+            .ok_or_else(|| GgsqlError::WriterError("Encoding is not an object".to_string()))?;
+
+        // Map the theta aesthetic to theta channel
+        if theta_aesthetic == "y" {
+            // Standard pie chart: y → theta, x → color/category
+            if let Some(y_enc) = enc_obj.remove("y") {
+                enc_obj.insert("theta".to_string(), y_enc);
+            }
+            // Map x to color if not already mapped, and remove x from positional encoding
+            if !enc_obj.contains_key("color") {
+                if let Some(x_enc) = enc_obj.remove("x") {
+                    enc_obj.insert("color".to_string(), x_enc);
+                }
+            } else {
+                // If color is already mapped, just remove x from positional encoding
+                enc_obj.remove("x");
+            }
+        } else if theta_aesthetic == "x" {
+            // Reversed: x → theta, y → radius
+            if let Some(x_enc) = enc_obj.remove("x") {
+                enc_obj.insert("theta".to_string(), x_enc);
+            }
+            if let Some(y_enc) = enc_obj.remove("y") {
+                enc_obj.insert("radius".to_string(), y_enc);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Helper methods
+
+    fn extract_limits(&self, value: &ParameterValue) -> Result<Option<(f64, f64)>> {
+        match value {
+            ParameterValue::Array(arr) => {
+                if arr.len() != 2 {
+                    return Err(GgsqlError::WriterError(format!(
+                        "xlim/ylim must be exactly 2 numbers, got {}",
+                        arr.len()
+                    )));
+                }
+                let min = match &arr[0] {
+                    ArrayElement::Number(n) => *n,
+                    _ => {
+                        return Err(GgsqlError::WriterError(
+                            "xlim/ylim values must be numbers".to_string(),
+                        ))
+                    }
+                };
+                let max = match &arr[1] {
+                    ArrayElement::Number(n) => *n,
+                    _ => {
+                        return Err(GgsqlError::WriterError(
+                            "xlim/ylim values must be numbers".to_string(),
+                        ))
+                    }
+                };
+
+                // Auto-swap if reversed
+                let (min, max) = if min > max { (max, min) } else { (min, max) };
+
+                Ok(Some((min, max)))
+            }
+            _ => Err(GgsqlError::WriterError(
+                "xlim/ylim must be an array".to_string(),
+            )),
+        }
+    }
+
+    fn extract_domain(&self, value: &ParameterValue) -> Result<Option<Vec<Value>>> {
+        match value {
+            ParameterValue::Array(arr) => {
+                let domain: Vec<Value> = arr
+                    .iter()
+                    .map(|elem| match elem {
+                        ArrayElement::String(s) => json!(s),
+                        ArrayElement::Number(n) => json!(n),
+                        ArrayElement::Boolean(b) => json!(b),
+                    })
+                    .collect();
+                Ok(Some(domain))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn apply_axis_limits(&self, vl_spec: &mut Value, axis: &str, limits: (f64, f64)) -> Result<()> {
+        let domain = json!([limits.0, limits.1]);
+
+        // Apply to encoding if present
+        if let Some(encoding) = vl_spec.get_mut("encoding") {
+            if let Some(axis_enc) = encoding.get_mut(axis) {
+                axis_enc["scale"] = json!({"domain": domain});
+            }
+        }
+
+        // Apply to layers if present
+        if let Some(layers) = vl_spec.get_mut("layer") {
+            if let Some(layers_arr) = layers.as_array_mut() {
+                for layer in layers_arr {
+                    if let Some(encoding) = layer.get_mut("encoding") {
+                        if let Some(axis_enc) = encoding.get_mut(axis) {
+                            axis_enc["scale"] = json!({"domain": domain});
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_aesthetic_domain(
+        &self,
+        vl_spec: &mut Value,
+        aesthetic: &str,
+        domain: Vec<Value>,
+    ) -> Result<()> {
+        let domain_json = json!(domain);
+
+        // Apply to encoding if present
+        if let Some(encoding) = vl_spec.get_mut("encoding") {
+            if let Some(aes_enc) = encoding.get_mut(aesthetic) {
+                aes_enc["scale"] = json!({"domain": domain_json});
+            }
+        }
+
+        // Apply to layers if present
+        if let Some(layers) = vl_spec.get_mut("layer") {
+            if let Some(layers_arr) = layers.as_array_mut() {
+                for layer in layers_arr {
+                    if let Some(encoding) = layer.get_mut("encoding") {
+                        if let Some(aes_enc) = encoding.get_mut(aesthetic) {
+                            aes_enc["scale"] = json!({"domain": domain_json});
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_aesthetic_name(&self, name: &str) -> bool {
+        matches!(
+            name,
+            "x" | "y"
+                | "xmin"
+                | "xmax"
+                | "ymin"
+                | "ymax"
+                | "xend"
+                | "yend"
+                | "z"
+                | "sample"
+                | "color"
+                | "colour"
+                | "fill"
+                | "stroke"
+                | "opacity"
+                | "size"
+                | "shape"
+                | "linetype"
+                | "linewidth"
+                | "width"
+                | "height"
+                | "label"
+                | "family"
+                | "fontface"
+                | "hjust"
+                | "vjust"
+        )
+    }
+
+    /// Build detail encoding from partition_by columns
+    /// Maps partition_by columns to Vega-Lite's detail channel for grouping
+    fn build_detail_encoding(&self, partition_by: &[String]) -> Option<Value> {
+        if partition_by.is_empty() {
+            return None;
+        }
+
+        if partition_by.len() == 1 {
+            // Single column: simple object
+            Some(json!({
+                "field": partition_by[0],
+                "type": "nominal"
+            }))
+        } else {
+            // Multiple columns: array of detail specifications
+            let details: Vec<Value> = partition_by
+                .iter()
+                .map(|col| {
+                    json!({
+                        "field": col,
+                        "type": "nominal"
+                    })
+                })
+                .collect();
+            Some(json!(details))
+        }
+    }
+}
+
+impl Writer for VegaLiteWriter {
+    fn write(&self, spec: &Plot, data: &HashMap<String, DataFrame>) -> Result<String> {
+        // Validate spec before processing
+        self.validate(spec)?;
+
+        // Determine which dataset key each layer should use
+        // A layer uses __layer_{idx}__ if:
+        // - It has an explicit source (FROM clause), OR
+        // - It has constants injected (no source but constants were added)
+        // Otherwise, use __global__
+        let layer_data_keys: Vec<String> = spec
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(idx, _layer)| {
+                let layer_key = naming::layer_key(idx);
+                if data.contains_key(&layer_key) {
+                    layer_key
+                } else {
+                    naming::GLOBAL_DATA_KEY.to_string()
+                }
+            })
+            .collect();
+
+        // Validate all required datasets exist and validate column references
+        for (layer_idx, (layer, key)) in spec.layers.iter().zip(layer_data_keys.iter()).enumerate()
+        {
+            let df = data.get(key).ok_or_else(|| {
+                GgsqlError::WriterError(format!(
+                    "Missing data source '{}' for layer {}",
+                    key,
+                    layer_idx + 1
+                ))
+            })?;
+            self.validate_layer_columns(layer, df, layer_idx)?;
+        }
+
+        // Build the base Vega-Lite spec
+        let mut vl_spec = json!({
+            "$schema": self.schema
+        });
+
+        // Responsive plot sizing
+        vl_spec["width"] = json!("container");
+        vl_spec["height"] = json!("container");
+
+        // Add title if present
+        if let Some(labels) = &spec.labels {
+            if let Some(title) = labels.labels.get("title") {
+                vl_spec["title"] = json!(title);
+            }
+        }
+
+        // Build datasets - convert all DataFrames to Vega-Lite format
+        let mut datasets = Map::new();
+        for (key, df) in data {
+            let timezones = display_timezones(spec, key, &layer_data_keys)?;
+            let values = self.dataframe_to_values(df, &timezones)?;
+            datasets.insert(key.clone(), json!(values));
+        }
+        vl_spec["datasets"] = Value::Object(datasets);
+
+        // Build layers array
+        let mut layers = Vec::new();
+        for (layer_idx, layer) in spec.layers.iter().enumerate() {
+            let data_key = &layer_data_keys[layer_idx];
+            let df = data.get(data_key).unwrap();
+
+            // Faceting replaces the per-layer data again; see apply_facet
+            let mut layer_spec = json!({
+                "data": {"name": data_key},
+                "mark": self.geom_to_mark(&layer.geom)
+            });
+
+            // For Bar geom, set mark with width parameter
+            if layer.geom.geom_type() == GeomType::Bar {
+                use crate::plot::ParameterValue;
+                let width = layer
+                    .parameters
+                    .get("width")
+                    .and_then(|p| match p {
+                        ParameterValue::Number(n) => Some(*n),
+                        _ => None,
+                    })
+                    .unwrap_or(0.9);
+                layer_spec["mark"] = json!({
+                    "type": "bar",
+                    "width": {"band": width}
+                });
+            }
+
+            // Add window transform for Path and Polygon geoms to preserve data order
+            // (Line geom uses Vega-Lite's default x-axis sorting)
+            let keeps_data_order =
+                matches!(layer.geom.geom_type(), GeomType::Path | GeomType::Polygon);
+            if keeps_data_order {
+                let mut window_transform = json!({
+                    "window": [{"op": "row_number", "as": naming::ORDER_COLUMN}]
+                });
+
+                // Add groupby if partition_by is present (restarts numbering per group)
+                if !layer.partition_by.is_empty() {
+                    window_transform["groupby"] = json!(layer.partition_by);
+                }
+
+                layer_spec["transform"] = json!([window_transform]);
+            }
+
+            // Build encoding for this layer
+            // Track which aesthetic families have been titled to ensure only one title per family
+            let mut encoding = Map::new();
+            let mut titled_families: std::collections::HashSet<String> =
+                std::collections::HashSet::new();
+            // Smooth's fill only colours its confidence band (see build_smooth_band)
+            let is_smooth = layer.geom.geom_type() == GeomType::Smooth;
+            for (aesthetic, value) in &layer.mappings.aesthetics {
+                if is_smooth && aesthetic == "fill" {
+                    continue;
+                }
+                let channel_name = self.map_aesthetic_name(aesthetic);
+                let channel_encoding =
+                    self.build_encoding_channel(aesthetic, value, df, spec, &mut titled_families)?;
+                encoding.insert(channel_name, channel_encoding);
+            }
+
+            // Also add aesthetic parameters from SETTING as literal encodings
+            // (e.g., SETTING color => 'red' becomes {"color": {"value": "red"}})
+            // Only parameters that are supported aesthetics for this geom type are included
+            let supported_aesthetics = layer.geom.aesthetics().supported;
+            for (param_name, param_value) in &layer.parameters {
+                if is_smooth && param_name == "fill" {
+                    continue;
+                }
+                if supported_aesthetics.contains(&param_name.as_str()) {
+                    let channel_name = self.map_aesthetic_name(param_name);
+                    // Only add if not already set by MAPPING (MAPPING takes precedence)
+                    if !encoding.contains_key(&channel_name) {
+                        let value = aesthetic_value(param_name, &param_value.to_json())?;
+                        encoding.insert(channel_name, json!({"value": value}));
+                    }
+                }
+            }
+
+            // Open and filled point shapes are a property of the mark
+            let literal_shape = match layer.mappings.get("shape") {
+                Some(AestheticValue::Literal(LiteralValue::Number(n))) => Some(json!(n)),
+                Some(AestheticValue::Literal(LiteralValue::String(s))) => Some(json!(s)),
+                Some(_) => None,
+                None => layer.parameters.get("shape").map(|p| p.to_json()),
+            };
+            if let Some((_, filled)) = literal_shape.as_ref().and_then(shape_value) {
+                if layer_spec["mark"] == "point" {
+                    layer_spec["mark"] = json!({"type": "point", "filled": filled});
+                }
+            }
+            apply_point_shapes(&mut layer_spec, &mut encoding)?;
+            apply_size_units(
+                &mut encoding,
+                matches!(layer.geom.geom_type(), GeomType::Text | GeomType::Label),
+            )?;
+
+            // Add detail encoding for partition_by columns (grouping)
+            if let Some(detail) = self.build_detail_encoding(&layer.partition_by) {
+                encoding.insert("detail".to_string(), detail);
+            }
+
+            // Violins are drawn on the numeric x axis computed by stat_violin
+            if layer.geom.geom_type() == GeomType::Violin
+                && self.apply_violin_encoding(layer, df, &mut encoding)?
+            {
+                layer_spec["mark"] = json!({"type": "area", "orient": "horizontal"});
+            }
+
+            // Hexagons are closed, filled outlines through the corners computed by stat_hex
+            if layer.geom.geom_type() == GeomType::Hex {
+                layer_spec["mark"] = json!({
+                    "type": "line",
+                    "interpolate": "linear-closed",
+                    "filled": true
+                });
+                self.apply_outline_encoding(layer, "hex", &mut encoding);
+            }
+
+            // Contours are drawn piece by piece; filled ones as closed outlines
+            if matches!(
+                layer.geom.geom_type(),
+                GeomType::Density2d | GeomType::Contour
+            ) {
+                let filled = matches!(
+                    layer.parameters.get("filled"),
+                    Some(ParameterValue::Boolean(true))
+                );
+                if filled {
+                    layer_spec["mark"] = json!({
+                        "type": "line",
+                        "interpolate": "linear-closed",
+                        "filled": true
+                    });
+                }
+                self.apply_outline_encoding(layer, "piece", &mut encoding);
+            }
+
+            // ECDFs step up at each x value; the order keeps the padding row first
+            if layer.geom.geom_type() == GeomType::Ecdf {
+                layer_spec["mark"] = json!({"type": "line", "interpolate": "step-after"});
+                encoding.insert(
+                    "order".to_string(),
+                    json!({
+                        "field": naming::stat_column("ecdf"),
+                        "type": "quantitative"
+                    }),
+                );
+            }
+
+            // Error bars span ymin to ymax (or xmin to xmax when horizontal)
+            if layer.geom.geom_type() == GeomType::ErrorBar
+                && self.apply_range_encoding(&mut encoding)
+            {
+                layer_spec["mark"] = json!({"type": "errorbar", "ticks": true});
+            }
+
+            // Line ranges span ymin to ymax (or xmin to xmax when horizontal)
+            if layer.geom.geom_type() == GeomType::LineRange {
+                self.apply_range_encoding(&mut encoding);
+            }
+
+            // Rectangles span both ranges
+            if layer.geom.geom_type() == GeomType::Rect {
+                self.apply_axis_range_encoding(&mut encoding, "x");
+                self.apply_axis_range_encoding(&mut encoding, "y");
+            }
+
+            // Polygons are closed, filled outlines through their vertices in data order
+            if layer.geom.geom_type() == GeomType::Polygon {
+                layer_spec["mark"] = json!({
+                    "type": "line",
+                    "interpolate": "linear-closed",
+                    "filled": true
+                });
+            }
+
+            // Steps change y either at (hv), before (vh) or halfway to (mid) the next x;
+            // the Step geom has checked the direction
+            if layer.geom.geom_type() == GeomType::Step {
+                let interpolate = match layer.parameters.get("direction").and_then(|d| d.as_str()) {
+                    Some("vh") => "step-before",
+                    Some("mid") => "step",
+                    _ => "step-after",
+                };
+                layer_spec["mark"] = json!({"type": "line", "interpolate": interpolate});
+            }
+
+            self.rename_line_channels(layer.geom.geom_type(), &mut encoding);
+
+            // Position adjustments are resolved in the data; see apply_position_encoding
+            self.apply_position_encoding(layer, df, &mut encoding);
+
+            // Add y2 baseline when x2 is present (for histogram bars)
+            // Vega-Lite requires y2 when using x2 for bar marks
+            if encoding.contains_key("x2")
+                && !encoding.contains_key("y2")
+                && !matches!(
+                    layer.geom.geom_type(),
+                    GeomType::ErrorBar | GeomType::LineRange
+                )
+            {
+                encoding.insert("y2".to_string(), json!({"datum": 0}));
+            }
+
+            // Add order encoding for Path and Polygon geoms (preserves data order instead of x-axis sorting)
+            if keeps_data_order {
+                encoding.insert(
+                    "order".to_string(),
+                    json!({
+                        "field": naming::ORDER_COLUMN,
+                        "type": "quantitative"
+                    }),
+                );
+            }
+
+            // Apply guides to first layer's encoding only (they apply globally)
+            if layer_idx == 0 {
+                self.apply_guides_to_encoding(&mut encoding, spec);
+            }
+
+            // Smooth draws its confidence band as an area layer underneath the line
+            if is_smooth {
+                if let Some(band) = self.build_smooth_band(
+                    layer,
+                    &layer_spec,
+                    &mut encoding,
+                    df,
+                    spec,
+                    &mut titled_families,
+                )? {
+                    layers.push(band);
+                }
+            }
+
+            // Boxplot statistics are drawn as whiskers, boxes, medians and outliers
+            if layer.geom.geom_type() == GeomType::Boxplot
+                && encoding.contains_key("lower")
+                && encoding.contains_key("upper")
+            {
+                layers.extend(self.build_boxplot_layers(layer, &layer_spec, encoding));
+                continue;
+            }
+
+            // Pointranges and crossbars are drawn as an interval plus an estimate
+            if matches!(
+                layer.geom.geom_type(),
+                GeomType::PointRange | GeomType::CrossBar
+            ) {
+                layers.extend(self.build_interval_layers(layer, &layer_spec, encoding));
+                continue;
+            }
+
+            // Rugs are drawn as ticks along the chosen sides of the plot
+            if layer.geom.geom_type() == GeomType::Rug {
+                layers.extend(self.build_rug_layers(layer, &layer_spec, encoding)?);
+                continue;
+            }
+
+            layer_spec["encoding"] = Value::Object(encoding);
+            layers.push(layer_spec);
+        }
+
+        vl_spec["layer"] = json!(layers);
+
+        // Expand position scales now that every layer's encoding is known
+        self.apply_scale_expansion(spec, data, &layer_data_keys, &mut vl_spec)?;
+
+        // Apply coordinate transforms (flip, polar, cartesian limits)
+        // This must happen AFTER layers are built since transforms modify layer encodings
+        self.apply_coord_transforms(spec, data, &layer_data_keys, &mut vl_spec)?;
+
+        // Apply guide configurations for multi-layer specs
+        if spec.layers.len() > 1 && !spec.guides.is_empty() {
+            let mut resolve = json!({"legend": {}, "scale": {}});
+            for guide in &spec.guides {
+                let channel = self.map_aesthetic_name(&guide.aesthetic);
+                resolve["legend"][&channel] = json!("shared");
+                resolve["scale"][&channel] = json!("shared");
+            }
+            vl_spec["resolve"] = resolve;
+        }
+
+        // Secondary axes are extra layers with their own, independent scale
+        self.apply_secondary_axes(spec, data, &layer_data_keys, &mut vl_spec)?;
+
+        // Handle faceting if present
+        if let Some(facet) = &spec.facet {
+            self.apply_facet(spec, facet, data, &mut vl_spec)?;
+        }
+
+        // Marks are clipped to the panel unless COORD sets clip => 'off'. This comes
+        // last so that it also reaches the layers added by secondary axes and facets
+        let clip = match &spec.coord {
+            Some(coord) => coord.clip()?.unwrap_or(true),
+            None => true,
+        };
+        set_mark_clip(&mut vl_spec, clip);
+
+        if let Some(theme) = &spec.theme {
+            vl_spec["config"] = theme_config(&theme.resolve(&self.themes)?);
+        }
+
+        serde_json::to_string_pretty(&vl_spec).map_err(|e| {
+            GgsqlError::WriterError(format!("Failed to serialize Vega-Lite JSON: {}", e))
+        })
+    }
+
+    fn validate(&self, spec: &Plot) -> Result<()> {
+        // Check that we have at least one layer
+        if spec.layers.is_empty() {
+            return Err(GgsqlError::ValidationError(
+                "VegaLiteWriter requires at least one layer".to_string(),
+            ));
+        }
+
+        // Validate each layer
+        for layer in &spec.layers {
+            // Check required aesthetics
+            layer.validate_required_aesthetics().map_err(|e| {
+                GgsqlError::ValidationError(format!("Layer validation failed: {}", e))
+            })?;
+
+            // Check SETTING parameters are valid for this geom
+            layer.validate_settings().map_err(|e| {
+                GgsqlError::ValidationError(format!("Layer validation failed: {}", e))
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot::{Labels, Layer, LiteralValue, ParameterValue};
+    use std::collections::HashMap;
+
+    /// Helper to wrap a DataFrame in a data map for testing
+    fn wrap_data(df: DataFrame) -> HashMap<String, DataFrame> {
+        let mut data_map = HashMap::new();
+        data_map.insert(naming::GLOBAL_DATA_KEY.to_string(), df);
+        data_map
+    }
+
+    #[test]
+    fn test_geom_to_mark_mapping() {
+        let writer = VegaLiteWriter::new();
+        assert_eq!(writer.geom_to_mark(&Geom::point()), "point");
+        assert_eq!(writer.geom_to_mark(&Geom::line()), "line");
+        assert_eq!(writer.geom_to_mark(&Geom::bar()), "bar");
+        assert_eq!(writer.geom_to_mark(&Geom::area()), "area");
+        assert_eq!(writer.geom_to_mark(&Geom::tile()), "rect");
+    }
+
+    #[test]
+    fn test_aesthetic_name_mapping() {
+        let writer = VegaLiteWriter::new();
+        assert_eq!(writer.map_aesthetic_name("x"), "x");
+        assert_eq!(writer.map_aesthetic_name("fill"), "color");
+        assert_eq!(writer.map_aesthetic_name("linetype"), "strokeDash");
+        assert_eq!(writer.map_aesthetic_name("linewidth"), "strokeWidth");
+    }
+
+    #[test]
+    fn test_validation_requires_layers() {
+        let writer = VegaLiteWriter::new();
+        let spec = Plot::new();
+        assert!(writer.validate(&spec).is_err());
+    }
+
+    #[test]
+    fn test_simple_point_spec() {
+        let writer = VegaLiteWriter::new();
+
+        // Create a simple spec
+        let mut spec = Plot::new();
+        let layer = Layer::new(Geom::point())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("x".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column("y".to_string()),
+            );
+        spec.layers.push(layer);
+
+        // Create simple DataFrame
+        let df = df! {
+            "x" => &[1, 2, 3],
+            "y" => &[4, 5, 6],
+        }
+        .unwrap();
+
+        // Generate Vega-Lite JSON
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+
+        // Verify structure (now uses layer array and datasets)
+        assert_eq!(vl_spec["$schema"], writer.schema);
+        assert!(vl_spec["layer"].is_array());
+        assert_eq!(vl_spec["layer"][0]["mark"]["type"], "point");
+        assert_eq!(vl_spec["layer"][0]["mark"]["clip"], true);
+        assert!(vl_spec["datasets"][naming::GLOBAL_DATA_KEY].is_array());
+        assert_eq!(
+            vl_spec["datasets"][naming::GLOBAL_DATA_KEY]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+        assert!(vl_spec["layer"][0]["encoding"]["x"].is_object());
+        assert!(vl_spec["layer"][0]["encoding"]["y"].is_object());
+    }
+
+    #[test]
+    fn test_with_title() {
+        let writer = VegaLiteWriter::new();
+
+        let mut spec = Plot::new();
+        let layer = Layer::new(Geom::line())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("date".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column("value".to_string()),
+            );
+        spec.layers.push(layer);
+
+        let mut labels = Labels {
+            labels: HashMap::new(),
+        };
+        labels
+            .labels
+            .insert("title".to_string(), "My Chart".to_string());
+        spec.labels = Some(labels);
+
+        let df = df! {
+            "date" => &["2024-01-01", "2024-01-02"],
+            "value" => &[10, 20],
+        }
+        .unwrap();
+
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+
+        assert_eq!(vl_spec["title"], "My Chart");
+        assert_eq!(vl_spec["layer"][0]["mark"]["type"], "line");
+    }
+
+    #[test]
+    fn test_literal_color() {
+        let writer = VegaLiteWriter::new();
+
+        let mut spec = Plot::new();
+        let layer = Layer::new(Geom::point())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("x".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column("y".to_string()),
+            )
+            .with_aesthetic(
+                "color".to_string(),
+                AestheticValue::Literal(LiteralValue::String("blue".to_string())),
+            );
+        spec.layers.push(layer);
+
+        let df = df! {
+            "x" => &[1, 2],
+            "y" => &[3, 4],
+        }
+        .unwrap();
+
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+
+        assert_eq!(vl_spec["layer"][0]["encoding"]["color"]["value"], "blue");
+    }
+
+    #[test]
+    fn test_missing_column_error() {
+        let writer = VegaLiteWriter::new();
+
+        let mut spec = Plot::new();
+        let layer = Layer::new(Geom::point())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("x".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column("foo".to_string()),
+            );
+        spec.layers.push(layer);
+
+        let df = df! {
+            "x" => &[1, 2],
+            "y" => &[3, 4],
+        }
+        .unwrap();
+
+        let result = writer.write(&spec, &wrap_data(df));
+        assert!(result.is_err());
+
+        let err = result.unwrap_err();
+        let err_msg = err.to_string();
+        assert!(err_msg.contains("Column 'foo'"));
+        assert!(err_msg.contains("does not exist"));
+        assert!(err_msg.contains("Available columns: x, y"));
+    }
+
+    #[test]
+    fn test_missing_column_in_multi_layer() {
+        let writer = VegaLiteWriter::new();
+
+        let mut spec = Plot::new();
+
+        // First layer is valid
+        let layer1 = Layer::new(Geom::line())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("x".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column("y".to_string()),
+            );
+        spec.layers.push(layer1);
+
+        // Second layer references non-existent column
+        let layer2 = Layer::new(Geom::point())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("x".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column("missing_col".to_string()),
+            );
+        spec.layers.push(layer2);
+
+        let df = df! {
+            "x" => &[1, 2],
+            "y" => &[3, 4],
+        }
+        .unwrap();
+
+        let result = writer.write(&spec, &wrap_data(df));
+        assert!(result.is_err());
+
+        let err = result.unwrap_err();
+        let err_msg = err.to_string();
+        assert!(err_msg.contains("Column 'missing_col'"));
+        assert!(err_msg.contains("layer 2"));
+    }
+
+    // ========================================
+    // Comprehensive Grammar Coverage Tests
+    // ========================================
+
+    #[test]
+    fn test_all_basic_geom_types() {
+        let writer = VegaLiteWriter::new();
+
+        let geoms = vec![
+            (Geom::point(), "point"),
+            (Geom::line(), "line"),
+            (Geom::path(), "line"),
+            (Geom::bar(), "bar"),
+            (Geom::area(), "area"),
+            (Geom::tile(), "rect"),
+            (Geom::ribbon(), "area"),
+        ];
+
+        for (geom, expected_mark) in geoms {
+            let mut spec = Plot::new();
+            let layer = Layer::new(geom.clone())
+                .with_aesthetic(
+                    "x".to_string(),
+                    AestheticValue::standard_column("x".to_string()),
+                )
+                .with_aesthetic(
+                    "y".to_string(),
+                    AestheticValue::standard_column("y".to_string()),
+                )
+                .with_aesthetic(
+                    "ymin".to_string(),
+                    AestheticValue::standard_column("ymin".to_string()),
+                )
+                .with_aesthetic(
+                    "ymax".to_string(),
+                    AestheticValue::standard_column("ymax".to_string()),
+                );
+            spec.layers.push(layer);
+
+            let df = df! {
+                "x" => &[1, 2, 3],
+                "y" => &[4, 5, 6],
+                "ymin" => &[3, 4, 5],
+                "ymax" => &[5, 6, 7],
+            }
+            .unwrap();
+
+            let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+            let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+
+            // Handle both string marks and object marks (e.g., Bar has {"type": "bar", "width": ...})
+            let mark_type = vl_spec["layer"][0]["mark"]
+                .as_str()
+                .or_else(|| vl_spec["layer"][0]["mark"]["type"].as_str())
+                .unwrap();
+            assert_eq!(mark_type, expected_mark, "Failed for geom: {:?}", geom);
+        }
+    }
+
+    #[test]
+    fn test_statistical_geom_types() {
+        let writer = VegaLiteWriter::new();
+
+        let geoms = vec![
+            (Geom::histogram(), "bar"),
+            (Geom::density(), "area"),
+            (Geom::smooth(), "line"),
+            (Geom::boxplot(), "boxplot"),
+        ];
+
+        for (geom, expected_mark) in geoms {
+            let mut spec = Plot::new();
+            let layer = Layer::new(geom.clone())
+                .with_aesthetic(
+                    "x".to_string(),
+                    AestheticValue::standard_column("x".to_string()),
+                )
+                .with_aesthetic(
+                    "y".to_string(),
+                    AestheticValue::standard_column("y".to_string()),
+                );
+            spec.layers.push(layer);
+
+            let df = df! {
+                "x" => &[1, 2, 3],
+                "y" => &[4, 5, 6],
+            }
+            .unwrap();
+
+            let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+            let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+
+            assert_eq!(
+                vl_spec["layer"][0]["mark"]["type"].as_str().unwrap(),
+                expected_mark
+            );
+        }
+    }
+
+    #[test]
+    fn test_smooth_confidence_band() {
+        let writer = VegaLiteWriter::new();
+
+        let mut spec = Plot::new();
+        let layer = Layer::new(Geom::smooth())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_x".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_y".to_string()),
+            )
+            .with_aesthetic(
+                "ymin".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_ymin".to_string()),
+            )
+            .with_aesthetic(
+                "ymax".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_ymax".to_string()),
+            )
+            .with_parameter(
+                "fill".to_string(),
+                ParameterValue::String("#ff0000".to_string()),
+            );
+        spec.layers.push(layer);
+
+        let df = df! {
+            "__ggsql_stat_x" => &[1.0, 2.0, 3.0],
+            "__ggsql_stat_y" => &[2.0, 4.0, 6.0],
+            "__ggsql_stat_ymin" => &[1.5, 3.5, 5.5],
+            "__ggsql_stat_ymax" => &[2.5, 4.5, 6.5],
+        }
+        .unwrap();
 
-            // Apply guides to first layer's encoding only (they apply globally)
-            if layer_idx == 0 {
-                self.apply_guides_to_encoding(&mut encoding, spec);
-            }
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
 
-            layer_spec["encoding"] = Value::Object(encoding);
-            layers.push(layer_spec);
-        }
+        // Band is drawn first, underneath the fitted line
+        let band = &vl_spec["layer"][0];
+        assert_eq!(band["mark"]["type"], "area");
+        assert_eq!(band["encoding"]["y"]["field"], "__ggsql_stat_ymin");
+        assert_eq!(band["encoding"]["y2"]["field"], "__ggsql_stat_ymax");
+        assert_eq!(band["encoding"]["color"]["value"], "#ff0000");
+
+        let line = &vl_spec["layer"][1];
+        assert_eq!(line["mark"]["type"], "line");
+        assert_eq!(line["encoding"]["y"]["field"], "__ggsql_stat_y");
+        assert!(line["encoding"].get("ymin").is_none());
+        assert!(line["encoding"].get("ymax").is_none());
+        assert!(line["encoding"].get("color").is_none());
+    }
 
-        vl_spec["layer"] = json!(layers);
+    #[test]
+    fn test_boxplot_stat_layers() {
+        let writer = VegaLiteWriter::new();
 
-        // Apply coordinate transforms (flip, polar, cartesian limits)
-        // This must happen AFTER layers are built since transforms modify layer encodings
-        let first_df = data.get(&layer_data_keys[0]).unwrap();
-        self.apply_coord_transforms(spec, first_df, &mut vl_spec)?;
+        let mut spec = Plot::new();
+        let mut layer = Layer::new(Geom::boxplot()).with_aesthetic(
+            "x".to_string(),
+            AestheticValue::standard_column("species".to_string()),
+        );
+        for (aesthetic, stat) in [
+            ("ymin", "lower"),
+            ("lower", "q1"),
+            ("middle", "median"),
+            ("upper", "q3"),
+            ("ymax", "upper"),
+            ("y", "outlier"),
+        ] {
+            layer = layer.with_aesthetic(
+                aesthetic.to_string(),
+                AestheticValue::standard_column(naming::stat_column(stat)),
+            );
+        }
+        spec.layers.push(layer);
 
-        // Apply guide configurations for multi-layer specs
-        if spec.layers.len() > 1 && !spec.guides.is_empty() {
-            let mut resolve = json!({"legend": {}, "scale": {}});
-            for guide in &spec.guides {
-                let channel = self.map_aesthetic_name(&guide.aesthetic);
-                resolve["legend"][&channel] = json!("shared");
-                resolve["scale"][&channel] = json!("shared");
-            }
-            vl_spec["resolve"] = resolve;
+        let df = df! {
+            "species" => &["A", "A"],
+            "__ggsql_stat_lower" => &[1.0, 1.0],
+            "__ggsql_stat_q1" => &[2.0, 2.0],
+            "__ggsql_stat_median" => &[3.0, 3.0],
+            "__ggsql_stat_q3" => &[4.0, 4.0],
+            "__ggsql_stat_upper" => &[5.0, 5.0],
+            "__ggsql_stat_outlier" => &[None, Some(12.0)],
+            "__ggsql_stat_n" => &[10, 10],
         }
+        .unwrap();
 
-        // Handle faceting if present
-        if let Some(facet) = &spec.facet {
-            // Determine the data key for faceting (prefer global data, fallback to first layer's data)
-            let facet_data_key = if data.contains_key(naming::GLOBAL_DATA_KEY) {
-                naming::GLOBAL_DATA_KEY.to_string()
-            } else {
-                layer_data_keys[0].clone()
-            };
-            let facet_data = data.get(&facet_data_key).unwrap();
-
-            use crate::plot::Facet;
-            match facet {
-                Facet::Wrap { variables, .. } => {
-                    if !variables.is_empty() {
-                        let field_type = self.infer_field_type(facet_data, &variables[0]);
-                        vl_spec["facet"] = json!({
-                            "field": variables[0],
-                            "type": field_type,
-                        });
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let layers = vl_spec["layer"].as_array().unwrap();
+        assert_eq!(layers.len(), 4);
 
-                        // Set top-level data reference for faceting
-                        vl_spec["data"] = json!({"name": facet_data_key});
+        // Whiskers span lower to upper
+        assert_eq!(layers[0]["mark"]["type"], "rule");
+        assert_eq!(layers[0]["encoding"]["y"]["field"], "__ggsql_stat_lower");
+        assert_eq!(layers[0]["encoding"]["y2"]["field"], "__ggsql_stat_upper");
 
-                        // Move layer into spec, keep datasets at top level
-                        let mut spec_inner = json!({});
-                        if let Some(layer) = vl_spec.get("layer") {
-                            spec_inner["layer"] = layer.clone();
-                        }
+        // Box spans the quartiles
+        assert_eq!(layers[1]["mark"]["type"], "bar");
+        assert_eq!(layers[1]["encoding"]["y"]["field"], "__ggsql_stat_q1");
+        assert_eq!(layers[1]["encoding"]["y2"]["field"], "__ggsql_stat_q3");
 
-                        vl_spec["spec"] = spec_inner;
-                        vl_spec.as_object_mut().unwrap().remove("layer");
-                    }
-                }
-                Facet::Grid { rows, cols, .. } => {
-                    let mut facet_spec = Map::new();
-                    if !rows.is_empty() {
-                        let field_type = self.infer_field_type(facet_data, &rows[0]);
-                        facet_spec.insert(
-                            "row".to_string(),
-                            json!({"field": rows[0], "type": field_type}),
-                        );
-                    }
-                    if !cols.is_empty() {
-                        let field_type = self.infer_field_type(facet_data, &cols[0]);
-                        facet_spec.insert(
-                            "column".to_string(),
-                            json!({"field": cols[0], "type": field_type}),
-                        );
-                    }
-                    vl_spec["facet"] = Value::Object(facet_spec);
+        assert_eq!(layers[2]["mark"]["type"], "tick");
+        assert_eq!(layers[2]["encoding"]["y"]["field"], "__ggsql_stat_median");
+
+        // Outliers are points, restricted to the outlier rows
+        assert_eq!(layers[3]["mark"]["type"], "point");
+        assert_eq!(layers[3]["encoding"]["y"]["field"], "__ggsql_stat_outlier");
+        assert_eq!(
+            layers[3]["transform"][0]["filter"],
+            "isValid(datum[\"__ggsql_stat_outlier\"])"
+        );
+        assert_eq!(
+            layers[1]["transform"][0]["filter"],
+            "!isValid(datum[\"__ggsql_stat_outlier\"])"
+        );
+    }
 
-                    // Set top-level data reference for faceting
-                    vl_spec["data"] = json!({"name": facet_data_key});
+    #[test]
+    fn test_errorbar_range_encoding() {
+        let writer = VegaLiteWriter::new();
 
-                    // Move layer into spec, keep datasets at top level
-                    let mut spec_inner = json!({});
-                    if let Some(layer) = vl_spec.get("layer") {
-                        spec_inner["layer"] = layer.clone();
-                    }
+        let mut spec = Plot::new();
+        let layer = Layer::new(Geom::errorbar())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("group".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_y".to_string()),
+            )
+            .with_aesthetic(
+                "ymin".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_ymin".to_string()),
+            )
+            .with_aesthetic(
+                "ymax".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_ymax".to_string()),
+            );
+        spec.layers.push(layer);
 
-                    vl_spec["spec"] = spec_inner;
-                    vl_spec.as_object_mut().unwrap().remove("layer");
-                }
-            }
+        let df = df! {
+            "group" => &["A", "B"],
+            "__ggsql_stat_y" => &[2.0, 15.0],
+            "__ggsql_stat_ymin" => &[1.0, 10.0],
+            "__ggsql_stat_ymax" => &[3.0, 20.0],
         }
+        .unwrap();
 
-        serde_json::to_string_pretty(&vl_spec).map_err(|e| {
-            GgsqlError::WriterError(format!("Failed to serialize Vega-Lite JSON: {}", e))
-        })
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+
+        let layer = &vl_spec["layer"][0];
+        assert_eq!(layer["mark"]["type"], "errorbar");
+        assert_eq!(layer["encoding"]["y"]["field"], "__ggsql_stat_ymin");
+        assert_eq!(layer["encoding"]["y2"]["field"], "__ggsql_stat_ymax");
+        assert!(layer["encoding"].get("ymin").is_none());
+        assert!(layer["encoding"].get("ymax").is_none());
     }
 
-    fn validate(&self, spec: &Plot) -> Result<()> {
-        // Check that we have at least one layer
-        if spec.layers.is_empty() {
-            return Err(GgsqlError::ValidationError(
-                "VegaLiteWriter requires at least one layer".to_string(),
-            ));
+    #[test]
+    fn test_bin2d_and_hex_marks() {
+        let writer = VegaLiteWriter::new();
+
+        let mut spec = Plot::new();
+        let layer = Layer::new(Geom::bin2d())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_xbin".to_string()),
+            )
+            .with_aesthetic(
+                "x2".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_xbin_end".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_ybin".to_string()),
+            )
+            .with_aesthetic(
+                "y2".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_ybin_end".to_string()),
+            )
+            .with_aesthetic(
+                "fill".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_count".to_string()),
+            );
+        spec.layers.push(layer);
+
+        let df = df! {
+            "__ggsql_stat_xbin" => &[0.0, 1.0],
+            "__ggsql_stat_xbin_end" => &[1.0, 2.0],
+            "__ggsql_stat_ybin" => &[0.0, 1.0],
+            "__ggsql_stat_ybin_end" => &[1.0, 2.0],
+            "__ggsql_stat_count" => &[3i64, 1],
         }
+        .unwrap();
 
-        // Validate each layer
-        for layer in &spec.layers {
-            // Check required aesthetics
-            layer.validate_required_aesthetics().map_err(|e| {
-                GgsqlError::ValidationError(format!("Layer validation failed: {}", e))
-            })?;
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let layer = &vl_spec["layer"][0];
+        assert_eq!(layer["mark"]["type"], "rect");
+        assert_eq!(layer["encoding"]["y2"]["field"], "__ggsql_stat_ybin_end");
+        assert_eq!(layer["encoding"]["color"]["field"], "__ggsql_stat_count");
+        assert_eq!(layer["encoding"]["color"]["type"], "quantitative");
 
-            // Check SETTING parameters are valid for this geom
-            layer.validate_settings().map_err(|e| {
-                GgsqlError::ValidationError(format!("Layer validation failed: {}", e))
-            })?;
+        let mut spec = Plot::new();
+        let layer = Layer::new(Geom::hex())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_x".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_y".to_string()),
+            )
+            .with_aesthetic(
+                "fill".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_count".to_string()),
+            );
+        spec.layers.push(layer);
+
+        let df = df! {
+            "__ggsql_stat_x" => &[0.5, 0.0, -0.5],
+            "__ggsql_stat_y" => &[0.2, 0.4, 0.2],
+            "__ggsql_stat_count" => &[3i64, 3, 3],
+            "__ggsql_stat_hex" => &[0i64, 0, 0],
+            "__ggsql_stat_vertex" => &[1i64, 2, 3],
         }
+        .unwrap();
 
-        Ok(())
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let layer = &vl_spec["layer"][0];
+        assert_eq!(layer["mark"]["type"], "line");
+        assert_eq!(layer["mark"]["interpolate"], "linear-closed");
+        assert_eq!(layer["encoding"]["detail"]["field"], "__ggsql_stat_hex");
+        assert_eq!(layer["encoding"]["order"]["field"], "__ggsql_stat_vertex");
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::plot::{Labels, Layer, LiteralValue, ParameterValue};
-    use std::collections::HashMap;
+    #[test]
+    fn test_contour_marks() {
+        let writer = VegaLiteWriter::new();
 
-    /// Helper to wrap a DataFrame in a data map for testing
-    fn wrap_data(df: DataFrame) -> HashMap<String, DataFrame> {
-        let mut data_map = HashMap::new();
-        data_map.insert(naming::GLOBAL_DATA_KEY.to_string(), df);
-        data_map
+        let contour_spec = |filled: bool| {
+            let mut spec = Plot::new();
+            let mut layer = Layer::new(Geom::contour())
+                .with_aesthetic(
+                    "x".to_string(),
+                    AestheticValue::standard_column("__ggsql_stat_x".to_string()),
+                )
+                .with_aesthetic(
+                    "y".to_string(),
+                    AestheticValue::standard_column("__ggsql_stat_y".to_string()),
+                )
+                .with_aesthetic(
+                    "fill".to_string(),
+                    AestheticValue::standard_column("__ggsql_stat_level".to_string()),
+                )
+                .with_parameter("filled".to_string(), ParameterValue::Boolean(filled));
+            // As left by the contour stat, which consumes z
+            layer.consumed_aesthetics = vec!["x".to_string(), "y".to_string(), "z".to_string()];
+            spec.layers.push(layer);
+            spec
+        };
+        let df = df! {
+            "__ggsql_stat_x" => &[0.0, 1.0, 0.0],
+            "__ggsql_stat_y" => &[0.0, 0.0, 1.0],
+            "__ggsql_stat_level" => &[0.5, 0.5, 0.5],
+            "__ggsql_stat_piece" => &[1.0, 1.0, 1.0],
+            "__ggsql_stat_vertex" => &[1.0, 2.0, 3.0],
+        }
+        .unwrap();
+
+        let json_str = writer
+            .write(&contour_spec(false), &wrap_data(df.clone()))
+            .unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let layer = &vl_spec["layer"][0];
+        assert_eq!(layer["mark"]["type"], "line");
+        assert_eq!(layer["encoding"]["color"]["field"], "__ggsql_stat_level");
+        assert_eq!(layer["encoding"]["detail"]["field"], "__ggsql_stat_piece");
+        assert_eq!(layer["encoding"]["order"]["field"], "__ggsql_stat_vertex");
+
+        let json_str = writer.write(&contour_spec(true), &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let layer = &vl_spec["layer"][0];
+        assert_eq!(layer["mark"]["interpolate"], "linear-closed");
+        assert_eq!(layer["mark"]["filled"], true);
     }
 
     #[test]
-    fn test_geom_to_mark_mapping() {
+    fn test_ecdf_step_mark() {
         let writer = VegaLiteWriter::new();
-        assert_eq!(writer.geom_to_mark(&Geom::point()), "point");
-        assert_eq!(writer.geom_to_mark(&Geom::line()), "line");
-        assert_eq!(writer.geom_to_mark(&Geom::bar()), "bar");
-        assert_eq!(writer.geom_to_mark(&Geom::area()), "area");
-        assert_eq!(writer.geom_to_mark(&Geom::tile()), "rect");
+
+        let mut spec = Plot::new();
+        let layer = Layer::new(Geom::ecdf())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_x".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column("__ggsql_stat_ecdf".to_string()),
+            );
+        spec.layers.push(layer);
+
+        let df = df! {
+            "__ggsql_stat_x" => &[1.0, 1.0, 2.0],
+            "__ggsql_stat_ecdf" => &[0.0, 0.5, 1.0],
+        }
+        .unwrap();
+
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let layer = &vl_spec["layer"][0];
+        assert_eq!(layer["mark"]["type"], "line");
+        assert_eq!(layer["mark"]["interpolate"], "step-after");
+        assert_eq!(layer["encoding"]["order"]["field"], "__ggsql_stat_ecdf");
     }
 
     #[test]
-    fn test_aesthetic_name_mapping() {
+    fn test_position_encoding() {
         let writer = VegaLiteWriter::new();
-        assert_eq!(writer.map_aesthetic_name("x"), "x");
-        assert_eq!(writer.map_aesthetic_name("fill"), "color");
+
+        let mut spec = Plot::new();
+        let bars = Layer::new(Geom::bar())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("category".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column("value".to_string()),
+            )
+            .with_aesthetic(
+                "fill".to_string(),
+                AestheticValue::standard_column("series".to_string()),
+            )
+            .with_partition_by(vec!["series".to_string()])
+            .with_position(Position::Dodge {
+                width: 0.9,
+                padding: 0.2,
+                reverse: false,
+            });
+        spec.layers.push(bars);
+
+        let df = df! {
+            "category" => &["A", "A", "B"],
+            "series" => &["p", "q", "p"],
+            "value" => &[1, 3, 2],
+            "__ggsql_pos_dodge__" => &[0, 1, 0],
+        }
+        .unwrap();
+
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let encoding = &vl_spec["layer"][0]["encoding"];
+        // Dodged bars are offset within the band instead of stacked by Vega-Lite
+        assert_eq!(encoding["y"]["stack"], Value::Null);
+        assert!(encoding["y"].as_object().unwrap().contains_key("stack"));
+        assert_eq!(encoding["xOffset"]["field"], "__ggsql_pos_dodge__");
+        assert_eq!(encoding["xOffset"]["type"], "nominal");
+        assert_eq!(encoding["xOffset"]["scale"]["paddingInner"], 0.2);
     }
 
     #[test]
-    fn test_validation_requires_layers() {
+    fn test_step_and_rect_marks() {
         let writer = VegaLiteWriter::new();
-        let spec = Plot::new();
-        assert!(writer.validate(&spec).is_err());
+
+        let mut spec = Plot::new();
+        let step = Layer::new(Geom::step())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("a".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column("b".to_string()),
+            )
+            .with_parameter(
+                "direction".to_string(),
+                ParameterValue::String("vh".to_string()),
+            );
+        let rect = Layer::new(Geom::rect())
+            .with_aesthetic(
+                "xmin".to_string(),
+                AestheticValue::standard_column("a".to_string()),
+            )
+            .with_aesthetic(
+                "xmax".to_string(),
+                AestheticValue::standard_column("b".to_string()),
+            )
+            .with_aesthetic(
+                "ymin".to_string(),
+                AestheticValue::standard_column("a".to_string()),
+            )
+            .with_aesthetic(
+                "ymax".to_string(),
+                AestheticValue::standard_column("b".to_string()),
+            );
+        spec.layers.push(step);
+        spec.layers.push(rect);
+
+        let df = df! {
+            "a" => &[1.0, 2.0, 3.0],
+            "b" => &[2.0, 3.0, 5.0],
+        }
+        .unwrap();
+
+        let json_str = writer.write(&spec, &wrap_data(df.clone())).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        assert_eq!(vl_spec["layer"][0]["mark"]["interpolate"], "step-before");
+
+        let rect = &vl_spec["layer"][1];
+        assert_eq!(rect["mark"]["type"], "rect");
+        assert_eq!(rect["encoding"]["x"]["field"], "a");
+        assert_eq!(rect["encoding"]["x2"]["field"], "b");
+        assert_eq!(rect["encoding"]["y"]["field"], "a");
+        assert_eq!(rect["encoding"]["y2"]["field"], "b");
+
+        // Unknown directions are rejected rather than drawn as a plain line
+        spec.layers[0].parameters.insert(
+            "direction".to_string(),
+            ParameterValue::String("up".to_string()),
+        );
+        assert!(writer.write(&spec, &wrap_data(df)).is_err());
     }
 
     #[test]
-    fn test_simple_point_spec() {
+    fn test_rug_sides() {
         let writer = VegaLiteWriter::new();
 
-        // Create a simple spec
         let mut spec = Plot::new();
-        let layer = Layer::new(Geom::point())
+        let layer = Layer::new(Geom::rug())
             .with_aesthetic(
                 "x".to_string(),
-                AestheticValue::standard_column("x".to_string()),
+                AestheticValue::standard_column("a".to_string()),
             )
             .with_aesthetic(
                 "y".to_string(),
-                AestheticValue::standard_column("y".to_string()),
+                AestheticValue::standard_column("b".to_string()),
+            )
+            .with_parameter(
+                "sides".to_string(),
+                ParameterValue::String("tl".to_string()),
             );
         spec.layers.push(layer);
 
-        // Create simple DataFrame
         let df = df! {
-            "x" => &[1, 2, 3],
-            "y" => &[4, 5, 6],
+            "a" => &[1.0, 2.0, 3.0],
+            "b" => &[2.0, 3.0, 5.0],
         }
         .unwrap();
 
-        // Generate Vega-Lite JSON
         let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
         let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let layers = vl_spec["layer"].as_array().unwrap();
+        assert_eq!(layers.len(), 2);
 
-        // Verify structure (now uses layer array and datasets)
-        assert_eq!(vl_spec["$schema"], writer.schema);
-        assert!(vl_spec["layer"].is_array());
-        assert_eq!(vl_spec["layer"][0]["mark"], "point");
-        assert!(vl_spec["datasets"][naming::GLOBAL_DATA_KEY].is_array());
-        assert_eq!(
-            vl_spec["datasets"][naming::GLOBAL_DATA_KEY]
-                .as_array()
-                .unwrap()
-                .len(),
-            3
-        );
-        assert!(vl_spec["layer"][0]["encoding"]["x"].is_object());
-        assert!(vl_spec["layer"][0]["encoding"]["y"].is_object());
+        // Top ticks mark x values from the top edge down
+        let top = &layers[0]["encoding"];
+        assert_eq!(layers[0]["mark"]["type"], "rule");
+        assert_eq!(top["x"]["field"], "a");
+        assert_eq!(top["y"]["value"], 0);
+        assert_eq!(top["y2"]["value"]["expr"], "height * 0.03");
+
+        // Left ticks mark y values from the left edge
+        let left = &layers[1]["encoding"];
+        assert_eq!(left["y"]["field"], "b");
+        assert_eq!(left["x"]["value"], 0);
+        assert_eq!(left["x2"]["value"]["expr"], "width * 0.03");
     }
 
     #[test]
-    fn test_with_title() {
+    fn test_interval_layers() {
         let writer = VegaLiteWriter::new();
 
-        let mut spec = Plot::new();
-        let layer = Layer::new(Geom::line())
-            .with_aesthetic(
-                "x".to_string(),
-                AestheticValue::standard_column("date".to_string()),
-            )
-            .with_aesthetic(
-                "y".to_string(),
-                AestheticValue::standard_column("value".to_string()),
-            );
-        spec.layers.push(layer);
-
-        let mut labels = Labels {
-            labels: HashMap::new(),
+        let interval = |geom: Geom| {
+            Layer::new(geom)
+                .with_aesthetic(
+                    "x".to_string(),
+                    AestheticValue::standard_column("group".to_string()),
+                )
+                .with_aesthetic(
+                    "y".to_string(),
+                    AestheticValue::standard_column("estimate".to_string()),
+                )
+                .with_aesthetic(
+                    "ymin".to_string(),
+                    AestheticValue::standard_column("lower".to_string()),
+                )
+                .with_aesthetic(
+                    "ymax".to_string(),
+                    AestheticValue::standard_column("upper".to_string()),
+                )
         };
-        labels
-            .labels
-            .insert("title".to_string(), "My Chart".to_string());
-        spec.labels = Some(labels);
+        let mut spec = Plot::new();
+        spec.layers.push(interval(Geom::linerange()));
+        spec.layers.push(interval(Geom::pointrange()));
+        spec.layers.push(interval(Geom::crossbar()));
 
         let df = df! {
-            "date" => &["2024-01-01", "2024-01-02"],
-            "value" => &[10, 20],
+            "group" => &["A", "B"],
+            "estimate" => &[2.0, 3.0],
+            "lower" => &[1.0, 2.5],
+            "upper" => &[3.0, 3.5],
         }
         .unwrap();
 
         let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
         let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
-
-        assert_eq!(vl_spec["title"], "My Chart");
-        assert_eq!(vl_spec["layer"][0]["mark"], "line");
+        let layers = vl_spec["layer"].as_array().unwrap();
+        assert_eq!(layers.len(), 5);
+
+        // Line range: a rule from ymin to ymax without a y2 baseline
+        assert_eq!(layers[0]["mark"]["type"], "rule");
+        assert_eq!(layers[0]["encoding"]["y"]["field"], "lower");
+        assert_eq!(layers[0]["encoding"]["y2"]["field"], "upper");
+
+        // Point range: the same rule plus a point at the estimate
+        assert_eq!(layers[1]["mark"]["type"], "rule");
+        assert_eq!(layers[1]["encoding"]["y2"]["field"], "upper");
+        assert_eq!(layers[2]["mark"]["type"], "point");
+        assert_eq!(layers[2]["encoding"]["y"]["field"], "estimate");
+
+        // Crossbar: a box over the interval and a line at the estimate
+        assert_eq!(layers[3]["mark"]["type"], "bar");
+        assert_eq!(layers[3]["mark"]["width"]["band"], 0.9);
+        assert_eq!(layers[3]["encoding"]["y"]["field"], "lower");
+        assert_eq!(layers[4]["mark"]["type"], "tick");
+        assert_eq!(layers[4]["encoding"]["y"]["field"], "estimate");
     }
 
     #[test]
-    fn test_literal_color() {
+    fn test_scale_transforms() {
+        use crate::plot::{Scale, ScaleType};
+
         let writer = VegaLiteWriter::new();
 
         let mut spec = Plot::new();
-        let layer = Layer::new(Geom::point())
+        let points = Layer::new(Geom::point())
             .with_aesthetic(
                 "x".to_string(),
                 AestheticValue::standard_column("x".to_string()),
@@ -1428,121 +4530,317 @@ mod tests {
                 AestheticValue::standard_column("y".to_string()),
             )
             .with_aesthetic(
-                "color".to_string(),
-                AestheticValue::Literal(LiteralValue::String("blue".to_string())),
+                "size".to_string(),
+                AestheticValue::standard_column("y".to_string()),
             );
-        spec.layers.push(layer);
+        spec.layers.push(points);
+        for (aesthetic, scale_type) in [
+            ("x", ScaleType::Reverse),
+            ("y", ScaleType::Log2),
+            ("size", ScaleType::Sqrt),
+        ] {
+            spec.scales.push(Scale {
+                aesthetic: aesthetic.to_string(),
+                scale_type: Some(scale_type),
+                properties: HashMap::new(),
+            });
+        }
 
         let df = df! {
-            "x" => &[1, 2],
-            "y" => &[3, 4],
+            "x" => &[1, 2, 3],
+            "y" => &[1.0, 10.0, 100.0],
         }
         .unwrap();
 
         let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
         let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
-
-        assert_eq!(vl_spec["layer"][0]["encoding"]["color"]["value"], "blue");
+        let encoding = &vl_spec["layer"][0]["encoding"];
+        assert_eq!(encoding["x"]["scale"]["reverse"], true);
+        assert_eq!(encoding["y"]["scale"]["type"], "log");
+        assert_eq!(encoding["y"]["scale"]["base"], 2);
+        assert_eq!(encoding["y"]["type"], "quantitative");
+        assert_eq!(encoding["size"]["scale"]["type"], "sqrt");
     }
 
     #[test]
-    fn test_missing_column_error() {
+    fn test_scale_breaks_labels_and_expand() {
+        use crate::plot::{ArrayElement, Scale};
+
         let writer = VegaLiteWriter::new();
 
         let mut spec = Plot::new();
-        let layer = Layer::new(Geom::point())
+        let points = Layer::new(Geom::point())
             .with_aesthetic(
                 "x".to_string(),
                 AestheticValue::standard_column("x".to_string()),
             )
             .with_aesthetic(
                 "y".to_string(),
-                AestheticValue::standard_column("foo".to_string()),
+                AestheticValue::standard_column("y".to_string()),
+            )
+            .with_aesthetic(
+                "color".to_string(),
+                AestheticValue::standard_column("group".to_string()),
             );
-        spec.layers.push(layer);
+        spec.layers.push(points);
+
+        let scale = |aesthetic: &str, properties: Vec<(&str, ParameterValue)>| Scale {
+            aesthetic: aesthetic.to_string(),
+            scale_type: None,
+            properties: properties
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        };
+        spec.scales.push(scale(
+            "x",
+            vec![
+                (
+                    "breaks",
+                    ParameterValue::Array(vec![
+                        ArrayElement::Number(1.0),
+                        ArrayElement::Number(3.0),
+                    ]),
+                ),
+                (
+                    "labels",
+                    ParameterValue::Array(vec![
+                        ArrayElement::String("one".to_string()),
+                        ArrayElement::String("it's three".to_string()),
+                    ]),
+                ),
+            ],
+        ));
+        spec.scales.push(scale(
+            "y",
+            vec![
+                ("labels", ParameterValue::String("percent".to_string())),
+                ("expand", ParameterValue::Number(0.1)),
+            ],
+        ));
+        spec.scales.push(scale(
+            "color",
+            vec![("na_value", ParameterValue::String("grey".to_string()))],
+        ));
 
         let df = df! {
-            "x" => &[1, 2],
-            "y" => &[3, 4],
+            "x" => &[1, 2, 3],
+            "y" => &[0.0, 0.5, 1.0],
+            "group" => &[Some("a"), None, Some("b")],
         }
         .unwrap();
 
-        let result = writer.write(&spec, &wrap_data(df));
-        assert!(result.is_err());
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let encoding = &vl_spec["layer"][0]["encoding"];
+        assert_eq!(encoding["x"]["axis"]["values"], json!([1.0, 3.0]));
+        assert_eq!(
+            encoding["x"]["axis"]["labelExpr"],
+            "datum.value === 1.0 ? 'one' : datum.value === 3.0 ? 'it\\'s three' : datum.label"
+        );
+        assert_eq!(encoding["y"]["axis"]["format"], ".0%");
+        assert_eq!(encoding["y"]["scale"]["domain"], json!([-0.1, 1.1]));
+        assert_eq!(encoding["color"]["condition"]["value"], "grey");
+        assert_eq!(
+            encoding["color"]["condition"]["test"],
+            "!isValid(datum[\"group\"])"
+        );
 
-        let err = result.unwrap_err();
-        let err_msg = err.to_string();
-        assert!(err_msg.contains("Column 'foo'"));
-        assert!(err_msg.contains("does not exist"));
-        assert!(err_msg.contains("Available columns: x, y"));
+        // Labels without matching breaks can't be placed
+        let small = df! {
+            "x" => &[1], "y" => &[1.0], "group" => &["a"],
+        }
+        .unwrap();
+        spec.scales[0].properties.remove("breaks");
+        let err = writer.write(&spec, &wrap_data(small.clone())).unwrap_err();
+        assert!(matches!(err, GgsqlError::ValidationError(_)));
+
+        // Missing positions are dropped, so they have no na_value
+        spec.scales[0].properties.remove("labels");
+        spec.scales[1].properties.insert(
+            "na_value".to_string(),
+            ParameterValue::String("grey".to_string()),
+        );
+        let err = writer.write(&spec, &wrap_data(small)).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("SCALE y does not support na_value"));
     }
 
     #[test]
-    fn test_missing_column_in_multi_layer() {
+    fn test_temporal_scale_formatting() {
+        use crate::plot::{Scale, ScaleType};
+
         let writer = VegaLiteWriter::new();
 
         let mut spec = Plot::new();
-
-        // First layer is valid
-        let layer1 = Layer::new(Geom::line())
+        let points = Layer::new(Geom::point())
             .with_aesthetic(
                 "x".to_string(),
-                AestheticValue::standard_column("x".to_string()),
+                AestheticValue::standard_column("t".to_string()),
             )
             .with_aesthetic(
                 "y".to_string(),
-                AestheticValue::standard_column("y".to_string()),
+                AestheticValue::standard_column("v".to_string()),
             );
-        spec.layers.push(layer1);
-
-        // Second layer references non-existent column
-        let layer2 = Layer::new(Geom::point())
-            .with_aesthetic(
-                "x".to_string(),
-                AestheticValue::standard_column("x".to_string()),
-            )
-            .with_aesthetic(
-                "y".to_string(),
-                AestheticValue::standard_column("missing_col".to_string()),
+        spec.layers.push(points);
+        let properties = |timezone: bool| {
+            let mut properties = HashMap::new();
+            properties.insert(
+                "breaks".to_string(),
+                ParameterValue::String("1 quarter".to_string()),
             );
-        spec.layers.push(layer2);
+            properties.insert(
+                "labels".to_string(),
+                ParameterValue::String("%b %Y".to_string()),
+            );
+            if timezone {
+                properties.insert(
+                    "timezone".to_string(),
+                    ParameterValue::String("Europe/Oslo".to_string()),
+                );
+            }
+            properties
+        };
+        spec.scales.push(Scale {
+            aesthetic: "x".to_string(),
+            scale_type: Some(ScaleType::DateTime),
+            properties: properties(true),
+        });
+
+        // 2024-01-01T00:00:00Z, stored with a UTC time zone
+        let t = Series::new("t".into(), &[1_704_067_200_000i64])
+            .cast(&DataType::Datetime(
+                TimeUnit::Milliseconds,
+                Some(polars::prelude::TimeZone::UTC),
+            ))
+            .unwrap();
+        let df = DataFrame::new(vec![t.into(), Series::new("v".into(), &[1.0]).into()]).unwrap();
+
+        let json_str = writer.write(&spec, &wrap_data(df.clone())).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let axis = &vl_spec["layer"][0]["encoding"]["x"]["axis"];
+        assert_eq!(axis["tickCount"], json!({"interval": "month", "step": 3}));
+        assert_eq!(axis["format"], "%b %Y");
+        assert_eq!(axis["formatType"], "time");
+        // Wall-clock time in Oslo, without an offset
+        assert_eq!(
+            vl_spec["datasets"][naming::GLOBAL_DATA_KEY][0]["t"],
+            "2024-01-01T01:00:00.000"
+        );
+
+        // Without a display time zone the column's own zone is kept
+        spec.scales[0].properties = properties(false);
+        let json_str = writer.write(&spec, &wrap_data(df.clone())).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        assert_eq!(
+            vl_spec["datasets"][naming::GLOBAL_DATA_KEY][0]["t"],
+            "2024-01-01T00:00:00.000+00:00"
+        );
+
+        // The time zone of another scale leaves the column alone
+        spec.scales.push(Scale {
+            aesthetic: "color".to_string(),
+            scale_type: None,
+            properties: HashMap::from([(
+                "timezone".to_string(),
+                ParameterValue::String("Europe/Oslo".to_string()),
+            )]),
+        });
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        assert_eq!(
+            vl_spec["datasets"][naming::GLOBAL_DATA_KEY][0]["t"],
+            "2024-01-01T00:00:00.000+00:00"
+        );
+
+        assert_eq!(time_format("%F %k:%M").unwrap(), "%Y-%m-%d %_H:%M");
+        assert_eq!(time_format("%-d %B").unwrap(), "%-d %B");
+        assert!(time_format("%H %Z").is_err());
+    }
+
+    #[test]
+    fn test_binned_colour_scales() {
+        use crate::plot::{resolve_palette, ArrayElement, Scale, ScaleType};
 
+        let writer = VegaLiteWriter::new();
         let df = df! {
-            "x" => &[1, 2],
-            "y" => &[3, 4],
+            "x" => &[1, 2, 3, 4],
+            "y" => &[1, 2, 3, 4],
+            "value" => &[0.5, 1.5, 2.5, 3.5],
         }
         .unwrap();
 
-        let result = writer.write(&spec, &wrap_data(df));
-        assert!(result.is_err());
+        let write_with_scale = |scale_type: ScaleType, properties: Vec<(&str, ParameterValue)>| {
+            let mut spec = Plot::new();
+            spec.layers.push(
+                Layer::new(Geom::tile())
+                    .with_aesthetic(
+                        "x".to_string(),
+                        AestheticValue::standard_column("x".to_string()),
+                    )
+                    .with_aesthetic(
+                        "y".to_string(),
+                        AestheticValue::standard_column("y".to_string()),
+                    )
+                    .with_aesthetic(
+                        "fill".to_string(),
+                        AestheticValue::standard_column("value".to_string()),
+                    ),
+            );
+            let mut scale = Scale {
+                aesthetic: "fill".to_string(),
+                scale_type: Some(scale_type),
+                properties: properties
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect(),
+            };
+            resolve_palette(&mut scale).unwrap();
+            spec.scales.push(scale);
+            writer
+                .write(&spec, &wrap_data(df.clone()))
+                .map(|json| serde_json::from_str::<Value>(&json).unwrap())
+        };
 
-        let err = result.unwrap_err();
-        let err_msg = err.to_string();
-        assert!(err_msg.contains("Column 'missing_col'"));
-        assert!(err_msg.contains("layer 2"));
+        // Equal-width bins with one colour per class
+        let vl_spec =
+            write_with_scale(ScaleType::Binned, vec![("n", ParameterValue::Number(4.0))]).unwrap();
+        let scale = &vl_spec["layer"][0]["encoding"]["color"]["scale"];
+        assert_eq!(scale["type"], "quantize");
+        assert_eq!(scale["range"].as_array().unwrap().len(), 4);
+        assert_eq!(scale["range"][0], "#440154");
+        assert_eq!(scale["range"][3], "#fde725");
+
+        // Breaks turn a binned scale into thresholds
+        let breaks =
+            ParameterValue::Array(vec![ArrayElement::Number(1.0), ArrayElement::Number(3.0)]);
+        let vl_spec =
+            write_with_scale(ScaleType::Binned, vec![("breaks", breaks.clone())]).unwrap();
+        let color = &vl_spec["layer"][0]["encoding"]["color"];
+        assert_eq!(color["scale"]["type"], "threshold");
+        assert_eq!(color["scale"]["domain"], json!([1.0, 3.0]));
+        assert_eq!(color["scale"]["range"].as_array().unwrap().len(), 3);
+        assert!(color.get("legend").is_none());
+
+        let vl_spec = write_with_scale(ScaleType::Quantile, vec![]).unwrap();
+        let scale = &vl_spec["layer"][0]["encoding"]["color"]["scale"];
+        assert_eq!(scale["type"], "quantile");
+        assert_eq!(scale["range"].as_array().unwrap().len(), 5);
+
+        assert!(write_with_scale(ScaleType::Threshold, vec![]).is_err());
+        assert!(write_with_scale(ScaleType::Quantile, vec![("breaks", breaks)]).is_err());
     }
 
-    // ========================================
-    // Comprehensive Grammar Coverage Tests
-    // ========================================
-
     #[test]
-    fn test_all_basic_geom_types() {
-        let writer = VegaLiteWriter::new();
+    fn test_manual_scale_values() {
+        use crate::plot::{ArrayElement, Scale};
 
-        let geoms = vec![
-            (Geom::point(), "point"),
-            (Geom::line(), "line"),
-            (Geom::path(), "line"),
-            (Geom::bar(), "bar"),
-            (Geom::area(), "area"),
-            (Geom::tile(), "rect"),
-            (Geom::ribbon(), "area"),
-        ];
+        let writer = VegaLiteWriter::new();
 
-        for (geom, expected_mark) in geoms {
-            let mut spec = Plot::new();
-            let layer = Layer::new(geom.clone())
+        let mut spec = Plot::new();
+        spec.layers.push(
+            Layer::new(Geom::point())
                 .with_aesthetic(
                     "x".to_string(),
                     AestheticValue::standard_column("x".to_string()),
@@ -1552,69 +4850,265 @@ mod tests {
                     AestheticValue::standard_column("y".to_string()),
                 )
                 .with_aesthetic(
-                    "ymin".to_string(),
-                    AestheticValue::standard_column("ymin".to_string()),
-                )
-                .with_aesthetic(
-                    "ymax".to_string(),
-                    AestheticValue::standard_column("ymax".to_string()),
-                );
-            spec.layers.push(layer);
-
-            let df = df! {
-                "x" => &[1, 2, 3],
-                "y" => &[4, 5, 6],
-                "ymin" => &[3, 4, 5],
-                "ymax" => &[5, 6, 7],
-            }
-            .unwrap();
-
-            let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
-            let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+                    "color".to_string(),
+                    AestheticValue::standard_column("species".to_string()),
+                ),
+        );
+        let mut properties = HashMap::new();
+        properties.insert(
+            "values".to_string(),
+            ParameterValue::Map(vec![
+                (
+                    ArrayElement::String("Adelie".to_string()),
+                    ArrayElement::String("#1b9e77".to_string()),
+                ),
+                (
+                    ArrayElement::String("Chinstrap".to_string()),
+                    ArrayElement::String("#d95f02".to_string()),
+                ),
+            ]),
+        );
+        spec.scales.push(Scale {
+            aesthetic: "color".to_string(),
+            scale_type: None,
+            properties,
+        });
 
-            // Handle both string marks and object marks (e.g., Bar has {"type": "bar", "width": ...})
-            let mark_type = vl_spec["layer"][0]["mark"]
-                .as_str()
-                .or_else(|| vl_spec["layer"][0]["mark"]["type"].as_str())
-                .unwrap();
-            assert_eq!(mark_type, expected_mark, "Failed for geom: {:?}", geom);
+        // Chinstrap is missing from the data but keeps its colour
+        let df = df! {
+            "x" => &[1, 2],
+            "y" => &[1, 2],
+            "species" => &["Adelie", "Adelie"],
         }
+        .unwrap();
+
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let color = &vl_spec["layer"][0]["encoding"]["color"];
+        assert_eq!(color["type"], "nominal");
+        assert_eq!(color["scale"]["domain"], json!(["Adelie", "Chinstrap"]));
+        assert_eq!(color["scale"]["range"], json!(["#1b9e77", "#d95f02"]));
     }
 
-    #[test]
-    fn test_statistical_geom_types() {
-        let writer = VegaLiteWriter::new();
+    #[test]
+    fn test_secondary_axis() {
+        use crate::plot::Scale;
+
+        let writer = VegaLiteWriter::new();
+
+        let mut spec = Plot::new();
+        spec.layers.push(
+            Layer::new(Geom::line())
+                .with_aesthetic(
+                    "x".to_string(),
+                    AestheticValue::standard_column("day".to_string()),
+                )
+                .with_aesthetic(
+                    "y".to_string(),
+                    AestheticValue::standard_column("celsius".to_string()),
+                ),
+        );
+        let mut properties = HashMap::new();
+        properties.insert(
+            "sec_axis".to_string(),
+            ParameterValue::String("x * 1.8 + 32".to_string()),
+        );
+        properties.insert(
+            "sec_name".to_string(),
+            ParameterValue::String("°F".to_string()),
+        );
+        spec.scales.push(Scale {
+            aesthetic: "y".to_string(),
+            scale_type: None,
+            properties,
+        });
+
+        let df = df! {
+            "day" => &[1, 2, 3],
+            "celsius" => &[0.0, 50.0, 100.0],
+        }
+        .unwrap();
 
-        let geoms = vec![
-            (Geom::histogram(), "bar"),
-            (Geom::density(), "area"),
-            (Geom::boxplot(), "boxplot"),
-        ];
+        let json_str = writer.write(&spec, &wrap_data(df.clone())).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
 
-        for (geom, expected_mark) in geoms {
-            let mut spec = Plot::new();
-            let layer = Layer::new(geom.clone())
+        // The primary domain is fixed with ggplot2's 5% expansion
+        let y = &vl_spec["layer"][0]["layer"][0]["encoding"]["y"];
+        assert_eq!(y["scale"]["domain"], json!([-5.0, 105.0]));
+        assert_eq!(y["scale"]["nice"], false);
+
+        // The secondary axis spans the transformed domain
+        let secondary = &vl_spec["layer"][1];
+        let sec_y = &secondary["encoding"]["y"];
+        assert_eq!(sec_y["title"], "°F");
+        let domain = sec_y["scale"]["domain"].as_array().unwrap();
+        assert!((domain[0].as_f64().unwrap() - 23.0).abs() < 1e-9);
+        assert!((domain[1].as_f64().unwrap() - 221.0).abs() < 1e-9);
+        assert_eq!(secondary["mark"]["opacity"], 0);
+        assert_eq!(secondary["mark"]["clip"], true);
+        assert_eq!(secondary["data"]["values"].as_array().unwrap().len(), 2);
+        assert_eq!(vl_spec["resolve"]["scale"]["y"], "independent");
+
+        // Further primary layers share the primary axis
+        spec.layers.push(
+            Layer::new(Geom::point())
                 .with_aesthetic(
                     "x".to_string(),
-                    AestheticValue::standard_column("x".to_string()),
+                    AestheticValue::standard_column("day".to_string()),
                 )
                 .with_aesthetic(
                     "y".to_string(),
-                    AestheticValue::standard_column("y".to_string()),
-                );
-            spec.layers.push(layer);
+                    AestheticValue::standard_column("celsius".to_string()),
+                ),
+        );
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let layers = vl_spec["layer"].as_array().unwrap();
+        assert_eq!(layers.len(), 2);
+        let primary = layers[0]["layer"].as_array().unwrap();
+        assert_eq!(primary.len(), 2);
+        for layer in primary {
+            assert_eq!(
+                layer["encoding"]["y"]["scale"]["domain"],
+                json!([-5.0, 105.0])
+            );
+        }
+        assert!(layers[0].get("resolve").is_none());
+        assert_eq!(layers[1]["encoding"]["y"]["title"], "°F");
+        assert_eq!(vl_spec["resolve"], json!({"scale": {"y": "independent"}}));
 
-            let df = df! {
-                "x" => &[1, 2, 3],
-                "y" => &[4, 5, 6],
-            }
+        // A secondary axis can't be faceted
+        spec.facet = Some(crate::plot::Facet::Wrap {
+            variables: vec!["day".to_string()],
+            scales: crate::plot::FacetScales::Fixed,
+            properties: HashMap::new(),
+        });
+        let err = writer
+            .write(
+                &spec,
+                &wrap_data(df! {"day" => &[1], "celsius" => &[1.0]}.unwrap()),
+            )
+            .unwrap_err();
+        assert!(matches!(err, GgsqlError::ValidationError(_)));
+    }
+
+    #[test]
+    fn test_theme_config() {
+        use crate::plot::Theme;
+
+        let writer = VegaLiteWriter::new();
+        let mut spec = Plot::new();
+        spec.layers.push(
+            Layer::new(Geom::point())
+                .with_aesthetic("x".to_string(), AestheticValue::standard_column("x"))
+                .with_aesthetic("y".to_string(), AestheticValue::standard_column("y")),
+        );
+        let df = df! {"x" => &[1, 2], "y" => &[3, 4]}.unwrap();
+
+        // Without a THEME clause the Vega-Lite defaults are kept
+        let vl_spec: Value =
+            serde_json::from_str(&writer.write(&spec, &wrap_data(df.clone())).unwrap()).unwrap();
+        assert!(vl_spec.get("config").is_none());
+
+        let mut properties = HashMap::new();
+        properties.insert(
+            "legend_position".to_string(),
+            ParameterValue::String("none".to_string()),
+        );
+        properties.insert("text_size".to_string(), ParameterValue::Number(14.0));
+        spec.theme = Some(Theme {
+            style: Some("classic".to_string()),
+            properties,
+        });
+        let vl_spec: Value =
+            serde_json::from_str(&writer.write(&spec, &wrap_data(df.clone())).unwrap()).unwrap();
+        let config = &vl_spec["config"];
+        assert_eq!(config["axis"]["grid"], false);
+        assert_eq!(config["axis"]["domain"], true);
+        assert_eq!(config["axis"]["domainColor"], "black");
+        assert_eq!(config["axis"]["titleFontSize"], 14.0);
+        assert_eq!(config["view"]["stroke"], Value::Null);
+        assert_eq!(config["legend"]["disable"], true);
+
+        spec.theme = Some(Theme {
+            style: Some("grey".to_string()),
+            properties: HashMap::new(),
+        });
+        let vl_spec: Value =
+            serde_json::from_str(&writer.write(&spec, &wrap_data(df.clone())).unwrap()).unwrap();
+        assert_eq!(vl_spec["config"]["view"]["fill"], "#ebebeb");
+        assert_eq!(vl_spec["config"]["axis"]["gridColor"], "white");
+        assert_eq!(vl_spec["config"]["legend"]["orient"], "right");
+
+        spec.theme = Some(Theme {
+            style: Some("acme".to_string()),
+            properties: HashMap::new(),
+        });
+        // An unregistered theme is a config error, not an empty config
+        let err = writer.write(&spec, &wrap_data(df.clone())).unwrap_err();
+        assert!(matches!(err, GgsqlError::ConfigError(_)), "{}", err);
+
+        let mut themes = ThemeRegistry::new();
+        themes
+            .load_json(
+                r##"{"inherits": "minimal", "background": "#fdf6e3"}"##,
+                "acme",
+            )
             .unwrap();
+        let writer = VegaLiteWriter::new().with_themes(themes);
+        let vl_spec: Value =
+            serde_json::from_str(&writer.write(&spec, &wrap_data(df)).unwrap()).unwrap();
+        assert_eq!(vl_spec["config"]["background"], "#fdf6e3");
+        assert_eq!(vl_spec["config"]["view"]["fill"], Value::Null);
+    }
 
-            let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
-            let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+    #[test]
+    fn test_violin_numeric_axis() {
+        let writer = VegaLiteWriter::new();
+
+        let mut spec = Plot::new();
+        let layer = Layer::new(Geom::violin())
+            .with_aesthetic(
+                "x".to_string(),
+                AestheticValue::standard_column("species".to_string()),
+            )
+            .with_aesthetic(
+                "y".to_string(),
+                AestheticValue::standard_column(naming::stat_column("y")),
+            )
+            .with_aesthetic(
+                "xmin".to_string(),
+                AestheticValue::standard_column(naming::stat_column("xmin")),
+            )
+            .with_aesthetic(
+                "xmax".to_string(),
+                AestheticValue::standard_column(naming::stat_column("xmax")),
+            );
+        spec.layers.push(layer);
 
-            assert_eq!(vl_spec["layer"][0]["mark"].as_str().unwrap(), expected_mark);
+        let df = df! {
+            "species" => &["A", "A", "B", "B"],
+            "__ggsql_stat_y" => &[1.0, 2.0, 1.0, 2.0],
+            "__ggsql_stat_xpos" => &[1, 1, 2, 2],
+            "__ggsql_stat_xmin" => &[0.8, 0.6, 1.9, 1.7],
+            "__ggsql_stat_xmax" => &[1.2, 1.4, 2.1, 2.3],
         }
+        .unwrap();
+
+        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let layer = &vl_spec["layer"][0];
+
+        assert_eq!(layer["mark"]["type"], "area");
+        assert_eq!(layer["mark"]["orient"], "horizontal");
+        assert_eq!(layer["encoding"]["x"]["field"], "__ggsql_stat_xmin");
+        assert_eq!(layer["encoding"]["x2"]["field"], "__ggsql_stat_xmax");
+        assert_eq!(layer["encoding"]["x"]["axis"]["values"], json!([1, 2]));
+        assert_eq!(
+            layer["encoding"]["x"]["axis"]["labelExpr"],
+            "datum.value === 1 ? \"A\" : datum.value === 2 ? \"B\" : ''"
+        );
+        assert_eq!(layer["encoding"]["detail"]["field"], "species");
     }
 
     #[test]
@@ -1643,7 +5137,10 @@ mod tests {
             let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
             let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
 
-            assert_eq!(vl_spec["layer"][0]["mark"].as_str().unwrap(), "text");
+            assert_eq!(
+                vl_spec["layer"][0]["mark"]["type"].as_str().unwrap(),
+                "text"
+            );
         }
     }
 
@@ -1821,7 +5318,7 @@ mod tests {
             )
             .with_aesthetic(
                 "size".to_string(),
-                AestheticValue::Literal(LiteralValue::Number(100.0)),
+                AestheticValue::Literal(LiteralValue::Number(3.0)),
             );
         spec.layers.push(layer);
 
@@ -1834,11 +5331,15 @@ mod tests {
         let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
         let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
 
-        assert_eq!(vl_spec["layer"][0]["encoding"]["size"]["value"], 100.0);
+        // Sizes in millimetres become the area of the point
+        let size = vl_spec["layer"][0]["encoding"]["size"]["value"]
+            .as_f64()
+            .unwrap();
+        assert!((size - (3.0 * POINTS_PER_MM).powi(2)).abs() < 1e-9);
     }
 
     #[test]
-    fn test_literal_boolean_value() {
+    fn test_literal_line_values() {
         let writer = VegaLiteWriter::new();
 
         let mut spec = Plot::new();
@@ -1853,8 +5354,9 @@ mod tests {
             )
             .with_aesthetic(
                 "linetype".to_string(),
-                AestheticValue::Literal(LiteralValue::Boolean(true)),
-            );
+                AestheticValue::Literal(LiteralValue::String("dashed".to_string())),
+            )
+            .with_parameter("linewidth".to_string(), ParameterValue::Number(1.0));
         spec.layers.push(layer);
 
         let df = df! {
@@ -1863,10 +5365,147 @@ mod tests {
         }
         .unwrap();
 
-        let json_str = writer.write(&spec, &wrap_data(df)).unwrap();
+        let json_str = writer.write(&spec, &wrap_data(df.clone())).unwrap();
+        let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
+        let encoding = &vl_spec["layer"][0]["encoding"];
+        assert_eq!(encoding["strokeDash"]["value"], json!([8.0, 8.0]));
+        let width = encoding["strokeWidth"]["value"].as_f64().unwrap();
+        assert!((width - 72.27 / 25.4).abs() < 1e-9);
+
+        // Booleans are not line types
+        spec.layers[0] = spec.layers[0].clone().with_aesthetic(
+            "linetype".to_string(),
+            AestheticValue::Literal(LiteralValue::Boolean(true)),
+        );
+        assert!(writer.write(&spec, &wrap_data(df)).is_err());
+    }
+
+    #[test]
+    fn test_shape_and_linetype_scales() {
+        use crate::plot::{ArrayElement, Scale};
+
+        let writer = VegaLiteWriter::new();
+
+        let mut spec = Plot::new();
+        spec.layers.push(
+            Layer::new(Geom::point())
+                .with_aesthetic(
+                    "x".to_string(),
+                    AestheticValue::standard_column("x".to_string()),
+                )
+                .with_aesthetic(
+                    "y".to_string(),
+                    AestheticValue::standard_column("y".to_string()),
+                )
+                .with_aesthetic(
+                    "shape".to_string(),
+                    AestheticValue::standard_column("group".to_string()),
+                ),
+        );
+        spec.layers.push(
+            Layer::new(Geom::line())
+                .with_aesthetic(
+                    "x".to_string(),
+                    AestheticValue::standard_column("x".to_string()),
+                )
+                .with_aesthetic(
+                    "y".to_string(),
+                    AestheticValue::standard_column("y".to_string()),
+                )
+                .with_aesthetic(
+                    "linetype".to_string(),
+                    AestheticValue::standard_column("group".to_string()),
+                ),
+        );
+        spec.layers.push(
+            Layer::new(Geom::point())
+                .with_aesthetic(
+                    "x".to_string(),
+                    AestheticValue::standard_column("x".to_string()),
+                )
+                .with_aesthetic(
+                    "y".to_string(),
+                    AestheticValue::standard_column("y".to_string()),
+                )
+                .with_parameter("shape".to_string(), ParameterValue::Number(2.0))
+                .with_parameter("size".to_string(), ParameterValue::Number(2.0)),
+        );
+        let mut properties = HashMap::new();
+        properties.insert(
+            "values".to_string(),
+            ParameterValue::Map(vec![
+                (
+                    ArrayElement::Number(1.0),
+                    ArrayElement::String("longdash".to_string()),
+                ),
+                (
+                    ArrayElement::Number(2.0),
+                    ArrayElement::String("1342".to_string()),
+                ),
+            ]),
+        );
+        spec.scales.push(Scale {
+            aesthetic: "linetype".to_string(),
+            scale_type: None,
+            properties,
+        });
+
+        let df = df! {
+            "x" => &[1, 2],
+            "y" => &[3, 4],
+            "group" => &[1, 2],
+        }
+        .unwrap();
+
+        let json_str = writer.write(&spec, &wrap_data(df.clone())).unwrap();
         let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
 
-        assert_eq!(vl_spec["layer"][0]["encoding"]["linetype"]["value"], true);
+        // Numeric columns get discrete shapes from the ggplot2 palette
+        let shape = &vl_spec["layer"][0]["encoding"]["shape"];
+        assert_eq!(shape["type"], "ordinal");
+        assert_eq!(shape["scale"]["range"][0], "circle");
+        assert_eq!(shape["scale"]["range"][1], "triangle-up");
+        // Its solid shapes are filled and the line shapes left open
+        let layer = &vl_spec["layer"][0];
+        assert_eq!(layer["mark"]["filled"], true);
+        let fill_opacity = &layer["encoding"]["fillOpacity"];
+        assert_eq!(fill_opacity["field"], "group");
+        assert_eq!(
+            fill_opacity["scale"]["range"],
+            json!([1.0, 1.0, 1.0, 0.0, 0.0, 0.0])
+        );
+
+        // Manual line types become dash arrays
+        let dash = &vl_spec["layer"][1]["encoding"]["strokeDash"];
+        assert_eq!(dash["type"], "nominal");
+        assert_eq!(dash["scale"]["domain"], json!([1.0, 2.0]));
+        assert_eq!(
+            dash["scale"]["range"],
+            json!([[14.0, 6.0], [2.0, 6.0, 8.0, 4.0]])
+        );
+
+        // Shape code 2 is an open triangle, 2 mm across
+        let layer = &vl_spec["layer"][2];
+        assert_eq!(layer["encoding"]["shape"]["value"], "triangle-up");
+        assert_eq!(layer["mark"]["filled"], false);
+        let size = layer["encoding"]["size"]["value"].as_f64().unwrap();
+        assert!((size - (2.0 * POINTS_PER_MM).powi(2)).abs() < 1e-9);
+
+        // Only whole shape codes from 0 to 25 exist
+        for code in [-1.0, 2.7, 26.0] {
+            spec.layers[2]
+                .parameters
+                .insert("shape".to_string(), ParameterValue::Number(code));
+            let err = writer.write(&spec, &wrap_data(df.clone())).unwrap_err();
+            assert!(matches!(err, GgsqlError::ValidationError(_)), "{}", code);
+        }
+
+        // Text is sized by its font size
+        let mut encoding = Map::new();
+        encoding.insert("size".to_string(), json!({"value": 3.88}));
+        apply_size_units(&mut encoding, true).unwrap();
+        let font_size = encoding["size"]["value"].as_f64().unwrap();
+        assert!((font_size - 11.04).abs() < 0.01);
     }
 
     #[test]
@@ -1918,12 +5557,12 @@ mod tests {
         assert_eq!(layers.len(), 2);
 
         // Check first layer
-        assert_eq!(layers[0]["mark"], "line");
+        assert_eq!(layers[0]["mark"]["type"], "line");
         assert_eq!(layers[0]["encoding"]["x"]["field"], "x");
         assert_eq!(layers[0]["encoding"]["y"]["field"], "y");
 
         // Check second layer
-        assert_eq!(layers[1]["mark"], "point");
+        assert_eq!(layers[1]["mark"]["type"], "point");
         assert_eq!(layers[1]["encoding"]["color"]["value"], "red");
     }
 
@@ -1983,9 +5622,9 @@ mod tests {
 
         let layers = vl_spec["layer"].as_array().unwrap();
         assert_eq!(layers.len(), 3);
-        assert_eq!(layers[0]["mark"], "area");
-        assert_eq!(layers[1]["mark"], "line");
-        assert_eq!(layers[2]["mark"], "point");
+        assert_eq!(layers[0]["mark"]["type"], "area");
+        assert_eq!(layers[1]["mark"]["type"], "line");
+        assert_eq!(layers[2]["mark"]["type"], "point");
     }
 
     #[test]
@@ -3189,6 +6828,108 @@ mod tests {
         assert_eq!(vl_spec["layer"][0]["encoding"]["size"]["field"], "value");
     }
 
+    #[test]
+    fn test_coord_fixed_trans_and_clip() {
+        use crate::plot::{train_scales, Coord};
+
+        let writer = VegaLiteWriter::new();
+        let mut spec = Plot::new();
+        spec.layers.push(
+            Layer::new(Geom::point())
+                .with_aesthetic("x".to_string(), AestheticValue::standard_column("x"))
+                .with_aesthetic("y".to_string(), AestheticValue::standard_column("y")),
+        );
+        let data = wrap_data(
+            df! {
+                "x" => &[10.0, 20.0, 30.0],
+                "y" => &[58.0, 60.0, 62.0],
+            }
+            .unwrap(),
+        );
+        spec.trained_scales = train_scales(&spec, &data).unwrap();
+        let coord = |coord_type: CoordType, properties: &[(&str, ParameterValue)]| Coord {
+            coord_type,
+            properties: properties
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        };
+
+        // 4 units of y drawn three times as long as the 20 units of x
+        spec.coord = Some(coord(
+            CoordType::Fixed,
+            &[("ratio", ParameterValue::Number(3.0))],
+        ));
+        let vl_spec: Value = serde_json::from_str(&writer.write(&spec, &data).unwrap()).unwrap();
+        assert_eq!(vl_spec["width"], 400.0);
+        assert_eq!(vl_spec["height"], 240.0);
+        assert_eq!(
+            vl_spec["layer"][0]["encoding"]["y"]["scale"],
+            json!({"domain": [58.0, 62.0], "nice": false, "zero": false})
+        );
+
+        // Without trained scales the ranges come from the data
+        let mut untrained = spec.clone();
+        untrained.trained_scales.clear();
+        let vl_spec: Value =
+            serde_json::from_str(&writer.write(&untrained, &data).unwrap()).unwrap();
+        assert_eq!(vl_spec["width"], 400.0);
+        assert_eq!(vl_spec["height"], 240.0);
+
+        // Longitude is stretched by 1 / cos(60°) at a latitude of 60°
+        spec.coord = Some(coord(CoordType::QuickMap, &[]));
+        let vl_spec: Value = serde_json::from_str(&writer.write(&spec, &data).unwrap()).unwrap();
+        assert_eq!(vl_spec["width"], 400.0);
+        assert_eq!(vl_spec["height"], 160.0);
+
+        spec.coord = Some(coord(
+            CoordType::Trans,
+            &[
+                ("x", ParameterValue::String("log10".to_string())),
+                ("y", ParameterValue::String("reverse".to_string())),
+                ("clip", ParameterValue::String("on".to_string())),
+            ],
+        ));
+        let vl_spec: Value = serde_json::from_str(&writer.write(&spec, &data).unwrap()).unwrap();
+        let layer = &vl_spec["layer"][0];
+        assert_eq!(layer["encoding"]["x"]["scale"]["type"], "log");
+        assert_eq!(layer["encoding"]["x"]["scale"]["base"], 10);
+        assert_eq!(layer["encoding"]["y"]["scale"]["reverse"], true);
+        assert_eq!(layer["mark"], json!({"type": "point", "clip": true}));
+        assert_eq!(vl_spec["width"], "container");
+
+        // Marks are clipped unless clip is turned off
+        let mut unclipped = spec.clone();
+        unclipped.coord = Some(coord(
+            CoordType::Trans,
+            &[("clip", ParameterValue::String("off".to_string()))],
+        ));
+        let vl_spec: Value =
+            serde_json::from_str(&writer.write(&unclipped, &data).unwrap()).unwrap();
+        assert_eq!(vl_spec["layer"][0]["mark"]["clip"], false);
+        unclipped.coord = None;
+        let vl_spec: Value =
+            serde_json::from_str(&writer.write(&unclipped, &data).unwrap()).unwrap();
+        assert_eq!(vl_spec["layer"][0]["mark"]["clip"], true);
+
+        // Lines would have to bend on a log axis, but not on a reversed one
+        let mut lines = spec.clone();
+        lines.layers[0].geom = Geom::line();
+        let err = writer.write(&lines, &data).unwrap_err().to_string();
+        assert!(err.contains("can't transform x for a line layer"));
+        lines.coord = Some(coord(
+            CoordType::Trans,
+            &[("y", ParameterValue::String("reverse".to_string()))],
+        ));
+        assert!(writer.write(&lines, &data).is_ok());
+
+        // Log scales need positive values
+        let data = wrap_data(df! {"x" => &[0.0, 1.0], "y" => &[1.0, 2.0]}.unwrap());
+        spec.trained_scales = train_scales(&spec, &data).unwrap();
+        let err = writer.write(&spec, &data).unwrap_err().to_string();
+        assert!(err.contains("can't log-transform x"));
+    }
+
     #[test]
     fn test_coord_polar_basic_pie_chart() {
         use crate::plot::Coord;
@@ -3223,7 +6964,7 @@ mod tests {
         let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
 
         // Bar in polar should become arc
-        assert_eq!(vl_spec["layer"][0]["mark"], "arc");
+        assert_eq!(vl_spec["layer"][0]["mark"]["type"], "arc");
 
         // y should be mapped to theta
         assert!(vl_spec["layer"][0]["encoding"]["theta"].is_object());
@@ -3282,7 +7023,7 @@ mod tests {
         let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
 
         // Should produce same result as default
-        assert_eq!(vl_spec["layer"][0]["mark"], "arc");
+        assert_eq!(vl_spec["layer"][0]["mark"]["type"], "arc");
         assert_eq!(vl_spec["layer"][0]["encoding"]["theta"]["field"], "value");
     }
 
@@ -3650,6 +7391,7 @@ mod tests {
         spec.facet = Some(Facet::Wrap {
             variables: vec!["region".to_string()],
             scales: crate::plot::FacetScales::Fixed,
+            properties: HashMap::new(),
         });
 
         let df = df! {
@@ -3684,6 +7426,7 @@ mod tests {
             vl_spec["spec"]["layer"][0].get("data").is_none(),
             "Faceted layers should not have per-layer data"
         );
+        assert_eq!(vl_spec["spec"]["layer"][0]["mark"]["clip"], true);
     }
 
     #[test]
@@ -3707,6 +7450,7 @@ mod tests {
             rows: vec!["region".to_string()],
             cols: vec!["category".to_string()],
             scales: crate::plot::FacetScales::Fixed,
+            properties: HashMap::new(),
         });
 
         let df = df! {
@@ -3745,6 +7489,187 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_facet_settings() {
+        use crate::plot::{Facet, FacetScales};
+
+        let writer = VegaLiteWriter::new();
+        let mut spec = Plot::new();
+        spec.layers.push(
+            Layer::new(Geom::point())
+                .with_aesthetic("x".to_string(), AestheticValue::standard_column("x"))
+                .with_aesthetic("y".to_string(), AestheticValue::standard_column("y")),
+        );
+        let df = df! {
+            "x" => &[1, 2, 3, 4, 5],
+            "y" => &[10, 20, 15, 25, 30],
+            "region" => &["North", "North", "South", "South", "East"],
+            "year" => &[2020, 2021, 2020, 2021, 2020],
+        }
+        .unwrap();
+        let properties = |entries: &[(&str, ParameterValue)]| -> HashMap<String, ParameterValue> {
+            entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect()
+        };
+
+        // Several variables are combined into one label per panel
+        spec.facet = Some(Facet::Wrap {
+            variables: vec!["region".to_string(), "year".to_string()],
+            scales: FacetScales::FreeY,
+            properties: properties(&[
+                ("ncol", ParameterValue::Number(2.0)),
+                ("label", ParameterValue::String("both".to_string())),
+            ]),
+        });
+        let vl_spec: Value =
+            serde_json::from_str(&writer.write(&spec, &wrap_data(df.clone())).unwrap()).unwrap();
+        let column = naming::facet_column("facet");
+        assert_eq!(vl_spec["facet"]["field"], column.as_str());
+        assert_eq!(
+            vl_spec["facet"]["sort"],
+            json!([
+                "region: East, year: 2020",
+                "region: North, year: 2020",
+                "region: North, year: 2021",
+                "region: South, year: 2020",
+                "region: South, year: 2021"
+            ])
+        );
+        assert_eq!(
+            vl_spec["datasets"][naming::GLOBAL_DATA_KEY][0][column.as_str()],
+            "region: North, year: 2020"
+        );
+        assert_eq!(vl_spec["columns"], 2);
+        assert_eq!(vl_spec["resolve"]["scale"]["y"], "independent");
+        assert!(vl_spec["resolve"]["scale"].get("x").is_none());
+
+        // Filling columns first reorders the panels
+        spec.facet = Some(Facet::Wrap {
+            variables: vec!["region".to_string()],
+            scales: FacetScales::Fixed,
+            properties: properties(&[
+                ("nrow", ParameterValue::Number(2.0)),
+                ("dir", ParameterValue::String("v".to_string())),
+            ]),
+        });
+        let vl_spec: Value =
+            serde_json::from_str(&writer.write(&spec, &wrap_data(df.clone())).unwrap()).unwrap();
+        assert_eq!(vl_spec["facet"]["field"], "region");
+        assert_eq!(vl_spec["columns"], 2);
+        assert_eq!(vl_spec["facet"]["sort"], json!(["East", "South", "North"]));
+
+        // Five panels can't fill four rows of two columns, column by column, without a gap
+        spec.facet = Some(Facet::Wrap {
+            variables: vec!["region".to_string(), "year".to_string()],
+            scales: FacetScales::Fixed,
+            properties: properties(&[
+                ("nrow", ParameterValue::Number(4.0)),
+                ("dir", ParameterValue::String("v".to_string())),
+            ]),
+        });
+        assert!(writer.write(&spec, &wrap_data(df.clone())).is_err());
+
+        spec.facet = Some(Facet::Wrap {
+            variables: vec!["region".to_string(), "year".to_string()],
+            scales: FacetScales::Fixed,
+            properties: properties(&[
+                ("ncol", ParameterValue::Number(2.0)),
+                ("nrow", ParameterValue::Number(2.0)),
+            ]),
+        });
+        assert!(writer.write(&spec, &wrap_data(df.clone())).is_err());
+
+        // Grid rows with several variables, free scales and wrapped labels
+        spec.facet = Some(Facet::Grid {
+            rows: vec!["region".to_string(), "year".to_string()],
+            cols: vec![],
+            scales: FacetScales::Free,
+            properties: properties(&[("label_wrap", ParameterValue::Number(6.0))]),
+        });
+        let vl_spec: Value =
+            serde_json::from_str(&writer.write(&spec, &wrap_data(df.clone())).unwrap()).unwrap();
+        assert_eq!(
+            vl_spec["facet"]["row"]["field"],
+            naming::facet_column("row").as_str()
+        );
+        assert_eq!(vl_spec["facet"]["row"]["sort"][0], "East,\n2020");
+        assert_eq!(vl_spec["facet"]["row"]["header"]["title"], "region, year");
+        assert_eq!(vl_spec["resolve"]["scale"]["x"], "independent");
+        assert_eq!(vl_spec["resolve"]["scale"]["y"], "independent");
+
+        spec.facet = Some(Facet::Wrap {
+            variables: vec!["missing".to_string()],
+            scales: FacetScales::Fixed,
+            properties: HashMap::new(),
+        });
+        let err = writer.write(&spec, &wrap_data(df)).unwrap_err().to_string();
+        assert!(err.contains("Facet variable 'missing' not found"));
+    }
+
+    #[test]
+    fn test_facet_layers_from_different_sources() {
+        use crate::plot::{Facet, FacetScales};
+
+        let writer = VegaLiteWriter::new();
+        let mut spec = Plot::new();
+        for _ in 0..2 {
+            spec.layers.push(
+                Layer::new(Geom::point())
+                    .with_aesthetic("x".to_string(), AestheticValue::standard_column("x"))
+                    .with_aesthetic("y".to_string(), AestheticValue::standard_column("y")),
+            );
+        }
+        spec.facet = Some(Facet::Wrap {
+            variables: vec!["region".to_string()],
+            scales: FacetScales::Fixed,
+            properties: HashMap::new(),
+        });
+
+        // The second layer has no region and is drawn in every panel
+        let mut data = HashMap::new();
+        data.insert(
+            naming::GLOBAL_DATA_KEY.to_string(),
+            df! {
+                "x" => &[1, 2, 3],
+                "y" => &[10, 20, 15],
+                "region" => &["North", "South", "North"],
+            }
+            .unwrap(),
+        );
+        data.insert(
+            naming::layer_key(1),
+            df! {"x" => &[2], "y" => &[12]}.unwrap(),
+        );
+        let vl_spec: Value = serde_json::from_str(&writer.write(&spec, &data).unwrap()).unwrap();
+
+        assert_eq!(vl_spec["data"]["name"], naming::FACET_DATA_KEY);
+        assert_eq!(vl_spec["facet"]["field"], "region");
+        let datasets = vl_spec["datasets"].as_object().unwrap();
+        assert_eq!(datasets.len(), 1);
+        let rows = datasets[naming::FACET_DATA_KEY].as_array().unwrap();
+        assert_eq!(rows.len(), 5);
+        let repeated: Vec<&Value> = rows
+            .iter()
+            .filter(|row| row[naming::SOURCE_COLUMN] == naming::layer_key(1).as_str())
+            .map(|row| &row["region"])
+            .collect();
+        assert_eq!(repeated, vec![&json!("North"), &json!("South")]);
+
+        let layers = vl_spec["spec"]["layer"].as_array().unwrap();
+        for (layer, key) in layers
+            .iter()
+            .zip([naming::GLOBAL_DATA_KEY.to_string(), naming::layer_key(1)])
+        {
+            assert!(layer.get("data").is_none());
+            assert_eq!(
+                layer["transform"][0]["filter"],
+                json!({"field": naming::SOURCE_COLUMN, "equal": key})
+            );
+        }
+    }
+
     #[test]
     fn test_aesthetic_in_setting_literal_encoding() {
         // Test that aesthetics in SETTING (e.g., SETTING color => 'red') are encoded as literals
@@ -3797,7 +7722,7 @@ mod tests {
                 "y".to_string(),
                 AestheticValue::standard_column("y".to_string()),
             )
-            .with_parameter("size".to_string(), ParameterValue::Number(100.0))
+            .with_parameter("size".to_string(), ParameterValue::Number(3.0))
             .with_parameter("opacity".to_string(), ParameterValue::Number(0.5));
         spec.layers.push(layer);
 
@@ -3811,9 +7736,12 @@ mod tests {
         let vl_spec: Value = serde_json::from_str(&json_str).unwrap();
 
         // Size and opacity should be encoded as literal values
-        assert_eq!(
-            vl_spec["layer"][0]["encoding"]["size"]["value"], 100.0,
-            "SETTING size => 100 should produce {{\"value\": 100}}"
+        let size = vl_spec["layer"][0]["encoding"]["size"]["value"]
+            .as_f64()
+            .unwrap();
+        assert!(
+            (size - (3.0 * POINTS_PER_MM).powi(2)).abs() < 1e-9,
+            "SETTING size => 3 should produce the area of a 3 mm point"
         );
         assert_eq!(
             vl_spec["layer"][0]["encoding"]["opacity"]["value"], 0.5,
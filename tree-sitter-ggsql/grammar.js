@@ -439,7 +439,7 @@ module.exports = grammar({
     geom_type: $ => choice(
       'point', 'line', 'path', 'bar', 'area', 'tile', 'polygon', 'ribbon',
       'histogram', 'density', 'smooth', 'boxplot', 'violin',
-      'text', 'label', 'segment', 'arrow', 'hline', 'vline', 'abline', 'errorbar'
+      'text', 'label', 'segment', 'arrow', 'hline', 'vline', 'abline', 'errorbar', 'bin2d', 'hex', 'density2d', 'contour', 'ecdf', 'qq', 'qqline', 'step', 'rect', 'rug', 'linerange', 'pointrange', 'crossbar'
     ),
 
     // MAPPING clause for aesthetic mappings: MAPPING col AS x, "blue" AS color [FROM source]
@@ -491,7 +491,8 @@ module.exports = grammar({
     parameter_value: $ => choice(
       $.string,
       $.number,
-      $.boolean
+      $.boolean,
+      $.array
     ),
 
     // PARTITION BY clause for grouping: PARTITION BY category, region
@@ -605,6 +606,10 @@ module.exports = grammar({
     aesthetic_name: $ => choice(
       // Position aesthetics
       'x', 'y', 'xmin', 'xmax', 'ymin', 'ymax', 'xend', 'yend',
+      // Surface height (for contours)
+      'z',
+      // Sample values (for quantile-quantile plots)
+      'sample',
       // Aggregation aesthetic (for bar charts)
       'weight',
       // Color aesthetics
@@ -642,17 +647,19 @@ module.exports = grammar({
 
     scale_property_name: $ => choice(
       'type', 'limits', 'breaks', 'labels', 'expand',
-      'direction', 'na_value', 'palette', 'domain', 'range'
+      'direction', 'na_value', 'palette', 'domain', 'range', 'timezone', 'n', 'values',
+      'sec_axis', 'sec_name', 'oob'
     ),
 
     scale_property_value: $ => choice(
       $.string,
       $.number,
       $.boolean,
-      $.array
+      $.array,
+      $.map
     ),
 
-    // FACET clause - FACET ... SETTING scales => ...
+    // FACET clause - FACET ... SETTING scales => ..., ncol => ...
     facet_clause: $ => choice(
       // FACET row_vars BY col_vars
       seq(
@@ -660,17 +667,27 @@ module.exports = grammar({
         $.facet_vars,
         alias(caseInsensitive('BY'), $.facet_by),
         $.facet_vars,
-        optional(seq(caseInsensitive('SETTING'), caseInsensitive('scales'), '=>', $.facet_scales))
+        optional(seq(caseInsensitive('SETTING'), $.facet_property, repeat(seq(',', $.facet_property))))
       ),
       // FACET WRAP vars
       seq(
         caseInsensitive('FACET'),
         alias(caseInsensitive('WRAP'), $.facet_wrap),
         $.facet_vars,
-        optional(seq(caseInsensitive('SETTING'), caseInsensitive('scales'), '=>', $.facet_scales))
+        optional(seq(caseInsensitive('SETTING'), $.facet_property, repeat(seq(',', $.facet_property))))
       )
     ),
 
+    facet_property: $ => seq(
+      $.facet_property_name,
+      '=>',
+      choice($.facet_scales, $.string, $.number, $.boolean)
+    ),
+
+    facet_property_name: $ => choice(
+      'scales', 'ncol', 'nrow', 'dir', 'label', 'label_wrap'
+    ),
+
     facet_wrap: $ => 'WRAP',
     facet_by: $ => 'BY',
 
@@ -780,9 +797,9 @@ module.exports = grammar({
       )
     ),
 
-    theme_name: $ => choice(
-      'minimal', 'classic', 'gray', 'grey', 'bw', 'dark', 'light', 'void'
-    ),
+    // Built-in themes (minimal, classic, grey, bw, ...) or themes loaded from theme files.
+    // Lower precedence so that SETTING still ends the theme name
+    theme_name: $ => token(prec(-1, /[a-zA-Z_][a-zA-Z0-9_]*/)),
 
     theme_property: $ => seq(
       $.theme_property_name,
@@ -842,6 +859,22 @@ module.exports = grammar({
       $.boolean
     ),
 
+    // Key-value pairs, e.g. {'Adelie': 'red', 'Gentoo': 'blue'}
+    map: $ => seq(
+      '{',
+      optional(seq(
+        $.map_entry,
+        repeat(seq(',', $.map_entry))
+      )),
+      '}'
+    ),
+
+    map_entry: $ => seq(
+      field('key', $.array_element),
+      ':',
+      field('value', $.array_element)
+    ),
+
     // Comments
     comment: $ => choice(
       seq('//', /.*/),
@@ -28,6 +28,19 @@
   "vline"
   "abline"
   "errorbar"
+  "bin2d"
+  "hex"
+  "density2d"
+  "contour"
+  "ecdf"
+  "qq"
+  "qqline"
+  "step"
+  "rect"
+  "rug"
+  "linerange"
+  "pointrange"
+  "crossbar"
 ] @type.builtin
 
 ; Aesthetic names
@@ -41,6 +54,8 @@
   "ymax"
   "xend"
   "yend"
+  "z"
+  "sample"
   "color"
   "colour"
   "fill"
@@ -76,6 +91,7 @@
 
 ; Property names
 (scale_property_name) @property
+(facet_property_name) @property
 (coord_property_name) @property
 (guide_property_name) @property
 (theme_property_name) @property